@@ -0,0 +1,46 @@
+// cargo bench: the speed benchmarks of every parameter set.
+// `cargo bench --bench speed -- --json` prints JSON instead, and `--iterations <N>` overrides
+// the number of calls per operation.
+
+use kyber::bench::{self, BenchReport, DEFAULT_ITERATIONS};
+use kyber::{Kem, KyberError};
+use kyber::{Kyber1024, Kyber1024_90s, Kyber512, Kyber512_90s, Kyber768, Kyber768_90s, MlKem1024, MlKem512, MlKem768};
+
+fn main() -> Result<(), KyberError> {
+    let args: Vec<String> = std::env::args().collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    let iterations = args
+        .iter()
+        .position(|arg| arg == "--iterations")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_ITERATIONS);
+
+    let runs: [fn(usize) -> Result<BenchReport, KyberError>; 9] = [
+        run::<Kyber512>,
+        run::<Kyber768>,
+        run::<Kyber1024>,
+        run::<Kyber512_90s>,
+        run::<Kyber768_90s>,
+        run::<Kyber1024_90s>,
+        run::<MlKem512>,
+        run::<MlKem768>,
+        run::<MlKem1024>,
+    ];
+    let mut reports = Vec::new();
+    for run in runs {
+        let report = run(iterations)?;
+        if !json {
+            report.print();
+        }
+        reports.push(report);
+    }
+    if json {
+        println!("{}", bench::to_json(&reports));
+    }
+    Ok(())
+}
+
+fn run<K: Kem>(iterations: usize) -> Result<BenchReport, KyberError> {
+    bench::run::<K>(iterations)
+}
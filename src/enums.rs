@@ -56,6 +56,37 @@ pub enum Kyber_Category {
     Exit
    
 }
+// Which KEM construction a key belongs to. Both share the same IND-CPA core
+// and key sizes, but derive the shared secret differently.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum KyberVariant {
+    // Round-3 Kyber as submitted to the NIST competition
+    Round3,
+    // Round-3 Kyber with AES-256-CTR and SHA-2 in place of SHAKE and SHA-3
+    Kyber90s,
+    // FIPS 203 ML-KEM
+    MlKem,
+}
+
+impl KyberVariant {
+    // Name recorded in key files
+    pub fn name(&self) -> &'static str {
+        match self {
+            KyberVariant::Round3 => "Kyber",
+            KyberVariant::Kyber90s => "Kyber-90s",
+            KyberVariant::MlKem => "ML-KEM",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<KyberVariant> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "kyber" => Some(KyberVariant::Round3),
+            "kyber-90s" | "kyber90s" => Some(KyberVariant::Kyber90s),
+            "ml-kem" | "mlkem" => Some(KyberVariant::MlKem),
+            _ => None,
+        }
+    }
+}
 #[derive(Debug, PartialEq, Clone)]
 pub enum Primality_Category {
     Erastothenes,
@@ -194,7 +225,7 @@ pub enum Extra_Category {
  
     
 }
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Export_Category {
     Binary,
     Hexadecimal,
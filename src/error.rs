@@ -0,0 +1,109 @@
+use std::fmt;
+use std::io;
+
+/// Errors reported by every layer of the crate, from reading key files up to the KEM itself.
+#[derive(Debug)]
+pub enum KyberError {
+    /// Command line arguments are missing, unknown or invalid.
+    Usage(String),
+    /// A public or secret key does not have the length of the selected parameter set.
+    WrongKeyLength { expected: usize, actual: usize },
+    /// A ciphertext does not have the length of the selected parameter set.
+    WrongCiphertextLength { expected: usize, actual: usize },
+    /// A key file value is not valid hex.
+    BadHex(hex::FromHexError),
+    /// A key file does not contain the requested keyword.
+    MissingKeyword(String),
+    /// A key failed validation: it is all zero, fails the FIPS 203 modulus or
+    /// H(pk) check, does not match its seed, or is an X25519 key OpenSSL rejects.
+    InvalidKey(&'static str),
+    /// The random number generator failed to produce output.
+    Rng(&'static str),
+    /// The security level or variant is not one this crate implements.
+    UnsupportedParameterSet(String),
+    Io(io::Error),
+    /// Exported key or ciphertext data that cannot be decoded, such as malformed
+    /// DER or Base64, or data for another parameter set.
+    Encoding(String),
+    /// A key file is malformed, truncated or fails its checksum.
+    KeyFile(String),
+    /// A private key cannot be encrypted or decrypted with the given password.
+    Password(&'static str),
+    /// An HPKE context cannot be set up, or a message does not open under it.
+    Hpke(&'static str),
+    /// A sealed file is malformed, was altered or truncated, or is not for the given key.
+    Sealed(String),
+    /// A key exchange message is malformed, or its key confirmation does not match.
+    Ake(&'static str),
+}
+
+impl KyberError {
+    /// Process exit code used by the command line tool, distinct for each variant.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            KyberError::Usage(_) => 2,
+            KyberError::WrongKeyLength { .. } => 3,
+            KyberError::WrongCiphertextLength { .. } => 4,
+            KyberError::BadHex(_) => 5,
+            KyberError::MissingKeyword(_) => 6,
+            KyberError::InvalidKey(_) => 7,
+            KyberError::Rng(_) => 8,
+            KyberError::UnsupportedParameterSet(_) => 9,
+            KyberError::Io(_) => 10,
+            KyberError::Encoding(_) => 11,
+            KyberError::KeyFile(_) => 12,
+            KyberError::Password(_) => 13,
+            KyberError::Hpke(_) => 14,
+            KyberError::Sealed(_) => 15,
+            KyberError::Ake(_) => 16,
+        }
+    }
+}
+
+impl fmt::Display for KyberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KyberError::Usage(reason) => write!(f, "{}", reason),
+            KyberError::WrongKeyLength { expected, actual } => {
+                write!(f, "key is {} bytes, expected {}", actual, expected)
+            }
+            KyberError::WrongCiphertextLength { expected, actual } => {
+                write!(f, "ciphertext is {} bytes, expected {}", actual, expected)
+            }
+            KyberError::BadHex(err) => write!(f, "invalid hex: {}", err),
+            KyberError::MissingKeyword(keyword) => write!(f, "no value found after '{}'", keyword),
+            KyberError::InvalidKey(reason) => write!(f, "invalid key: {}", reason),
+            KyberError::Rng(reason) => write!(f, "random number generation failed: {}", reason),
+            KyberError::UnsupportedParameterSet(name) => write!(f, "unsupported parameter set: {}", name),
+            KyberError::Io(err) => write!(f, "I/O error: {}", err),
+            KyberError::Encoding(reason) => write!(f, "cannot decode: {}", reason),
+            KyberError::KeyFile(reason) => write!(f, "invalid key file: {}", reason),
+            KyberError::Password(reason) => write!(f, "password: {}", reason),
+            KyberError::Hpke(reason) => write!(f, "HPKE: {}", reason),
+            KyberError::Sealed(reason) => write!(f, "invalid sealed file: {}", reason),
+            KyberError::Ake(reason) => write!(f, "key exchange: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for KyberError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            KyberError::BadHex(err) => Some(err),
+            KyberError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for KyberError {
+    fn from(err: io::Error) -> Self {
+        KyberError::Io(err)
+    }
+}
+
+impl From<hex::FromHexError> for KyberError {
+    fn from(err: hex::FromHexError) -> Self {
+        KyberError::BadHex(err)
+    }
+}
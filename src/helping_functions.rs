@@ -1,6 +1,9 @@
 pub mod helping_functions{
 //TODO: Update comments for this
     use std::{fs::File, io::{self, BufRead, Seek}};
+    use crate::error::KyberError;
+    use crate::kyber::kyber::KyberParams;
+    use zeroize::Zeroizing;
 
 
     pub fn ask_for_number_question_integer(question: &str) -> u32
@@ -57,6 +60,32 @@ pub mod helping_functions{
     
         return -1;
     }
+
+    // Reads a password from the terminal without echoing it, twice when
+    // `confirm` is set, as for a new password
+    pub fn read_password(prompt: &str, confirm: bool) -> Result<Zeroizing<String>, KyberError> {
+        let password = Zeroizing::new(rpassword::prompt_password(prompt)?);
+        if confirm {
+            let again = Zeroizing::new(rpassword::prompt_password("Enter the password again: ")?);
+            if *again != *password {
+                return Err(KyberError::Password("the passwords do not match"));
+            }
+        }
+        if password.is_empty() {
+            return Err(KyberError::Password("the password is empty"));
+        }
+        Ok(password)
+    }
+
+    // The password is the first line of the file, without its line ending
+    pub fn read_password_file(path: &str) -> Result<Zeroizing<String>, KyberError> {
+        let contents = Zeroizing::new(std::fs::read_to_string(path.trim())?);
+        let password = Zeroizing::new(contents.lines().next().unwrap_or_default().to_string());
+        if password.is_empty() {
+            return Err(KyberError::Password("the password file is empty"));
+        }
+        Ok(password)
+    }
    
     
 
@@ -84,7 +113,7 @@ pub mod helping_functions{
         }
     }
   
-    pub fn get_keys_generated(mut public:&mut Vec<u8>, mut private:&mut Vec<u8>) {
+    pub fn get_keys_generated(params: &KyberParams, mut public:&mut Vec<u8>, mut private:&mut Vec<u8>) {
         let mut input = String::new();
         let mut selected_number = 0;
         loop {
@@ -114,19 +143,19 @@ pub mod helping_functions{
             
         }
 
-        do_key_generation(selected_number, public, private, false);
+        do_key_generation(params, selected_number, public, private, false);
 
        
     }
 
-    pub fn do_key_generation(selected_number:u32, mut public:&mut Vec<u8>, mut private:&mut Vec<u8>, included_pub_key:bool)
+    pub fn do_key_generation(params: &KyberParams, selected_number:u32, mut public:&mut Vec<u8>, mut private:&mut Vec<u8>, included_pub_key:bool)
     {
         let mut copy_pub_key = public.clone();
 
         match selected_number
         {
             1 => {
-            if let Ok(()) = crate::kyber::kem::kem::crypto_kem_keypair(&mut public, &mut private) 
+            if let Ok(()) = crate::kyber::kem::kem::crypto_kem_keypair(params, &mut public, &mut private, &mut crate::kyber::kem_rng::GlobalKyberRng) 
                 {
                     println!("Key pair generated successfully.");
                 }   
@@ -209,7 +238,11 @@ pub fn is_valid_hex(input: &str) -> bool {
 }
 
 
-pub fn read_hex_from_file_after_keyword(file: &mut File, keyword: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+pub fn read_hex_from_file_after_keyword(file: &mut File, keyword: &str) -> Result<Vec<u8>, KyberError> {
+    let hex_string = read_string_from_file_after_keyword(file, keyword)?;
+    Ok(hex::decode(hex_string.trim())?)
+}
+pub fn read_string_from_file_after_keyword(file: &mut File, keyword: &str) -> Result<String, KyberError> {
     file.seek(io::SeekFrom::Start(0))?; 
     let buf_reader = io::BufReader::new(&*file);
     let lines = buf_reader.lines();
@@ -217,17 +250,16 @@ pub fn read_hex_from_file_after_keyword(file: &mut File, keyword: &str) -> Resul
     for line in lines {
         let line = line?;
         if line.to_ascii_lowercase().contains(keyword) {
-            // Extract the hex value enclosed in single quotes
+            // Extract the value enclosed in single quotes
             if let Some(start) = line.find('\'') {
                 if let Some(end) = line[start+1..].find('\'') {
-                    let hex_string = &line[start+1..start+1+end];
-                    return hex::decode(hex_string.trim()).map_err(|e| e.into());
+                    return Ok(line[start+1..start+1+end].to_string());
                 }
             }
         }
     }
 
-    Err("Keyword not found or no data after keyword".into())
+    Err(KyberError::MissingKeyword(keyword.to_string()))
 }
 pub fn read_message_from_file(file: &mut File) -> Option<String> {
     let reader = io::BufReader::new(file);
@@ -0,0 +1,445 @@
+// Key files written and read by the command line tool.
+//
+// Version 1 files start with a header naming the format version, the variant
+// and the parameter set, followed by the values and a SHA-256 checksum:
+//
+//     KYBER-KEYFILE v1
+//     algorithm: ML-KEM
+//     parameter-set: ML-KEM-768
+//     private-key: <hex>
+//     public-key: <hex>
+//     ciphertext: <hex>
+//     shared-secret: <hex>
+//     checksum: <hex SHA-256 of every line above, newlines included>
+//
+// Every value is optional, but those present appear in this order, once each,
+// with the length of the parameter set. Anything else, including blank lines,
+// comments and text after the checksum, is rejected, and the checksum catches
+// truncation and editing.
+//
+// A private key protected by a password takes the place of the private-key line:
+//
+//     encrypted-private-key: scrypt:<log2 N>:<r>:<p>:<salt>:<nonce>:<ciphertext>
+//
+// The AES-256-GCM key is derived from the password with scrypt, and the hex
+// ciphertext of the private key ends in the 16 byte tag. The header line and
+// parameter set are the associated data, so the key cannot be moved to a file
+// for another parameter set.
+//
+// Keys of the X25519 hybrid KEM, see `kyber::hybrid`, have the algorithm
+// `X25519+<algorithm>` and a parameter set such as `X25519+ML-KEM-768`, with the
+// 32 byte X25519 value after each Kyber key and ciphertext.
+//
+// Files without the header are in the legacy format of earlier versions, lines
+// like `PRIVATE KEY: '<hex>'` found anywhere in free text. They are still read,
+// with the parameter set inferred from the value lengths, but are deprecated.
+
+use std::fs;
+
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::enums::KyberVariant;
+use crate::error::KyberError;
+use crate::kyber::hybrid::X25519_BYTES;
+use crate::kyber::kyber::KyberParams;
+use crate::kyber::kyber_api::{parameter_set, Kem};
+
+/// Version written by `KeyFile::encode`.
+pub const KEYFILE_VERSION: u32 = 1;
+
+/// scrypt cost of newly encrypted private keys, N = 2^17, r = 8 and p = 1, which
+/// takes 128 MiB and a fraction of a second.
+pub const SCRYPT_LOG_N: u8 = 17;
+pub const SCRYPT_R: u32 = 8;
+pub const SCRYPT_P: u32 = 1;
+
+const MAGIC: &str = "KYBER-KEYFILE";
+const HYBRID_PREFIX: &str = "X25519+";
+const SHARED_SECRET_BYTES: usize = 32;
+// Files asking for more than 1 GiB of scrypt memory are refused
+const MAX_SCRYPT_MEMORY: u64 = 1 << 30;
+const SALT_BYTES: usize = 16;
+const NONCE_BYTES: usize = 12;
+const TAG_BYTES: usize = 16;
+
+/// Which format a key file was read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyFileFormat {
+    V1,
+    /// Free text from before the versioned format, deprecated.
+    Legacy,
+}
+
+/// The contents of a key file.
+pub struct KeyFile {
+    pub variant: KyberVariant,
+    /// 2, 3 or 5, as given to --sec.
+    pub security_level: u8,
+    /// Values of the X25519 hybrid KEM rather than Kyber alone.
+    pub hybrid: bool,
+    pub private_key: Option<Zeroizing<Vec<u8>>>,
+    /// Written instead of `private_key` when set.
+    pub encrypted_private_key: Option<EncryptedPrivateKey>,
+    pub public_key: Option<Vec<u8>>,
+    pub ciphertext: Option<Vec<u8>>,
+    pub shared_secret: Option<Zeroizing<Vec<u8>>>,
+}
+
+/// A private key encrypted under a password, see `KeyFile::encrypt_private_key`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedPrivateKey {
+    /// scrypt parameters, N = 2^log_n.
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+    pub salt: [u8; SALT_BYTES],
+    pub nonce: [u8; NONCE_BYTES],
+    /// The AES-256-GCM ciphertext followed by the tag.
+    pub ciphertext: Vec<u8>,
+}
+
+// Field names in the order they are written
+const FIELDS: [&str; 5] = ["private-key", "encrypted-private-key", "public-key", "ciphertext", "shared-secret"];
+
+// A value, its field name and its expected length
+type Field<'a> = (Option<&'a [u8]>, &'static str, usize);
+
+fn error(reason: impl Into<String>) -> KyberError {
+    KyberError::KeyFile(reason.into())
+}
+
+impl KeyFile {
+    /// An empty key file for `variant` at --sec level `security_level`.
+    pub fn new(variant: KyberVariant, security_level: u8) -> KeyFile {
+        KeyFile {
+            variant,
+            security_level,
+            hybrid: false,
+            private_key: None,
+            encrypted_private_key: None,
+            public_key: None,
+            ciphertext: None,
+            shared_secret: None,
+        }
+    }
+
+    /// An empty key file for the parameter set of `K`.
+    pub fn for_kem<K: Kem>() -> KeyFile {
+        KeyFile::new(K::VARIANT, if K::SECURITY_LEVEL == 4 { 5 } else { K::SECURITY_LEVEL as u8 })
+    }
+
+    /// An empty key file for X25519 with the parameter set of `K`.
+    pub fn for_hybrid<K: Kem>() -> KeyFile {
+        KeyFile { hybrid: true, ..KeyFile::for_kem::<K>() }
+    }
+
+    /// e.g. "ML-KEM" or "X25519+ML-KEM".
+    pub fn algorithm(&self) -> String {
+        format!("{}{}", if self.hybrid { HYBRID_PREFIX } else { "" }, self.variant.name())
+    }
+
+    /// e.g. "ML-KEM-768" or "X25519+ML-KEM-768".
+    pub fn parameter_set_name(&self) -> String {
+        let kem = parameter_set(self.variant, self.security_level).map(String::from).unwrap_or_else(|| format!("{}-{}", self.variant.name(), self.security_level));
+        format!("{}{}", if self.hybrid { HYBRID_PREFIX } else { "" }, kem)
+    }
+
+    /// Fails unless the file is for the parameter set of `K`.
+    pub fn check_parameter_set<K: Kem>(&self) -> Result<(), KyberError> {
+        self.check_matches(KeyFile::for_kem::<K>())
+    }
+
+    /// Fails unless the file is for X25519 with the parameter set of `K`.
+    pub fn check_hybrid_parameter_set<K: Kem>(&self) -> Result<(), KyberError> {
+        self.check_matches(KeyFile::for_hybrid::<K>())
+    }
+
+    fn check_matches(&self, expected: KeyFile) -> Result<(), KyberError> {
+        if self.variant != expected.variant || self.security_level != expected.security_level || self.hybrid != expected.hybrid {
+            return Err(error(format!("key file holds {} values, expected {}", self.parameter_set_name(), expected.parameter_set_name())));
+        }
+        Ok(())
+    }
+
+    // (value, field name) in file order, with the expected length of each
+    fn values(&self) -> Result<[Field<'_>; 4], KyberError> {
+        let level = if self.security_level == 5 { 4 } else { self.security_level as u32 };
+        let params = KyberParams::set_parameters(level).ok_or_else(|| KyberError::UnsupportedParameterSet(format!("security level {}", self.security_level)))?;
+        let x25519 = if self.hybrid { X25519_BYTES } else { 0 };
+        Ok([
+            (self.private_key.as_deref().map(|v| v.as_slice()), FIELDS[0], params.kyber_secretkeybytes as usize + x25519),
+            (self.public_key.as_deref(), FIELDS[2], params.kyber_publickeybytes as usize + x25519),
+            (self.ciphertext.as_deref(), FIELDS[3], params.kyber_ciphertextbytes as usize + x25519),
+            (self.shared_secret.as_deref().map(|v| v.as_slice()), FIELDS[4], SHARED_SECRET_BYTES),
+        ])
+    }
+
+    /// The file in the current version of the format.
+    pub fn encode(&self) -> Result<Zeroizing<String>, KyberError> {
+        let mut out = Zeroizing::new(format!("{} v{}\n", MAGIC, KEYFILE_VERSION));
+        out.push_str(&format!("algorithm: {}\n", self.algorithm()));
+        out.push_str(&format!("parameter-set: {}\n", self.parameter_set_name()));
+        for (value, name, len) in self.values()? {
+            // A private key with a password is never written in the clear
+            if let Some(encrypted) = self.encrypted_private_key.as_ref().filter(|_| name == FIELDS[0]) {
+                if encrypted.ciphertext.len() != len + TAG_BYTES {
+                    return Err(error(format!("encrypted private key is {} bytes, expected {}", encrypted.ciphertext.len(), len + TAG_BYTES)));
+                }
+                out.push_str(&format!("{}: {}\n", FIELDS[1], encrypted.encode()));
+                continue;
+            }
+            if let Some(value) = value {
+                if value.len() != len {
+                    return Err(error(format!("{} is {} bytes, expected {}", name, value.len(), len)));
+                }
+                out.push_str(name);
+                out.push_str(": ");
+                out.push_str(&Zeroizing::new(hex::encode(value)));
+                out.push('\n');
+            }
+        }
+        let checksum = hex::encode(Sha256::digest(out.as_bytes()));
+        out.push_str(&format!("checksum: {}\n", checksum));
+        Ok(out)
+    }
+
+    /// Parses either format, telling which one it was. `legacy_variant` is the
+    /// variant of a legacy file without a VARIANT line.
+    pub fn parse(text: &str, legacy_variant: KyberVariant) -> Result<(KeyFile, KeyFileFormat), KyberError> {
+        if text.starts_with(MAGIC) {
+            Ok((KeyFile::parse_v1(text)?, KeyFileFormat::V1))
+        } else {
+            Ok((KeyFile::parse_legacy(text, legacy_variant)?, KeyFileFormat::Legacy))
+        }
+    }
+
+    /// Strict parser for the versioned format.
+    pub fn parse_v1(text: &str) -> Result<KeyFile, KyberError> {
+        // Every line, the checksum included, ends in a newline, so a file cut
+        // anywhere fails one of the checks below
+        let body_end = text.rfind("checksum: ").ok_or_else(|| error("no checksum, the file may be truncated"))?;
+        let (body, checksum_line) = text.split_at(body_end);
+        if !body.ends_with('\n') {
+            return Err(error("checksum is not on a line of its own"));
+        }
+        let checksum = checksum_line["checksum: ".len()..].strip_suffix('\n').ok_or_else(|| error("checksum line is incomplete, the file may be truncated"))?;
+        if checksum != hex::encode(Sha256::digest(body.as_bytes())) {
+            return Err(error("checksum does not match, the file is corrupted or was edited"));
+        }
+
+        let mut lines = body.lines();
+        let header = lines.next().unwrap_or_default();
+        match header.strip_prefix(MAGIC).and_then(|rest| rest.strip_prefix(" v")) {
+            Some("1") => {}
+            Some(version) => return Err(error(format!("unsupported key file version {}", version))),
+            None => return Err(error("missing key file header")),
+        }
+
+        let mut field = |name: &str| -> Result<&str, KyberError> {
+            let line = lines.next().ok_or_else(|| error(format!("missing {}", name)))?;
+            line.strip_prefix(name).and_then(|rest| rest.strip_prefix(": ")).ok_or_else(|| error(format!("expected {}, found '{}'", name, line)))
+        };
+        let algorithm = field("algorithm")?;
+        let (hybrid, kem) = match algorithm.strip_prefix(HYBRID_PREFIX) {
+            Some(kem) => (true, kem),
+            None => (false, algorithm),
+        };
+        let variant = KyberVariant::from_name(kem).filter(|variant| variant.name() == kem).ok_or_else(|| error(format!("unknown algorithm '{}'", algorithm)))?;
+        let parameter_set = field("parameter-set")?;
+        let security_level = [2, 3, 5]
+            .into_iter()
+            .find(|&level| KeyFile { hybrid, ..KeyFile::new(variant, level) }.parameter_set_name() == parameter_set)
+            .ok_or_else(|| error(format!("parameter set '{}' does not match algorithm {}", parameter_set, algorithm)))?;
+
+        let mut file = KeyFile { hybrid, ..KeyFile::new(variant, security_level) };
+        let mut decoded: [Option<Zeroizing<Vec<u8>>>; 5] = Default::default();
+        let mut next = 0;
+        for line in lines {
+            let (name, value) = line.split_once(": ").ok_or_else(|| error(format!("malformed line '{}'", line)))?;
+            let index = FIELDS.iter().position(|&field| field == name).ok_or_else(|| error(format!("unknown field '{}'", name)))?;
+            if index < next {
+                return Err(error(format!("{} is repeated or out of order", name)));
+            }
+            next = index + 1;
+            if value.bytes().any(|b| b.is_ascii_uppercase()) {
+                return Err(error(format!("{} is not lowercase hex", name)));
+            }
+            if name == FIELDS[1] {
+                if decoded[0].is_some() {
+                    return Err(error("holds both a private key and an encrypted private key"));
+                }
+                file.encrypted_private_key = Some(EncryptedPrivateKey::decode(value)?);
+                continue;
+            }
+            decoded[index] = Some(Zeroizing::new(hex::decode(value)?));
+        }
+
+        let [private_key, _, public_key, ciphertext, shared_secret] = decoded;
+        file.private_key = private_key;
+        file.public_key = public_key.map(|value| value.to_vec());
+        file.ciphertext = ciphertext.map(|value| value.to_vec());
+        file.shared_secret = shared_secret;
+        for (value, name, len) in file.values()? {
+            if let Some(value) = value {
+                if value.len() != len {
+                    return Err(error(format!("{} is {} bytes, expected {} for {}", name, value.len(), len, file.parameter_set_name())));
+                }
+            }
+        }
+        if let Some(encrypted) = &file.encrypted_private_key {
+            let len = file.values()?[0].2 + TAG_BYTES;
+            if encrypted.ciphertext.len() != len {
+                return Err(error(format!("encrypted private key is {} bytes, expected {} for {}", encrypted.ciphertext.len(), len, file.parameter_set_name())));
+            }
+        }
+        Ok(file)
+    }
+
+    /// Reads the deprecated free-text format. The first line containing a
+    /// keyword, in any case, gives the value between its single quotes. The
+    /// parameter set comes from the VARIANT line, `default_variant` without one,
+    /// and the lengths of the values, which must agree.
+    pub fn parse_legacy(text: &str, default_variant: KyberVariant) -> Result<KeyFile, KyberError> {
+        let value = |keyword: &str| -> Option<&str> {
+            text.lines().filter(|line| line.to_ascii_lowercase().contains(keyword)).find_map(|line| {
+                let start = line.find('\'')? + 1;
+                let end = line[start..].find('\'')? + start;
+                Some(&line[start..end])
+            })
+        };
+        let hex_value = |keyword: &str| -> Result<Option<Zeroizing<Vec<u8>>>, KyberError> {
+            match value(keyword) {
+                Some(value) => Ok(Some(Zeroizing::new(hex::decode(value.trim())?))),
+                None => Ok(None),
+            }
+        };
+
+        let variant = match value("variant:") {
+            Some(name) => KyberVariant::from_name(name).ok_or_else(|| KyberError::UnsupportedParameterSet(format!("variant '{}'", name)))?,
+            None => default_variant,
+        };
+        let mut file = KeyFile::new(variant, 0);
+        file.private_key = hex_value("private key:")?;
+        file.shared_secret = hex_value("shared secret:")?;
+        file.public_key = hex_value("public key:")?.map(|value| value.to_vec());
+        file.ciphertext = hex_value("ciphertext:")?.map(|value| value.to_vec());
+
+        // Earlier versions wrote placeholder values, such as an all-zero private
+        // key next to a user's own public key, which are dropped
+        if file.private_key.as_ref().is_some_and(|value| value.iter().all(|&b| b == 0)) {
+            file.private_key = None;
+        }
+        if file.public_key.as_ref().is_some_and(|value| value.iter().all(|&b| b == 0)) {
+            file.public_key = None;
+        }
+
+        for level in [2, 3, 5] {
+            file.security_level = level;
+            let values = file.values()?;
+            let present = values.iter().filter(|(value, name, _)| value.is_some() && *name != FIELDS[4]).count();
+            if present > 0 && values.iter().all(|(value, _, len)| value.is_none_or(|value| value.len() == *len)) {
+                return Ok(file);
+            }
+        }
+        Err(error("cannot tell the parameter set from the lengths of the values"))
+    }
+
+    /// Whether the file holds a private key that needs a password.
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted_private_key.is_some()
+    }
+
+    // The header and parameter set, authenticated along with the private key
+    fn associated_data(&self) -> String {
+        format!("{} v{} {}", MAGIC, KEYFILE_VERSION, self.parameter_set_name())
+    }
+
+    /// Encrypts the private key under `password`, with a fresh salt and nonce.
+    /// The key stays available in `private_key` but is only written encrypted.
+    pub fn encrypt_private_key(&mut self, password: &[u8]) -> Result<(), KyberError> {
+        let private_key = self.private_key.as_ref().ok_or_else(|| KyberError::MissingKeyword("private key".to_string()))?;
+        if password.is_empty() {
+            return Err(KyberError::Password("the password is empty"));
+        }
+        let mut salt = [0u8; SALT_BYTES];
+        let mut nonce = [0u8; NONCE_BYTES];
+        openssl::rand::rand_bytes(&mut salt).map_err(|_| KyberError::Rng("OpenSSL could not generate a salt"))?;
+        openssl::rand::rand_bytes(&mut nonce).map_err(|_| KyberError::Rng("OpenSSL could not generate a nonce"))?;
+
+        let key = derive_key(password, &salt, SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P)?;
+        let mut tag = [0u8; TAG_BYTES];
+        let mut ciphertext = encrypt_aead(Cipher::aes_256_gcm(), &key[..], Some(&nonce), self.associated_data().as_bytes(), private_key, &mut tag)
+            .map_err(|err| error(format!("cannot encrypt the private key: {}", err)))?;
+        ciphertext.extend_from_slice(&tag);
+        self.encrypted_private_key = Some(EncryptedPrivateKey { log_n: SCRYPT_LOG_N, r: SCRYPT_R, p: SCRYPT_P, salt, nonce, ciphertext });
+        Ok(())
+    }
+
+    /// Decrypts the encrypted private key into `private_key`. Does nothing for a
+    /// file without one.
+    pub fn decrypt_private_key(&mut self, password: &[u8]) -> Result<(), KyberError> {
+        let encrypted = match &self.encrypted_private_key {
+            Some(encrypted) => encrypted,
+            None => return Ok(()),
+        };
+        let (ciphertext, tag) = encrypted.ciphertext.split_at(encrypted.ciphertext.len().saturating_sub(TAG_BYTES));
+        let key = derive_key(password, &encrypted.salt, encrypted.log_n, encrypted.r, encrypted.p)?;
+        let private_key = decrypt_aead(Cipher::aes_256_gcm(), &key[..], Some(&encrypted.nonce), self.associated_data().as_bytes(), ciphertext, tag)
+            .map_err(|_| KyberError::Password("wrong password, or the encrypted private key was altered"))?;
+        self.private_key = Some(Zeroizing::new(private_key));
+        Ok(())
+    }
+
+    /// Reads and parses the file at `path`, see `parse`.
+    pub fn read(path: &str, legacy_variant: KyberVariant) -> Result<(KeyFile, KeyFileFormat), KyberError> {
+        let text = Zeroizing::new(fs::read_to_string(path.trim())?);
+        KeyFile::parse(&text, legacy_variant)
+    }
+
+    /// Writes the file in the current format, replacing any existing file.
+    pub fn write(&self, path: &str) -> Result<(), KyberError> {
+        fs::write(path.trim(), self.encode()?.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl EncryptedPrivateKey {
+    // scrypt:<log2 N>:<r>:<p>:<salt>:<nonce>:<ciphertext>
+    fn encode(&self) -> String {
+        format!("scrypt:{}:{}:{}:{}:{}:{}", self.log_n, self.r, self.p, hex::encode(self.salt), hex::encode(self.nonce), hex::encode(&self.ciphertext))
+    }
+
+    fn decode(value: &str) -> Result<EncryptedPrivateKey, KyberError> {
+        let parts: Vec<&str> = value.split(':').collect();
+        let [kdf, log_n, r, p, salt, nonce, ciphertext] = parts[..] else {
+            return Err(error("encrypted-private-key is malformed"));
+        };
+        if kdf != "scrypt" {
+            return Err(error(format!("unknown key derivation '{}'", kdf)));
+        }
+        let number = |value: &str| value.parse::<u32>().ok().filter(|&n| n > 0 && n.to_string() == value);
+        let (log_n, r, p) = match (number(log_n), number(r), number(p)) {
+            (Some(log_n), Some(r), Some(p)) if log_n < 64 && scrypt_memory(log_n as u8, r, p) <= MAX_SCRYPT_MEMORY => (log_n as u8, r, p),
+            _ => return Err(error("scrypt parameters are invalid or too costly")),
+        };
+        let mut encrypted = EncryptedPrivateKey { log_n, r, p, salt: [0; SALT_BYTES], nonce: [0; NONCE_BYTES], ciphertext: hex::decode(ciphertext)? };
+        hex::decode_to_slice(salt, &mut encrypted.salt)?;
+        hex::decode_to_slice(nonce, &mut encrypted.nonce)?;
+        Ok(encrypted)
+    }
+}
+
+// Bytes of memory scrypt needs, 128 r (N + p) plus a little
+fn scrypt_memory(log_n: u8, r: u32, p: u32) -> u64 {
+    128u64.saturating_mul(r as u64).saturating_mul((1u64 << log_n).saturating_add(p as u64 + 2))
+}
+
+fn derive_key(password: &[u8], salt: &[u8], log_n: u8, r: u32, p: u32) -> Result<Zeroizing<[u8; 32]>, KyberError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    // OpenSSL refuses more than 32 MiB unless given a higher limit
+    openssl::pkcs5::scrypt(password, salt, 1 << log_n, r as u64, p as u64, scrypt_memory(log_n, r, p), &mut key[..])
+        .map_err(|err| error(format!("scrypt failed: {}", err)))?;
+    Ok(key)
+}
@@ -1,4 +1,5 @@
 pub mod aes256ctr {
+    use zeroize::Zeroize;
 
     /*
      * Copyright (c) 2016 Thomas Pornin <pornin@bolet.org>
@@ -45,7 +46,8 @@ pub mod aes256ctr {
     }
     
     pub fn br_swap32(x: u32) -> u32 {
-        ((x & 0x00FF00FF) << 8) | ((x >> 8) & 0x00FF00FF) | (x << 16) | (x >> 16)
+        let x = ((x & 0x00FF00FF) << 8) | ((x >> 8) & 0x00FF00FF);
+        (x << 16) | (x >> 16)
     }
     
     pub fn br_enc32le(dst: &mut [u8; 4], x: u32) {
@@ -165,7 +167,7 @@ pub mod aes256ctr {
         t44 = t33 ^ t37;
         t45 = t42 ^ t41;
         z0 = t44 & y15;
-        z1 = t37 & y3;
+        z1 = t37 & y6;
         z2 = t33 & x7;
         z3 = t43 & y16;
         z4 = t40 & y1;
@@ -250,7 +252,7 @@ pub mod aes256ctr {
 
         macro_rules! swap8 {
         ($x:expr, $y:expr) => {
-            swapn!(0x0F0F0F0F0F0F0F0Fu128, 0xF0F0F0F0F0F0F0F0Fu128, 4, $x, $y);
+            swapn!(0x0F0F0F0F0F0F0F0Fu128, 0xF0F0F0F0F0F0F0F0u128, 4, $x, $y);
         };
     }
 
@@ -283,6 +285,14 @@ pub mod aes256ctr {
         x1 &= 0x0000FFFF0000FFFF;
         x2 &= 0x0000FFFF0000FFFF;
         x3 &= 0x0000FFFF0000FFFF;
+        x0 |= x0 << 8;
+        x1 |= x1 << 8;
+        x2 |= x2 << 8;
+        x3 |= x3 << 8;
+        x0 &= 0x00FF00FF00FF00FF;
+        x1 &= 0x00FF00FF00FF00FF;
+        x2 &= 0x00FF00FF00FF00FF;
+        x3 &= 0x00FF00FF00FF00FF;
         *q0 = x0 | (x2 << 8);
         *q1 = x1 | (x3 << 8);
     }
@@ -340,42 +350,41 @@ pub mod aes256ctr {
             }
             tmp ^= skey[i - nk];
             skey[i] = tmp;
+            j += 1;
             if j == nk {
                 j = 0;
                 k += 1;
-            } else {
-                j += 1;
             }
         }
 
         let mut j = 0;
 
         for i in (0..nkf).step_by(4) {
-            let mut temp: [u32; 4] = [0; 4];
-
-            // Extract 4 u32 values from skey into temp
-            temp.copy_from_slice(&skey[i..i + 4]);
-
-            let mut q0 = 0;
-            let mut q1 = 0;
-
-            br_aes_ct64_interleave_in(&mut q0, &mut q1, &temp);
-
-            br_aes_ct64_ortho(&mut [q0, q1, 0, 0, 0, 0, 0, 0]);
-
-            for idx in 0..4 {
-                let q_idx = idx * 2;
-                comp_skey[j] = (q0 & 0x1111111111111111)
-                    | (q1 & 0x2222222222222222)
-                    | (comp_skey[j] & 0x4444444444444444)
-                    | (comp_skey[j + 1] & 0x8888888888888888);
-                comp_skey[j + 1] = (q0 & 0x1111111111111111)
-                    | (q1 & 0x2222222222222222)
-                    | (comp_skey[j] & 0x4444444444444444)
-                    | (comp_skey[j + 1] & 0x8888888888888888);
-                j += 2;
-            }
+            let mut q: [u64; 8] = [0; 8];
+            let (mut q0, mut q4) = (0, 0);
+
+            br_aes_ct64_interleave_in(&mut q0, &mut q4, &skey[i..i + 4]);
+            q[0] = q0;
+            q[1] = q0;
+            q[2] = q0;
+            q[3] = q0;
+            q[4] = q4;
+            q[5] = q4;
+            q[6] = q4;
+            q[7] = q4;
+            br_aes_ct64_ortho(&mut q);
+
+            comp_skey[j] = (q[0] & 0x1111111111111111)
+                | (q[1] & 0x2222222222222222)
+                | (q[2] & 0x4444444444444444)
+                | (q[3] & 0x8888888888888888);
+            comp_skey[j + 1] = (q[4] & 0x1111111111111111)
+                | (q[5] & 0x2222222222222222)
+                | (q[6] & 0x4444444444444444)
+                | (q[7] & 0x8888888888888888);
+            j += 2;
         }
+        skey.zeroize();
     }
 
     pub fn br_aes_ct64_skey_expand(skey: &mut [u64], comp_skey: &[u64]) {
@@ -396,10 +405,10 @@ pub mod aes256ctr {
             x1 >>= 1;
             x2 >>= 2;
             x3 >>= 3;
-            skey[v] = (x0 << 4) - x0;
-            skey[v + 1] = (x1 << 4) - x1;
-            skey[v + 2] = (x2 << 4) - x2;
-            skey[v + 3] = (x3 << 4) - x3;
+            skey[v] = (x0 << 4).wrapping_sub(x0);
+            skey[v + 1] = (x1 << 4).wrapping_sub(x1);
+            skey[v + 2] = (x2 << 4).wrapping_sub(x2);
+            skey[v + 3] = (x3 << 4).wrapping_sub(x3);
             u += 1;
             v += 4;
         }
@@ -466,11 +475,9 @@ pub mod aes256ctr {
         q[7] = q6 ^ r6 ^ r7 ^ rotr32(q7 ^ r7);
     }
     
-    pub fn inc4_be(x: &mut [u32]) {
-        for xi in x.iter_mut() {
-            *xi = xi.to_be() + 4;
-            *xi = u32::from_be(*xi);
-        }
+    pub fn inc4_be(x: &mut u32) {
+        let t = br_swap32(*x).wrapping_add(4);
+        *x = br_swap32(t);
     }
 
     pub fn aes_ctr4x(out: &mut [u8], ivw: &mut [u32], sk_exp: &[u64]) {
@@ -511,8 +518,14 @@ pub mod aes256ctr {
         }
 
         br_range_enc32le(out, &w, 16);
-
-        inc4_be(&mut ivw[12..]);
+        w.zeroize();
+        q.zeroize();
+
+        // Increase counter for next 4 blocks
+        inc4_be(&mut ivw[3]);
+        inc4_be(&mut ivw[7]);
+        inc4_be(&mut ivw[11]);
+        inc4_be(&mut ivw[15]);
     }
 
     pub fn br_aes_ct64_ctr_init(sk_exp: &mut [u64; 120], key: &[u8]) {
@@ -520,32 +533,36 @@ pub mod aes256ctr {
     
         br_aes_ct64_keysched(&mut skey, key);
         br_aes_ct64_skey_expand(sk_exp, &skey);
+        skey.zeroize();
     }
     pub fn br_aes_ct64_ctr_run(sk_exp: &mut [u64; 120], iv: &[u8], cc: u32, data: &mut [u8], mut len: usize) {
         let mut ivw: [u32; 16] = [0; 16];
 
         br_range_dec32le(&mut ivw, 3, iv);
-
-        let mut ivw_copy: [u32; 16] = [0; 16]; // Create a temporary array for the copy
-        ivw_copy[0..12].copy_from_slice(&ivw[0..12]);
-        ivw_copy[3] = cc.to_be();
-        ivw_copy[7] = (cc + 1).to_be();
-        ivw_copy[11] = (cc + 2).to_be();
-        ivw_copy[15] = (cc + 3).to_be();
+        for i in 0..3 {
+            ivw[4 + i] = ivw[i];
+            ivw[8 + i] = ivw[i];
+            ivw[12 + i] = ivw[i];
+        }
+        ivw[3] = br_swap32(cc);
+        ivw[7] = br_swap32(cc + 1);
+        ivw[11] = br_swap32(cc + 2);
+        ivw[15] = br_swap32(cc + 3);
 
         let mut data_ptr = 0;  
 
         while len > 64 {
-            aes_ctr4x(&mut data[data_ptr..(data_ptr + 64)], &mut ivw_copy, sk_exp);
+            aes_ctr4x(&mut data[data_ptr..(data_ptr + 64)], &mut ivw, sk_exp);
             data_ptr += 64;
             len -= 64;
         }
         if len > 0 {
             let mut tmp: [u8; 64] = [0; 64];
-            aes_ctr4x(&mut tmp, &mut ivw_copy, sk_exp);
+            aes_ctr4x(&mut tmp, &mut ivw, sk_exp);
             for i in 0..len {
                 data[data_ptr + i] = tmp[i];
             }
+            tmp.zeroize();
         }
     }
 
@@ -555,21 +572,23 @@ pub mod aes256ctr {
     
         br_aes_ct64_ctr_init(&mut sk_exp, key);
         br_aes_ct64_ctr_run(&mut sk_exp, nonce, 0, out, outlen);
+        // The key is a secret noise seed
+        sk_exp.zeroize();
     }
 
     pub fn aes256ctr_init(s: &mut crate::kyber::xof_state::Aes256CtrCtx, key: &[u8], nonce: &[u8]) {
         br_aes_ct64_ctr_init(&mut s.sk_exp, key);
 
         br_range_dec32le(&mut s.ivw, 3, nonce);
-
-        let mut temp_slice = [0u32; 16];
-        temp_slice[0..12].copy_from_slice(&s.ivw[0..12]);
-        temp_slice[3] = 0;
-        temp_slice[7] = 1;
-        temp_slice[11] = 2;
-        temp_slice[15] = 3;
-
-        s.ivw.copy_from_slice(&temp_slice);
+        for i in 0..3 {
+            s.ivw[4 + i] = s.ivw[i];
+            s.ivw[8 + i] = s.ivw[i];
+            s.ivw[12 + i] = s.ivw[i];
+        }
+        s.ivw[3] = br_swap32(0);
+        s.ivw[7] = br_swap32(1);
+        s.ivw[11] = br_swap32(2);
+        s.ivw[15] = br_swap32(3);
     }
 
     pub fn aes256ctr_squeezeblocks(out: &mut [u8], nblocks: usize, s: &mut crate::kyber::xof_state::Aes256CtrCtx) {
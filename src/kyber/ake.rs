@@ -0,0 +1,289 @@
+// Kyber.UAKE and Kyber.AKE, the key exchanges of section 5 of the Kyber paper
+// (Bos et al., "CRYSTALS-Kyber: a CCA-secure module-lattice-based KEM"), with
+// a transcript hash and key confirmation added.
+//
+// In Kyber.UAKE only the responder is authenticated, by a static key the
+// initiator knows in advance:
+//
+//     initiator                                  responder (pk_R, sk_R)
+//     (pk_e, sk_e) = KeyGen()
+//     (c_R, k_R) = Enc(pk_R)
+//                      pk_e || c_R  -------->
+//                                                k_R = Dec(sk_R, c_R)
+//                                                (c_e, k_e) = Enc(pk_e)
+//                   <--------  c_e || tag_R
+//     k_e = Dec(sk_e, c_e)
+//                            tag_I  -------->
+//
+// Kyber.AKE authenticates both sides. The responder also knows the initiator's
+// static key pk_I, encapsulates to it with (c_I, k_I) = Enc(pk_I) and sends c_I
+// after c_e, and the initiator decapsulates it with sk_I.
+//
+// The paper takes the session key as H(k_e || k_R [|| k_I]). Here the whole
+// transcript is hashed in as well, and a second key is derived for the
+// confirmation tags:
+//
+//     th    = SHA3-256(label || pk_R [|| pk_I] || pk_e || c_R || c_e [|| c_I])
+//     k     = k_e || k_R [|| k_I]
+//     key   = SHA3-256(label || " session key" || k || th)
+//     kc    = SHA3-256(label || " confirmation key" || k || th)
+//     tag_R = SHA3-256(kc || "responder")
+//     tag_I = SHA3-256(kc || "initiator")
+//
+// where label is "Kyber.UAKE " or "Kyber.AKE " followed by the parameter set
+// name, for example "Kyber.AKE ML-KEM-768". Every field has a length fixed by
+// the label, so the concatenations are unambiguous.
+//
+// Decapsulation never fails, a wrong key just gives another secret, so a
+// responder without sk_R, an initiator without sk_I or an altered message all
+// show up as a tag that does not match. tag_R is sent first and convinces the
+// initiator, tag_I then convinces the responder.
+//
+// Messages are plain bytes of a length known from the protocol and parameter
+// set, so they can be carried over any transport.
+
+use zeroize::Zeroizing;
+
+use crate::error::KyberError;
+use crate::kyber::fips202::fips202::sha3_256;
+use crate::kyber::kem_rng::{GlobalKyberRng, KemRng};
+use crate::kyber::kyber_api::{Kem, KemBytes, SharedSecret};
+use crate::kyber::verify::verify::verify;
+
+/// Length of a confirmation tag, and so of the last message.
+pub const CONFIRMATION_BYTES: usize = 32;
+
+/// Which of the two key exchanges to run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// Kyber.UAKE, only the responder has a static key.
+    Uake,
+    /// Kyber.AKE, both sides have a static key known to the other.
+    Ake,
+}
+
+impl Protocol {
+    pub fn name(self) -> &'static str {
+        match self {
+            Protocol::Uake => "Kyber.UAKE",
+            Protocol::Ake => "Kyber.AKE",
+        }
+    }
+
+    /// Length of the initiator's first message, `pk_e || c_R`.
+    pub fn initiation_bytes<K: Kem>(self) -> usize {
+        K::PUBLIC_KEY_BYTES + K::CIPHERTEXT_BYTES
+    }
+
+    /// Length of the responder's message, the ciphertexts and `tag_R`.
+    pub fn response_bytes<K: Kem>(self) -> usize {
+        self.response_ciphertexts() * K::CIPHERTEXT_BYTES + CONFIRMATION_BYTES
+    }
+
+    fn response_ciphertexts(self) -> usize {
+        match self {
+            Protocol::Uake => 1,
+            Protocol::Ake => 2,
+        }
+    }
+
+    fn label<K: Kem>(self) -> String {
+        format!("{} {}", self.name(), K::PARAMETER_SET)
+    }
+}
+
+// The session key and the two tags of a finished transcript
+struct Keys {
+    session: SharedSecret,
+    responder_tag: [u8; CONFIRMATION_BYTES],
+    initiator_tag: [u8; CONFIRMATION_BYTES],
+}
+
+fn sha3(parts: &[&[u8]]) -> [u8; 32] {
+    let data = Zeroizing::new(parts.concat());
+    let mut out = [0u8; 32];
+    sha3_256(&mut out, &data);
+    out
+}
+
+fn derive<K: Kem>(
+    protocol: Protocol,
+    responder_pk: &K::PublicKey,
+    initiator_pk: Option<&K::PublicKey>,
+    initiation: &[u8],
+    ciphertexts: &[u8],
+    secrets: &[&SharedSecret],
+) -> Result<Keys, KyberError> {
+    let label = protocol.label::<K>();
+    let initiator_pk = initiator_pk.map(KemBytes::as_bytes).unwrap_or_default();
+    let th = sha3(&[label.as_bytes(), responder_pk.as_bytes(), initiator_pk, initiation, ciphertexts]);
+    let k = Zeroizing::new(secrets.iter().flat_map(|secret| secret.as_bytes()).copied().collect::<Vec<u8>>());
+
+    let session = Zeroizing::new(sha3(&[label.as_bytes(), b" session key", &k, &th]));
+    let kc = Zeroizing::new(sha3(&[label.as_bytes(), b" confirmation key", &k, &th]));
+    Ok(Keys {
+        session: SharedSecret::from_bytes(&session[..])?,
+        responder_tag: sha3(&[&kc[..], b"responder"]),
+        initiator_tag: sha3(&[&kc[..], b"initiator"]),
+    })
+}
+
+fn tags_match(expected: &[u8; CONFIRMATION_BYTES], received: &[u8]) -> bool {
+    received.len() == CONFIRMATION_BYTES && verify(expected, received, CONFIRMATION_BYTES) == 0
+}
+
+/// The initiator after sending its first message, waiting for the response.
+pub struct Initiator<'a, K: Kem> {
+    protocol: Protocol,
+    responder_pk: &'a K::PublicKey,
+    own: Option<(&'a K::PublicKey, &'a K::SecretKey)>,
+    ephemeral_sk: K::SecretKey,
+    k_r: SharedSecret,
+    initiation: Vec<u8>,
+}
+
+impl<'a, K: Kem> Initiator<'a, K> {
+    /// Starts Kyber.UAKE with the responder whose static key is `responder_pk`,
+    /// returning the state and the first message. The randomness comes from the
+    /// library-wide DRBG.
+    pub fn uake(responder_pk: &'a K::PublicKey) -> Result<(Self, Vec<u8>), KyberError> {
+        Self::uake_with_rng(responder_pk, &mut GlobalKyberRng)
+    }
+
+    /// As `uake`, with the ephemeral key and the encapsulation drawn from `rng`.
+    pub fn uake_with_rng<R: KemRng + ?Sized>(responder_pk: &'a K::PublicKey, rng: &mut R) -> Result<(Self, Vec<u8>), KyberError> {
+        Self::start(Protocol::Uake, responder_pk, None, rng)
+    }
+
+    /// Starts Kyber.AKE with the responder whose static key is `responder_pk`,
+    /// authenticating with the static key pair `own_pk`, `own_sk`.
+    pub fn ake(responder_pk: &'a K::PublicKey, own_pk: &'a K::PublicKey, own_sk: &'a K::SecretKey) -> Result<(Self, Vec<u8>), KyberError> {
+        Self::ake_with_rng(responder_pk, own_pk, own_sk, &mut GlobalKyberRng)
+    }
+
+    /// As `ake`, with the ephemeral key and the encapsulation drawn from `rng`.
+    pub fn ake_with_rng<R: KemRng + ?Sized>(
+        responder_pk: &'a K::PublicKey,
+        own_pk: &'a K::PublicKey,
+        own_sk: &'a K::SecretKey,
+        rng: &mut R,
+    ) -> Result<(Self, Vec<u8>), KyberError> {
+        Self::start(Protocol::Ake, responder_pk, Some((own_pk, own_sk)), rng)
+    }
+
+    fn start<R: KemRng + ?Sized>(
+        protocol: Protocol,
+        responder_pk: &'a K::PublicKey,
+        own: Option<(&'a K::PublicKey, &'a K::SecretKey)>,
+        rng: &mut R,
+    ) -> Result<(Self, Vec<u8>), KyberError> {
+        let (ephemeral_pk, ephemeral_sk) = K::keypair_with_rng(rng)?;
+        let (c_r, k_r) = K::encapsulate_with_rng(responder_pk, rng)?;
+        let initiation = [ephemeral_pk.as_bytes(), c_r.as_bytes()].concat();
+        let state = Initiator { protocol, responder_pk, own, ephemeral_sk, k_r, initiation: initiation.clone() };
+        Ok((state, initiation))
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Checks the responder's message, returning the session key and the
+    /// confirmation to send back. Fails if the responder does not hold the
+    /// static key or a message was altered.
+    pub fn finish(self, response: &[u8]) -> Result<(SharedSecret, Vec<u8>), KyberError> {
+        if response.len() != self.protocol.response_bytes::<K>() {
+            return Err(KyberError::Ake("the response has the wrong length"));
+        }
+        let (ciphertexts, tag) = response.split_at(response.len() - CONFIRMATION_BYTES);
+        let (c_e, c_i) = ciphertexts.split_at(K::CIPHERTEXT_BYTES);
+        let k_e = K::decapsulate(&self.ephemeral_sk, &K::Ciphertext::from_bytes(c_e)?)?;
+
+        let own_pk = self.own.map(|(pk, _)| pk);
+        let keys = match self.own {
+            Some((_, own_sk)) => {
+                let k_i = K::decapsulate(own_sk, &K::Ciphertext::from_bytes(c_i)?)?;
+                derive::<K>(self.protocol, self.responder_pk, own_pk, &self.initiation, ciphertexts, &[&k_e, &self.k_r, &k_i])?
+            }
+            None => derive::<K>(self.protocol, self.responder_pk, None, &self.initiation, ciphertexts, &[&k_e, &self.k_r])?,
+        };
+        if !tags_match(&keys.responder_tag, tag) {
+            return Err(KyberError::Ake("the response does not confirm the key, the responder does not hold its static key or a message was altered"));
+        }
+        Ok((keys.session, keys.initiator_tag.to_vec()))
+    }
+}
+
+/// The responder's static key, and in Kyber.AKE the initiator's, ready to
+/// answer any number of initiations.
+pub struct Responder<'a, K: Kem> {
+    protocol: Protocol,
+    own_pk: &'a K::PublicKey,
+    own_sk: &'a K::SecretKey,
+    initiator_pk: Option<&'a K::PublicKey>,
+}
+
+impl<'a, K: Kem> Responder<'a, K> {
+    /// A Kyber.UAKE responder with the static key pair `own_pk`, `own_sk`.
+    pub fn uake(own_pk: &'a K::PublicKey, own_sk: &'a K::SecretKey) -> Self {
+        Responder { protocol: Protocol::Uake, own_pk, own_sk, initiator_pk: None }
+    }
+
+    /// A Kyber.AKE responder with the static key pair `own_pk`, `own_sk`,
+    /// accepting only the initiator whose static key is `initiator_pk`.
+    pub fn ake(own_pk: &'a K::PublicKey, own_sk: &'a K::SecretKey, initiator_pk: &'a K::PublicKey) -> Self {
+        Responder { protocol: Protocol::Ake, own_pk, own_sk, initiator_pk: Some(initiator_pk) }
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Answers the initiator's first message, returning the state waiting for
+    /// its confirmation and the response to send. The randomness comes from the
+    /// library-wide DRBG.
+    pub fn respond(&self, initiation: &[u8]) -> Result<(AwaitingConfirmation, Vec<u8>), KyberError> {
+        self.respond_with_rng(initiation, &mut GlobalKyberRng)
+    }
+
+    /// As `respond`, with the encapsulations drawn from `rng`.
+    pub fn respond_with_rng<R: KemRng + ?Sized>(&self, initiation: &[u8], rng: &mut R) -> Result<(AwaitingConfirmation, Vec<u8>), KyberError> {
+        if initiation.len() != self.protocol.initiation_bytes::<K>() {
+            return Err(KyberError::Ake("the initiation has the wrong length"));
+        }
+        let (ephemeral_pk, c_r) = initiation.split_at(K::PUBLIC_KEY_BYTES);
+        let k_r = K::decapsulate(self.own_sk, &K::Ciphertext::from_bytes(c_r)?)?;
+        let (c_e, k_e) = K::encapsulate_with_rng(&K::PublicKey::from_bytes(ephemeral_pk)?, rng)?;
+
+        let mut response = c_e.as_bytes().to_vec();
+        let keys = match self.initiator_pk {
+            Some(initiator_pk) => {
+                let (c_i, k_i) = K::encapsulate_with_rng(initiator_pk, rng)?;
+                response.extend_from_slice(c_i.as_bytes());
+                derive::<K>(self.protocol, self.own_pk, Some(initiator_pk), initiation, &response, &[&k_e, &k_r, &k_i])?
+            }
+            None => derive::<K>(self.protocol, self.own_pk, None, initiation, &response, &[&k_e, &k_r])?,
+        };
+        response.extend_from_slice(&keys.responder_tag);
+        Ok((AwaitingConfirmation { session: keys.session, expected: keys.initiator_tag }, response))
+    }
+}
+
+/// The responder after sending its response, holding the session key until
+/// the initiator confirms it.
+pub struct AwaitingConfirmation {
+    session: SharedSecret,
+    expected: [u8; CONFIRMATION_BYTES],
+}
+
+impl AwaitingConfirmation {
+    /// Checks the initiator's confirmation and releases the session key. Fails
+    /// if the initiator did not derive the same key, because it does not hold
+    /// its static key or a message was altered.
+    pub fn finish(self, confirmation: &[u8]) -> Result<SharedSecret, KyberError> {
+        if !tags_match(&self.expected, confirmation) {
+            return Err(KyberError::Ake("the confirmation does not match, the initiator does not hold its static key or a message was altered"));
+        }
+        Ok(self.session)
+    }
+}
@@ -0,0 +1,135 @@
+// Runtime selection between the scalar reference code and the SIMD versions of
+// the NTT, the base multiplication and Keccak. The CPU is probed once; every
+// backend gives bit for bit the output of the scalar code, which stays the
+// reference and is what non-x86_64 targets use.
+
+use lazy_static::lazy_static;
+
+use crate::kyber::config::KYBER_N;
+use crate::kyber::fips202::fips202::keccak_f1600_state_permute;
+use crate::kyber::ntt::ntt;
+
+/// An implementation of the polynomial arithmetic and Keccak permutation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// The reference code, available everywhere.
+    Scalar,
+    /// 128-bit vectors, available on every x86_64 CPU.
+    Sse2,
+    /// 256-bit vectors, the layout of the pqcrystals avx2 implementation.
+    Avx2,
+}
+
+lazy_static! {
+    static ref DETECTED: Backend = Backend::detect();
+}
+
+impl Backend {
+    /// Every backend, from slowest to fastest.
+    pub const ALL: [Backend; 3] = [Backend::Scalar, Backend::Sse2, Backend::Avx2];
+
+    fn detect() -> Backend {
+        if Backend::Avx2.is_available() {
+            Backend::Avx2
+        } else if Backend::Sse2.is_available() {
+            Backend::Sse2
+        } else {
+            Backend::Scalar
+        }
+    }
+
+    /// The fastest backend this CPU supports, which the KEM uses.
+    pub fn detected() -> Backend {
+        *DETECTED
+    }
+
+    pub fn is_available(self) -> bool {
+        match self {
+            Backend::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse2 => is_x86_feature_detected!("sse2"),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+
+    /// The backends this CPU supports.
+    pub fn available() -> Vec<Backend> {
+        Backend::ALL.iter().copied().filter(|backend| backend.is_available()).collect()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Scalar => "scalar",
+            Backend::Sse2 => "sse2",
+            Backend::Avx2 => "avx2",
+        }
+    }
+
+    // Falls back to the scalar code for a backend the CPU lacks, which keeps
+    // the unsafe calls below sound whatever backend is asked for
+    fn checked(self) -> Backend {
+        if self.is_available() {
+            self
+        } else {
+            Backend::Scalar
+        }
+    }
+
+    /// Forward NTT in place, output in bit-reversed order and not reduced.
+    pub fn ntt(self, r: &mut [i16; KYBER_N]) {
+        match self.checked() {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => unsafe { crate::kyber::ntt_avx2::ntt(r) },
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse2 => unsafe { crate::kyber::ntt_sse2::ntt(r) },
+            _ => ntt::ntt(r),
+        }
+    }
+
+    /// Inverse NTT in place, multiplying by the Montgomery factor 2^16.
+    pub fn invntt(self, r: &mut [i16; KYBER_N]) {
+        match self.checked() {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => unsafe { crate::kyber::ntt_avx2::invntt(r) },
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse2 => unsafe { crate::kyber::ntt_sse2::invntt(r) },
+            _ => ntt::invntt(r),
+        }
+    }
+
+    /// Product of `a` and `b` in the NTT domain, with a Montgomery reduction.
+    pub fn basemul(self, r: &mut [i16; KYBER_N], a: &[i16; KYBER_N], b: &[i16; KYBER_N]) {
+        match self.checked() {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => unsafe { crate::kyber::ntt_avx2::basemul(r, a, b) },
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse2 => unsafe { crate::kyber::ntt_sse2::basemul(r, a, b) },
+            _ => {
+                for i in 0..KYBER_N / 4 {
+                    let mut a0 = [a[4 * i], a[4 * i + 1]];
+                    let mut a1 = [a[4 * i + 2], a[4 * i + 3]];
+                    ntt::basemul(&mut r[4 * i..4 * i + 2], &mut a0, &b[4 * i..4 * i + 2], ntt::ZETAS[64 + i]);
+                    ntt::basemul(&mut r[4 * i + 2..4 * i + 4], &mut a1, &b[4 * i + 2..4 * i + 4], -ntt::ZETAS[64 + i]);
+                }
+            }
+        }
+    }
+
+    /// Applies Keccak-f1600 to each of four states.
+    pub fn keccak_f1600_x4(self, states: &mut [[u64; 25]; 4]) {
+        match self.checked() {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => unsafe { crate::kyber::fips202x4::avx2::keccak_f1600_x4(states) },
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse2 => unsafe { crate::kyber::fips202x4::sse2::keccak_f1600_x4(states) },
+            _ => {
+                for state in states.iter_mut() {
+                    keccak_f1600_state_permute(state);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// Speed benchmarks, run like test_speed.c in the reference code: each operation
+// is called `iterations` times back to back with the cycle counter read before
+// every call, and speed_print turns the readings into a median and average per
+// call.
+
+use std::fmt::Write;
+use std::hint::black_box;
+
+use crate::error::KyberError;
+use crate::kyber::backend::Backend;
+use crate::kyber::indcpa::indcpa::{gen_matrix, indcpa_dec, indcpa_enc, indcpa_keypair_derand};
+use crate::kyber::kyber::KyberParams;
+use crate::kyber::kyber_api::{prepare, Kem};
+use crate::kyber::poly::poly::{poly_getnoise_eta1, poly_invntt_tomont, poly_ntt};
+use crate::kyber::poly_struct::PolyStruct;
+use crate::kyber::polyvec_struct::{with_rank, PolyVec};
+use crate::kyber::speed_print::speed_print::{cpucycles, print_summary, summarize, UNIT};
+
+/// Calls per operation used by `kyber bench` and `cargo bench` unless told otherwise.
+pub const DEFAULT_ITERATIONS: usize = 1000;
+
+/// Median and average count per call of one operation.
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    pub name: &'static str,
+    pub median: u64,
+    pub average: u64,
+}
+
+/// Result of `run` for one parameter set.
+#[derive(Clone, Debug)]
+pub struct BenchReport {
+    pub parameter_set: String,
+    /// Backend the NTT, basemul and 4-way Keccak ran on.
+    pub backend: &'static str,
+    /// "cycles", or "ns" where there is no cycle counter.
+    pub unit: &'static str,
+    pub iterations: usize,
+    pub results: Vec<BenchResult>,
+}
+
+impl BenchReport {
+    /// Prints every result through speed_print, as the reference speed test does.
+    pub fn print(&self) {
+        println!("{} ({} backend, {} iterations)", self.parameter_set, self.backend, self.iterations);
+        println!();
+        for result in &self.results {
+            print_summary(result.name, result.median, result.average);
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        write!(
+            json,
+            "{{\"parameter_set\":\"{}\",\"backend\":\"{}\",\"unit\":\"{}\",\"iterations\":{},\"results\":[",
+            self.parameter_set, self.backend, self.unit, self.iterations
+        )
+        .unwrap();
+        for (i, result) in self.results.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(json, "{{\"name\":\"{}\",\"median\":{},\"average\":{}}}", result.name, result.median, result.average).unwrap();
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+/// Several reports as one JSON array, for regression tracking.
+pub fn to_json(reports: &[BenchReport]) -> String {
+    let reports: Vec<String> = reports.iter().map(BenchReport::to_json).collect();
+    format!("[{}]", reports.join(","))
+}
+
+// Calls `f` `iterations` times, reading the counter before each call and once
+// after the last
+fn time(name: &'static str, iterations: usize, mut f: impl FnMut()) -> BenchResult {
+    let mut t = vec![0u64; iterations + 1];
+    for stamp in t.iter_mut() {
+        *stamp = cpucycles();
+        f();
+    }
+    let (median, average) = summarize(&mut t).unwrap();
+    BenchResult { name, median, average }
+}
+
+/// Times the matrix expansion, NTT, noise sampling, IND-CPA and KEM operations
+/// of `K`, `iterations` calls each.
+pub fn run<K: Kem>(iterations: usize) -> Result<BenchReport, KyberError> {
+    let iterations = iterations.max(1);
+    let params = prepare(K::SECURITY_LEVEL, K::VARIANT)?;
+    let mut results = with_rank!(params, internals(&params, iterations));
+
+    let (pk, sk) = K::keypair()?;
+    let (ct, _) = K::encapsulate(&pk)?;
+    results.push(time("kyber_keypair", iterations, || {
+        let _ = black_box(K::keypair());
+    }));
+    results.push(time("kyber_encaps", iterations, || {
+        let _ = black_box(K::encapsulate(black_box(&pk)));
+    }));
+    results.push(time("kyber_decaps", iterations, || {
+        let _ = black_box(K::decapsulate(black_box(&sk), black_box(&ct)));
+    }));
+
+    Ok(BenchReport {
+        parameter_set: K::PARAMETER_SET.to_string(),
+        backend: Backend::detected().name(),
+        unit: UNIT,
+        iterations,
+        results,
+    })
+}
+
+// The operations below the KEM, on fixed inputs
+fn internals<const K: usize>(params: &KyberParams, iterations: usize) -> Vec<BenchResult> {
+    let seed = [7u8; 32];
+    let mut results = Vec::new();
+
+    let mut a: [PolyVec<K>; K] = core::array::from_fn(|_| PolyVec::new());
+    results.push(time("gen_matrix", iterations, || gen_matrix(params, black_box(&mut a), &seed, false)));
+
+    // The NTTs start from the same polynomial every call so the coefficients stay in range
+    let mut input = PolyStruct::new();
+    poly_getnoise_eta1(params, &mut input, &seed, 0);
+    let mut r = PolyStruct::new();
+    results.push(time("poly_ntt", iterations, || {
+        r.coeffs = input.coeffs;
+        poly_ntt(black_box(&mut r));
+    }));
+    results.push(time("poly_invntt_tomont", iterations, || {
+        r.coeffs = input.coeffs;
+        poly_invntt_tomont(black_box(&mut r));
+    }));
+    results.push(time("poly_getnoise_eta1", iterations, || poly_getnoise_eta1(params, black_box(&mut r), &seed, 0)));
+
+    let mut pk = vec![0u8; params.kyber_indcpa_publickeybytes as usize];
+    let mut sk = vec![0u8; params.kyber_indcpa_secretkeybytes as usize];
+    let mut ct = vec![0u8; params.kyber_indcpa_bytes as usize];
+    let mut m = [0u8; 32];
+    results.push(time("indcpa_keypair", iterations, || indcpa_keypair_derand(params, black_box(&mut pk), &mut sk, &seed)));
+    results.push(time("indcpa_enc", iterations, || indcpa_enc(params, black_box(&mut ct), &m, &pk, &seed)));
+    results.push(time("indcpa_dec", iterations, || indcpa_dec(params, black_box(&mut m), &ct, &sk)));
+
+    results
+}
@@ -1,7 +1,8 @@
 use std::convert::TryInto;
 pub mod cbd
 {
-    use std::env;
+    use crate::kyber::config::KYBER_N;
+    use crate::kyber::kyber::KyberParams;
 
     pub fn test()
     {
@@ -12,7 +13,7 @@ pub mod cbd
     }
 
     pub fn cbd2(r: &mut crate::kyber::poly_struct::PolyStruct, buf: &[u8]) {
-        let kyber_n: u32 = crate::kyber::get_env_var("KYBER_N").unwrap();
+        let kyber_n = KYBER_N as u32;
         for i in 0..kyber_n / 8 {
             let t = load32_littleendian(&buf[(4 * i) as usize..(4 * (i + 1)) as usize]);
             let d = t & 0x55555555;
@@ -33,7 +34,7 @@ pub mod cbd
 
 
     pub fn cbd3(r: &mut crate::kyber::poly_struct::PolyStruct, buf: &[u8]) {
-        let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
+        let kyber_n = KYBER_N as u32;
             for i in 0..kyber_n / 4 {
                 let t = load24_littleendian(&buf[(3 * i) as usize..]);
 
@@ -50,8 +51,8 @@ pub mod cbd
         
     }
 
-    pub fn cbd_eta1(r: &mut crate::kyber::poly_struct::PolyStruct, buf: &[u8]) { // had ; kyber_eta1 * kyber_n / 4]
-        let kyber_eta1: u32 = crate::kyber ::get_env_var("KYBER_ETA1").unwrap();
+    pub fn cbd_eta1(params: &KyberParams, r: &mut crate::kyber::poly_struct::PolyStruct, buf: &[u8]) { // had ; kyber_eta1 * kyber_n / 4]
+        let kyber_eta1 = params.kyber_eta1;
             if kyber_eta1 == 2
             {
                 cbd2(r, buf);
@@ -64,8 +65,8 @@ pub mod cbd
 
     }
 
-    pub fn cbd_eta2(r: &mut crate::kyber::poly_struct::PolyStruct, buf: &[u8]) { // was  kyber_eta2 * kyber_n / 4
-        let kyber_eta2: u32 = crate::kyber ::get_env_var("KYBER_ETA2").unwrap();
+    pub fn cbd_eta2(params: &KyberParams, r: &mut crate::kyber::poly_struct::PolyStruct, buf: &[u8]) { // was  kyber_eta2 * kyber_n / 4
+        let kyber_eta2 = params.kyber_eta2;
             if kyber_eta2 == 2
             {
                 cbd2(r, buf);
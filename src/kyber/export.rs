@@ -0,0 +1,595 @@
+// Export and import of keys and ciphertexts in the formats of
+// enums::Export_Category.
+//
+// PEM wraps the DER structures of the IETF LAMPS ML-KEM certificate profile:
+// public keys in a SubjectPublicKeyInfo, secret keys in a PKCS#8
+// OneAsymmetricKey holding the expanded decapsulation key. That is what
+// OpenSSL 3.5 reads and writes for ML-KEM. Round-3 Kyber and Kyber-90s have no
+// NIST OIDs and use the ones assigned by the Open Quantum Safe project.
+// Ciphertexts have no standard container and are exported as raw bytes.
+//
+// JSON and Protobuf carry the parameter set and object type next to the bytes,
+// so importing data of another parameter set fails instead of decoding as
+// garbage. The Protobuf message is
+//
+//     message KyberObject {
+//         string parameter_set = 1;  // e.g. "ML-KEM-768"
+//         Type type = 2;             // PUBLIC_KEY = 1, SECRET_KEY = 2, CIPHERTEXT = 3
+//         bytes value = 3;
+//     }
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use zeroize::Zeroizing;
+
+use crate::enums::{Export_Category, KyberVariant};
+use crate::error::KyberError;
+use crate::kyber::kyber_api::{Kem, KemBytes};
+use crate::kyber::verify::verify::verify;
+
+const SEED_BYTES: usize = 64;
+
+/// Dotted OID identifying the parameter set `K` in SubjectPublicKeyInfo and PKCS#8.
+pub fn oid<K: Kem>() -> &'static str {
+    match (K::VARIANT, K::SECURITY_LEVEL) {
+        (KyberVariant::MlKem, 2) => "2.16.840.1.101.3.4.4.1",
+        (KyberVariant::MlKem, 3) => "2.16.840.1.101.3.4.4.2",
+        (KyberVariant::MlKem, _) => "2.16.840.1.101.3.4.4.3",
+        (KyberVariant::Round3, 2) => "1.3.6.1.4.1.22554.5.6.1",
+        (KyberVariant::Round3, 3) => "1.3.6.1.4.1.22554.5.6.2",
+        (KyberVariant::Round3, _) => "1.3.6.1.4.1.22554.5.6.3",
+        (KyberVariant::Kyber90s, 2) => "1.3.6.1.4.1.22554.5.10.1",
+        (KyberVariant::Kyber90s, 3) => "1.3.6.1.4.1.22554.5.10.2",
+        (KyberVariant::Kyber90s, _) => "1.3.6.1.4.1.22554.5.10.3",
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Object {
+    PublicKey,
+    SecretKey,
+    Ciphertext,
+}
+
+impl Object {
+    fn name(self) -> &'static str {
+        match self {
+            Object::PublicKey => "public_key",
+            Object::SecretKey => "secret_key",
+            Object::Ciphertext => "ciphertext",
+        }
+    }
+
+    // Value of the Type enum in the Protobuf message
+    fn protobuf_type(self) -> u64 {
+        match self {
+            Object::PublicKey => 1,
+            Object::SecretKey => 2,
+            Object::Ciphertext => 3,
+        }
+    }
+
+    fn pem_label<K: Kem>(self) -> String {
+        match self {
+            Object::PublicKey => "PUBLIC KEY".to_string(),
+            Object::SecretKey => "PRIVATE KEY".to_string(),
+            Object::Ciphertext => format!("{} CIPHERTEXT", K::PARAMETER_SET),
+        }
+    }
+}
+
+fn encoding_error(reason: impl Into<String>) -> KyberError {
+    KyberError::Encoding(reason.into())
+}
+
+/// `pk` as a DER SubjectPublicKeyInfo.
+pub fn public_key_to_der<K: Kem>(pk: &K::PublicKey) -> Vec<u8> {
+    let mut algorithm = Vec::new();
+    der::write(der::OID, &der::encode_oid(oid::<K>()), &mut algorithm);
+
+    let mut key = vec![0u8]; // no unused bits
+    key.extend_from_slice(pk.as_bytes());
+
+    let mut spki = Vec::new();
+    der::write(der::SEQUENCE, &algorithm, &mut spki);
+    der::write(der::BIT_STRING, &key, &mut spki);
+
+    let mut out = Vec::new();
+    der::write(der::SEQUENCE, &spki, &mut out);
+    out
+}
+
+/// Reads a DER SubjectPublicKeyInfo, which must name `K` and hold a key that
+/// passes `Kem::validate_public_key`.
+pub fn public_key_from_der<K: Kem>(data: &[u8]) -> Result<K::PublicKey, KyberError> {
+    let mut outer = der::Reader::new(data);
+    let mut spki = der::Reader::new(outer.read(der::SEQUENCE)?);
+    outer.finish()?;
+
+    check_algorithm::<K>(spki.read(der::SEQUENCE)?)?;
+    let key = spki.read(der::BIT_STRING)?;
+    spki.finish()?;
+
+    match key.split_first() {
+        Some((0, key)) => {
+            let pk = K::PublicKey::from_bytes(key)?;
+            K::validate_public_key(&pk)?;
+            Ok(pk)
+        }
+        _ => Err(encoding_error("public key bit string is not whole bytes")),
+    }
+}
+
+/// `sk` as a DER PKCS#8 OneAsymmetricKey in the expanded key form.
+pub fn secret_key_to_der<K: Kem>(sk: &K::SecretKey) -> Zeroizing<Vec<u8>> {
+    let mut algorithm = Vec::new();
+    der::write(der::OID, &der::encode_oid(oid::<K>()), &mut algorithm);
+
+    let mut expanded = Zeroizing::new(Vec::new());
+    der::write(der::OCTET_STRING, sk.as_bytes(), &mut expanded);
+
+    let mut pkcs8 = Zeroizing::new(Vec::new());
+    der::write(der::INTEGER, &[0], &mut pkcs8);
+    der::write(der::SEQUENCE, &algorithm, &mut pkcs8);
+    der::write(der::OCTET_STRING, &expanded, &mut pkcs8);
+
+    let mut out = Zeroizing::new(Vec::new());
+    der::write(der::SEQUENCE, &pkcs8, &mut out);
+    out
+}
+
+/// Reads a DER PKCS#8 OneAsymmetricKey naming `K`. The key may be given as the
+/// 64-byte seed d || z, as the expanded key, or as both, in which case they must
+/// agree. The key must pass `Kem::validate_secret_key`.
+pub fn secret_key_from_der<K: Kem>(data: &[u8]) -> Result<K::SecretKey, KyberError> {
+    let mut outer = der::Reader::new(data);
+    let mut pkcs8 = der::Reader::new(outer.read(der::SEQUENCE)?);
+    outer.finish()?;
+
+    // Version 1 (v2) keys may carry the public key after the private key, which is ignored
+    match pkcs8.read(der::INTEGER)? {
+        [0] | [1] => {}
+        _ => return Err(encoding_error("unknown PKCS#8 version")),
+    }
+    check_algorithm::<K>(pkcs8.read(der::SEQUENCE)?)?;
+    let private_key = pkcs8.read(der::OCTET_STRING)?;
+
+    let mut choice = der::Reader::new(private_key);
+    let sk = match choice.peek_tag() {
+        Some(der::SEED) => secret_key_from_seed::<K>(choice.read(der::SEED)?)?,
+        Some(der::OCTET_STRING) => K::SecretKey::from_bytes(choice.read(der::OCTET_STRING)?)?,
+        Some(der::SEQUENCE) => {
+            let mut both = der::Reader::new(choice.read(der::SEQUENCE)?);
+            let from_seed = secret_key_from_seed::<K>(both.read(der::OCTET_STRING)?)?;
+            let sk = K::SecretKey::from_bytes(both.read(der::OCTET_STRING)?)?;
+            both.finish()?;
+            if verify(sk.as_bytes(), from_seed.as_bytes(), K::SECRET_KEY_BYTES) != 0 {
+                return Err(KyberError::InvalidKey("expanded key does not match its seed"));
+            }
+            sk
+        }
+        _ => return Err(encoding_error("unknown private key encoding")),
+    };
+    choice.finish()?;
+
+    K::validate_secret_key(&sk)?;
+    Ok(sk)
+}
+
+fn secret_key_from_seed<K: Kem>(seed: &[u8]) -> Result<K::SecretKey, KyberError> {
+    if seed.len() != SEED_BYTES {
+        return Err(KyberError::WrongKeyLength { expected: SEED_BYTES, actual: seed.len() });
+    }
+    let mut d = Zeroizing::new([0u8; 32]);
+    let mut z = Zeroizing::new([0u8; 32]);
+    d.copy_from_slice(&seed[..32]);
+    z.copy_from_slice(&seed[32..]);
+    let (_, sk) = K::keypair_derand(&d, &z)?;
+    Ok(sk)
+}
+
+// The AlgorithmIdentifier must be K's OID with no parameters
+fn check_algorithm<K: Kem>(algorithm: &[u8]) -> Result<(), KyberError> {
+    let mut algorithm = der::Reader::new(algorithm);
+    let found = algorithm.read(der::OID)?;
+    algorithm.finish()?;
+    if found != der::encode_oid(oid::<K>()).as_slice() {
+        return Err(encoding_error(format!("key is not a {} key", K::PARAMETER_SET)));
+    }
+    Ok(())
+}
+
+/// `pk` in `format`. PEM holds a SubjectPublicKeyInfo.
+pub fn export_public_key<K: Kem>(pk: &K::PublicKey, format: Export_Category) -> Result<Vec<u8>, KyberError> {
+    let der = public_key_to_der::<K>(pk);
+    Ok(export::<K>(Object::PublicKey, pk.as_bytes(), &der, format)?.to_vec())
+}
+
+/// Reads a public key exported with `export_public_key` in `format`.
+pub fn import_public_key<K: Kem>(data: &[u8], format: Export_Category) -> Result<K::PublicKey, KyberError> {
+    if format == Export_Category::PEM {
+        return public_key_from_der::<K>(&pem_decode(data, &Object::PublicKey.pem_label::<K>())?);
+    }
+    let pk = K::PublicKey::from_bytes(&import::<K>(Object::PublicKey, data, format)?)?;
+    K::validate_public_key(&pk)?;
+    Ok(pk)
+}
+
+/// `sk` in `format`. PEM holds a PKCS#8 OneAsymmetricKey.
+pub fn export_secret_key<K: Kem>(sk: &K::SecretKey, format: Export_Category) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+    let der = secret_key_to_der::<K>(sk);
+    export::<K>(Object::SecretKey, sk.as_bytes(), &der, format)
+}
+
+/// Reads a secret key exported with `export_secret_key` in `format`.
+pub fn import_secret_key<K: Kem>(data: &[u8], format: Export_Category) -> Result<K::SecretKey, KyberError> {
+    if format == Export_Category::PEM {
+        return secret_key_from_der::<K>(&pem_decode(data, &Object::SecretKey.pem_label::<K>())?);
+    }
+    let sk = K::SecretKey::from_bytes(&import::<K>(Object::SecretKey, data, format)?)?;
+    K::validate_secret_key(&sk)?;
+    Ok(sk)
+}
+
+/// `ct` in `format`. PEM holds the raw ciphertext under a label naming the
+/// parameter set, e.g. "ML-KEM-768 CIPHERTEXT".
+pub fn export_ciphertext<K: Kem>(ct: &K::Ciphertext, format: Export_Category) -> Result<Vec<u8>, KyberError> {
+    Ok(export::<K>(Object::Ciphertext, ct.as_bytes(), ct.as_bytes(), format)?.to_vec())
+}
+
+/// Reads a ciphertext exported with `export_ciphertext` in `format`.
+pub fn import_ciphertext<K: Kem>(data: &[u8], format: Export_Category) -> Result<K::Ciphertext, KyberError> {
+    if format == Export_Category::PEM {
+        return K::Ciphertext::from_bytes(&pem_decode(data, &Object::Ciphertext.pem_label::<K>())?);
+    }
+    K::Ciphertext::from_bytes(&import::<K>(Object::Ciphertext, data, format)?)
+}
+
+// `raw` is the object itself, `der` what goes inside PEM
+fn export<K: Kem>(object: Object, raw: &[u8], der: &[u8], format: Export_Category) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+    let out = match format {
+        Export_Category::Binary => raw.to_vec(),
+        Export_Category::Hexadecimal => hex::encode(raw).into_bytes(),
+        Export_Category::Base64 => STANDARD.encode(raw).into_bytes(),
+        Export_Category::PEM => pem_encode(der, &object.pem_label::<K>()),
+        Export_Category::JSON => format!(
+            "{{\"parameter_set\":\"{}\",\"type\":\"{}\",\"value\":\"{}\"}}",
+            K::PARAMETER_SET,
+            object.name(),
+            Zeroizing::new(STANDARD.encode(raw)).as_str()
+        )
+        .into_bytes(),
+        Export_Category::Protobuf => {
+            let mut out = Vec::new();
+            protobuf::write_bytes(1, K::PARAMETER_SET.as_bytes(), &mut out);
+            protobuf::write_varint_field(2, object.protobuf_type(), &mut out);
+            protobuf::write_bytes(3, raw, &mut out);
+            out
+        }
+        Export_Category::Exit => return Err(encoding_error("no export format selected")),
+    };
+    Ok(Zeroizing::new(out))
+}
+
+// The raw object bytes from every format but PEM
+fn import<K: Kem>(object: Object, data: &[u8], format: Export_Category) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+    let raw = match format {
+        Export_Category::Binary => data.to_vec(),
+        Export_Category::Hexadecimal => hex::decode(text(data)?.trim())?,
+        Export_Category::Base64 => base64_decode(text(data)?.trim())?,
+        Export_Category::JSON => {
+            let fields = json::parse_object(text(data)?)?;
+            let field = |name: &str| {
+                fields.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str()).ok_or_else(|| KyberError::MissingKeyword(name.to_string()))
+            };
+            check_header::<K>(object, field("parameter_set")?, field("type")? == object.name())?;
+            base64_decode(field("value")?)?
+        }
+        Export_Category::Protobuf => {
+            let message = protobuf::parse(data)?;
+            let parameter_set = String::from_utf8_lossy(message.parameter_set);
+            check_header::<K>(object, &parameter_set, message.object_type == object.protobuf_type())?;
+            message.value.to_vec()
+        }
+        Export_Category::PEM | Export_Category::Exit => return Err(encoding_error("no import format selected")),
+    };
+    Ok(Zeroizing::new(raw))
+}
+
+fn check_header<K: Kem>(object: Object, parameter_set: &str, type_matches: bool) -> Result<(), KyberError> {
+    if parameter_set != K::PARAMETER_SET {
+        return Err(encoding_error(format!("data is for {}, expected {}", parameter_set, K::PARAMETER_SET)));
+    }
+    if !type_matches {
+        return Err(encoding_error(format!("data is not a {}", object.name())));
+    }
+    Ok(())
+}
+
+fn text(data: &[u8]) -> Result<&str, KyberError> {
+    std::str::from_utf8(data).map_err(|_| encoding_error("data is not UTF-8 text"))
+}
+
+fn base64_decode(data: &str) -> Result<Vec<u8>, KyberError> {
+    STANDARD.decode(data).map_err(|err| encoding_error(format!("invalid Base64: {}", err)))
+}
+
+// RFC 7468 textual encoding, 64 characters per line
+fn pem_encode(der: &[u8], label: &str) -> Vec<u8> {
+    let body = Zeroizing::new(STANDARD.encode(der));
+    let mut out = format!("-----BEGIN {}-----\n", label);
+    for line in body.as_bytes().chunks(64) {
+        out.push_str(std::str::from_utf8(line).unwrap());
+        out.push('\n');
+    }
+    out.push_str(&format!("-----END {}-----\n", label));
+    out.into_bytes()
+}
+
+// The DER inside the first block labelled `label`, skipping anything around it
+fn pem_decode(data: &[u8], label: &str) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+    let data = text(data)?;
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let start = data.find(&begin).ok_or_else(|| encoding_error(format!("no {} PEM block", label)))? + begin.len();
+    let stop = data[start..].find(&end).ok_or_else(|| encoding_error(format!("unterminated {} PEM block", label)))? + start;
+    let body: Zeroizing<String> = Zeroizing::new(data[start..stop].chars().filter(|c| !c.is_ascii_whitespace()).collect());
+    Ok(Zeroizing::new(base64_decode(&body)?))
+}
+
+// The subset of DER needed for SubjectPublicKeyInfo and PKCS#8
+mod der {
+    use crate::error::KyberError;
+
+    pub const INTEGER: u8 = 0x02;
+    pub const BIT_STRING: u8 = 0x03;
+    pub const OCTET_STRING: u8 = 0x04;
+    pub const OID: u8 = 0x06;
+    pub const SEQUENCE: u8 = 0x30;
+    // seed [0] IMPLICIT OCTET STRING of the ML-KEM private key CHOICE
+    pub const SEED: u8 = 0x80;
+
+    pub fn write(tag: u8, content: &[u8], out: &mut Vec<u8>) {
+        out.push(tag);
+        let len = content.len();
+        if len < 0x80 {
+            out.push(len as u8);
+        } else {
+            let bytes = len.to_be_bytes();
+            let skip = bytes.iter().take_while(|&&b| b == 0).count();
+            out.push(0x80 | (bytes.len() - skip) as u8);
+            out.extend_from_slice(&bytes[skip..]);
+        }
+        out.extend_from_slice(content);
+    }
+
+    pub fn encode_oid(dotted: &str) -> Vec<u8> {
+        let arcs: Vec<u64> = dotted.split('.').map(|arc| arc.parse().unwrap()).collect();
+        let mut out = Vec::new();
+        // The first two arcs share one subidentifier
+        for arc in std::iter::once(arcs[0] * 40 + arcs[1]).chain(arcs[2..].iter().copied()) {
+            let mut groups = vec![(arc & 0x7f) as u8];
+            let mut rest = arc >> 7;
+            while rest > 0 {
+                groups.push(0x80 | (rest & 0x7f) as u8);
+                rest >>= 7;
+            }
+            out.extend(groups.iter().rev());
+        }
+        out
+    }
+
+    pub struct Reader<'a> {
+        data: &'a [u8],
+    }
+
+    impl<'a> Reader<'a> {
+        pub fn new(data: &'a [u8]) -> Self {
+            Reader { data }
+        }
+
+        pub fn peek_tag(&self) -> Option<u8> {
+            self.data.first().copied()
+        }
+
+        /// Content of the next element, which must have tag `tag`.
+        pub fn read(&mut self, tag: u8) -> Result<&'a [u8], KyberError> {
+            let truncated = || KyberError::Encoding("truncated DER".to_string());
+            match self.data.first() {
+                Some(&t) if t == tag => {}
+                Some(&t) => return Err(KyberError::Encoding(format!("unexpected DER tag {:#04x}, expected {:#04x}", t, tag))),
+                None => return Err(truncated()),
+            }
+            let first = *self.data.get(1).ok_or_else(truncated)? as usize;
+            let (len, header) = if first < 0x80 {
+                (first, 2)
+            } else {
+                let n = first & 0x7f;
+                if n == 0 || n > 4 {
+                    return Err(KyberError::Encoding("unsupported DER length".to_string()));
+                }
+                let bytes = self.data.get(2..2 + n).ok_or_else(truncated)?;
+                (bytes.iter().fold(0usize, |len, &b| (len << 8) | b as usize), 2 + n)
+            };
+            let content = self.data.get(header..header + len).ok_or_else(truncated)?;
+            self.data = &self.data[header + len..];
+            Ok(content)
+        }
+
+        /// Fails if anything is left.
+        pub fn finish(&self) -> Result<(), KyberError> {
+            if self.data.is_empty() {
+                Ok(())
+            } else {
+                Err(KyberError::Encoding("trailing data after DER element".to_string()))
+            }
+        }
+    }
+}
+
+// Wire format of the KyberObject message
+mod protobuf {
+    use crate::error::KyberError;
+
+    const VARINT: u64 = 0;
+    const FIXED64: u64 = 1;
+    const LENGTH_DELIMITED: u64 = 2;
+    const FIXED32: u64 = 5;
+
+    pub struct Message<'a> {
+        pub parameter_set: &'a [u8],
+        pub object_type: u64,
+        pub value: &'a [u8],
+    }
+
+    fn write_varint(mut x: u64, out: &mut Vec<u8>) {
+        while x >= 0x80 {
+            out.push(0x80 | (x & 0x7f) as u8);
+            x >>= 7;
+        }
+        out.push(x as u8);
+    }
+
+    pub fn write_varint_field(field: u64, x: u64, out: &mut Vec<u8>) {
+        write_varint(field << 3 | VARINT, out);
+        write_varint(x, out);
+    }
+
+    pub fn write_bytes(field: u64, bytes: &[u8], out: &mut Vec<u8>) {
+        write_varint(field << 3 | LENGTH_DELIMITED, out);
+        write_varint(bytes.len() as u64, out);
+        out.extend_from_slice(bytes);
+    }
+
+    fn read_varint(data: &mut &[u8]) -> Result<u64, KyberError> {
+        let mut x = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&b, rest) = data.split_first().ok_or_else(|| KyberError::Encoding("truncated Protobuf varint".to_string()))?;
+            *data = rest;
+            x |= ((b & 0x7f) as u64) << shift;
+            if b < 0x80 {
+                return Ok(x);
+            }
+        }
+        Err(KyberError::Encoding("Protobuf varint too long".to_string()))
+    }
+
+    fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], KyberError> {
+        if data.len() < len {
+            return Err(KyberError::Encoding("truncated Protobuf field".to_string()));
+        }
+        let (field, rest) = data.split_at(len);
+        *data = rest;
+        Ok(field)
+    }
+
+    /// Decodes a KyberObject, skipping unknown fields as Protobuf parsers do.
+    pub fn parse(mut data: &[u8]) -> Result<Message<'_>, KyberError> {
+        let mut message = Message { parameter_set: &[], object_type: 0, value: &[] };
+        while !data.is_empty() {
+            let key = read_varint(&mut data)?;
+            match (key >> 3, key & 7) {
+                (1, LENGTH_DELIMITED) => {
+                    let len = read_varint(&mut data)? as usize;
+                    message.parameter_set = take(&mut data, len)?;
+                }
+                (2, VARINT) => message.object_type = read_varint(&mut data)?,
+                (3, LENGTH_DELIMITED) => {
+                    let len = read_varint(&mut data)? as usize;
+                    message.value = take(&mut data, len)?;
+                }
+                (_, VARINT) => {
+                    read_varint(&mut data)?;
+                }
+                (_, FIXED64) => {
+                    take(&mut data, 8)?;
+                }
+                (_, LENGTH_DELIMITED) => {
+                    let len = read_varint(&mut data)? as usize;
+                    take(&mut data, len)?;
+                }
+                (_, FIXED32) => {
+                    take(&mut data, 4)?;
+                }
+                (_, wire_type) => return Err(KyberError::Encoding(format!("unsupported Protobuf wire type {}", wire_type))),
+            }
+        }
+        Ok(message)
+    }
+}
+
+// Just enough JSON to read back the flat object written by `export`
+mod json {
+    use crate::error::KyberError;
+
+    fn error(reason: &str) -> KyberError {
+        KyberError::Encoding(format!("invalid JSON: {}", reason))
+    }
+
+    fn skip_whitespace(s: &mut &str) {
+        *s = s.trim_start();
+    }
+
+    fn expect(s: &mut &str, c: char) -> Result<(), KyberError> {
+        skip_whitespace(s);
+        match s.strip_prefix(c) {
+            Some(rest) => {
+                *s = rest;
+                Ok(())
+            }
+            None => Err(error(&format!("expected '{}'", c))),
+        }
+    }
+
+    fn string(s: &mut &str) -> Result<String, KyberError> {
+        expect(s, '"')?;
+        let mut out = String::new();
+        let mut chars = s.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    *s = &s[i + 1..];
+                    return Ok(out);
+                }
+                '\\' => match chars.next() {
+                    Some((_, '"')) => out.push('"'),
+                    Some((_, '\\')) => out.push('\\'),
+                    Some((_, '/')) => out.push('/'),
+                    Some((_, 'n')) => out.push('\n'),
+                    _ => return Err(error("unsupported escape")),
+                },
+                c => out.push(c),
+            }
+        }
+        Err(error("unterminated string"))
+    }
+
+    /// The fields of an object whose values are all strings.
+    pub fn parse_object(mut s: &str) -> Result<Vec<(String, String)>, KyberError> {
+        let mut fields = Vec::new();
+        expect(&mut s, '{')?;
+        skip_whitespace(&mut s);
+        if let Some(rest) = s.strip_prefix('}') {
+            s = rest;
+        } else {
+            loop {
+                let key = string(&mut s)?;
+                expect(&mut s, ':')?;
+                skip_whitespace(&mut s);
+                fields.push((key, string(&mut s)?));
+                skip_whitespace(&mut s);
+                if let Some(rest) = s.strip_prefix(',') {
+                    s = rest;
+                    skip_whitespace(&mut s);
+                } else {
+                    expect(&mut s, '}')?;
+                    break;
+                }
+            }
+        }
+        if !s.trim().is_empty() {
+            return Err(error("trailing data after object"));
+        }
+        Ok(fields)
+    }
+}
@@ -8,6 +8,7 @@
 
  pub mod fips202{
      use crate::kyber::xof_state::XofAbsorb;
+     use zeroize::Zeroize;
 
 
      pub fn test()
@@ -368,7 +369,7 @@
  *              - uint8_t p: domain-separation byte for different
  *                           Keccak-derived functions
  **************************************************/
- fn keccak_absorb(s: &mut [u64; 25], r: usize, m: &[u8], mut mlen: usize, p: u8) {
+ pub fn keccak_absorb(s: &mut [u64; 25], r: usize, m: &[u8], mut mlen: usize, p: u8) {
     // Zero State
     s.fill(0);
 
@@ -511,6 +512,7 @@
         shake128_squeezeblocks(&mut t, 1, &mut state);
         out[i..].copy_from_slice(&t[..nblocks]);
     }
+    t.zeroize();
 }
  
  /*************************************************
@@ -539,6 +541,9 @@
         shake256_squeezeblocks(&mut t, 1, &mut state);
         out[nblocks * SHAKE256_RATE..nblocks * SHAKE256_RATE + remaining].copy_from_slice(&t[..remaining]);
     }
+    // The inputs are often secret (PRF keys, implicit rejection values)
+    state.zeroize();
+    t.zeroize();
 }
 
  
@@ -559,6 +564,8 @@
     keccak_squeezeblocks(&mut t, 1, &mut s, SHA3_256_RATE);
 
     h.copy_from_slice(&t[..32]);
+    s.zeroize();
+    t.zeroize();
 }
  /*************************************************
  * Name:        sha3_512
@@ -579,6 +586,8 @@
      keccak_squeezeblocks(&mut t, 1, &mut s, SHA3_512_RATE);
  
      h.copy_from_slice(&t[..64]);
+     s.zeroize();
+     t.zeroize();
  }
  
 }
\ No newline at end of file
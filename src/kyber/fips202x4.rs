@@ -0,0 +1,196 @@
+// Four Keccak-f1600 states permuted side by side, as in fips202x4.c of the
+// pqcrystals avx2 code. gen_matrix uses it to squeeze four matrix entries at
+// once. The permutation is the textbook theta, rho, pi, chi, iota round on a
+// 5x5 array of lanes, with one 64-bit lane per state in each vector.
+
+use crate::kyber::backend::Backend;
+use crate::kyber::fips202::fips202::{keccak_absorb, store64, KECCAKF_ROUND_CONSTANTS};
+
+const SHAKE128_RATE: usize = 168;
+
+// Rotation offsets of rho, indexed by x + 5y
+#[cfg(target_arch = "x86_64")]
+const RHO: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+// The lane pi moves lane x + 5y to, which is y + 5((2x + 3y) mod 5)
+#[cfg(target_arch = "x86_64")]
+const PI: [usize; 25] = {
+    let mut pi = [0usize; 25];
+    let mut i = 0;
+    while i < 25 {
+        let (x, y) = (i % 5, i / 5);
+        pi[i] = y + 5 * ((2 * x + 3 * y) % 5);
+        i += 1;
+    }
+    pi
+};
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) mod avx2 {
+    use core::arch::x86_64::*;
+
+    use super::{KECCAKF_ROUND_CONSTANTS, PI, RHO};
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn rol(a: __m256i, offset: u32) -> __m256i {
+        // Shift counts of 64 give 0, so an offset of 0 leaves a unchanged
+        let left = _mm_cvtsi32_si128(offset as i32);
+        let right = _mm_cvtsi32_si128(64 - offset as i32);
+        _mm256_or_si256(_mm256_sll_epi64(a, left), _mm256_srl_epi64(a, right))
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(crate) unsafe fn keccak_f1600_x4(states: &mut [[u64; 25]; 4]) {
+        let mut a = [_mm256_setzero_si256(); 25];
+        for (i, lane) in a.iter_mut().enumerate() {
+            *lane = _mm256_setr_epi64x(states[0][i] as i64, states[1][i] as i64, states[2][i] as i64, states[3][i] as i64);
+        }
+
+        let mut b = [_mm256_setzero_si256(); 25];
+        for rc in KECCAKF_ROUND_CONSTANTS {
+            // theta
+            let mut c = [_mm256_setzero_si256(); 5];
+            for x in 0..5 {
+                c[x] = _mm256_xor_si256(
+                    _mm256_xor_si256(a[x], a[x + 5]),
+                    _mm256_xor_si256(_mm256_xor_si256(a[x + 10], a[x + 15]), a[x + 20]),
+                );
+            }
+            for x in 0..5 {
+                let d = _mm256_xor_si256(c[(x + 4) % 5], rol(c[(x + 1) % 5], 1));
+                for y in 0..5 {
+                    a[x + 5 * y] = _mm256_xor_si256(a[x + 5 * y], d);
+                }
+            }
+            // rho and pi
+            for i in 0..25 {
+                b[PI[i]] = rol(a[i], RHO[i]);
+            }
+            // chi
+            for y in 0..5 {
+                for x in 0..5 {
+                    let t = _mm256_andnot_si256(b[(x + 1) % 5 + 5 * y], b[(x + 2) % 5 + 5 * y]);
+                    a[x + 5 * y] = _mm256_xor_si256(b[x + 5 * y], t);
+                }
+            }
+            // iota
+            a[0] = _mm256_xor_si256(a[0], _mm256_set1_epi64x(rc as i64));
+        }
+
+        let mut lanes = [0u64; 4];
+        for (i, lane) in a.iter().enumerate() {
+            _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, *lane);
+            for (state, &value) in states.iter_mut().zip(lanes.iter()) {
+                state[i] = value;
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub(crate) mod sse2 {
+    use core::arch::x86_64::*;
+
+    use super::{KECCAKF_ROUND_CONSTANTS, PI, RHO};
+
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn rol(a: __m128i, offset: u32) -> __m128i {
+        let left = _mm_cvtsi32_si128(offset as i32);
+        let right = _mm_cvtsi32_si128(64 - offset as i32);
+        _mm_or_si128(_mm_sll_epi64(a, left), _mm_srl_epi64(a, right))
+    }
+
+    // Two states per register
+    #[target_feature(enable = "sse2")]
+    unsafe fn keccak_f1600_x2(s0: &mut [u64; 25], s1: &mut [u64; 25]) {
+        let mut a = [_mm_setzero_si128(); 25];
+        for (i, lane) in a.iter_mut().enumerate() {
+            *lane = _mm_set_epi64x(s1[i] as i64, s0[i] as i64);
+        }
+
+        let mut b = [_mm_setzero_si128(); 25];
+        for rc in KECCAKF_ROUND_CONSTANTS {
+            let mut c = [_mm_setzero_si128(); 5];
+            for x in 0..5 {
+                c[x] = _mm_xor_si128(_mm_xor_si128(a[x], a[x + 5]), _mm_xor_si128(_mm_xor_si128(a[x + 10], a[x + 15]), a[x + 20]));
+            }
+            for x in 0..5 {
+                let d = _mm_xor_si128(c[(x + 4) % 5], rol(c[(x + 1) % 5], 1));
+                for y in 0..5 {
+                    a[x + 5 * y] = _mm_xor_si128(a[x + 5 * y], d);
+                }
+            }
+            for i in 0..25 {
+                b[PI[i]] = rol(a[i], RHO[i]);
+            }
+            for y in 0..5 {
+                for x in 0..5 {
+                    let t = _mm_andnot_si128(b[(x + 1) % 5 + 5 * y], b[(x + 2) % 5 + 5 * y]);
+                    a[x + 5 * y] = _mm_xor_si128(b[x + 5 * y], t);
+                }
+            }
+            a[0] = _mm_xor_si128(a[0], _mm_set1_epi64x(rc as i64));
+        }
+
+        let mut lanes = [0u64; 2];
+        for (i, lane) in a.iter().enumerate() {
+            _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, *lane);
+            s0[i] = lanes[0];
+            s1[i] = lanes[1];
+        }
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(crate) unsafe fn keccak_f1600_x4(states: &mut [[u64; 25]; 4]) {
+        let (lo, hi) = states.split_at_mut(2);
+        let (s0, s1) = lo.split_at_mut(1);
+        let (s2, s3) = hi.split_at_mut(1);
+        keccak_f1600_x2(&mut s0[0], &mut s1[0]);
+        keccak_f1600_x2(&mut s2[0], &mut s3[0]);
+    }
+}
+
+/*************************************************
+* Name:        shake128x4_absorb
+*
+* Description: Absorb step of four SHAKE128 instances at once,
+*              non-incremental, starts by zeroing the states.
+*
+* Arguments:   - [[u64; 25]; 4] states: output Keccak states
+*              - [&[u8]; 4] in_data:     the four inputs
+**************************************************/
+pub(crate) fn shake128x4_absorb(states: &mut [[u64; 25]; 4], in_data: [&[u8]; 4]) {
+    for (state, input) in states.iter_mut().zip(in_data) {
+        *state = [0u64; 25];
+        keccak_absorb(state, SHAKE128_RATE, input, input.len(), 0x1F);
+    }
+}
+
+/*************************************************
+* Name:        shake128x4_squeezeblocks
+*
+* Description: Squeezes nblocks blocks of SHAKE128_RATE bytes from each of
+*              four SHAKE128 states, permuting them with the fastest
+*              available backend.
+*
+* Arguments:   - [&mut [u8]; 4] out:         the four output buffers
+*              - usize nblocks:              number of blocks to squeeze
+*              - [[u64; 25]; 4] states:      input/output Keccak states
+**************************************************/
+pub(crate) fn shake128x4_squeezeblocks(out: [&mut [u8]; 4], nblocks: usize, states: &mut [[u64; 25]; 4]) {
+    let backend = Backend::detected();
+    let mut out = out;
+    for block in 0..nblocks {
+        backend.keccak_f1600_x4(states);
+        for (buf, state) in out.iter_mut().zip(states.iter()) {
+            let block_out = &mut buf[block * SHAKE128_RATE..(block + 1) * SHAKE128_RATE];
+            for (j, word) in block_out.chunks_exact_mut(8).enumerate() {
+                store64(word, state[j]);
+            }
+        }
+    }
+}
@@ -0,0 +1,444 @@
+// Hybrid Public Key Encryption, RFC 9180, with Kyber KEMs.
+//
+// The key schedule, the nonce sequence and the exporter follow RFC 9180
+// exactly, so with a KEM registered for HPKE the outputs match any other
+// implementation. The KEMs here are
+//
+//     0x0041  ML-KEM-768, enc = the ML-KEM ciphertext, as in draft-ietf-hpke-pq
+//     0xFF41  X25519+ML-KEM-768 from `kyber::hybrid`, enc = the hybrid ciphertext
+//
+// ML-KEM-768 has no authenticated encapsulation, so it only supports the base
+// and PSK modes. The hybrid KEM supports all four, authenticating the sender
+// with the X25519 half of its key. The KDFs are HKDF-SHA256 (0x0001) and
+// HKDF-SHA3-256 (0xFF03), and the AEADs AES-256-GCM (0x0002),
+// ChaCha20-Poly1305 (0x0003) and export-only (0xFFFF), all through OpenSSL.
+//
+// 0xFF41 and 0xFF03 are not registered with IANA, the first because the hybrid
+// combiner is this crate's own, the second because no HKDF-SHA3 is registered,
+// so suites using them only interoperate with this crate.
+
+use std::marker::PhantomData;
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use zeroize::Zeroizing;
+
+use crate::error::KyberError;
+use crate::kyber::hybrid::{HybridCiphertext, HybridPublicKey, HybridSecretKey, X25519MlKem768};
+use crate::kyber::kem_rng::{GlobalKyberRng, KemRng};
+use crate::kyber::kyber_api::{Kem, KemBytes, MlKem768, SharedSecret};
+
+/// Smallest pre-shared key accepted, RFC 9180 asks for 32 bytes of entropy.
+pub const MIN_PSK_BYTES: usize = 32;
+
+/// A KEM usable with HPKE: `Encap`, `Decap` and optionally `AuthEncap` and `AuthDecap`.
+pub trait HpkeKem {
+    /// The KEM identifier in the suite ID.
+    const KEM_ID: u16;
+    /// Length of the encapsulated key `enc`.
+    const N_ENC: usize;
+
+    type PublicKey;
+    type SecretKey;
+
+    /// Returns `enc` and the shared secret, with the randomness drawn from `rng`.
+    fn encap<R: KemRng + ?Sized>(pk_r: &Self::PublicKey, rng: &mut R) -> Result<(Vec<u8>, SharedSecret), KyberError>;
+
+    fn decap(enc: &[u8], sk_r: &Self::SecretKey) -> Result<SharedSecret, KyberError>;
+
+    /// As `encap`, with a shared secret only the holder of `sk_s` could produce.
+    fn auth_encap<R: KemRng + ?Sized>(_pk_r: &Self::PublicKey, _sk_s: &Self::SecretKey, _rng: &mut R) -> Result<(Vec<u8>, SharedSecret), KyberError> {
+        Err(KyberError::Hpke("the KEM has no authenticated mode"))
+    }
+
+    fn auth_decap(_enc: &[u8], _sk_r: &Self::SecretKey, _pk_s: &Self::PublicKey) -> Result<SharedSecret, KyberError> {
+        Err(KyberError::Hpke("the KEM has no authenticated mode"))
+    }
+}
+
+impl HpkeKem for MlKem768 {
+    const KEM_ID: u16 = 0x0041;
+    const N_ENC: usize = MlKem768::CIPHERTEXT_BYTES;
+
+    type PublicKey = <MlKem768 as Kem>::PublicKey;
+    type SecretKey = <MlKem768 as Kem>::SecretKey;
+
+    fn encap<R: KemRng + ?Sized>(pk_r: &Self::PublicKey, rng: &mut R) -> Result<(Vec<u8>, SharedSecret), KyberError> {
+        let (ct, ss) = MlKem768::encapsulate_with_rng(pk_r, rng)?;
+        Ok((ct.as_bytes().to_vec(), ss))
+    }
+
+    fn decap(enc: &[u8], sk_r: &Self::SecretKey) -> Result<SharedSecret, KyberError> {
+        MlKem768::decapsulate(sk_r, &<MlKem768 as Kem>::Ciphertext::from_bytes(enc)?)
+    }
+}
+
+impl HpkeKem for X25519MlKem768 {
+    const KEM_ID: u16 = 0xFF41;
+    const N_ENC: usize = X25519MlKem768::CIPHERTEXT_BYTES;
+
+    type PublicKey = HybridPublicKey<MlKem768>;
+    type SecretKey = HybridSecretKey<MlKem768>;
+
+    fn encap<R: KemRng + ?Sized>(pk_r: &Self::PublicKey, rng: &mut R) -> Result<(Vec<u8>, SharedSecret), KyberError> {
+        let (ct, ss) = X25519MlKem768::encapsulate_with_rng(pk_r, rng)?;
+        Ok((ct.to_bytes(), ss))
+    }
+
+    fn decap(enc: &[u8], sk_r: &Self::SecretKey) -> Result<SharedSecret, KyberError> {
+        X25519MlKem768::decapsulate(sk_r, &HybridCiphertext::from_bytes(enc)?)
+    }
+
+    fn auth_encap<R: KemRng + ?Sized>(pk_r: &Self::PublicKey, sk_s: &Self::SecretKey, rng: &mut R) -> Result<(Vec<u8>, SharedSecret), KyberError> {
+        let (ct, ss) = X25519MlKem768::auth_encapsulate_with_rng(pk_r, sk_s, rng)?;
+        Ok((ct.to_bytes(), ss))
+    }
+
+    fn auth_decap(enc: &[u8], sk_r: &Self::SecretKey, pk_s: &Self::PublicKey) -> Result<SharedSecret, KyberError> {
+        X25519MlKem768::auth_decapsulate(sk_r, &HybridCiphertext::from_bytes(enc)?, pk_s)
+    }
+}
+
+/// Key derivation functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kdf {
+    HkdfSha256,
+    /// HKDF over SHA3-256, under an identifier of this crate's choosing.
+    HkdfSha3_256,
+}
+
+impl Kdf {
+    pub fn id(self) -> u16 {
+        match self {
+            Kdf::HkdfSha256 => 0x0001,
+            Kdf::HkdfSha3_256 => 0xFF03,
+        }
+    }
+
+    /// Output length of the hash, `Nh`.
+    pub fn n_h(self) -> usize {
+        32
+    }
+
+    fn digest(self) -> MessageDigest {
+        match self {
+            Kdf::HkdfSha256 => MessageDigest::sha256(),
+            Kdf::HkdfSha3_256 => MessageDigest::sha3_256(),
+        }
+    }
+
+    fn hmac(self, key: &[u8], parts: &[&[u8]]) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        let failed = |_| KyberError::Hpke("OpenSSL HMAC failed");
+        let key = PKey::hmac(key).map_err(failed)?;
+        let mut signer = Signer::new(self.digest(), &key).map_err(failed)?;
+        for part in parts {
+            signer.update(part).map_err(failed)?;
+        }
+        Ok(Zeroizing::new(signer.sign_to_vec().map_err(failed)?))
+    }
+
+    /// HKDF-Extract, RFC 5869. An empty salt stands for `Nh` zero bytes.
+    pub fn extract(self, salt: &[u8], ikm: &[u8]) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        let zeros = vec![0u8; self.n_h()];
+        self.hmac(if salt.is_empty() { &zeros } else { salt }, &[ikm])
+    }
+
+    /// HKDF-Expand, RFC 5869, for at most 255 `Nh` bytes.
+    pub fn expand(self, prk: &[u8], info: &[u8], len: usize) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        if len > 255 * self.n_h() {
+            return Err(KyberError::Hpke("requested output is too long for the KDF"));
+        }
+        let mut okm = Zeroizing::new(Vec::with_capacity(len + self.n_h()));
+        let mut block = Zeroizing::new(Vec::new());
+        let mut counter = 1u8;
+        while okm.len() < len {
+            block = self.hmac(prk, &[&block, info, &[counter]])?;
+            okm.extend_from_slice(&block);
+            counter = counter.wrapping_add(1);
+        }
+        okm.truncate(len);
+        Ok(okm)
+    }
+}
+
+/// Authenticated encryption algorithms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aead {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+    /// No encryption, the context is only used to export secrets.
+    ExportOnly,
+}
+
+impl Aead {
+    pub fn id(self) -> u16 {
+        match self {
+            Aead::Aes256Gcm => 0x0002,
+            Aead::ChaCha20Poly1305 => 0x0003,
+            Aead::ExportOnly => 0xFFFF,
+        }
+    }
+
+    /// Key length, `Nk`.
+    pub fn n_k(self) -> usize {
+        match self {
+            Aead::ExportOnly => 0,
+            _ => 32,
+        }
+    }
+
+    /// Nonce length, `Nn`.
+    pub fn n_n(self) -> usize {
+        match self {
+            Aead::ExportOnly => 0,
+            _ => 12,
+        }
+    }
+
+    /// Tag length, `Nt`.
+    pub fn n_t(self) -> usize {
+        match self {
+            Aead::ExportOnly => 0,
+            _ => 16,
+        }
+    }
+
+    fn cipher(self) -> Result<Cipher, KyberError> {
+        match self {
+            Aead::Aes256Gcm => Ok(Cipher::aes_256_gcm()),
+            Aead::ChaCha20Poly1305 => Ok(Cipher::chacha20_poly1305()),
+            Aead::ExportOnly => Err(KyberError::Hpke("the context is export-only")),
+        }
+    }
+}
+
+/// A pre-shared key and its identifier, both non-empty.
+#[derive(Clone, Copy)]
+pub struct Psk<'a> {
+    pub psk: &'a [u8],
+    pub id: &'a [u8],
+}
+
+/// The HPKE mode, with what it takes besides the recipient's key: a
+/// pre-shared key and/or the sender's key, its secret key when sending and its
+/// public key when receiving.
+pub enum Mode<'a, T> {
+    Base,
+    Psk(Psk<'a>),
+    Auth(&'a T),
+    AuthPsk(Psk<'a>, &'a T),
+}
+
+impl<T> Mode<'_, T> {
+    pub fn id(&self) -> u8 {
+        match self {
+            Mode::Base => 0x00,
+            Mode::Psk(_) => 0x01,
+            Mode::Auth(_) => 0x02,
+            Mode::AuthPsk(..) => 0x03,
+        }
+    }
+
+    fn psk(&self) -> Result<Psk<'_>, KyberError> {
+        match self {
+            Mode::Base | Mode::Auth(_) => Ok(Psk { psk: b"", id: b"" }),
+            Mode::Psk(psk) | Mode::AuthPsk(psk, _) if psk.psk.is_empty() || psk.id.is_empty() => Err(KyberError::Hpke("the pre-shared key and its identifier must not be empty")),
+            Mode::Psk(psk) | Mode::AuthPsk(psk, _) if psk.psk.len() < MIN_PSK_BYTES => Err(KyberError::Hpke("the pre-shared key is shorter than 32 bytes")),
+            Mode::Psk(psk) | Mode::AuthPsk(psk, _) => Ok(*psk),
+        }
+    }
+
+    fn key(&self) -> Option<&T> {
+        match self {
+            Mode::Auth(key) | Mode::AuthPsk(_, key) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+/// An HPKE cipher suite, the KEM `K` with a KDF and an AEAD.
+pub struct Hpke<K: HpkeKem> {
+    pub kdf: Kdf,
+    pub aead: Aead,
+    kem: PhantomData<K>,
+}
+
+impl<K: HpkeKem> Clone for Hpke<K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K: HpkeKem> Copy for Hpke<K> {}
+
+impl<K: HpkeKem> Hpke<K> {
+    pub fn new(kdf: Kdf, aead: Aead) -> Self {
+        Hpke { kdf, aead, kem: PhantomData }
+    }
+
+    /// "HPKE" || KEM ID || KDF ID || AEAD ID.
+    pub fn suite_id(&self) -> [u8; 10] {
+        let mut id = *b"HPKE\0\0\0\0\0\0";
+        id[4..6].copy_from_slice(&K::KEM_ID.to_be_bytes());
+        id[6..8].copy_from_slice(&self.kdf.id().to_be_bytes());
+        id[8..10].copy_from_slice(&self.aead.id().to_be_bytes());
+        id
+    }
+
+    /// Sets up a context for sending to `pk_r`, returning `enc` for the recipient.
+    /// The randomness comes from the library-wide DRBG.
+    pub fn setup_sender(&self, pk_r: &K::PublicKey, info: &[u8], mode: Mode<'_, K::SecretKey>) -> Result<(Vec<u8>, SenderContext), KyberError> {
+        self.setup_sender_with_rng(pk_r, info, mode, &mut GlobalKyberRng)
+    }
+
+    /// As `setup_sender`, with the randomness of the KEM drawn from `rng`.
+    pub fn setup_sender_with_rng<R: KemRng + ?Sized>(&self, pk_r: &K::PublicKey, info: &[u8], mode: Mode<'_, K::SecretKey>, rng: &mut R) -> Result<(Vec<u8>, SenderContext), KyberError> {
+        let psk = mode.psk()?;
+        let (enc, ss) = match mode.key() {
+            Some(sk_s) => K::auth_encap(pk_r, sk_s, rng)?,
+            None => K::encap(pk_r, rng)?,
+        };
+        Ok((enc, SenderContext(self.key_schedule(mode.id(), &ss, info, psk)?)))
+    }
+
+    /// Sets up the context matching the sender's from `enc`.
+    pub fn setup_receiver(&self, enc: &[u8], sk_r: &K::SecretKey, info: &[u8], mode: Mode<'_, K::PublicKey>) -> Result<ReceiverContext, KyberError> {
+        let psk = mode.psk()?;
+        let ss = match mode.key() {
+            Some(pk_s) => K::auth_decap(enc, sk_r, pk_s)?,
+            None => K::decap(enc, sk_r)?,
+        };
+        Ok(ReceiverContext(self.key_schedule(mode.id(), &ss, info, psk)?))
+    }
+
+    /// Encrypts a single message to `pk_r`, returning `enc` and the ciphertext.
+    pub fn seal(&self, pk_r: &K::PublicKey, info: &[u8], aad: &[u8], plaintext: &[u8], mode: Mode<'_, K::SecretKey>) -> Result<(Vec<u8>, Vec<u8>), KyberError> {
+        let (enc, mut context) = self.setup_sender(pk_r, info, mode)?;
+        Ok((enc, context.seal(aad, plaintext)?))
+    }
+
+    /// Decrypts a message from `seal`.
+    pub fn open(&self, enc: &[u8], sk_r: &K::SecretKey, info: &[u8], aad: &[u8], ciphertext: &[u8], mode: Mode<'_, K::PublicKey>) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        self.setup_receiver(enc, sk_r, info, mode)?.open(aad, ciphertext)
+    }
+
+    // LabeledExtract(salt, label, ikm) = Extract(salt, "HPKE-v1" || suite_id || label || ikm)
+    fn labeled_extract(&self, salt: &[u8], label: &[u8], ikm: &[u8]) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        let labeled = Zeroizing::new([b"HPKE-v1".as_slice(), &self.suite_id(), label, ikm].concat());
+        self.kdf.extract(salt, &labeled)
+    }
+
+    // KeySchedule of RFC 9180 section 5.1
+    fn key_schedule(&self, mode: u8, shared_secret: &SharedSecret, info: &[u8], psk: Psk<'_>) -> Result<Context, KyberError> {
+        let psk_id_hash = self.labeled_extract(b"", b"psk_id_hash", psk.id)?;
+        let info_hash = self.labeled_extract(b"", b"info_hash", info)?;
+        let context = [&[mode][..], &psk_id_hash, &info_hash].concat();
+
+        let secret = self.labeled_extract(shared_secret.as_bytes(), b"secret", psk.psk)?;
+        let expand = |label: &[u8], len| labeled_expand(self.kdf, &self.suite_id(), &secret, label, &context, len);
+        let key = expand(b"key", self.aead.n_k())?;
+        let base_nonce = expand(b"base_nonce", self.aead.n_n())?;
+        let exporter_secret = expand(b"exp", self.kdf.n_h())?;
+        Ok(Context { kdf: self.kdf, aead: self.aead, suite_id: self.suite_id(), key, base_nonce, seq: 0, exporter_secret })
+    }
+}
+
+// LabeledExpand(prk, label, info, L) =
+//     Expand(prk, I2OSP(L, 2) || "HPKE-v1" || suite_id || label || info, L)
+fn labeled_expand(kdf: Kdf, suite_id: &[u8], prk: &[u8], label: &[u8], info: &[u8], len: usize) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+    let length = u16::try_from(len).map_err(|_| KyberError::Hpke("requested output is too long for the KDF"))?;
+    let labeled = [&length.to_be_bytes()[..], b"HPKE-v1", suite_id, label, info].concat();
+    kdf.expand(prk, &labeled, len)
+}
+
+// The state shared by both ends, the keys and the sequence number
+struct Context {
+    kdf: Kdf,
+    aead: Aead,
+    suite_id: [u8; 10],
+    key: Zeroizing<Vec<u8>>,
+    base_nonce: Zeroizing<Vec<u8>>,
+    seq: u64,
+    exporter_secret: Zeroizing<Vec<u8>>,
+}
+
+impl Context {
+    // base_nonce XOR the big-endian sequence number
+    fn nonce(&self) -> Vec<u8> {
+        let mut nonce = self.base_nonce.to_vec();
+        let n = nonce.len();
+        for (byte, seq) in nonce[n - 8..].iter_mut().zip(self.seq.to_be_bytes()) {
+            *byte ^= seq;
+        }
+        nonce
+    }
+
+    fn export(&self, exporter_context: &[u8], len: usize) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        labeled_expand(self.kdf, &self.suite_id, &self.exporter_secret, b"sec", exporter_context, len)
+    }
+}
+
+/// The sender's context, sealing messages in order.
+pub struct SenderContext(Context);
+
+/// The recipient's context, opening messages in the order they were sealed.
+pub struct ReceiverContext(Context);
+
+impl SenderContext {
+    /// Encrypts the next message, the tag appended to the ciphertext.
+    pub fn seal(&mut self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, KyberError> {
+        let context = &mut self.0;
+        let cipher = context.aead.cipher()?;
+        if context.seq == u64::MAX {
+            return Err(KyberError::Hpke("the context has sealed its last message"));
+        }
+        let mut tag = vec![0u8; context.aead.n_t()];
+        let mut ciphertext = encrypt_aead(cipher, &context.key, Some(&context.nonce()), aad, plaintext, &mut tag)
+            .map_err(|_| KyberError::Hpke("OpenSSL encryption failed"))?;
+        ciphertext.extend_from_slice(&tag);
+        context.seq += 1;
+        Ok(ciphertext)
+    }
+
+    /// Derives `len` bytes bound to the context and `exporter_context`.
+    pub fn export(&self, exporter_context: &[u8], len: usize) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        self.0.export(exporter_context, len)
+    }
+
+    /// Number of messages sealed so far.
+    pub fn sequence_number(&self) -> u64 {
+        self.0.seq
+    }
+}
+
+impl ReceiverContext {
+    /// Decrypts the next message. A message that does not open leaves the
+    /// sequence number where it was.
+    pub fn open(&mut self, aad: &[u8], ciphertext: &[u8]) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        let context = &mut self.0;
+        let cipher = context.aead.cipher()?;
+        if ciphertext.len() < context.aead.n_t() {
+            return Err(KyberError::Hpke("the ciphertext is shorter than the tag"));
+        }
+        if context.seq == u64::MAX {
+            return Err(KyberError::Hpke("the context has opened its last message"));
+        }
+        let (ciphertext, tag) = ciphertext.split_at(ciphertext.len() - context.aead.n_t());
+        let plaintext = decrypt_aead(cipher, &context.key, Some(&context.nonce()), aad, ciphertext, tag)
+            .map_err(|_| KyberError::Hpke("the message does not open, it was altered or is out of order"))?;
+        context.seq += 1;
+        Ok(Zeroizing::new(plaintext))
+    }
+
+    /// Derives the same bytes as the sender's `export`.
+    pub fn export(&self, exporter_context: &[u8], len: usize) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        self.0.export(exporter_context, len)
+    }
+
+    /// Number of messages opened so far.
+    pub fn sequence_number(&self) -> u64 {
+        self.0.seq
+    }
+}
@@ -0,0 +1,244 @@
+// Hybrid KEM running X25519 alongside Kyber, in the style of X-Wing, so the
+// shared secret stays safe as long as either of the two holds.
+//
+// Keys and ciphertexts are the Kyber value followed by the 32 byte X25519 value:
+//
+//     public key  = pk_K || pk_X
+//     secret key  = sk_K || sk_X
+//     ciphertext  = ct_K || ct_X
+//
+// where ct_X is the encapsulator's ephemeral X25519 public key. The two shared
+// secrets are combined with SHA3-256 over both ciphertexts and both public keys:
+//
+//     ss = SHA3-256(ss_K || ss_X || ct_K || ct_X || pk_K || pk_X || label)
+//
+// with the label "X25519+" followed by the parameter set name, for example
+// "X25519+ML-KEM-768". X-Wing itself leaves out ct_K and pk_K, relying on
+// ML-KEM binding them; they are hashed here so the combiner does not depend on
+// that property, which means the output differs from X-Wing's.
+//
+// The authenticated variant, used by HPKE's auth modes, adds the X25519 secret
+// of the sender's static key and the recipient's key, in the way of DHKEM:
+//
+//     ss = SHA3-256(ss || X25519(sk_S, pk_R) || pk_S || label || " auth")
+//
+// where ss is the secret above and pk_S the X25519 half of the sender's key.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use openssl::derive::Deriver;
+use openssl::pkey::{Id, PKey};
+use zeroize::Zeroizing;
+
+use crate::error::KyberError;
+use crate::kyber::fips202::fips202::sha3_256;
+use crate::kyber::kem_rng::{fill, GlobalKyberRng, KemRng};
+use crate::kyber::kyber_api::{Kem, KemBytes, Kyber768, MlKem768, SharedSecret};
+
+/// Length of an X25519 public key, secret key or shared secret.
+pub const X25519_BYTES: usize = 32;
+
+/// X25519 combined with the Kyber parameter set `K`.
+pub struct Hybrid<K: Kem>(PhantomData<K>);
+
+/// X25519 with round-3 Kyber768.
+pub type X25519Kyber768 = Hybrid<Kyber768>;
+/// X25519 with FIPS 203 ML-KEM-768.
+pub type X25519MlKem768 = Hybrid<MlKem768>;
+
+/// A hybrid public key, the Kyber public key and an X25519 public key.
+pub struct HybridPublicKey<K: Kem> {
+    pub kem: K::PublicKey,
+    pub x25519: [u8; X25519_BYTES],
+}
+
+/// A hybrid secret key, wiped when dropped.
+pub struct HybridSecretKey<K: Kem> {
+    pub kem: K::SecretKey,
+    pub x25519: Zeroizing<[u8; X25519_BYTES]>,
+}
+
+/// A hybrid ciphertext, the Kyber ciphertext and the ephemeral X25519 public key.
+pub struct HybridCiphertext<K: Kem> {
+    pub kem: K::Ciphertext,
+    pub x25519: [u8; X25519_BYTES],
+}
+
+impl<K: Kem> fmt::Debug for HybridSecretKey<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HybridSecretKey([REDACTED])")
+    }
+}
+
+// Splits `bytes` into the Kyber part of `len` bytes and the X25519 part
+fn split(bytes: &[u8], len: usize, error: KyberError) -> Result<(&[u8], [u8; X25519_BYTES]), KyberError> {
+    if bytes.len() != len + X25519_BYTES {
+        return Err(error);
+    }
+    let (kem, x25519) = bytes.split_at(len);
+    Ok((kem, x25519.try_into().unwrap()))
+}
+
+impl<K: Kem> HybridPublicKey<K> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, KyberError> {
+        let expected = Hybrid::<K>::PUBLIC_KEY_BYTES;
+        let (kem, x25519) = split(bytes, K::PUBLIC_KEY_BYTES, KyberError::WrongKeyLength { expected, actual: bytes.len() })?;
+        Ok(HybridPublicKey { kem: K::PublicKey::from_bytes(kem)?, x25519 })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.kem.as_bytes(), &self.x25519].concat()
+    }
+}
+
+impl<K: Kem> HybridSecretKey<K> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, KyberError> {
+        let expected = Hybrid::<K>::SECRET_KEY_BYTES;
+        let (kem, x25519) = split(bytes, K::SECRET_KEY_BYTES, KyberError::WrongKeyLength { expected, actual: bytes.len() })?;
+        Ok(HybridSecretKey { kem: K::SecretKey::from_bytes(kem)?, x25519: Zeroizing::new(x25519) })
+    }
+
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new([self.kem.as_bytes(), &self.x25519[..]].concat())
+    }
+}
+
+impl<K: Kem> HybridCiphertext<K> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, KyberError> {
+        let expected = Hybrid::<K>::CIPHERTEXT_BYTES;
+        let (kem, x25519) = split(bytes, K::CIPHERTEXT_BYTES, KyberError::WrongCiphertextLength { expected, actual: bytes.len() })?;
+        Ok(HybridCiphertext { kem: K::Ciphertext::from_bytes(kem)?, x25519 })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.kem.as_bytes(), &self.x25519].concat()
+    }
+}
+
+// The public key of the X25519 secret key `sk`
+fn x25519_public_key(sk: &[u8; X25519_BYTES]) -> Result<[u8; X25519_BYTES], KyberError> {
+    let key = PKey::private_key_from_raw_bytes(sk, Id::X25519).map_err(|_| KyberError::InvalidKey("X25519 secret key rejected by OpenSSL"))?;
+    let pk = key.raw_public_key().map_err(|_| KyberError::InvalidKey("X25519 public key could not be computed"))?;
+    Ok(pk.try_into().unwrap())
+}
+
+// X25519(sk, pk). OpenSSL refuses public keys of small order, whose shared
+// secret is all zero
+fn x25519(sk: &[u8; X25519_BYTES], pk: &[u8; X25519_BYTES]) -> Result<Zeroizing<[u8; X25519_BYTES]>, KyberError> {
+    let sk = PKey::private_key_from_raw_bytes(sk, Id::X25519).map_err(|_| KyberError::InvalidKey("X25519 secret key rejected by OpenSSL"))?;
+    let pk = PKey::public_key_from_raw_bytes(pk, Id::X25519).map_err(|_| KyberError::InvalidKey("X25519 public key rejected by OpenSSL"))?;
+    let mut ss = Zeroizing::new([0u8; X25519_BYTES]);
+    let mut deriver = Deriver::new(&sk).map_err(|_| KyberError::InvalidKey("X25519 key agreement failed"))?;
+    deriver.set_peer(&pk).map_err(|_| KyberError::InvalidKey("X25519 public key rejected by OpenSSL"))?;
+    match deriver.derive(&mut ss[..]) {
+        Ok(X25519_BYTES) => Ok(ss),
+        _ => Err(KyberError::InvalidKey("X25519 public key has small order")),
+    }
+}
+
+impl<K: Kem> Hybrid<K> {
+    pub const PUBLIC_KEY_BYTES: usize = K::PUBLIC_KEY_BYTES + X25519_BYTES;
+    pub const SECRET_KEY_BYTES: usize = K::SECRET_KEY_BYTES + X25519_BYTES;
+    pub const CIPHERTEXT_BYTES: usize = K::CIPHERTEXT_BYTES + X25519_BYTES;
+
+    /// e.g. "X25519+ML-KEM-768", also the label of the combiner.
+    pub fn name() -> String {
+        format!("X25519+{}", K::PARAMETER_SET)
+    }
+
+    /// Generates a key pair from the library-wide DRBG.
+    pub fn keypair() -> Result<(HybridPublicKey<K>, HybridSecretKey<K>), KyberError> {
+        Self::keypair_with_rng(&mut GlobalKyberRng)
+    }
+
+    /// Generates a key pair with `d`, `z` and the X25519 secret key drawn from `rng`, in that order.
+    pub fn keypair_with_rng<R: KemRng + ?Sized>(rng: &mut R) -> Result<(HybridPublicKey<K>, HybridSecretKey<K>), KyberError> {
+        let mut seeds = Zeroizing::new([[0u8; 32]; 3]);
+        for seed in seeds.iter_mut() {
+            fill(rng, seed)?;
+        }
+        Self::keypair_derand(&seeds[0], &seeds[1], &seeds[2])
+    }
+
+    /// Generates a key pair deterministically from the Kyber seeds `d` and `z`
+    /// and the X25519 secret key `x`.
+    pub fn keypair_derand(d: &[u8; 32], z: &[u8; 32], x: &[u8; X25519_BYTES]) -> Result<(HybridPublicKey<K>, HybridSecretKey<K>), KyberError> {
+        let (pk, sk) = K::keypair_derand(d, z)?;
+        let x25519 = x25519_public_key(x)?;
+        Ok((HybridPublicKey { kem: pk, x25519 }, HybridSecretKey { kem: sk, x25519: Zeroizing::new(*x) }))
+    }
+
+    /// Encapsulates to `pk` with `m` and the ephemeral X25519 key drawn from the library-wide DRBG.
+    pub fn encapsulate(pk: &HybridPublicKey<K>) -> Result<(HybridCiphertext<K>, SharedSecret), KyberError> {
+        Self::encapsulate_with_rng(pk, &mut GlobalKyberRng)
+    }
+
+    /// Encapsulates to `pk` with `m` and the ephemeral X25519 key drawn from `rng`, in that order.
+    pub fn encapsulate_with_rng<R: KemRng + ?Sized>(pk: &HybridPublicKey<K>, rng: &mut R) -> Result<(HybridCiphertext<K>, SharedSecret), KyberError> {
+        let mut seeds = Zeroizing::new([[0u8; 32]; 2]);
+        for seed in seeds.iter_mut() {
+            fill(rng, seed)?;
+        }
+        Self::encapsulate_derand(pk, &seeds[0], &seeds[1])
+    }
+
+    /// Encapsulates to `pk` deterministically, with the Kyber message `m` and
+    /// the ephemeral X25519 secret key `e`.
+    pub fn encapsulate_derand(pk: &HybridPublicKey<K>, m: &[u8; 32], e: &[u8; X25519_BYTES]) -> Result<(HybridCiphertext<K>, SharedSecret), KyberError> {
+        let (ct_kem, ss_kem) = K::encapsulate_derand(&pk.kem, m)?;
+        let ct = HybridCiphertext { kem: ct_kem, x25519: x25519_public_key(e)? };
+        let ss_x25519 = x25519(e, &pk.x25519)?;
+        let ss = Self::combine(&ss_kem, &ss_x25519, &ct, &pk.kem, &pk.x25519);
+        Ok((ct, ss))
+    }
+
+    /// Recovers the shared secret of `ct`. A Kyber ciphertext that does not
+    /// decrypt gives the implicit rejection value as usual, while an X25519
+    /// share of small order is an error.
+    pub fn decapsulate(sk: &HybridSecretKey<K>, ct: &HybridCiphertext<K>) -> Result<SharedSecret, KyberError> {
+        let ss_kem = K::decapsulate(&sk.kem, &ct.kem)?;
+        let ss_x25519 = x25519(&sk.x25519, &ct.x25519)?;
+        let pk_x25519 = x25519_public_key(&sk.x25519)?;
+
+        // The Kyber public key sits in the secret key before H(pk) and z
+        let sk_bytes = sk.kem.as_bytes();
+        let pk_kem = K::PublicKey::from_bytes(&sk_bytes[K::SECRET_KEY_BYTES - 64 - K::PUBLIC_KEY_BYTES..K::SECRET_KEY_BYTES - 64])?;
+        Ok(Self::combine(&ss_kem, &ss_x25519, ct, &pk_kem, &pk_x25519))
+    }
+
+    /// Encapsulates to `pk` as the holder of `sk_s`, with seeds drawn from `rng`
+    /// as for `encapsulate_with_rng`. Only the X25519 half of `sk_s` is used.
+    pub fn auth_encapsulate_with_rng<R: KemRng + ?Sized>(pk: &HybridPublicKey<K>, sk_s: &HybridSecretKey<K>, rng: &mut R) -> Result<(HybridCiphertext<K>, SharedSecret), KyberError> {
+        let (ct, ss) = Self::encapsulate_with_rng(pk, rng)?;
+        let ss_static = x25519(&sk_s.x25519, &pk.x25519)?;
+        let ss = Self::combine_auth(&ss, &ss_static, &x25519_public_key(&sk_s.x25519)?);
+        Ok((ct, ss))
+    }
+
+    /// Recovers the shared secret of a ciphertext from `auth_encapsulate_with_rng`,
+    /// which only matches the sender's if it was made with the secret key of `pk_s`.
+    pub fn auth_decapsulate(sk: &HybridSecretKey<K>, ct: &HybridCiphertext<K>, pk_s: &HybridPublicKey<K>) -> Result<SharedSecret, KyberError> {
+        let ss = Self::decapsulate(sk, ct)?;
+        let ss_static = x25519(&sk.x25519, &pk_s.x25519)?;
+        Ok(Self::combine_auth(&ss, &ss_static, &pk_s.x25519))
+    }
+
+    // SHA3-256(ss || ss_S || pk_S || label || " auth")
+    fn combine_auth(ss: &SharedSecret, ss_static: &[u8; X25519_BYTES], pk_s: &[u8; X25519_BYTES]) -> SharedSecret {
+        let label = format!("{} auth", Self::name());
+        let input = Zeroizing::new([ss.as_bytes(), ss_static, pk_s, label.as_bytes()].concat());
+        let mut out = Zeroizing::new([0u8; 32]);
+        sha3_256(&mut out, &input);
+        SharedSecret::from_bytes(&out[..]).unwrap()
+    }
+
+    // SHA3-256(ss_K || ss_X || ct_K || ct_X || pk_K || pk_X || label)
+    fn combine(ss_kem: &SharedSecret, ss_x25519: &[u8; X25519_BYTES], ct: &HybridCiphertext<K>, pk_kem: &K::PublicKey, pk_x25519: &[u8; X25519_BYTES]) -> SharedSecret {
+        let label = Self::name();
+        let input = Zeroizing::new([ss_kem.as_bytes(), ss_x25519, ct.kem.as_bytes(), &ct.x25519, pk_kem.as_bytes(), pk_x25519, label.as_bytes()].concat());
+        let mut ss = Zeroizing::new([0u8; 32]);
+        sha3_256(&mut ss, &input);
+        SharedSecret::from_bytes(&ss[..]).unwrap()
+    }
+}
@@ -13,13 +13,17 @@ pub mod indcpa
     *              polyvec *pk:         pointer to the input public-key polyvec
     *              const uint8_t *seed: pointer to the input public seed
     **************************************************/
-    use crate::kyber::{poly, poly_struct, polyvec_struct};
-    use crate::kyber ::get_env_var;
+    use crate::kyber::{fips202x4, poly, poly_struct};
+    use crate::kyber::polyvec_struct::{with_rank, PolyVec};
+    use crate::kyber::config::{KYBER_N, KYBER_Q, KYBER_SYMBYTES};
+    use crate::enums::KyberVariant;
+    use crate::kyber::kyber::KyberParams;
     use crate::kyber::xof_state::XofAbsorb;
+    use zeroize::{Zeroize, Zeroizing};
 
-    pub fn pack_pk(r: &mut [u8], pk: & mut polyvec_struct::PolyVec, seed: &[u8]) {
-        let kyber_symbytes: u32 = get_env_var("KYBER_SYMBYTES").unwrap();
-        let kyber_polyvecbytes: usize = get_env_var("KYBER_POLYVECBYTES").unwrap();
+    pub fn pack_pk<const K: usize>(params: &KyberParams, r: &mut [u8], pk: & mut PolyVec<K>, seed: &[u8]) {
+        let kyber_symbytes = KYBER_SYMBYTES as u32;
+        let kyber_polyvecbytes = params.kyber_polyvecbytes as usize;
             crate::kyber::polyvec::polyvec::polyvec_tobytes(r, pk);
             for i in 0..kyber_symbytes {
                 r[i as usize + kyber_polyvecbytes] = seed[i as usize];
@@ -39,9 +43,9 @@ pub mod indcpa
     *                                         matrix A
     *              - const uint8_t *packedpk: pointer to input serialized public key
     **************************************************/
-    pub fn unpack_pk(pk: &mut polyvec_struct::PolyVec, seed: &mut [u8], packedpk: &[u8]) {
-        let kyber_polyvecbytes: usize = crate::kyber ::get_env_var("KYBER_POLYVECBYTES").unwrap();
-        let kyber_symbytes: u32 = crate::kyber ::get_env_var("KYBER_SYMBYTES").unwrap();
+    pub fn unpack_pk<const K: usize>(params: &KyberParams, pk: &mut PolyVec<K>, seed: &mut [u8], packedpk: &[u8]) {
+        let kyber_polyvecbytes = params.kyber_polyvecbytes as usize;
+        let kyber_symbytes = KYBER_SYMBYTES as u32;
             crate::kyber::polyvec::polyvec::polyvec_frombytes(pk, packedpk);
             for i in 0..kyber_symbytes {
                 seed[i as usize] = packedpk[i as usize + kyber_polyvecbytes];
@@ -56,7 +60,7 @@ pub mod indcpa
     * Arguments:   - uint8_t *r:  pointer to output serialized secret key
     *              - polyvec *sk: pointer to input vector of polynomials (secret key)
     **************************************************/
-    pub fn pack_sk(r: &mut [u8], sk: & mut polyvec_struct::PolyVec) {
+    pub fn pack_sk<const K: usize>(r: &mut [u8], sk: & mut PolyVec<K>) {
         crate::kyber::polyvec::polyvec::polyvec_tobytes(r, sk);
     }
     /*************************************************
@@ -69,7 +73,7 @@ pub mod indcpa
     *                                         polynomials (secret key)
     *              - const uint8_t *packedsk: pointer to input serialized secret key
     **************************************************/
-    pub fn unpack_sk(sk: &mut polyvec_struct::PolyVec, packedsk: &[u8]) {
+    pub fn unpack_sk<const K: usize>(sk: &mut PolyVec<K>, packedsk: &[u8]) {
         crate::kyber::polyvec::polyvec::polyvec_frombytes(sk, packedsk);
     }
     
@@ -84,10 +88,10 @@ pub mod indcpa
     *              poly *pk:   pointer to the input vector of polynomials b
     *              poly *v:    pointer to the input polynomial v
     **************************************************/
-    pub fn pack_ciphertext(r: &mut [u8], b: &mut polyvec_struct::PolyVec, v: &mut poly_struct::PolyStruct) {
-        let kyber_polyveccompressedbytes: usize = crate::kyber ::get_env_var("KYBER_POLYVECCOMPRESSEDBYTES").unwrap();
-            crate::kyber::polyvec::polyvec::polyvec_compress(r, b);
-            poly::poly::poly_compress(&mut r[kyber_polyveccompressedbytes..], v);
+    pub fn pack_ciphertext<const K: usize>(params: &KyberParams, r: &mut [u8], b: &mut PolyVec<K>, v: &mut poly_struct::PolyStruct) {
+        let kyber_polyveccompressedbytes = params.kyber_polyveccompressedbytes as usize;
+            crate::kyber::polyvec::polyvec::polyvec_compress(params, r, b);
+            poly::poly::poly_compress(params, &mut r[kyber_polyveccompressedbytes..], v);
         }
     
     
@@ -101,10 +105,10 @@ pub mod indcpa
     *              - poly *v:          pointer to the output polynomial v
     *              - const uint8_t *c: pointer to the input serialized ciphertext
     **************************************************/
-    pub fn unpack_ciphertext(b: &mut polyvec_struct::PolyVec, v: &mut poly_struct::PolyStruct, c: &[u8]) {
-        let kyber_polyveccompressedbytes: usize = crate::kyber ::get_env_var("KYBER_POLYVECCOMPRESSEDBYTES").unwrap();
-        crate::kyber::polyvec::polyvec::polyvec_decompress(b, c);
-        poly::poly::poly_decompress(v, &c[kyber_polyveccompressedbytes..]);
+    pub fn unpack_ciphertext<const K: usize>(params: &KyberParams, b: &mut PolyVec<K>, v: &mut poly_struct::PolyStruct, c: &[u8]) {
+        let kyber_polyveccompressedbytes = params.kyber_polyveccompressedbytes as usize;
+        crate::kyber::polyvec::polyvec::polyvec_decompress(params, b, c);
+        poly::poly::poly_decompress(params, v, &c[kyber_polyveccompressedbytes..]);
     }
         
     
@@ -124,7 +128,7 @@ pub mod indcpa
     * Returns number of sampled 16-bit integers (at most len)
     **************************************************/
     pub fn rej_uniform(r: &mut [i16], len: usize, buf: &[u8], buflen: usize) -> usize {
-        let kyber_q: u32 = crate::kyber ::get_env_var("KYBER_Q").unwrap();
+        let kyber_q = KYBER_Q as u32;
             let mut ctr = 0;
             let mut pos = 0;
             let mut val0: u16;
@@ -162,7 +166,26 @@ pub mod indcpa
     *              - int transposed:      boolean deciding whether A or A^T
     *                                     is generated
     **************************************************/
-    pub fn gen_matrix(a: &mut Vec<polyvec_struct::PolyVec>, mut seed: &Vec<u8>, transposed: bool) {
+    pub fn gen_matrix<const K: usize>(params: &KyberParams, a: &mut [PolyVec<K>; K], seed: &[u8], transposed: bool) {
+        gen_matrices(params, core::slice::from_mut(a), &[seed], transposed);
+    }
+
+    /// Number of matrices `gen_matrices` and the batch functions below work on at once,
+    /// one per lane of the 4-way Keccak.
+    pub const BATCH_LANES: usize = 4;
+
+    /*************************************************
+    * Name:        gen_matrices
+    *
+    * Description: gen_matrix for up to BATCH_LANES seeds at once. The SHAKE128
+    *              streams of all entries of all matrices are squeezed four at a
+    *              time, so a batch of Kyber768 keys has no leftover entry.
+    *
+    * Arguments:   - [[PolyVec; K]] a:    output matrices, one per seed
+    *              - [&[u8]] seeds:       input seeds
+    *              - bool transposed:     whether A or A^T is generated
+    **************************************************/
+    pub fn gen_matrices<const K: usize>(params: &KyberParams, a: &mut [[PolyVec<K>; K]], seeds: &[&[u8]], transposed: bool) {
         /*
         #define GEN_MATRIX_NBLOCKS ((12*KYBER_N/8*(1 << 12)/KYBER_Q \
                              + XOF_BLOCKBYTES)/XOF_BLOCKBYTES)
@@ -171,79 +194,108 @@ pub mod indcpa
                              #define SHAKE128_RATE 168
 
          */ 
-        const XOF_BLOCKBYTES: usize = 168;
-        let kyber_n: usize = crate::kyber ::get_env_var("KYBER_N").unwrap();
-        let kyber_q: usize = crate::kyber ::get_env_var("KYBER_Q").unwrap();
-        let kyber_k: u32 = crate::kyber ::get_env_var("KYBER_K").unwrap();
-        let kyber_ssbytes: u32 = crate::kyber ::get_env_var("KYBER_SSBYTES").unwrap();
-            let gen_matrix_nblocks: usize = ((12 * kyber_n / 8 * (1 << 12) / kyber_q + XOF_BLOCKBYTES) / XOF_BLOCKBYTES);
+        // Kyber-90s swaps SHAKE128 for AES-256-CTR, which squeezes 64 byte blocks
+        const SHAKE128_RATE: usize = 168;
+        const AES256CTR_BLOCKBYTES: usize = 64;
+        const fn nblocks(xof_blockbytes: usize) -> usize {
+            (12 * KYBER_N / 8 * (1 << 12) / KYBER_Q + xof_blockbytes) / xof_blockbytes
+        }
+        const BUFLEN_SHAKE: usize = nblocks(SHAKE128_RATE) * SHAKE128_RATE;
+        const BUFLEN_AES: usize = nblocks(AES256CTR_BLOCKBYTES) * AES256CTR_BLOCKBYTES;
+        const BUFLEN: usize = if BUFLEN_SHAKE > BUFLEN_AES { BUFLEN_SHAKE } else { BUFLEN_AES };
+        let kyber_n = KYBER_N;
+        let kyber_k = K;
+        let xof_blockbytes = if params.kyber_90s { AES256CTR_BLOCKBYTES } else { SHAKE128_RATE };
+            let gen_matrix_nblocks: usize = nblocks(xof_blockbytes);
            
             let mut ctr;
             let mut buflen;
             let mut off;
-            let mut buf = vec![0u8; gen_matrix_nblocks * XOF_BLOCKBYTES + 2];
-
-            if kyber_ssbytes == 32
-            {
-                let mut state = crate::kyber::xof_state::KeccakState::new();
-                for i in 0..kyber_k {
-                    for j in 0..kyber_k {
-                        if transposed
-                        {
-                            crate::kyber::symmetric_shake::symmetric_shake::kyber_shake128_absorb(&mut state, &seed, i as u8, j as u8);
-                        }
-                        else
-                        {
-                            let seed_slice: &[u8] = &seed;
+            let mut buf = [0u8; BUFLEN + 2];
 
+            // Entry e is row i, column j of matrix m
+            let entry = |e: usize| (e / (kyber_k * kyber_k), e / kyber_k % kyber_k, e % kyber_k);
+            let xy = |i: usize, j: usize| if transposed { (i as u8, j as u8) } else { (j as u8, i as u8) };
 
-                            crate::kyber::symmetric_shake::symmetric_shake::kyber_shake128_absorb(&mut state, seed_slice, j as u8,  i as u8);
+            if !params.kyber_90s
+            {
+                // Four entries at a time with the 4-way Keccak, the rest one by one
+                let entries = seeds.len() * kyber_k * kyber_k;
+                let batched = entries - entries % 4;
+                let mut states = [[0u64; 25]; 4];
+                let mut bufs = [[0u8; BUFLEN + 2]; 4];
+                for first in (0..batched).step_by(4) {
+                    let mut extseeds = [[0u8; KYBER_SYMBYTES + 2]; 4];
+                    for (l, extseed) in extseeds.iter_mut().enumerate() {
+                        let (m, i, j) = entry(first + l);
+                        let (x, y) = xy(i, j);
+                        extseed[..KYBER_SYMBYTES].copy_from_slice(&seeds[m][..KYBER_SYMBYTES]);
+                        extseed[KYBER_SYMBYTES] = x;
+                        extseed[KYBER_SYMBYTES + 1] = y;
+                    }
+                    fips202x4::shake128x4_absorb(&mut states, [&extseeds[0], &extseeds[1], &extseeds[2], &extseeds[3]]);
+                    fips202x4::shake128x4_squeezeblocks(bufs.each_mut().map(|buf| &mut buf[..]), gen_matrix_nblocks, &mut states);
+
+                    buflen = gen_matrix_nblocks * xof_blockbytes;
+                    let mut ctrs = [0usize; 4];
+                    for l in 0..4 {
+                        let (m, i, j) = entry(first + l);
+                        ctrs[l] = rej_uniform(&mut a[m][i].vec[j].coeffs, kyber_n, &bufs[l], buflen);
+                    }
 
+                    // SHAKE128_RATE is a multiple of 3, so no bytes carry over between blocks
+                    while ctrs.iter().any(|&ctr| ctr < kyber_n) {
+                        fips202x4::shake128x4_squeezeblocks(bufs.each_mut().map(|buf| &mut buf[..]), 1, &mut states);
+                        for l in 0..4 {
+                            if ctrs[l] < kyber_n {
+                                let (m, i, j) = entry(first + l);
+                                ctrs[l] += rej_uniform(&mut a[m][i].vec[j].coeffs[ctrs[l]..], kyber_n - ctrs[l], &bufs[l], xof_blockbytes);
+                            }
                         }
-                        crate::kyber::fips202::fips202::shake128_squeezeblocks(&mut buf, gen_matrix_nblocks, &mut state);
-
-                        buflen = gen_matrix_nblocks * XOF_BLOCKBYTES;
-                        ctr = rej_uniform(&mut a[i as usize].vec[j as usize].coeffs, kyber_n as usize, &buf, buflen);
+                    }
+                }
 
-                        while ctr < kyber_n as usize {
-                            off = buflen % 3;
-                            for k in 0..off {
-                                buf[k] = buf[buflen - off + k];
-                            }
-                            crate::kyber::fips202::fips202::shake128_squeezeblocks(&mut buf[off..], 1, &mut state);
-                            buflen = off + XOF_BLOCKBYTES;
-                            ctr += rej_uniform(&mut a[i as usize].vec[j as usize].coeffs[ctr..], kyber_n as usize - ctr, &buf, buflen);
+                let mut state = crate::kyber::xof_state::KeccakState::new();
+                for e in batched..entries {
+                    let (m, i, j) = entry(e);
+                    let (x, y) = xy(i, j);
+                    crate::kyber::symmetric_shake::symmetric_shake::kyber_shake128_absorb(&mut state, seeds[m], x, y);
+                    crate::kyber::fips202::fips202::shake128_squeezeblocks(&mut buf, gen_matrix_nblocks, &mut state);
+
+                    buflen = gen_matrix_nblocks * xof_blockbytes;
+                    ctr = rej_uniform(&mut a[m][i].vec[j].coeffs, kyber_n, &buf, buflen);
+
+                    while ctr < kyber_n {
+                        off = buflen % 3;
+                        for k in 0..off {
+                            buf[k] = buf[buflen - off + k];
                         }
+                        crate::kyber::fips202::fips202::shake128_squeezeblocks(&mut buf[off..], 1, &mut state);
+                        buflen = off + xof_blockbytes;
+                        ctr += rej_uniform(&mut a[m][i].vec[j].coeffs[ctr..], kyber_n - ctr, &buf, buflen);
                     }
                 }
             }
             else
             {
                 let mut state = crate::kyber::xof_state::Aes256CtrCtx::new();
-                for i in 0..kyber_k {
-                    for j in 0..kyber_k {
-                        if transposed
-                        {
-                            crate::kyber::symmetric_aes::symmetric_aes::kyber_aes256xof_absorb(&mut state, seed, i as u8, j as u8);
-                        }
-                        else
-                        {
-                            crate::kyber::symmetric_aes::symmetric_aes::kyber_aes256xof_absorb(&mut state, seed, j as u8, i as u8);
-                        }
-
-
-                        buflen = gen_matrix_nblocks * XOF_BLOCKBYTES;
-                        ctr = rej_uniform(&mut a[i as usize].vec[j as usize].coeffs, kyber_n as usize, &buf, buflen);
-
-                        while ctr < kyber_n as usize {
-                            off = buflen % 3;
-                            for k in 0..off {
-                                buf[k] = buf[buflen - off + k];
-                            }
-
-                            buflen = off + XOF_BLOCKBYTES;
-                            ctr += rej_uniform(&mut a[i as usize].vec[j as usize].coeffs[ctr..], kyber_n as usize - ctr, &buf, buflen);
+                for e in 0..seeds.len() * kyber_k * kyber_k {
+                    let (m, i, j) = entry(e);
+                    let (x, y) = xy(i, j);
+                    crate::kyber::symmetric_aes::symmetric_aes::kyber_aes256xof_absorb(&mut state, seeds[m], x, y);
+                    crate::kyber::aes256ctr::aes256ctr::aes256ctr_squeezeblocks(&mut buf, gen_matrix_nblocks, &mut state);
+
+                    buflen = gen_matrix_nblocks * xof_blockbytes;
+                    ctr = rej_uniform(&mut a[m][i].vec[j].coeffs, kyber_n, &buf, buflen);
+
+                    while ctr < kyber_n {
+                        off = buflen % 3;
+                        for k in 0..off {
+                            buf[k] = buf[buflen - off + k];
                         }
+                        crate::kyber::aes256ctr::aes256ctr::aes256ctr_squeezeblocks(&mut buf[off..], 1, &mut state);
+                        buflen = off + xof_blockbytes;
+                        ctr += rej_uniform(&mut a[m][i].vec[j].coeffs[ctr..], kyber_n - ctr, &buf, buflen);
                     }
                 }
             }
@@ -252,87 +304,100 @@ pub mod indcpa
     
 
     /*************************************************
-    * Name:        indcpa_keypair
+    * Name:        indcpa_keypair_derand
     *
-    * Description: Generates public and private key for the CPA-secure
-    *              public-key encryption scheme underlying Kyber
+    * Description: Deterministically generates public and private key
+    *              for the CPA-secure public-key encryption scheme
+    *              underlying Kyber
     *
-    * Arguments:   - uint8_t *pk: pointer to output public key
-    *                             (of length KYBER_INDCPA_PUBLICKEYBYTES bytes)
-    *              - uint8_t *sk: pointer to output private key
-                                  (of length KYBER_INDCPA_SECRETKEYBYTES bytes)
+    * Arguments:   - uint8_t *pk:          pointer to output public key
+    *                                      (of length KYBER_INDCPA_PUBLICKEYBYTES bytes)
+    *              - uint8_t *sk:          pointer to output private key
+    *                                      (of length KYBER_INDCPA_SECRETKEYBYTES bytes)
+    *              - const uint8_t *coins: pointer to input randomness d
+    *                                      (of length KYBER_SYMBYTES bytes)
     **************************************************/
+    pub fn indcpa_keypair_derand(params: &KyberParams, pk: &mut [u8], sk: &mut [u8], coins: &[u8]) {
+        indcpa_keypair_derand_batch(params, pk, sk, &[&coins[..KYBER_SYMBYTES]]);
+    }
 
+    /*************************************************
+    * Name:        indcpa_keypair_derand_batch
+    *
+    * Description: indcpa_keypair_derand for up to BATCH_LANES key pairs,
+    *              sharing one gen_matrices call.
+    *
+    * Arguments:   - uint8_t *pk:    output public keys, one after the other
+    *              - uint8_t *sk:    output private keys, one after the other
+    *              - [&[u8]] coins:  input randomness d of each key pair
+    **************************************************/
+    pub fn indcpa_keypair_derand_batch(params: &KyberParams, pk: &mut [u8], sk: &mut [u8], coins: &[&[u8]]) {
+        with_rank!(params, keypair_derand(params, pk, sk, coins))
+    }
 
-    pub fn indcpa_keypair(pk: &mut Vec<u8>, sk: &mut Vec<u8>) {
-        let kyber_k: usize = crate::kyber ::get_env_var("KYBER_K").unwrap();
-        let kyber_symbytes: usize = crate::kyber ::get_env_var("KYBER_SYMBYTES").unwrap();
-    
-        // Create a vector to represent the buffer
-        let mut buf = vec![0u8; 2 * kyber_symbytes];
+    fn keypair_derand<const K: usize>(params: &KyberParams, pk: &mut [u8], sk: &mut [u8], coins: &[&[u8]]) {
+        let kyber_symbytes = KYBER_SYMBYTES;
+        let n = coins.len();
+        assert!(n <= BATCH_LANES);
+        let pk_len = params.kyber_indcpa_publickeybytes as usize;
+        let sk_len = params.kyber_indcpa_secretkeybytes as usize;
     
-        // Create mutable vectors for publicseed and noiseseed
-        let mut publicseed = vec![0u8; kyber_symbytes];
-        let mut noiseseed = vec![0u8; kyber_symbytes];
-    
-        // Copy data from buf to publicseed and noiseseed
-        publicseed.copy_from_slice(&buf[0..kyber_symbytes]);
-        noiseseed.copy_from_slice(&buf[kyber_symbytes..]);
-    
-        let mut nonce = 0;
-        let mut a = vec![polyvec_struct::PolyVec::new(); kyber_k];
+        // Each holds d and then the public and noise seeds, with room for the k FIPS 203 appends to d
+        let mut bufs = Zeroizing::new([[0u8; 2 * KYBER_SYMBYTES]; BATCH_LANES]);
+        let mut publicseeds = [[0u8; KYBER_SYMBYTES]; BATCH_LANES];
     
-        let mut e = polyvec_struct::PolyVec::new();
-        let mut pkpv = polyvec_struct::PolyVec::new();
-        let mut skpv = polyvec_struct::PolyVec::new();
-    
-
-        {
-            let mut rng = crate::kyber ::GLOBAL_RANDOM.lock().unwrap();
-            rng.randombytes(&mut publicseed, kyber_symbytes as u64);
-        }
-
-        for i in 0..publicseed.len()
-        {
-            buf[i] = publicseed[i];
-        }
-    let ninty:String = get_env_var("KYBER_90S").unwrap();
-
-        if ninty == "true"
-        { 
-            use sha2::{Sha512, Digest};
-            let mut hasher = Sha512::new();
-            hasher.update(&buf);
-            let result = hasher.finalize();
-    
-            if buf.len() >= 64 {
-                buf[..64].copy_from_slice(&result[..64]);
+        for l in 0..n {
+            let buf = &mut bufs[l];
+            buf[..kyber_symbytes].copy_from_slice(&coins[l][..kyber_symbytes]);
+            // FIPS 203 appends k to d before hashing so the parameter sets are domain separated
+            let mut seed_len = kyber_symbytes;
+            if params.kyber_variant == KyberVariant::MlKem
+            {
+                buf[kyber_symbytes] = K as u8;
+                seed_len += 1;
+            }
+            if params.kyber_90s
+            { 
+                use sha2::{Sha512, Digest};
+                let mut hasher = Sha512::new();
+                hasher.update(&buf[..kyber_symbytes]);
+                let mut result = hasher.finalize();
+                buf.copy_from_slice(&result);
+                result.as_mut_slice().zeroize();
             } else {
-                panic!("Error with hash_g");
+                let mut buf_array = [0u8; 64];
+                crate::kyber::fips202::fips202::sha3_512(&mut buf_array, &buf[..], seed_len);
+                buf.copy_from_slice(&buf_array);
+                buf_array.zeroize();
             }
-        } else {
-            let mut buf_array = [0u8; 64];
-            buf_array.copy_from_slice(&buf);
-    
-            let mut buff_copy = buf_array.clone();
-            crate::kyber::fips202::fips202::sha3_512(&mut buf_array, &buff_copy, kyber_symbytes);
-            buf.copy_from_slice(&buf_array);
 
+            publicseeds[l].copy_from_slice(&buf[0..kyber_symbytes]);
+            // rho is published in pk, rejection sampling may branch on it
+            crate::kyber::valgrind::declassify(&publicseeds[l]);
         }
 
-        publicseed.copy_from_slice(&buf[0..kyber_symbytes]);
+        let mut a: [[PolyVec<K>; K]; BATCH_LANES] = core::array::from_fn(|_| core::array::from_fn(|_| PolyVec::new()));
+        let seeds: [&[u8]; BATCH_LANES] = core::array::from_fn(|l| &publicseeds[l][..]);
+        gen_matrices(params, &mut a[..n], &seeds[..n], false);
     
-        gen_matrix(&mut a, &mut publicseed, false);
-    
-        let noise_copy = buf[32..].to_vec();
-        for i in 0..kyber_k {
-            let mut x = skpv.vec[i as usize].clone(); 
-            poly::poly::poly_getnoise_eta1(&mut x, &noise_copy, nonce);
-            skpv.vec[i as usize] = x;
+        for l in 0..n {
+            keypair_from_matrix(params, &mut pk[l * pk_len..(l + 1) * pk_len], &mut sk[l * sk_len..(l + 1) * sk_len], &a[l], &publicseeds[l], &bufs[l][kyber_symbytes..]);
+        }
+    }
+
+    // The rest of key generation once A is known
+    fn keypair_from_matrix<const K: usize>(params: &KyberParams, pk: &mut [u8], sk: &mut [u8], a: &[PolyVec<K>; K], publicseed: &[u8], noiseseed: &[u8]) {
+        let mut nonce = 0;
+        let mut e = PolyVec::<K>::new();
+        let mut pkpv = PolyVec::<K>::new();
+        let mut skpv = PolyVec::<K>::new();
+
+        for i in 0..K {
+            poly::poly::poly_getnoise_eta1(params, &mut skpv.vec[i], noiseseed, nonce);
             nonce += 1;
         }
-        for i in 0..kyber_k {
-            poly::poly::poly_getnoise_eta1(&mut e.vec[i as usize], &noise_copy, nonce);
+        for i in 0..K {
+            poly::poly::poly_getnoise_eta1(params, &mut e.vec[i], noiseseed, nonce);
             nonce += 1;
         }
     
@@ -340,17 +405,16 @@ pub mod indcpa
         crate::kyber::polyvec::polyvec::polyvec_ntt(&mut e);
     
         // matrix-vector multiplication
-        for i in 0..kyber_k {
-            crate::kyber::polyvec::polyvec::polyvec_pointwise_acc_montgomery(&mut pkpv.vec[i as usize], &a[i as usize], &skpv);
-            poly::poly::poly_tomont(&mut pkpv.vec[i as usize]);
+        for (pk, row) in pkpv.vec.iter_mut().zip(a.iter()) {
+            crate::kyber::polyvec::polyvec::polyvec_pointwise_acc_montgomery(pk, row, &skpv);
+            poly::poly::poly_tomont(pk);
         }
     
-        let pkpv_temp = pkpv.clone();
-        crate::kyber::polyvec::polyvec::polyvec_add(&mut pkpv, &pkpv_temp, &e);
+        crate::kyber::polyvec::polyvec::polyvec_add_assign(&mut pkpv, &e);
         crate::kyber::polyvec::polyvec::polyvec_reduce(&mut pkpv);
     
         pack_sk(sk, &mut skpv);
-        pack_pk(pk, &mut pkpv, &publicseed);
+        pack_pk(params, pk, &mut pkpv, publicseed);
     }
     
 
@@ -371,70 +435,142 @@ pub mod indcpa
     *                                      to deterministically generate all
     *                                      randomness
     **************************************************/
-    pub fn indcpa_enc(c: &mut [u8], m: &[u8], pk: &[u8], coins: &[u8]) {
+    pub fn indcpa_enc(params: &KyberParams, c: &mut [u8], m: &[u8], pk: &[u8], coins: &[u8]) {
+        indcpa_enc_batch(params, c, &[m], pk, &[coins]);
+    }
+
+    /*************************************************
+    * Name:        indcpa_enc_batch
+    *
+    * Description: indcpa_enc for up to BATCH_LANES messages, each to its
+    *              own public key, sharing one gen_matrices call.
+    *
+    * Arguments:   - uint8_t *c:      output ciphertexts, one after the other
+    *              - [&[u8]] m:       input messages
+    *              - const uint8_t *pk: input public keys, one after the other
+    *              - [&[u8]] coins:   input random coins of each encryption
+    **************************************************/
+    pub fn indcpa_enc_batch(params: &KyberParams, c: &mut [u8], m: &[&[u8]], pk: &[u8], coins: &[&[u8]]) {
+        with_rank!(params, enc(params, c, m, pk, coins))
+    }
+
+    fn enc<const K: usize>(params: &KyberParams, c: &mut [u8], m: &[&[u8]], pk: &[u8], coins: &[&[u8]]) {
+            let n = m.len();
+            assert!(n <= BATCH_LANES && coins.len() == n);
+            let pk_len = params.kyber_indcpa_publickeybytes as usize;
+            let c_len = params.kyber_indcpa_bytes as usize;
+
+            let mut seeds = [[0u8; KYBER_SYMBYTES]; BATCH_LANES];
+            let mut pkpvs: [PolyVec<K>; BATCH_LANES] = core::array::from_fn(|_| PolyVec::new());
+            for l in 0..n {
+                unpack_pk(params, &mut pkpvs[l], &mut seeds[l], &pk[l * pk_len..(l + 1) * pk_len]);
+            }
 
-        let kyber_symbytes: usize = crate::kyber ::get_env_var("KYBER_SYMBYTES").unwrap();
-        let kyber_k: usize = crate::kyber ::get_env_var("KYBER_K").unwrap();
-            let mut seed = vec![0u8; kyber_symbytes];
+            let mut at: [[PolyVec<K>; K]; BATCH_LANES] = core::array::from_fn(|_| core::array::from_fn(|_| PolyVec::new()));
+            let seed_refs: [&[u8]; BATCH_LANES] = core::array::from_fn(|l| &seeds[l][..]);
+            gen_matrices(params, &mut at[..n], &seed_refs[..n], true);
+
+            for l in 0..n {
+                enc_with_matrix(params, &mut c[l * c_len..(l + 1) * c_len], m[l], &pkpvs[l], &at[l], coins[l]);
+            }
+        }
+
+    // The rest of encryption once the public key is unpacked and A^T is known
+    fn enc_with_matrix<const K: usize>(params: &KyberParams, c: &mut [u8], m: &[u8], pkpv: &PolyVec<K>, at: &[PolyVec<K>; K], coins: &[u8]) {
             let mut nonce = 0;
-            let mut sp = polyvec_struct::PolyVec::new();
-            let mut pkpv = polyvec_struct::PolyVec::new();
-            let mut ep = polyvec_struct::PolyVec::new();
-            let mut at = vec![polyvec_struct::PolyVec::new(); kyber_k];
-            let mut bp = polyvec_struct::PolyVec::new();
+            let mut sp = PolyVec::<K>::new();
+            let mut ep = PolyVec::<K>::new();
+            let mut bp = PolyVec::<K>::new();
             let mut v = poly_struct::PolyStruct::new();
             let mut k = poly_struct::PolyStruct::new();
             let mut epp = poly_struct::PolyStruct::new();
 
-            unpack_pk(&mut pkpv, &mut seed, pk);
             poly::poly::poly_frommsg(&mut k, m);
 
-            gen_matrix(&mut at, &seed, true);
-            for i in 0..kyber_k {
-                poly::poly::poly_getnoise_eta1(&mut sp.vec[i as usize], coins, nonce);
+            for i in 0..K {
+                poly::poly::poly_getnoise_eta1(params, &mut sp.vec[i], coins, nonce);
                 nonce += 1;
             }
-            for i in 0..kyber_k {
-                poly::poly::poly_getnoise_eta2(&mut ep.vec[i as usize], coins, nonce);
+            for i in 0..K {
+                poly::poly::poly_getnoise_eta2(params, &mut ep.vec[i], coins, nonce);
                 nonce += 1;
             }
-            poly::poly::poly_getnoise_eta2(&mut epp, &coins, nonce);
-            nonce += 1;
+            poly::poly::poly_getnoise_eta2(params, &mut epp, coins, nonce);
 
             crate::kyber::polyvec::polyvec::polyvec_ntt(&mut sp);
 
             // matrix-vector multiplication
-            for i in 0..kyber_k {
-                let mut temp_bp = polyvec_struct::PolyVec::new();
-                crate::kyber::polyvec::polyvec::polyvec_pointwise_acc_montgomery(&mut temp_bp.vec[i as usize], &at[i as usize], &sp);
-                bp.vec[i as usize] = temp_bp.vec[i as usize].clone();
+            for (b, row) in bp.vec.iter_mut().zip(at.iter()) {
+                crate::kyber::polyvec::polyvec::polyvec_pointwise_acc_montgomery(b, row, &sp);
             }
 
-            let mut temp_v = poly_struct::PolyStruct::new();
-            crate::kyber::polyvec::polyvec::polyvec_pointwise_acc_montgomery(&mut temp_v, &pkpv, &sp);
-            v.coeffs = temp_v.coeffs.clone();
+            crate::kyber::polyvec::polyvec::polyvec_pointwise_acc_montgomery(&mut v, pkpv, &sp);
 
             crate::kyber::polyvec::polyvec::polyvec_invntt_tomont(&mut bp);
             poly::poly::poly_invntt_tomont(&mut v);
 
-            let mut temp_bp2 = polyvec_struct::PolyVec::new();
-            crate::kyber::polyvec::polyvec::polyvec_add(&mut temp_bp2, &bp, &ep);
-            bp = temp_bp2;
-
-            let mut temp_v2 = poly_struct::PolyStruct::new();
-            poly::poly::poly_add(&mut temp_v2, &v, &epp);
-            v = temp_v2;
-
-            let mut temp_v3 = poly_struct::PolyStruct::new();
-            poly::poly::poly_add(&mut temp_v3, &v, &k);
-            v = temp_v3;
+            crate::kyber::polyvec::polyvec::polyvec_add_assign(&mut bp, &ep);
+            poly::poly::poly_add_assign(&mut v, &epp);
+            poly::poly::poly_add_assign(&mut v, &k);
 
             crate::kyber::polyvec::polyvec::polyvec_reduce(&mut bp);
             poly::poly::poly_reduce(&mut v);
 
-            pack_ciphertext(c, &mut bp, &mut v);
+            pack_ciphertext(params, c, &mut bp, &mut v);
         }
     
+    /// The unpacked public key `pkpv` and the expanded matrix A^T, which
+    /// indcpa_enc otherwise derives from the packed key on every call.
+    #[derive(Clone)]
+    pub struct PreparedPk<const K: usize> {
+        pub(crate) pkpv: PolyVec<K>,
+        pub(crate) at: [PolyVec<K>; K],
+    }
+
+    /*************************************************
+    * Name:        indcpa_prepare_pk
+    *
+    * Description: Unpacks a public key and generates A^T from its seed,
+    *              once for any number of indcpa_enc_prepared calls
+    *
+    * Arguments:   - const uint8_t *pk: pointer to input public key
+    *                                   (of length KYBER_INDCPA_PUBLICKEYBYTES)
+    **************************************************/
+    pub fn indcpa_prepare_pk<const K: usize>(params: &KyberParams, pk: &[u8]) -> PreparedPk<K> {
+        let mut prepared = PreparedPk {
+            pkpv: PolyVec::new(),
+            at: core::array::from_fn(|_| PolyVec::new()),
+        };
+        let mut seed = [0u8; KYBER_SYMBYTES];
+        unpack_pk(params, &mut prepared.pkpv, &mut seed, pk);
+        gen_matrix(params, &mut prepared.at, &seed, true);
+        prepared
+    }
+
+    /*************************************************
+    * Name:        indcpa_enc_prepared
+    *
+    * Description: indcpa_enc to a public key prepared by indcpa_prepare_pk
+    **************************************************/
+    pub fn indcpa_enc_prepared<const K: usize>(params: &KyberParams, c: &mut [u8], m: &[u8], pk: &PreparedPk<K>, coins: &[u8]) {
+        enc_with_matrix(params, c, m, &pk.pkpv, &pk.at, coins);
+    }
+
+    /*************************************************
+    * Name:        indcpa_unpack_sk
+    *
+    * Description: Decodes the secret polynomial vector, which is stored
+    *              in NTT form, for indcpa_dec_prepared
+    *
+    * Arguments:   - const uint8_t *sk: pointer to input secret key
+    *                                   (of length KYBER_INDCPA_SECRETKEYBYTES)
+    **************************************************/
+    pub fn indcpa_unpack_sk<const K: usize>(sk: &[u8]) -> PolyVec<K> {
+        let mut skpv = PolyVec::<K>::new();
+        unpack_sk(&mut skpv, sk);
+        skpv
+    }
+
     /*************************************************
     * Name:        indcpa_dec
     *
@@ -448,17 +584,30 @@ pub mod indcpa
     *              - const uint8_t *sk: pointer to input secret key
     *                                   (of length KYBER_INDCPA_SECRETKEYBYTES)
     **************************************************/
-    pub fn indcpa_dec(m: &mut [u8], c: &[u8], sk: &[u8]) {
-        let mut bp = polyvec_struct::PolyVec::new();
-        let mut skpv = polyvec_struct::PolyVec::new();
+    pub fn indcpa_dec(params: &KyberParams, m: &mut [u8], c: &[u8], sk: &[u8]) {
+        with_rank!(params, dec(params, m, c, sk))
+    }
+
+    fn dec<const K: usize>(params: &KyberParams, m: &mut [u8], c: &[u8], sk: &[u8]) {
+        let skpv = indcpa_unpack_sk::<K>(sk);
+        indcpa_dec_prepared(params, m, c, &skpv);
+    }
+
+    /*************************************************
+    * Name:        indcpa_dec_prepared
+    *
+    * Description: indcpa_dec with the secret key already unpacked
+    *              by indcpa_unpack_sk
+    **************************************************/
+    pub fn indcpa_dec_prepared<const K: usize>(params: &KyberParams, m: &mut [u8], c: &[u8], skpv: &PolyVec<K>) {
+        let mut bp = PolyVec::<K>::new();
         let mut v = poly_struct::PolyStruct::new();
         let mut mp = poly_struct::PolyStruct::new();
 
-        unpack_ciphertext(&mut bp, &mut v, c);
-        unpack_sk(&mut skpv, sk);
+        unpack_ciphertext(params, &mut bp, &mut v, c);
 
         crate::kyber::polyvec::polyvec::polyvec_ntt(&mut bp);
-        crate::kyber::polyvec::polyvec::polyvec_pointwise_acc_montgomery(&mut mp, &skpv, &bp);
+        crate::kyber::polyvec::polyvec::polyvec_pointwise_acc_montgomery(&mut mp, skpv, &bp);
         poly::poly::poly_invntt_tomont(&mut mp);
 
         let mut result = poly_struct::PolyStruct::new();  
@@ -469,4 +618,4 @@ pub mod indcpa
     }
 
 
-}
\ No newline at end of file
+}
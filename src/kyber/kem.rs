@@ -7,7 +7,6 @@ trait HashFunction {
 }
 
 
-//#[cfg(feature = "kyber_90s")]
 mod kyber_90s {
     use super::HashFunction;
     use sha2::{Sha256, Sha512, Digest};
@@ -48,7 +47,6 @@ mod kyber_90s {
     }
 }
 
-#[cfg(not(feature = "kyber_90s"))]
 mod kyber_2020s {
     use super::HashFunction;
     use crate::kyber::fips202::fips202::sha3_256;
@@ -74,156 +72,434 @@ mod kyber_2020s {
 }
 
 pub mod kem{
-    use std::env;
-    use crate::kyber::config::KYBER_CIPHERTEXTBYTES;
+    use crate::enums::KyberVariant;
+    use crate::error::KyberError;
+    use crate::kyber::config::{KYBER_CIPHERTEXTBYTES_MAX, KYBER_POLYVECBYTES_MAX, KYBER_SSBYTES, KYBER_SYMBYTES};
+    use crate::kyber::polyvec_struct::{with_rank, PolyVec};
     use crate::kyber::kem::{HashFunction, kyber_2020s, kyber_90s};
-    use crate::kyber::kyber;
-    use crate::kyber::kyber_rng::KyberRng;
+    use crate::kyber::kem_rng::{fill, KemRng};
+    use crate::kyber::indcpa::indcpa::{PreparedPk, BATCH_LANES};
+    use crate::kyber::kyber::KyberParams;
+    use zeroize::{Zeroize, Zeroizing};
 
 
-    fn selected_hash_function() -> Box<dyn HashFunction> {
-        match env::var("KYBER_90S") {
-            Ok(value) => {
-                if value.eq_ignore_ascii_case("true")
-                {
-                    Box::new(kyber_90s::HashFunction90s)
-                }
-                else {
-                    Box::new(kyber_2020s::HashFunctions)
-                }
+    fn check_key_length(key: &[u8], expected: usize) -> Result<(), KyberError> {
+        if key.len() != expected {
+            return Err(KyberError::WrongKeyLength { expected, actual: key.len() });
+        }
+        Ok(())
+    }
 
-            }
-            Err(_) => {
-                Box::new(kyber_2020s::HashFunctions)
-            }
+    fn check_ciphertext_length(ct: &[u8], expected: usize) -> Result<(), KyberError> {
+        if ct.len() != expected {
+            return Err(KyberError::WrongCiphertextLength { expected, actual: ct.len() });
         }
+        Ok(())
+    }
 
+    fn selected_hash_function(params: &KyberParams) -> Box<dyn HashFunction> {
+        if params.kyber_90s
+        {
+            Box::new(kyber_90s::HashFunction90s)
+        }
+        else {
+            Box::new(kyber_2020s::HashFunctions)
+        }
     }
 
 
-   
-    pub fn crypto_kem_keypair(mut pk: &mut Vec<u8>, mut sk: &mut Vec<u8>) -> Result<(), ()> {
-        
-        let kyber_i_secret: usize = crate::kyber ::get_env_var("KYBER_INDCPA_SECRETKEYBYTES").unwrap();
-        let kyber_i_public: usize = crate::kyber ::get_env_var("KYBER_INDCPA_PUBLICKEYBYTES").unwrap();
-        let kyber_secret: usize = crate::kyber ::get_env_var("KYBER_SECRETKEYBYTES").unwrap();
-        let kyber_public: usize = crate::kyber ::get_env_var("KYBER_PUBLICKEYBYTES").unwrap();
-        let kyber_sym: usize = crate::kyber ::get_env_var("KYBER_SYMBYTES").unwrap();
+    /*************************************************
+    * Name:        crypto_kem_check_pk
+    *
+    * Description: Encapsulation key check of FIPS 203, section 7.2.
+    *              Decodes the polynomial vector of pk, encodes it again
+    *              and compares, which fails if any coefficient is not
+    *              reduced modulo q
+    *
+    * Arguments:   - const uint8_t *pk: pointer to input public key
+    *                (of length kyber_publickeybytes bytes)
+    **************************************************/
+    pub fn crypto_kem_check_pk(params: &KyberParams, pk: &[u8]) -> Result<(), KyberError> {
+        let kyber_polyvecbytes = params.kyber_polyvecbytes as usize;
+
+        check_key_length(pk, params.kyber_publickeybytes as usize)?;
+
+        if !with_rank!(params, reencodes_unchanged(&pk[..kyber_polyvecbytes])) {
+            return Err(KyberError::InvalidKey("public key modulus check failed, a coefficient is not reduced modulo q"));
+        }
+        Ok(())
+    }
 
-            crate::kyber::indcpa::indcpa::indcpa_keypair(pk, sk);
+    // Decodes and re-encodes the polynomial vector, true if no coefficient changed
+    fn reencodes_unchanged<const K: usize>(packed: &[u8]) -> bool {
+        let mut polyvec = PolyVec::<K>::new();
+        let mut reencoded = [0u8; KYBER_POLYVECBYTES_MAX];
+        crate::kyber::polyvec::polyvec::polyvec_frombytes(&mut polyvec, packed);
+        crate::kyber::polyvec::polyvec::polyvec_tobytes(&mut reencoded, &mut polyvec);
+        reencoded[..packed.len()] == *packed
+    }
 
-            // Copy data from pk to sk
-            for i in 0..kyber_i_public {
-                sk[kyber_i_secret + i] = pk[i];
-            }
+    // H(pk), which encapsulation hashes into every shared secret
+    pub fn crypto_kem_pk_hash(params: &KyberParams, pk: &[u8]) -> [u8; 32] {
+        let mut pk_hash: [u8; 32] = [0; 32];
+        selected_hash_function(params).hash_h(&mut pk_hash, pk);
+        pk_hash
+    }
 
-            // Calculate hash_h(sk + kyber_secretkeybytes - 2 * kyber_symbytes, pk, kyber_publickeybytes)
-            if sk.len() >= kyber_secret - 2 * kyber_sym + 32 {
-                let hash_output_slice = &mut sk[kyber_secret - 2 * kyber_sym..kyber_secret - 2 * kyber_sym + 32];
-                let hash_output_array: &mut [u8; 32] = hash_output_slice.try_into()
-                    .expect("Slice with incorrect length");
-                selected_hash_function().hash_h(hash_output_array, &pk[..kyber_public]);
-            } else {
-                // Handle error: slice is not long enough
-                return Err(());
-            }
+    /*************************************************
+    * Name:        crypto_kem_check_sk
+    *
+    * Description: Decapsulation key check of FIPS 203, section 7.3.
+    *              Checks the length of sk and that the stored H(pk)
+    *              matches the public key embedded in sk
+    *
+    * Arguments:   - const uint8_t *sk: pointer to input private key
+    *                (of length kyber_secretkeybytes bytes)
+    **************************************************/
+    pub fn crypto_kem_check_sk(params: &KyberParams, sk: &[u8]) -> Result<(), KyberError> {
+        let kyber_i_secret = params.kyber_indcpa_secretkeybytes as usize;
+        let kyber_public = params.kyber_publickeybytes as usize;
+        let kyber_secret = params.kyber_secretkeybytes as usize;
+        let kyber_sym = KYBER_SYMBYTES;
+
+        check_key_length(sk, kyber_secret)?;
+
+        let mut pk_hash: [u8; 32] = [0; 32];
+        selected_hash_function(params).hash_h(&mut pk_hash, &sk[kyber_i_secret..kyber_i_secret + kyber_public]);
+
+        if pk_hash[..] != sk[kyber_secret - 2 * kyber_sym..kyber_secret - kyber_sym] {
+            return Err(KyberError::InvalidKey("private key hash check failed, H(pk) does not match the embedded public key"));
+        }
+        Ok(())
+    }
 
-            {
-                let mut rng = crate::kyber ::GLOBAL_RANDOM.lock().unwrap();
-                let mut temp_vec = vec![0u8; kyber_sym as usize];
-                rng.randombytes(&mut temp_vec, kyber_sym as u64);
-                sk[kyber_secret - kyber_sym..kyber_secret].copy_from_slice(&temp_vec);
-            }
-            
-            
+    /*************************************************
+    * Name:        crypto_kem_keypair
+    *
+    * Description: Generates public and private key
+    *              for CCA-secure Kyber key encapsulation mechanism
+    *
+    * Arguments:   - uint8_t *pk: pointer to output public key
+    *                (an already allocated array of kyber_publickeybytes bytes)
+    *              - uint8_t *sk: pointer to output private key
+    *                (an already allocated array of kyber_secretkeybytes bytes)
+    *              - rng: source of the randomness d and z
+    **************************************************/
+    pub fn crypto_kem_keypair<R: KemRng + ?Sized>(params: &KyberParams, pk: &mut [u8], sk: &mut [u8], rng: &mut R) -> Result<(), KyberError> {
+        let kyber_sym = KYBER_SYMBYTES;
+        let mut coins = Zeroizing::new([0u8; 2 * KYBER_SYMBYTES]);
+
+        // d and z are drawn separately, in the order the reference code draws them
+        fill(rng, &mut coins[..kyber_sym])?;
+        fill(rng, &mut coins[kyber_sym..])?;
+
+        crypto_kem_keypair_derand(params, pk, sk, &coins[..])
+    }
 
-             return Ok(());
+    /*************************************************
+    * Name:        crypto_kem_keypair_derand
+    *
+    * Description: Deterministically generates public and private key
+    *              for CCA-secure Kyber key encapsulation mechanism
+    *
+    * Arguments:   - uint8_t *pk: pointer to output public key
+    *                (an already allocated array of kyber_publickeybytes bytes)
+    *              - uint8_t *sk: pointer to output private key
+    *                (an already allocated array of kyber_secretkeybytes bytes)
+    *              - const uint8_t *coins: pointer to input randomness d || z
+    *                (an already allocated array of 2*KYBER_SYMBYTES bytes)
+    **************************************************/
+    pub fn crypto_kem_keypair_derand(params: &KyberParams, pk: &mut [u8], sk: &mut [u8], coins: &[u8]) -> Result<(), KyberError> {
+        check_key_length(pk, params.kyber_publickeybytes as usize)?;
+        check_key_length(sk, params.kyber_secretkeybytes as usize)?;
+        if coins.len() != 2 * KYBER_SYMBYTES {
+            return Err(KyberError::Rng("key generation needs 64 bytes of coins"));
+        }
 
+        crypto_kem_keypair_derand_batch(params, pk, sk, coins)
+    }
 
+    /*************************************************
+    * Name:        crypto_kem_keypair_derand_batch
+    *
+    * Description: crypto_kem_keypair_derand for any number of key pairs,
+    *              with matrix generation shared by up to BATCH_LANES keys
+    *
+    * Arguments:   - uint8_t *pk: output public keys, one after the other
+    *              - uint8_t *sk: output private keys, one after the other
+    *              - const uint8_t *coins: d || z of each key pair, one
+    *                after the other
+    **************************************************/
+    pub fn crypto_kem_keypair_derand_batch(params: &KyberParams, pk: &mut [u8], sk: &mut [u8], coins: &[u8]) -> Result<(), KyberError> {
+        let kyber_i_secret = params.kyber_indcpa_secretkeybytes as usize;
+        let kyber_i_public = params.kyber_indcpa_publickeybytes as usize;
+        let kyber_secret = params.kyber_secretkeybytes as usize;
+        let kyber_public = params.kyber_publickeybytes as usize;
+        let kyber_sym = KYBER_SYMBYTES;
+
+        if !coins.len().is_multiple_of(2 * kyber_sym) {
+            return Err(KyberError::Rng("key generation needs 64 bytes of coins per key pair"));
+        }
+        let n = coins.len() / (2 * kyber_sym);
+        check_key_length(pk, n * kyber_public)?;
+        check_key_length(sk, n * kyber_secret)?;
+
+        let chunks = pk.chunks_mut(BATCH_LANES * kyber_public)
+            .zip(sk.chunks_mut(BATCH_LANES * kyber_secret))
+            .zip(coins.chunks(BATCH_LANES * 2 * kyber_sym));
+        for ((pk, sk), coins) in chunks {
+            let count = coins.len() / (2 * kyber_sym);
+            let d: [&[u8]; BATCH_LANES] = core::array::from_fn(|l| if l < count { &coins[2 * kyber_sym * l..][..kyber_sym] } else { &[] });
+            // The CPA secret keys are packed together here and then moved into place
+            let mut indcpa_sk = Zeroizing::new([0u8; BATCH_LANES * KYBER_POLYVECBYTES_MAX]);
+            crate::kyber::indcpa::indcpa::indcpa_keypair_derand_batch(params, pk, &mut indcpa_sk[..count * kyber_i_secret], &d[..count]);
+
+            for l in 0..count {
+                let pk = &pk[l * kyber_public..(l + 1) * kyber_public];
+                let sk = &mut sk[l * kyber_secret..(l + 1) * kyber_secret];
+                sk[..kyber_i_secret].copy_from_slice(&indcpa_sk[l * kyber_i_secret..(l + 1) * kyber_i_secret]);
+
+                // Copy data from pk to sk
+                sk[kyber_i_secret..kyber_i_secret + kyber_i_public].copy_from_slice(&pk[..kyber_i_public]);
+
+                // Calculate hash_h(sk + kyber_secretkeybytes - 2 * kyber_symbytes, pk, kyber_publickeybytes)
+                let mut pk_hash: [u8; 32] = [0; 32];
+                selected_hash_function(params).hash_h(&mut pk_hash, pk);
+                sk[kyber_secret - 2 * kyber_sym..kyber_secret - kyber_sym].copy_from_slice(&pk_hash);
+
+                // Value z for pseudo-random output on reject
+                sk[kyber_secret - kyber_sym..].copy_from_slice(&coins[2 * kyber_sym * l + kyber_sym..][..kyber_sym]);
             }
-    pub fn crypto_kem_enc( ct: &mut Vec<u8>, ss: &mut Vec<u8>, pk: &mut Vec<u8>) -> Result<(), ()> {
-        
-            let kyber_symbytes: usize = crate::kyber ::get_env_var("KYBER_SYMBYTES").unwrap();
-            let kyber_ciphertextbytes:usize = crate::kyber ::get_env_var("KYBER_CIPHERTEXTBYTES").unwrap();
+        }
 
-            let hash_function = selected_hash_function();
+        Ok(())
+    }
 
-            let mut buf = vec![0u8; 2 * kyber_symbytes];
-            let mut kr = vec![0u8; 2 * kyber_symbytes];
+    /*************************************************
+    * Name:        crypto_kem_enc
+    *
+    * Description: Generates cipher text and shared
+    *              secret for given public key
+    *
+    * Arguments:   - uint8_t *ct: pointer to output cipher text
+    *                (an already allocated array of kyber_ciphertextbytes bytes)
+    *              - uint8_t *ss: pointer to output shared secret
+    *                (an already allocated array of KYBER_SSBYTES bytes)
+    *              - const uint8_t *pk: pointer to input public key
+    *                (an already allocated array of kyber_publickeybytes bytes)
+    *              - rng: source of the randomness m
+    **************************************************/
+    pub fn crypto_kem_enc<R: KemRng + ?Sized>(params: &KyberParams, ct: &mut [u8], ss: &mut [u8], pk: &[u8], rng: &mut R) -> Result<(), KyberError> {
+        let mut coins = Zeroizing::new([0u8; KYBER_SYMBYTES]);
+        fill(rng, &mut coins[..])?;
+
+        crypto_kem_enc_derand(params, ct, ss, pk, &coins[..])
+    }
 
-            // Generate random data into buf
+    /*************************************************
+    * Name:        crypto_kem_enc_derand
+    *
+    * Description: Deterministically generates cipher text and shared
+    *              secret for given public key
+    *
+    * Arguments:   - uint8_t *ct: pointer to output cipher text
+    *                (an already allocated array of kyber_ciphertextbytes bytes)
+    *              - uint8_t *ss: pointer to output shared secret
+    *                (an already allocated array of KYBER_SSBYTES bytes)
+    *              - const uint8_t *pk: pointer to input public key
+    *                (an already allocated array of kyber_publickeybytes bytes)
+    *              - const uint8_t *coins: pointer to input randomness m
+    *                (an already allocated array of KYBER_SYMBYTES bytes)
+    **************************************************/
+    pub fn crypto_kem_enc_derand(params: &KyberParams, ct: &mut [u8], ss: &mut [u8], pk: &[u8], coins: &[u8]) -> Result<(), KyberError> {
+        check_key_length(pk, params.kyber_publickeybytes as usize)?;
+        check_ciphertext_length(ct, params.kyber_ciphertextbytes as usize)?;
+        check_key_length(ss, KYBER_SSBYTES)?;
+        if coins.len() != KYBER_SYMBYTES {
+            return Err(KyberError::Rng("encapsulation needs 32 bytes of coins"));
+        }
 
-            {
-                let mut rng = crate::kyber ::GLOBAL_RANDOM.lock().unwrap();
-                rng.randombytes(&mut buf, kyber_symbytes as u64);
+        crypto_kem_enc_derand_batch(params, ct, ss, pk, coins)
+    }
+
+    /*************************************************
+    * Name:        crypto_kem_enc_derand_batch
+    *
+    * Description: crypto_kem_enc_derand for any number of public keys,
+    *              with matrix generation shared by up to BATCH_LANES of them
+    *
+    * Arguments:   - uint8_t *ct: output cipher texts, one after the other
+    *              - uint8_t *ss: output shared secrets, one after the other
+    *              - const uint8_t *pk: input public keys, one after the other
+    *              - const uint8_t *coins: randomness m for each public key,
+    *                one after the other
+    **************************************************/
+    pub fn crypto_kem_enc_derand_batch(params: &KyberParams, ct: &mut [u8], ss: &mut [u8], pk: &[u8], coins: &[u8]) -> Result<(), KyberError> {
+        let kyber_symbytes = KYBER_SYMBYTES;
+        let kyber_ciphertextbytes = params.kyber_ciphertextbytes as usize;
+        let kyber_publickeybytes = params.kyber_publickeybytes as usize;
+
+        if !coins.len().is_multiple_of(kyber_symbytes) {
+            return Err(KyberError::Rng("encapsulation needs 32 bytes of coins per public key"));
+        }
+        let n = coins.len() / kyber_symbytes;
+        check_key_length(pk, n * kyber_publickeybytes)?;
+        check_ciphertext_length(ct, n * kyber_ciphertextbytes)?;
+        check_key_length(ss, n * KYBER_SSBYTES)?;
+        for pk in pk.chunks(kyber_publickeybytes) {
+            crypto_kem_check_pk(params, pk)?;
+        }
+
+        let hash_function = selected_hash_function(params);
+
+        let chunks = ct.chunks_mut(BATCH_LANES * kyber_ciphertextbytes)
+            .zip(ss.chunks_mut(BATCH_LANES * KYBER_SSBYTES))
+            .zip(pk.chunks(BATCH_LANES * kyber_publickeybytes))
+            .zip(coins.chunks(BATCH_LANES * kyber_symbytes));
+        for (((ct, ss), pk), coins) in chunks {
+            let count = coins.len() / kyber_symbytes;
+
+            // m, K and the coins r are all secret
+            let mut bufs = Zeroizing::new([[0u8; 2 * KYBER_SYMBYTES]; BATCH_LANES]);
+            let mut krs = Zeroizing::new([[0u8; 2 * KYBER_SYMBYTES]; BATCH_LANES]);
+            for l in 0..count {
+                let mut pk_hash: [u8; 32] = [0; 32];
+                hash_function.hash_h(&mut pk_hash, &pk[l * kyber_publickeybytes..(l + 1) * kyber_publickeybytes]);
+                enc_derive(params, &*hash_function, &mut bufs[l], &mut krs[l], &coins[l * kyber_symbytes..(l + 1) * kyber_symbytes], &pk_hash);
             }
 
+            // coins are in kr+kyber_symbytes
+            let m: [&[u8]; BATCH_LANES] = core::array::from_fn(|l| &bufs[l][..kyber_symbytes]);
+            let r: [&[u8]; BATCH_LANES] = core::array::from_fn(|l| &krs[l][kyber_symbytes..]);
+            crate::kyber::indcpa::indcpa::indcpa_enc_batch(params, ct, &m[..count], pk, &r[..count]);
 
-            // Hash buf (first half) and store the result in the same buffer
-            let mut buf_array_32: [u8; 32] = buf[..32].try_into().expect("Slice with incorrect length");
-            hash_function.hash_h(&mut buf_array_32, &buf[..kyber_symbytes]);
-            buf[..32].copy_from_slice(&buf_array_32);
+            for l in 0..count {
+                let ss: &mut [u8; 32] = (&mut ss[l * KYBER_SSBYTES..(l + 1) * KYBER_SSBYTES]).try_into().expect("KYBER_SSBYTES is 32");
+                enc_finish(params, &*hash_function, ss, &mut krs[l], &ct[l * kyber_ciphertextbytes..(l + 1) * kyber_ciphertextbytes]);
+            }
+        }
 
-            // Multitarget countermeasure for coins + contributory KEM
-            // Hash buf (second half) and store the result in the same buffer
-            let mut buf_array_32_second: [u8; 32] = buf[kyber_symbytes..kyber_symbytes + 32].try_into().expect("Slice with incorrect length");
-            hash_function.hash_h(&mut buf_array_32_second, pk);
-            buf[kyber_symbytes..kyber_symbytes + 32].copy_from_slice(&buf_array_32_second);
+        Ok(())
+    }
 
-            // Resize kr to 64 bytes for hash_g and create a mutable reference
-            kr.resize(64, 0);
-            let kr_array_64: &mut [u8; 64] = kr.as_mut_slice().try_into().expect("Slice with incorrect length");
-            hash_function.hash_g(kr_array_64, &buf);
 
-            // coins are in kr+kyber_symbytes
-            crate::kyber::indcpa::indcpa::indcpa_enc(ct, &buf, pk, &kr[kyber_symbytes..]);
 
-            // Overwrite coins in kr with H(c)
-            let mut hash_output: [u8; 32] = [0; 32];
-            hash_function.hash_h(&mut hash_output, ct);
-            
-            // Copy the hash result back into `kr` starting at KYBER_SYMBYTES
-            kr[kyber_symbytes..kyber_symbytes + 32].copy_from_slice(&hash_output);
-            
+    // Fills buf with m || H(pk) and kr with G(buf), whose second half is the
+    // coins of the CPA encryption. FIPS 203 uses m as drawn, round 3 hashes it first.
+    fn enc_derive(params: &KyberParams, hash_function: &dyn HashFunction, buf: &mut [u8; 64], kr: &mut [u8; 64], coins: &[u8], pk_hash: &[u8; 32]) {
+        let kyber_symbytes = KYBER_SYMBYTES;
+        buf[..kyber_symbytes].copy_from_slice(coins);
+        if params.kyber_variant != KyberVariant::MlKem
+        {
+            let mut m: [u8; 32] = [0; 32];
+            hash_function.hash_h(&mut m, &buf[..kyber_symbytes]);
+            buf[..kyber_symbytes].copy_from_slice(&m);
+            m.zeroize();
+        }
 
-            // Hash concatenation of pre-k and H(c) to k
-            if ss.len() < 32 {
-                return Err(());
-            }
+        // Multitarget countermeasure for coins + contributory KEM
+        buf[kyber_symbytes..].copy_from_slice(pk_hash);
+        hash_function.hash_g(kr, &buf[..]);
+    }
 
-            // Convert the first 32 bytes of ss into a mutable array reference
-            let ss_array_32: &mut [u8; 32] = {
-                let ptr = ss.as_mut_ptr() as *mut [u8; 32];
-                unsafe { &mut *ptr }
-            };
+    // The shared secret once the ciphertext is known. FIPS 203 takes K straight
+    // out of G(m || H(pk)), round 3 hashes pre-k with H(c).
+    fn enc_finish(params: &KyberParams, hash_function: &dyn HashFunction, ss: &mut [u8; 32], kr: &mut [u8; 64], ct: &[u8]) {
+        let kyber_symbytes = KYBER_SYMBYTES;
+        if params.kyber_variant == KyberVariant::MlKem
+        {
+            ss.copy_from_slice(&kr[..32]);
+            return;
+        }
+
+        // Overwrite coins in kr with H(c)
+        let mut hash_output: [u8; 32] = [0; 32];
+        hash_function.hash_h(&mut hash_output, ct);
+        kr[kyber_symbytes..].copy_from_slice(&hash_output);
+        hash_function.kdf(ss, &kr[..]);
+    }
+
+    /*************************************************
+    * Name:        crypto_kem_enc_prepared
+    *
+    * Description: crypto_kem_enc_derand to a public key whose matrix was
+    *              expanded by indcpa_prepare_pk. The caller has already run
+    *              crypto_kem_check_pk on the key.
+    *
+    * Arguments:   - uint8_t *ct: pointer to output cipher text
+    *              - uint8_t *ss: pointer to output shared secret
+    *              - pk_hash:     H(pk)
+    *              - pk:          the prepared public key
+    *              - const uint8_t *coins: pointer to input randomness m
+    **************************************************/
+    pub fn crypto_kem_enc_prepared<const K: usize>(params: &KyberParams, ct: &mut [u8], ss: &mut [u8], pk_hash: &[u8; 32], pk: &PreparedPk<K>, coins: &[u8]) -> Result<(), KyberError> {
+        check_ciphertext_length(ct, params.kyber_ciphertextbytes as usize)?;
+        check_key_length(ss, KYBER_SSBYTES)?;
+        if coins.len() != KYBER_SYMBYTES {
+            return Err(KyberError::Rng("encapsulation needs 32 bytes of coins"));
+        }
 
+        let hash_function = selected_hash_function(params);
+        let mut buf = Zeroizing::new([0u8; 2 * KYBER_SYMBYTES]);
+        let mut kr = Zeroizing::new([0u8; 2 * KYBER_SYMBYTES]);
+        enc_derive(params, &*hash_function, &mut buf, &mut kr, coins, pk_hash);
 
-            hash_function.kdf(ss_array_32, &kr);
+        crate::kyber::indcpa::indcpa::indcpa_enc_prepared(params, ct, &buf[..KYBER_SYMBYTES], pk, &kr[KYBER_SYMBYTES..]);
 
-            return Ok(());
+        let ss: &mut [u8; 32] = ss.try_into().expect("KYBER_SSBYTES is 32");
+        enc_finish(params, &*hash_function, ss, &mut kr, ct);
+        Ok(())
     }
 
+    pub fn crypto_kem_dec(params: &KyberParams, ss: &mut [u8], ct: &[u8], sk: &[u8]) -> Result<(), KyberError> {
+        let kyber_i_secret = params.kyber_indcpa_secretkeybytes as usize;
+        let kyber_cipher = params.kyber_ciphertextbytes as usize;
+        let kyber_secret = params.kyber_secretkeybytes as usize;
 
+        check_key_length(sk, kyber_secret)?;
+        check_ciphertext_length(ct, kyber_cipher)?;
+        check_key_length(ss, KYBER_SSBYTES)?;
+        crypto_kem_check_sk(params, sk)?;
 
-    pub fn crypto_kem_dec(ss: &mut [u8], ct: &[u8], sk: &[u8]) -> Result<(), ()> {
-        let kyber_i_secret: usize = crate::kyber ::get_env_var("KYBER_INDCPA_SECRETKEYBYTES").unwrap();
-        let kyber_cipher: usize = crate::kyber ::get_env_var("KYBER_CIPHERTEXTBYTES").unwrap();
-        let kyber_secret: usize = crate::kyber ::get_env_var("KYBER_SECRETKEYBYTES").unwrap();
-        let kyber_symbytes: usize = crate::kyber ::get_env_var("KYBER_SYMBYTES").unwrap();
+        let pk = &sk[kyber_i_secret..];
+        dec_with(params, ss, ct, sk,
+            |m| crate::kyber::indcpa::indcpa::indcpa_dec(params, m, ct, sk),
+            |cmp, m, coins| crate::kyber::indcpa::indcpa::indcpa_enc(params, cmp, m, pk, coins))
+    }
 
-            let hash_function = selected_hash_function();
-            let mut buf = vec![0u8; 2 * kyber_symbytes];
-            let mut kr = vec![0u8; 2 * kyber_symbytes];
-            let mut cmp = vec![0u8; kyber_cipher];
-            let pk = &sk[kyber_i_secret..];
+    /*************************************************
+    * Name:        crypto_kem_dec_prepared
+    *
+    * Description: crypto_kem_dec with the secret polynomial vector already
+    *              unpacked and the matrix of the embedded public key already
+    *              expanded. The caller has already run crypto_kem_check_sk.
+    **************************************************/
+    pub fn crypto_kem_dec_prepared<const K: usize>(params: &KyberParams, ss: &mut [u8], ct: &[u8], sk: &[u8], skpv: &PolyVec<K>, pk: &PreparedPk<K>) -> Result<(), KyberError> {
+        check_key_length(sk, params.kyber_secretkeybytes as usize)?;
+        check_ciphertext_length(ct, params.kyber_ciphertextbytes as usize)?;
+        check_key_length(ss, KYBER_SSBYTES)?;
+
+        dec_with(params, ss, ct, sk,
+            |m| crate::kyber::indcpa::indcpa::indcpa_dec_prepared(params, m, ct, skpv),
+            |cmp, m, coins| crate::kyber::indcpa::indcpa::indcpa_enc_prepared(params, cmp, m, pk, coins))
+    }
 
+    // The Fujisaki-Okamoto part of decapsulation around a CPA decryption and re-encryption
+    fn dec_with(params: &KyberParams, ss: &mut [u8], ct: &[u8], sk: &[u8], decrypt: impl FnOnce(&mut [u8]), reencrypt: impl FnOnce(&mut [u8], &[u8], &[u8])) -> Result<(), KyberError> {
+        let kyber_cipher = params.kyber_ciphertextbytes as usize;
+        let kyber_secret = params.kyber_secretkeybytes as usize;
+        let kyber_symbytes = KYBER_SYMBYTES;
 
-            // Create a mutable reference to the entire vector as &[u8; 64]
-            let mut kr_whole: &mut [u8; 64] = kr.as_mut_slice().try_into().expect("Slice with incorrect length");
+            let hash_function = selected_hash_function(params);
+            // m', K', r' and the re-encryption are all secret
+            let mut buf = Zeroizing::new([0u8; 2 * KYBER_SYMBYTES]);
+            let mut kr = Zeroizing::new([0u8; 2 * KYBER_SYMBYTES]);
+            let mut cmp_buf = Zeroizing::new([0u8; KYBER_CIPHERTEXTBYTES_MAX]);
+            let cmp = &mut cmp_buf[..kyber_cipher];
 
+            let kr_whole: &mut [u8; 64] = &mut kr;
 
-                        // Call the indcpa_dec function
-            crate::kyber::indcpa::indcpa::indcpa_dec(&mut buf, ct, sk);
+            decrypt(&mut buf[..]);
 
             // Multitarget countermeasure for coins + contributory KEM
             for i in 0..kyber_symbytes {
@@ -232,7 +508,7 @@ pub mod kem{
 
 
             // Hash buf and store the result in kr
-            hash_function.hash_g(&mut kr_whole, &buf);
+            hash_function.hash_g(kr_whole, &buf[..]);
 
             // Create arrays with non-constant values
             let mut kr_half1_array: [u8; 32] = Default::default();
@@ -247,31 +523,45 @@ pub mod kem{
             kr_half2.copy_from_slice(&kr_whole[32..]);
             
             // coins are in kr+kyber_symbytes
-            crate::kyber::indcpa::indcpa::indcpa_enc(&mut cmp, &buf, pk, kr_half2);
+            reencrypt(cmp, &buf[..], kr_half2);
 
 
             // Verify ct and cmp
-            let fail = crate::kyber::verify::verify::verify(ct, &cmp, kyber_cipher);
+            let fail = crate::kyber::verify::verify::verify(ct, cmp, kyber_cipher);
+
+            // verify already returns 0 or 1, no need to branch on it
+            let fail_u8 = fail as u8;
+
+            if params.kyber_variant == KyberVariant::MlKem
+            {
+
+                // Implicit rejection: K is replaced by J(z || c) on re-encryption failure
+                let mut rejection_input = Zeroizing::new([0u8; KYBER_SYMBYTES + KYBER_CIPHERTEXTBYTES_MAX]);
+                rejection_input[..kyber_symbytes].copy_from_slice(&sk[kyber_secret - kyber_symbytes..kyber_secret]);
+                rejection_input[kyber_symbytes..kyber_symbytes + kyber_cipher].copy_from_slice(ct);
+                let mut k_bar: [u8; 32] = [0; 32];
+                hash_function.kdf(&mut k_bar, &rejection_input[..kyber_symbytes + kyber_cipher]);
+
+                ss[..32].copy_from_slice(kr_half1);
+                crate::kyber::verify::verify::cmov(&mut ss[..32], &k_bar, kyber_symbytes, fail_u8);
+                k_bar.zeroize();
+                kr_half1_array.zeroize();
+                kr_half2_array.zeroize();
+
+                return Ok(());
+            }
 
             // Overwrite coins in kr with the hash of ct
             hash_function.hash_h(kr_half2, ct);
 
 
             // Conditionally overwrite pre-k with z on re-encryption failure
-            let fail_u8 = if fail != 0 { 1 } else { 0 };
 
             for (i, &val) in kr_half2.iter().enumerate() {
                 kr_whole[i + kyber_symbytes] = val;
             }
             crate::kyber::verify::verify::cmov(kr_whole, &sk[kyber_secret - kyber_symbytes..], kyber_symbytes, fail_u8);
 
-            // Hash concatenation of pre-k and H(c) to k
-
-            if ss.len() < 32 {
-                // Handle error: ss is not long enough
-                return Err(());
-            }
-
             // Convert the first 32 bytes of ss into a mutable array reference
             let ss_array_32: &mut [u8; 32] = {
                 let ptr = ss.as_mut_ptr() as *mut [u8; 32];
@@ -280,6 +570,8 @@ pub mod kem{
 
             // Hash concatenation of pre-k and H(c) to k
             hash_function.kdf(ss_array_32, kr_whole);
+            kr_half1_array.zeroize();
+            kr_half2_array.zeroize();
 
         Ok(())
     }
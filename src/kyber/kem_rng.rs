@@ -0,0 +1,81 @@
+use rand_core::{CryptoRng, RngCore};
+
+use crate::error::KyberError;
+pub use crate::kyber::kyber_rng::KyberRng;
+
+/// A source of randomness the KEM can draw seeds from.
+///
+/// Implemented for every `rand_core::RngCore + CryptoRng`, so `rand::rngs::OsRng`,
+/// `rand_chacha::ChaCha20Rng`, `rand::rngs::StdRng` and `KyberRng` can all be
+/// passed to `Kem::keypair_with_rng` and `Kem::encapsulate_with_rng` directly.
+pub trait KemRng: RngCore + CryptoRng {}
+
+impl<R: RngCore + CryptoRng + ?Sized> KemRng for R {}
+
+/// The library-wide `KyberRng` used by `Kem::keypair`, `Kem::encapsulate` and
+/// `randombytes`, reseeded by `randombytes_init`. Each request locks it separately.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GlobalKyberRng;
+
+impl RngCore for GlobalKyberRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).expect("Kyber DRBG failed")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        crate::kyber::GLOBAL_RANDOM.lock().unwrap().try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for GlobalKyberRng {}
+
+impl KyberRng {
+    /// A DRBG initialised with `entropy_input`, as `randombytes_init` does in the reference code.
+    pub fn from_seed(entropy_input: &[u8; 48]) -> Self {
+        let mut rng = KyberRng::new();
+        rng.randombytes_init(entropy_input.to_vec(), None, 256);
+        rng
+    }
+
+    /// A DRBG seeded from the operating system.
+    pub fn from_entropy() -> Self {
+        let mut entropy_input = [0u8; 48];
+        rand::rngs::OsRng.fill_bytes(&mut entropy_input);
+        KyberRng::from_seed(&entropy_input)
+    }
+}
+
+impl RngCore for KyberRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).expect("Kyber DRBG failed")
+    }
+
+    // One call is one reference randombytes call, so the output matches the KAT files
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        let len = dest.len() as u64;
+        self.randombytes(dest, len).map_err(rand_core::Error::new)
+    }
+}
+
+impl CryptoRng for KyberRng {}
+
+// Fills `dest` from `rng`, reporting a failing source as a KyberError
+pub(crate) fn fill<R: KemRng + ?Sized>(rng: &mut R, dest: &mut [u8]) -> Result<(), KyberError> {
+    rng.try_fill_bytes(dest).map_err(|_| KyberError::Rng("randomness source failed"))
+}
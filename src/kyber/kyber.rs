@@ -1,6 +1,8 @@
 
 use rand::Rng;
 
+use crate::enums::KyberVariant;
+
 use crate::kyber::helping_functions;
 
 use super::kem;
@@ -9,7 +11,9 @@ use super::kem;
 #[derive(Debug,Clone)]
 pub struct KyberParams {
     pub kyber_k: u32,
-    pub kyber_namespace: String,
+    pub kyber_90s: bool,
+    pub kyber_variant: KyberVariant,
+    pub kyber_namespace: &'static str,
     pub kyber_n: u32,
     pub kyber_q: u32,
     pub kyber_symbytes: u32,
@@ -34,7 +38,9 @@ impl KyberParams {
         match security_level {
             2 => Some(KyberParams {
                 kyber_k: 2,
-                kyber_namespace: "pqcrystals_kyber512_ref".to_string(),
+                kyber_90s: false,
+                kyber_variant: KyberVariant::Round3,
+                kyber_namespace: "pqcrystals_kyber512_ref",
                 kyber_n: 256,//
                 kyber_q: 3329,//
                 kyber_symbytes: 32,//
@@ -55,7 +61,9 @@ impl KyberParams {
             }),
             3 => Some(KyberParams {
                 kyber_k: 3,
-                kyber_namespace: "pqcrystals_kyber768_ref".to_string(),
+                kyber_90s: false,
+                kyber_variant: KyberVariant::Round3,
+                kyber_namespace: "pqcrystals_kyber768_ref",
                 kyber_n: 256,//
                 kyber_q: 3329,//
                 kyber_symbytes: 32,//
@@ -76,7 +84,9 @@ impl KyberParams {
             }),
             4 => Some(KyberParams {
                 kyber_k: 4,
-                kyber_namespace: "pqcrystals_kyber1024_ref".to_string(),
+                kyber_90s: false,
+                kyber_variant: KyberVariant::Round3,
+                kyber_namespace: "pqcrystals_kyber1024_ref",
                 kyber_n: 256,//
                 kyber_q: 3329,//
                 kyber_symbytes: 32,//
@@ -98,6 +108,13 @@ impl KyberParams {
             _ => None, // Handle other security levels or return an error
         }
     }
+
+    // Selects the KEM construction, the byte sizes are the same for all of them
+    pub fn with_variant(mut self, variant: KyberVariant) -> KyberParams {
+        self.kyber_variant = variant;
+        self.kyber_90s = variant == KyberVariant::Kyber90s;
+        self
+    }
 }
 #[derive(Debug,Clone)]
 pub struct Kyber {
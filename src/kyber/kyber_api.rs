@@ -0,0 +1,492 @@
+use std::fmt;
+use std::sync::Once;
+
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::enums::KyberVariant;
+use crate::error::KyberError;
+use crate::kyber::indcpa::indcpa::{indcpa_prepare_pk, indcpa_unpack_sk, PreparedPk, BATCH_LANES};
+use crate::kyber::polyvec_struct::PolyVec;
+use crate::kyber::kem_rng::{fill, GlobalKyberRng, KemRng};
+use crate::kyber::kyber::KyberParams;
+
+static SEED_RNG: Once = Once::new();
+
+pub(crate) fn prepare(security_level: u32, variant: KyberVariant) -> Result<KyberParams, KyberError> {
+    SEED_RNG.call_once(|| crate::helping_functions::helping_functions::seed_rng(None));
+
+    KyberParams::set_parameters(security_level)
+        .map(|params| params.with_variant(variant))
+        .ok_or_else(|| KyberError::UnsupportedParameterSet(format!("security level {}", security_level)))
+}
+
+/// Reseeds the NIST AES-256 CTR DRBG behind every operation, as `randombytes_init`
+/// does in the reference code. All output after this call is deterministic, which
+/// is how the PQCkemKAT `.rsp` files were generated.
+pub fn randombytes_init(entropy_input: &[u8; 48]) {
+    // Stop the first operation from replacing this seed with a random one
+    SEED_RNG.call_once(|| ());
+
+    let mut rng = crate::kyber::GLOBAL_RANDOM.lock().unwrap();
+    rng.randombytes_init(entropy_input.to_vec(), None, 256);
+}
+
+/// Draws `out.len()` bytes from the DRBG.
+pub fn randombytes(out: &mut [u8]) -> Result<(), KyberError> {
+    let len = out.len() as u64;
+    let mut rng = crate::kyber::GLOBAL_RANDOM.lock().unwrap();
+    rng.randombytes(out, len).map_err(KyberError::Rng)
+}
+
+/// Conversion between the fixed-size KEM types and raw bytes.
+pub trait KemBytes: Sized {
+    /// Fails if `bytes` is not exactly the length of the type.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, KyberError>;
+    fn as_bytes(&self) -> &[u8];
+}
+
+/// A Kyber public key of `LEN` bytes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PublicKey<const LEN: usize>([u8; LEN]);
+
+/// A Kyber secret key of `LEN` bytes, wiped when dropped.
+pub struct SecretKey<const LEN: usize>([u8; LEN]);
+
+/// A Kyber ciphertext of `LEN` bytes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Ciphertext<const LEN: usize>([u8; LEN]);
+
+/// The 32 byte shared secret agreed by encapsulation and decapsulation, wiped when dropped.
+pub struct SharedSecret([u8; 32]);
+
+/// A public key with its polynomial vector unpacked and the matrix A^T expanded,
+/// so repeated encapsulations to it skip matrix generation. Made by
+/// `Kem::prepare_public_key`, which also runs the public key check once.
+#[derive(Clone)]
+pub struct PreparedPublicKey<const K: usize, const LEN: usize> {
+    pk: PublicKey<LEN>,
+    pk_hash: [u8; 32],
+    prepared: PreparedPk<K>,
+}
+
+impl<const K: usize, const LEN: usize> PreparedPublicKey<K, LEN> {
+    pub fn public_key(&self) -> &PublicKey<LEN> {
+        &self.pk
+    }
+}
+
+impl<const K: usize, const LEN: usize> fmt::Debug for PreparedPublicKey<K, LEN> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PreparedPublicKey").field(&self.pk).finish()
+    }
+}
+
+/// A secret key with its secret polynomial vector decoded, already in NTT form,
+/// and the matrix of its embedded public key expanded for the re-encryption
+/// check. Made by `Kem::prepare_secret_key`, wiped when dropped.
+pub struct PreparedSecretKey<const K: usize, const LEN: usize> {
+    sk: SecretKey<LEN>,
+    skpv: PolyVec<K>,
+    public: PreparedPk<K>,
+}
+
+impl<const K: usize, const LEN: usize> fmt::Debug for PreparedSecretKey<K, LEN> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PreparedSecretKey([REDACTED])")
+    }
+}
+
+// Secret types are compared in constant time, never printed and not Clone,
+// so the only copies of their bytes are the ones a caller makes explicitly
+macro_rules! impl_secret {
+    ($ty:ty, $name:expr $(, $len:ident)?) => {
+        impl<$(const $len: usize)?> Drop for $ty {
+            fn drop(&mut self) {
+                self.0.zeroize();
+            }
+        }
+
+        impl<$(const $len: usize)?> PartialEq for $ty {
+            fn eq(&self, other: &Self) -> bool {
+                crate::kyber::verify::verify::verify(&self.0, &other.0, self.0.len()) == 0
+            }
+        }
+
+        impl<$(const $len: usize)?> Eq for $ty {}
+
+        impl<$(const $len: usize)?> fmt::Debug for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}([REDACTED])", $name)
+            }
+        }
+    };
+}
+
+impl_secret!(SecretKey<LEN>, "SecretKey", LEN);
+impl_secret!(SharedSecret, "SharedSecret");
+
+macro_rules! impl_kem_bytes {
+    ($($ty:ident => $error:ident),*) => {
+        $(
+            impl<const LEN: usize> KemBytes for $ty<LEN> {
+                fn from_bytes(bytes: &[u8]) -> Result<Self, KyberError> {
+                    bytes
+                        .try_into()
+                        .map($ty)
+                        .map_err(|_| KyberError::$error { expected: LEN, actual: bytes.len() })
+                }
+
+                fn as_bytes(&self) -> &[u8] {
+                    &self.0
+                }
+            }
+        )*
+    };
+}
+
+impl_kem_bytes!(PublicKey => WrongKeyLength, SecretKey => WrongKeyLength, Ciphertext => WrongCiphertextLength);
+
+impl KemBytes for SharedSecret {
+    fn from_bytes(bytes: &[u8]) -> Result<Self, KyberError> {
+        bytes
+            .try_into()
+            .map(SharedSecret)
+            .map_err(|_| KyberError::WrongKeyLength { expected: 32, actual: bytes.len() })
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// A public key and its secret key.
+pub type KeyPair<K> = (<K as Kem>::PublicKey, <K as Kem>::SecretKey);
+
+/// A Kyber, Kyber-90s or ML-KEM parameter set.
+pub trait Kem {
+    /// The security level passed to `KyberParams::set_parameters` (2, 3 or 4).
+    const SECURITY_LEVEL: u32;
+    /// Whether keys of this set are round-3 Kyber, Kyber-90s or FIPS 203 ML-KEM keys.
+    const VARIANT: KyberVariant;
+    /// The parameter set's name as the specifications write it, e.g. "ML-KEM-768".
+    const PARAMETER_SET: &'static str;
+    const PUBLIC_KEY_BYTES: usize;
+    const SECRET_KEY_BYTES: usize;
+    const CIPHERTEXT_BYTES: usize;
+
+    type PublicKey: KemBytes;
+    type SecretKey: KemBytes;
+    type Ciphertext: KemBytes;
+    type PreparedPublicKey;
+    type PreparedSecretKey;
+
+    /// Generates a fresh key pair from the library-wide DRBG.
+    fn keypair() -> Result<(Self::PublicKey, Self::SecretKey), KyberError> {
+        Self::keypair_with_rng(&mut GlobalKyberRng)
+    }
+
+    /// Generates a fresh key pair with `d` and `z` drawn from `rng`.
+    fn keypair_with_rng<R: KemRng + ?Sized>(rng: &mut R) -> Result<(Self::PublicKey, Self::SecretKey), KyberError>;
+
+    /// Generates a key pair deterministically from the seed `d` and the
+    /// implicit rejection value `z`. `keypair` is this with both drawn from the DRBG.
+    fn keypair_derand(d: &[u8; 32], z: &[u8; 32]) -> Result<(Self::PublicKey, Self::SecretKey), KyberError>;
+
+    /// Generates a shared secret and the ciphertext encapsulating it to `pk`,
+    /// drawing `m` from the library-wide DRBG.
+    fn encapsulate(pk: &Self::PublicKey) -> Result<(Self::Ciphertext, SharedSecret), KyberError> {
+        Self::encapsulate_with_rng(pk, &mut GlobalKyberRng)
+    }
+
+    /// Encapsulates to `pk` with `m` drawn from `rng`.
+    fn encapsulate_with_rng<R: KemRng + ?Sized>(pk: &Self::PublicKey, rng: &mut R) -> Result<(Self::Ciphertext, SharedSecret), KyberError>;
+
+    /// Encapsulates to `pk` deterministically using the message `m`.
+    /// `encapsulate` is this with `m` drawn from the DRBG.
+    fn encapsulate_derand(pk: &Self::PublicKey, m: &[u8; 32]) -> Result<(Self::Ciphertext, SharedSecret), KyberError>;
+
+    /// Generates `n` key pairs from the library-wide DRBG, locking it once for the
+    /// whole batch. Matrix generation is shared by up to four keys at a time.
+    fn keypair_batch(n: usize) -> Result<Vec<KeyPair<Self>>, KyberError> {
+        // Seeding the DRBG locks it, so this comes before taking the lock
+        prepare(Self::SECURITY_LEVEL, Self::VARIANT)?;
+        let mut rng = crate::kyber::GLOBAL_RANDOM.lock().unwrap();
+        Self::keypair_batch_with_rng(n, &mut *rng)
+    }
+
+    /// Generates `n` key pairs with seeds drawn from `rng`. The keys are the ones
+    /// `n` calls to `keypair_with_rng` would give.
+    fn keypair_batch_with_rng<R: KemRng + ?Sized>(n: usize, rng: &mut R) -> Result<Vec<KeyPair<Self>>, KyberError>;
+
+    /// Encapsulates once to each of `pks`, drawing every `m` from the library-wide
+    /// DRBG under a single lock.
+    fn encapsulate_batch(pks: &[Self::PublicKey]) -> Result<Vec<(Self::Ciphertext, SharedSecret)>, KyberError> {
+        prepare(Self::SECURITY_LEVEL, Self::VARIANT)?;
+        let mut rng = crate::kyber::GLOBAL_RANDOM.lock().unwrap();
+        Self::encapsulate_batch_with_rng(pks, &mut *rng)
+    }
+
+    /// Encapsulates once to each of `pks` with `m` drawn from `rng`, giving what
+    /// `encapsulate_with_rng` would for each key in turn.
+    fn encapsulate_batch_with_rng<R: KemRng + ?Sized>(pks: &[Self::PublicKey], rng: &mut R) -> Result<Vec<(Self::Ciphertext, SharedSecret)>, KyberError>;
+
+    /// Checks `pk` as `encapsulate` does and expands its matrix once, for any
+    /// number of `encapsulate_prepared` calls.
+    fn prepare_public_key(pk: &Self::PublicKey) -> Result<Self::PreparedPublicKey, KyberError>;
+
+    /// Encapsulates to a prepared public key, drawing `m` from the library-wide DRBG.
+    fn encapsulate_prepared(pk: &Self::PreparedPublicKey) -> Result<(Self::Ciphertext, SharedSecret), KyberError> {
+        Self::encapsulate_prepared_with_rng(pk, &mut GlobalKyberRng)
+    }
+
+    /// Encapsulates to a prepared public key with `m` drawn from `rng`.
+    fn encapsulate_prepared_with_rng<R: KemRng + ?Sized>(pk: &Self::PreparedPublicKey, rng: &mut R) -> Result<(Self::Ciphertext, SharedSecret), KyberError> {
+        let mut m = Zeroizing::new([0u8; 32]);
+        fill(rng, &mut m[..])?;
+        Self::encapsulate_prepared_derand(pk, &m)
+    }
+
+    /// Encapsulates to a prepared public key using the message `m`, giving
+    /// what `encapsulate_derand` gives for the unprepared key.
+    fn encapsulate_prepared_derand(pk: &Self::PreparedPublicKey, m: &[u8; 32]) -> Result<(Self::Ciphertext, SharedSecret), KyberError>;
+
+    /// Recovers the shared secret from `ct` using `sk`.
+    fn decapsulate(sk: &Self::SecretKey, ct: &Self::Ciphertext) -> Result<SharedSecret, KyberError>;
+
+    /// Checks `sk` as `decapsulate` does and decodes it once, for any number of
+    /// `decapsulate_prepared` calls.
+    fn prepare_secret_key(sk: &Self::SecretKey) -> Result<Self::PreparedSecretKey, KyberError>;
+
+    /// Recovers the shared secret from `ct` using a prepared secret key.
+    fn decapsulate_prepared(sk: &Self::PreparedSecretKey, ct: &Self::Ciphertext) -> Result<SharedSecret, KyberError>;
+
+    /// Runs the FIPS 203 encapsulation key check, which `encapsulate` also does.
+    fn validate_public_key(pk: &Self::PublicKey) -> Result<(), KyberError>;
+
+    /// Runs the FIPS 203 decapsulation key check, which `decapsulate` also does.
+    fn validate_secret_key(sk: &Self::SecretKey) -> Result<(), KyberError>;
+}
+
+macro_rules! kyber_parameter_set {
+    ($(#[$doc:meta])* $name:ident, $parameter_set:expr, $variant:expr, $level:expr, $pk:expr, $sk:expr, $ct:expr) => {
+        $(#[$doc])*
+        pub struct $name;
+
+        impl Kem for $name {
+            const SECURITY_LEVEL: u32 = $level;
+            const VARIANT: KyberVariant = $variant;
+            const PARAMETER_SET: &'static str = $parameter_set;
+            const PUBLIC_KEY_BYTES: usize = $pk;
+            const SECRET_KEY_BYTES: usize = $sk;
+            const CIPHERTEXT_BYTES: usize = $ct;
+
+            type PublicKey = PublicKey<$pk>;
+            type SecretKey = SecretKey<$sk>;
+            type Ciphertext = Ciphertext<$ct>;
+            type PreparedPublicKey = PreparedPublicKey<$level, $pk>;
+            type PreparedSecretKey = PreparedSecretKey<$level, $sk>;
+
+            fn keypair_with_rng<R: KemRng + ?Sized>(rng: &mut R) -> Result<(Self::PublicKey, Self::SecretKey), KyberError> {
+                let params = prepare($level, $variant)?;
+                let mut pk = [0u8; $pk];
+                // Written in place so no unwiped copy of the key is left on the stack
+                let mut sk = SecretKey([0u8; $sk]);
+                crate::kyber::kem::kem::crypto_kem_keypair(&params, &mut pk, &mut sk.0, rng)?;
+
+                Ok((PublicKey(pk), sk))
+            }
+
+            fn keypair_derand(d: &[u8; 32], z: &[u8; 32]) -> Result<(Self::PublicKey, Self::SecretKey), KyberError> {
+                let params = prepare($level, $variant)?;
+                let mut pk = [0u8; $pk];
+                let mut sk = SecretKey([0u8; $sk]);
+                let mut coins = Zeroizing::new([0u8; 64]);
+                coins[..32].copy_from_slice(d);
+                coins[32..].copy_from_slice(z);
+                crate::kyber::kem::kem::crypto_kem_keypair_derand(&params, &mut pk, &mut sk.0, &*coins)?;
+
+                Ok((PublicKey(pk), sk))
+            }
+
+            fn encapsulate_with_rng<R: KemRng + ?Sized>(pk: &Self::PublicKey, rng: &mut R) -> Result<(Self::Ciphertext, SharedSecret), KyberError> {
+                let params = prepare($level, $variant)?;
+                let mut ct = [0u8; $ct];
+                let mut ss = SharedSecret([0u8; 32]);
+                crate::kyber::kem::kem::crypto_kem_enc(&params, &mut ct, &mut ss.0, &pk.0, rng)?;
+
+                Ok((Ciphertext(ct), ss))
+            }
+
+            fn encapsulate_derand(pk: &Self::PublicKey, m: &[u8; 32]) -> Result<(Self::Ciphertext, SharedSecret), KyberError> {
+                let params = prepare($level, $variant)?;
+                let mut ct = [0u8; $ct];
+                let mut ss = SharedSecret([0u8; 32]);
+                crate::kyber::kem::kem::crypto_kem_enc_derand(&params, &mut ct, &mut ss.0, &pk.0, m)?;
+
+                Ok((Ciphertext(ct), ss))
+            }
+
+            fn keypair_batch_with_rng<R: KemRng + ?Sized>(n: usize, rng: &mut R) -> Result<Vec<KeyPair<Self>>, KyberError> {
+                let params = prepare($level, $variant)?;
+                let mut keys = Vec::with_capacity(n);
+                let mut pk = [0u8; BATCH_LANES * $pk];
+                let mut sk = Zeroizing::new([0u8; BATCH_LANES * $sk]);
+                let mut coins = Zeroizing::new([0u8; BATCH_LANES * 64]);
+                while keys.len() < n {
+                    let count = (n - keys.len()).min(BATCH_LANES);
+                    // d and z of each key in turn, as keypair_with_rng draws them
+                    for seed in coins[..count * 64].chunks_mut(32) {
+                        fill(rng, seed)?;
+                    }
+                    crate::kyber::kem::kem::crypto_kem_keypair_derand_batch(&params, &mut pk[..count * $pk], &mut sk[..count * $sk], &coins[..count * 64])?;
+
+                    for l in 0..count {
+                        let public = PublicKey(pk[l * $pk..(l + 1) * $pk].try_into().expect("public key length"));
+                        // Copied straight into the Vec so no unwiped copy is left on the stack
+                        keys.push((public, SecretKey([0u8; $sk])));
+                        keys.last_mut().unwrap().1 .0.copy_from_slice(&sk[l * $sk..(l + 1) * $sk]);
+                    }
+                }
+
+                Ok(keys)
+            }
+
+            fn encapsulate_batch_with_rng<R: KemRng + ?Sized>(pks: &[Self::PublicKey], rng: &mut R) -> Result<Vec<(Self::Ciphertext, SharedSecret)>, KyberError> {
+                let params = prepare($level, $variant)?;
+                let mut results = Vec::with_capacity(pks.len());
+                let mut pk = [0u8; BATCH_LANES * $pk];
+                let mut ct = [0u8; BATCH_LANES * $ct];
+                let mut ss = Zeroizing::new([0u8; BATCH_LANES * 32]);
+                let mut coins = Zeroizing::new([0u8; BATCH_LANES * 32]);
+                for chunk in pks.chunks(BATCH_LANES) {
+                    let count = chunk.len();
+                    for (l, key) in chunk.iter().enumerate() {
+                        pk[l * $pk..(l + 1) * $pk].copy_from_slice(&key.0);
+                        fill(rng, &mut coins[l * 32..(l + 1) * 32])?;
+                    }
+                    crate::kyber::kem::kem::crypto_kem_enc_derand_batch(&params, &mut ct[..count * $ct], &mut ss[..count * 32], &pk[..count * $pk], &coins[..count * 32])?;
+
+                    for l in 0..count {
+                        let ciphertext = Ciphertext(ct[l * $ct..(l + 1) * $ct].try_into().expect("ciphertext length"));
+                        results.push((ciphertext, SharedSecret([0u8; 32])));
+                        results.last_mut().unwrap().1 .0.copy_from_slice(&ss[l * 32..(l + 1) * 32]);
+                    }
+                }
+
+                Ok(results)
+            }
+
+            fn decapsulate(sk: &Self::SecretKey, ct: &Self::Ciphertext) -> Result<SharedSecret, KyberError> {
+                let params = prepare($level, $variant)?;
+                let mut ss = SharedSecret([0u8; 32]);
+                crate::kyber::kem::kem::crypto_kem_dec(&params, &mut ss.0, &ct.0, &sk.0)?;
+
+                Ok(ss)
+            }
+
+            fn prepare_public_key(pk: &Self::PublicKey) -> Result<Self::PreparedPublicKey, KyberError> {
+                let params = prepare($level, $variant)?;
+                crate::kyber::kem::kem::crypto_kem_check_pk(&params, &pk.0)?;
+
+                Ok(PreparedPublicKey {
+                    pk: pk.clone(),
+                    pk_hash: crate::kyber::kem::kem::crypto_kem_pk_hash(&params, &pk.0),
+                    prepared: indcpa_prepare_pk::<$level>(&params, &pk.0),
+                })
+            }
+
+            fn encapsulate_prepared_derand(pk: &Self::PreparedPublicKey, m: &[u8; 32]) -> Result<(Self::Ciphertext, SharedSecret), KyberError> {
+                let params = prepare($level, $variant)?;
+                let mut ct = [0u8; $ct];
+                let mut ss = SharedSecret([0u8; 32]);
+                crate::kyber::kem::kem::crypto_kem_enc_prepared(&params, &mut ct, &mut ss.0, &pk.pk_hash, &pk.prepared, m)?;
+
+                Ok((Ciphertext(ct), ss))
+            }
+
+            fn prepare_secret_key(sk: &Self::SecretKey) -> Result<Self::PreparedSecretKey, KyberError> {
+                let params = prepare($level, $variant)?;
+                crate::kyber::kem::kem::crypto_kem_check_sk(&params, &sk.0)?;
+                let indcpa_secret = params.kyber_indcpa_secretkeybytes as usize;
+
+                Ok(PreparedSecretKey {
+                    sk: SecretKey(sk.0),
+                    skpv: indcpa_unpack_sk::<$level>(&sk.0[..indcpa_secret]),
+                    public: indcpa_prepare_pk::<$level>(&params, &sk.0[indcpa_secret..indcpa_secret + $pk]),
+                })
+            }
+
+            fn decapsulate_prepared(sk: &Self::PreparedSecretKey, ct: &Self::Ciphertext) -> Result<SharedSecret, KyberError> {
+                let params = prepare($level, $variant)?;
+                let mut ss = SharedSecret([0u8; 32]);
+                crate::kyber::kem::kem::crypto_kem_dec_prepared(&params, &mut ss.0, &ct.0, &sk.sk.0, &sk.skpv, &sk.public)?;
+
+                Ok(ss)
+            }
+
+            fn validate_public_key(pk: &Self::PublicKey) -> Result<(), KyberError> {
+                let params = prepare($level, $variant)?;
+                crate::kyber::kem::kem::crypto_kem_check_pk(&params, &pk.0)
+            }
+
+            fn validate_secret_key(sk: &Self::SecretKey) -> Result<(), KyberError> {
+                let params = prepare($level, $variant)?;
+                crate::kyber::kem::kem::crypto_kem_check_sk(&params, &sk.0)
+            }
+        }
+    };
+}
+
+kyber_parameter_set!(
+    /// Kyber512, aiming at security roughly equivalent to AES-128.
+    Kyber512, "Kyber-512", KyberVariant::Round3, 2, 800, 1632, 768
+);
+kyber_parameter_set!(
+    /// Kyber768, aiming at security roughly equivalent to AES-192.
+    Kyber768, "Kyber-768", KyberVariant::Round3, 3, 1184, 2400, 1088
+);
+kyber_parameter_set!(
+    /// Kyber1024, aiming at security roughly equivalent to AES-256.
+    Kyber1024, "Kyber-1024", KyberVariant::Round3, 4, 1568, 3168, 1568
+);
+kyber_parameter_set!(
+    /// Kyber512-90s, using AES-256-CTR and SHA-2 instead of SHAKE and SHA-3.
+    Kyber512_90s, "Kyber-90s-512", KyberVariant::Kyber90s, 2, 800, 1632, 768
+);
+kyber_parameter_set!(
+    /// Kyber768-90s, using AES-256-CTR and SHA-2 instead of SHAKE and SHA-3.
+    Kyber768_90s, "Kyber-90s-768", KyberVariant::Kyber90s, 3, 1184, 2400, 1088
+);
+kyber_parameter_set!(
+    /// Kyber1024-90s, using AES-256-CTR and SHA-2 instead of SHAKE and SHA-3.
+    Kyber1024_90s, "Kyber-90s-1024", KyberVariant::Kyber90s, 4, 1568, 3168, 1568
+);
+kyber_parameter_set!(
+    /// ML-KEM-512 as standardised in FIPS 203.
+    MlKem512, "ML-KEM-512", KyberVariant::MlKem, 2, 800, 1632, 768
+);
+kyber_parameter_set!(
+    /// ML-KEM-768 as standardised in FIPS 203.
+    MlKem768, "ML-KEM-768", KyberVariant::MlKem, 3, 1184, 2400, 1088
+);
+kyber_parameter_set!(
+    /// ML-KEM-1024 as standardised in FIPS 203.
+    MlKem1024, "ML-KEM-1024", KyberVariant::MlKem, 4, 1568, 3168, 1568
+);
+
+/// `Kem::PARAMETER_SET` of the parameter set for `variant` at --sec level
+/// `security_level` (2, 3 or 5).
+pub(crate) fn parameter_set(variant: KyberVariant, security_level: u8) -> Option<&'static str> {
+    Some(match (variant, security_level) {
+        (KyberVariant::Round3, 2) => Kyber512::PARAMETER_SET,
+        (KyberVariant::Round3, 3) => Kyber768::PARAMETER_SET,
+        (KyberVariant::Round3, 5) => Kyber1024::PARAMETER_SET,
+        (KyberVariant::Kyber90s, 2) => Kyber512_90s::PARAMETER_SET,
+        (KyberVariant::Kyber90s, 3) => Kyber768_90s::PARAMETER_SET,
+        (KyberVariant::Kyber90s, 5) => Kyber1024_90s::PARAMETER_SET,
+        (KyberVariant::MlKem, 2) => MlKem512::PARAMETER_SET,
+        (KyberVariant::MlKem, 3) => MlKem768::PARAMETER_SET,
+        (KyberVariant::MlKem, 5) => MlKem1024::PARAMETER_SET,
+        _ => return None,
+    })
+}
@@ -5,7 +5,7 @@ use std::io::Write;
 
 use openssl::error::ErrorStack;
 use openssl::symm::{Cipher, Crypter, Mode};
-#[derive(Clone)]
+use zeroize::{Zeroize, Zeroizing};
 pub struct AesXofStruct {
     length_remaining: u64,
     key: Vec<u8>,
@@ -14,7 +14,6 @@ pub struct AesXofStruct {
     buffer: Vec<u8>,
 }
 
-#[derive(Clone)]
 pub struct AES256_CTR_DRBG_struct {
     V: Vec<u8>,
     reseed_counter: u64,
@@ -32,7 +31,7 @@ impl AES256_CTR_DRBG_struct
     }
 }
 
-#[derive(Clone)]
+// Not Clone, a copy would hand out the same output stream twice
 pub struct KyberRng {
     drbg_ctx: AES256_CTR_DRBG_struct,
     rng_success: i32,
@@ -41,6 +40,21 @@ pub struct KyberRng {
     rng_bad_req_len: i32,
 }
 
+impl Drop for AesXofStruct {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        self.ctr.zeroize();
+        self.buffer.zeroize();
+    }
+}
+
+impl Drop for AES256_CTR_DRBG_struct {
+    fn drop(&mut self) {
+        self.Key.zeroize();
+        self.V.zeroize();
+    }
+}
+
 impl KyberRng {
     pub fn new() -> Self {
         KyberRng {
@@ -56,7 +70,7 @@ impl KyberRng {
         }
     }
     pub fn randombytes_init(&mut self, entropy_input: Vec<u8>, personalization_string: Option<Vec<u8>>, security_strength: u32) {
-        let mut seed_material: Vec<u8> = vec![0; 48];
+        let mut seed_material = Zeroizing::new(vec![0u8; 48]);
 
         seed_material[..entropy_input.len()].copy_from_slice(&entropy_input);
 
@@ -68,18 +82,18 @@ impl KyberRng {
             }
         }
 
-        
-        self.drbg_ctx.Key = vec![0; 32];
-
-     
-        self.drbg_ctx.V = vec![0; 16];
+        // Cleared in place so the previous state is not left behind in freed memory
+        self.drbg_ctx.Key.as_mut_slice().zeroize();
+        self.drbg_ctx.V.as_mut_slice().zeroize();
 
         Self::AES256_CTR_DRBG_Update(Some(&seed_material), &mut self.drbg_ctx.Key, &mut self.drbg_ctx.V);
         self.drbg_ctx.reseed_counter = 1;
     }
 
-    pub fn randombytes(&mut self, x: &mut Vec<u8>, mut xlen: u64) -> Result<(), &'static str> {
-        let mut block: Vec<u8> = vec![0; 16];
+    // Writes straight into `x`, with the working block on the stack, so drawing
+    // seeds for the KEM does not allocate
+    pub fn randombytes(&mut self, x: &mut [u8], mut xlen: u64) -> Result<(), &'static str> {
+        let mut block = Zeroizing::new([0u8; 16]);
         let mut i: usize = 0; 
 
         while xlen > 0 {
@@ -92,14 +106,14 @@ impl KyberRng {
                 }
             }
 
-            Self::aes256_ecb(&self.drbg_ctx.Key, &self.drbg_ctx.V, &mut block);
+            Self::aes256_ecb(&self.drbg_ctx.Key, &self.drbg_ctx.V, &mut block[..]).map_err(|_| "AES-256 ECB failed")?;
 
             if xlen > 15 {
                 let end_index = i + 16;
                 if end_index > x.len() {
                     return Err("Buffer overflow");
                 }
-                x[i..end_index].copy_from_slice(&block);
+                x[i..end_index].copy_from_slice(&block[..]);
                 i += 16;
                 xlen -= 16;
             } else {
@@ -112,7 +126,7 @@ impl KyberRng {
             }
         }
 
-        Self::AES256_CTR_DRBG_Update(None, &mut self.drbg_ctx.Key, &mut self.drbg_ctx.V);
+        Self::AES256_CTR_DRBG_Update(None, &mut self.drbg_ctx.Key, &mut self.drbg_ctx.V)?;
         self.drbg_ctx.reseed_counter += 1;
 
 
@@ -120,7 +134,7 @@ impl KyberRng {
     }
 
     pub fn AES256_CTR_DRBG_Update(provided_data: Option<&Vec<u8>>, Key: &mut Vec<u8>, V: &mut Vec<u8>) -> Result<(), &'static str> {
-        let mut temp: Vec<u8> = vec![0; 48];
+        let mut temp = Zeroizing::new([0u8; 48]);
     
         for i in 0..3 {
             // Increment V
@@ -135,7 +149,7 @@ impl KyberRng {
     
             let block_start = i * 16;
             let block_end = block_start + 16;
-            Self::aes256_ecb(Key, V, &mut temp[block_start..block_end]);
+            Self::aes256_ecb(Key, V, &mut temp[block_start..block_end]).map_err(|_| "AES-256 ECB failed")?;
         }
     
         if let Some(data) = provided_data {
@@ -158,12 +172,12 @@ impl KyberRng {
         let mut crypter = Crypter::new(cipher, Mode::Encrypt, key, None)?;
         crypter.pad(false);
     
-        let mut temp_buffer = vec![0; buffer.len() + cipher.block_size()];
-        let count = crypter.update(ctr, &mut temp_buffer)?;
+        // One block of input and the block OpenSSL reserves for finalize
+        let mut temp_buffer = Zeroizing::new([0u8; 32]);
+        let count = crypter.update(ctr, &mut temp_buffer[..])?;
         let rest = crypter.finalize(&mut temp_buffer[count..])?;
-        temp_buffer.truncate(count + rest);
     
-        buffer.copy_from_slice(&temp_buffer);
+        buffer.copy_from_slice(&temp_buffer[..count + rest]);
     
     
         Ok(())
@@ -1,42 +1,67 @@
-#![allow(warnings)]
-use std::{env, fs, u8};
-use std::ffi::{CStr, CString};
-use std::os::unix::ffi::OsStrExt;
+use std::fs;
 use std::io;
-use std::io::prelude::*;
 use std::fs::File;
 use std::path::Path;
-use libloading::{Library, Symbol};
-use std::ptr;
 use lazy_static::lazy_static;
 
 extern crate sha2;
 use std::sync::Mutex;
-use std::io::{BufRead, Read};
 use std::io::{Write};
 use crate::enums::Kyber_Category;
-use crate::key_generation;
+#[allow(clippy::module_inception)]
 pub(crate) mod kem;
+#[allow(dead_code, unused_imports, unused_mut, clippy::let_and_return, clippy::module_inception)]
 pub(crate) mod kyber;
+pub mod kyber_api;
+pub mod kem_rng;
+pub mod timing;
+pub mod valgrind;
+pub mod bench;
+pub mod export;
+pub mod hybrid;
+pub mod hpke;
+pub mod ake;
+#[allow(dead_code, unused_mut, clippy::large_enum_variant)]
 mod xof_state;
+#[allow(dead_code, non_camel_case_types, non_snake_case, unused_imports, unused_must_use, unused_mut, unused_variables)]
+#[allow(clippy::new_without_default, clippy::ptr_arg, clippy::unnecessary_mut_passed)]
 mod kyber_rng;
+#[allow(dead_code, clippy::module_inception)]
 mod speed_print;
 use crate::helping_functions;
 mod config;
+#[allow(dead_code, non_snake_case, unused_assignments, unused_mut, unused_variables)]
+#[allow(clippy::manual_rotate, clippy::module_inception, clippy::needless_borrow, clippy::needless_range_loop, clippy::precedence)]
 mod fips202;
+#[allow(clippy::module_inception)]
 mod indcpa;
+#[allow(dead_code, unused_assignments, unused_mut)]
+#[allow(clippy::explicit_counter_loop, clippy::manual_memcpy, clippy::manual_rotate, clippy::module_inception, clippy::needless_range_loop)]
 mod aes256ctr;
-mod symmetric_aes;
+#[allow(clippy::module_inception, clippy::needless_borrow)]
+pub mod symmetric_aes;
+#[allow(dead_code, clippy::identity_op, clippy::module_inception, clippy::needless_range_loop, clippy::unnecessary_cast)]
 mod polyvec;
+#[allow(unused_mut, clippy::module_inception)]
 mod verify;
 mod poly_struct;
 mod polyvec_struct;
+#[allow(dead_code, clippy::identity_op, clippy::module_inception, clippy::needless_range_loop, clippy::unnecessary_cast)]
 mod poly;
+#[allow(dead_code, unused_imports, clippy::module_inception)]
 mod cbd;
+#[allow(unused_mut, clippy::module_inception, clippy::needless_return, clippy::unnecessary_cast)]
 mod reduce;
+#[allow(clippy::assign_op_pattern, clippy::module_inception, clippy::needless_range_loop)]
 mod ntt;
+#[cfg(target_arch = "x86_64")]
+mod ntt_avx2;
+#[cfg(target_arch = "x86_64")]
+mod ntt_sse2;
+mod fips202x4;
+pub mod backend;
+#[allow(clippy::module_inception, clippy::needless_borrow, clippy::unnecessary_cast)]
 mod symmetric_shake;
-use crate::random_number_generation;
 
 lazy_static! {
     pub static ref GLOBAL_RANDOM: Mutex<kyber_rng::KyberRng> = Mutex::new(kyber_rng::KyberRng::new());
@@ -50,6 +75,7 @@ pub fn first_welcome()
     println!("It is one of the candidates submitted to the NIST Post-Quantum Cryptography
                 Standardization Project.\nIt is used for securely encapsulating symmetric keys.");
 }
+#[allow(unused_assignments, unused_mut, unused_must_use)]
 pub fn encapsulate_long(cat:Option<Kyber_Category>) {
     println!("You have selected to encapsulate information");
     helping_functions::helping_functions::seed_rng(None);
@@ -82,21 +108,22 @@ pub fn encapsulate_long(cat:Option<Kyber_Category>) {
         return;
     }
     let mut kyber = kyber::Kyber::create(strength);
-    set_env_vars(kyber.params.clone());
+    // Key files count levels as --sec does, 5 for Kyber1024
+    let mut key_file = crate::keyfile::KeyFile::new(kyber.params.kyber_variant, if strength == 4 { 5 } else { strength as u8 });
 
-    let mut file = loop {
+    let output_path = loop {
         println!("Please enter the file path where you would like to save the key information:");
         let mut file_path = String::new();
         io::stdin().read_line(&mut file_path).expect("Failed to read line");
-        let file_path = file_path.trim();
+        let file_path = file_path.trim().to_string();
 
-        match File::create(file_path) {
-            Ok(file) => break file,
+        match File::create(&file_path) {
+            Ok(_) => break file_path,
             Err(_) => println!("Failed to create file. Please try again."),
         }
     };
 
-    let mut private_key = vec![0u8; kyber.params.kyber_secretkeybytes as usize];
+    let mut private_key = zeroize::Zeroizing::new(vec![0u8; kyber.params.kyber_secretkeybytes as usize]);
     let mut public_key = vec![0u8; kyber.params.kyber_publickeybytes as usize];
     let mut keep_loop = true;
     while keep_loop
@@ -108,67 +135,34 @@ pub fn encapsulate_long(cat:Option<Kyber_Category>) {
         let sel = input.trim().to_ascii_lowercase();
 
         if sel == "gen" {
-            helping_functions::helping_functions::get_keys_generated(&mut public_key, &mut private_key);
+            helping_functions::helping_functions::get_keys_generated(&kyber.params, &mut public_key, &mut private_key);
             keep_loop = false;
         } 
         else if sel == "enter" 
         {
-        loop {
             println!("Due to arbitrary limits on string litterals in Rust, you need to use a file to enter the keys");
-            let mut file_path = "".to_owned();
-            loop {
+            println!("Please enter the path to the file containing private key and public key:");
+            let mut file_path = String::new();
+            io::stdin().read_line(&mut file_path).expect("Failed to read line");
 
-                    println!("Please enter the path to the file containing private key and secret key:");
-                
-                    io::stdin().read_line(&mut file_path);
-                    let file_path = file_path.trim();
-            
-                    match File::open(&file_path) {
-                        Ok(f) => {
-                            file = f;
-                            break;
-                        },
-                        Err(_) => {
-                            println!("Failed to open file. Please try again.");
-                        }
+            match crate::keyfile::KeyFile::read(&file_path, kyber.params.kyber_variant) {
+                Ok((entered, format)) => {
+                    if format == crate::keyfile::KeyFileFormat::Legacy {
+                        println!("Warning: this is a legacy key file, which is deprecated. The keys will be saved in the versioned format.");
                     }
-                }
-                    let mut is_error:bool = false;
-                    private_key = read_hex_from_file_after_keyword(&mut file, "private key:").expect("Error converting private key to hex");
-                    public_key = read_hex_from_file_after_keyword(&mut file, "public key:").expect("Error converting public key to hex");
-            
-                    if private_key.is_empty() || private_key.iter().all(|&x| x == 0) 
-                    {
-                        println!("Error with private key");
-                        is_error = true;
-                    }
-                    
-                    // Check if ciphertext is null or all zeros
-                    if public_key.is_empty() || public_key.iter().all(|&x| x == 0) 
-                    {
-                        println!("Error with public key");
-                        is_error = true;
+                    if entered.security_level != key_file.security_level || entered.variant != key_file.variant {
+                        println!("The file holds {} keys, please try again with a {} key file!", entered.parameter_set_name(), key_file.parameter_set_name());
                     }
-            
-                    if is_error
-                    {
-                        println!("Sorry, either private key or public key could not be read properly. Please try again with a new file!")
-                    }
-                    else 
-                    {
+                    else if let (Some(pk), Some(sk)) = (&entered.public_key, &entered.private_key) {
+                        public_key = pk.clone();
+                        private_key = sk.clone();
                         keep_loop = false;
-                        break;
-                    }
-                    let pub_len = kyber.params.kyber_publickeybytes;
-                    let priv_len = kyber.params.kyber_secretkeybytes;
-                    if public_key.len() == pub_len as usize && private_key.len() == priv_len as usize 
-                    {
-                        keep_loop = false;
-                        break;
                     }
                     else {
-                        println!("Error with key length, please try again!");
+                        println!("Sorry, either private key or public key could not be read properly. Please try again with a new file!");
                     }
+                }
+                Err(err) => println!("Failed to read the key file ({}). Please try again.", err),
             }
         }
         else 
@@ -177,160 +171,116 @@ pub fn encapsulate_long(cat:Option<Kyber_Category>) {
         }
     }
     // Display keys in hex format on console
-    println!("PRIVATE KEY: {}", hex::encode(&private_key));
+    println!("PRIVATE KEY: {}", hex::encode(&*private_key));
     println!("PUBLIC KEY: {}", hex::encode(&public_key));
-    
-    // Save keys to file in hex format
-    writeln!(file, "PRIVATE KEY: '{}'", hex::encode(&private_key)).expect("Failed to write to file");
-    writeln!(file, "PUBLIC KEY: '{}'", hex::encode(&public_key)).expect("Failed to write to file");
 
     // Kyber encapsulation
     let mut cc: Vec<u8> = vec![0u8; kyber.params.kyber_ciphertextbytes as usize];
-    let mut ss: Vec<u8> = vec![0u8; kyber.params.kyber_ssbytes as usize];
+    let mut ss = zeroize::Zeroizing::new(vec![0u8; kyber.params.kyber_ssbytes as usize]);
     let mut nothing = String::new();
-    if kem::kem::crypto_kem_enc(&mut cc, &mut ss, &mut public_key).is_ok() {
+    if kem::kem::crypto_kem_enc(&kyber.params, &mut cc, &mut ss, &public_key, &mut kem_rng::GlobalKyberRng).is_ok() {
         println!("Encapsulation Completed");
         println!("Ciphertext: {}", hex::encode(&cc));
-        println!("Shared Secret: {}", hex::encode(&ss));
+        println!("Shared Secret: {}", hex::encode(&*ss));
 
-        // Save encapsulated data to file
-        writeln!(file, "Ciphertext: '{}'", hex::encode(&cc)).expect("Failed to write to file");
-        writeln!(file, "Shared Secret: '{}'", hex::encode(&ss)).expect("Failed to write to file");
-
-       
+        key_file.ciphertext = Some(cc);
+        key_file.shared_secret = Some(ss);
     } else {
         println!("Encapsulation Failed");
     }
-    println!("Press return to continue...");
-    io::stdin().read_line(&mut nothing);
-}
-fn read_hex_from_file_after_keyword(file: &mut File, keyword: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    file.seek(io::SeekFrom::Start(0))?; 
-    let buf_reader = io::BufReader::new(&*file);
-    let lines = buf_reader.lines();
 
-    for line in lines {
-        let line = line?;
-        if line.to_ascii_lowercase().contains(keyword) {
-            // Extract the hex value enclosed in single quotes
-            if let Some(start) = line.find('\'') {
-                if let Some(end) = line[start+1..].find('\'') {
-                    let hex_string = &line[start+1..start+1+end];
-                    return hex::decode(hex_string.trim()).map_err(|e| e.into());
-                }
-            }
+    // Save keys and any encapsulated data to file
+    key_file.private_key = Some(private_key);
+    key_file.public_key = Some(public_key);
+    loop {
+        println!("Would you like to protect the private key with a password? (y/n)");
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).expect("Failed to read line");
+        if !input.trim().eq_ignore_ascii_case("y") {
+            break;
+        }
+        let result = helping_functions::helping_functions::read_password("Enter a password: ", true)
+            .and_then(|password| key_file.encrypt_private_key(password.as_bytes()));
+        match result {
+            Ok(()) => break,
+            Err(err) => println!("{}, please try again.", err),
         }
     }
+    key_file.write(&output_path).expect("Failed to write to file");
 
-    Err("Keyword not found or no data after keyword".into())
+    println!("Press return to continue...");
+    io::stdin().read_line(&mut nothing);
 }
 
+#[allow(unused_must_use)]
 pub fn decapsulate_long() -> Result<(), Box<dyn std::error::Error>> {
 
-    let mut private_key;
-    let mut ss;
-    let mut ciphertext;
-    loop {
-
     println!("You have selected to decapsulate information.");
-    let mut file; 
-    let mut file_path = String::new();
-    loop {
-
+    let key_file = loop {
         println!("Please enter the path to the file containing private key, shared secret, and ciphertext:");
-       
+        let mut file_path = String::new();
         io::stdin().read_line(&mut file_path)?;
-        let file_path = file_path.trim();
 
-        match File::open(&file_path) {
-            Ok(f) => {
-                file = f;
-                break;
-            },
-            Err(_) => {
-                println!("Failed to open file. Please try again.");
+        match crate::keyfile::KeyFile::read(&file_path, crate::enums::KyberVariant::Round3) {
+            Ok((key_file, format)) => {
+                if format == crate::keyfile::KeyFileFormat::Legacy {
+                    println!("Warning: this is a legacy key file, which is deprecated.");
+                }
+                if (key_file.private_key.is_some() || key_file.is_encrypted()) && key_file.ciphertext.is_some() {
+                    break key_file;
+                }
+                println!("Sorry, either private key or ciphertext could not be read properly. Please try again with a new file!");
             }
+            Err(err) => println!("Failed to read the key file ({}). Please try again.", err),
         }
-    }
-        let mut is_error:bool = false;
-        private_key = read_hex_from_file_after_keyword(&mut file, "private key:")?;
-        ss = read_hex_from_file_after_keyword(&mut file, "shared secret:")?;
-        ciphertext = read_hex_from_file_after_keyword(&mut file, "ciphertext:")?;
-
-        if private_key.is_empty() || private_key.iter().all(|&x| x == 0) {
-            println!("Error with private key");
-            is_error = true;
-        }
-        
-        // Check if ciphertext is null or all zeros
-        if ciphertext.is_empty() || ciphertext.iter().all(|&x| x == 0) {
-            println!("Error with ciphertext");
-            is_error = true;
-        }
-
-        if is_error
-        {
-            println!("Sorry, either private key or ciphertext could not be read properly. Please try again with a new file!")
+    };
+    let mut key_file = key_file;
+    let mut attempts = 3;
+    while key_file.private_key.is_none() {
+        if attempts == 0 {
+            return Err("Wrong password".into());
         }
-        else {
-            break;
+        println!("The private key is encrypted, you have {} attempts", attempts);
+        let password = helping_functions::helping_functions::read_password("Enter the password: ", false)?;
+        if let Err(err) = key_file.decrypt_private_key(password.as_bytes()) {
+            println!("{}", err);
         }
+        attempts -= 1;
     }
+    let private_key = key_file.private_key.as_ref().unwrap();
+    let ciphertext = key_file.ciphertext.as_ref().unwrap();
 
-    if private_key.len() == 1632
-    {
-        let mut kyber = kyber::Kyber::create(2);
-    set_env_vars(kyber.params.clone());
-    }
-    else if private_key.len() == 2400
-    {
-        let mut kyber = kyber::Kyber::create(3);
-        set_env_vars(kyber.params.clone());
-    }
-    else if private_key.len() == 3168
-    {
-        let mut kyber = kyber::Kyber::create(4);
-        set_env_vars(kyber.params.clone());
-    }
-    else {
-        panic!("Something has gone very wrong with the private key input.");
-    }
-    let pkb:usize =  get_env_var("KYBER_CIPHERTEXTBYTES").unwrap();
-    let skb:usize =get_env_var("KYBER_SSBYTES").unwrap();
-    let mut cc: Vec<u8> = vec![0u8; pkb];
-    let mut ss_new: Vec<u8> = vec![0u8; skb ];
+    // The key file names its parameter set
+    let level = if key_file.security_level == 5 { 4 } else { key_file.security_level as u32 };
+    let params = kyber::KyberParams::set_parameters(level).ok_or("unsupported security level")?.with_variant(key_file.variant);
+    let mut ss_new = zeroize::Zeroizing::new(vec![0u8; params.kyber_ssbytes as usize]);
     let mut nothing = String::new();
-    println!("Performing decapsulation...");
-    if kem::kem::crypto_kem_dec(&mut ss_new, &ciphertext, &private_key).is_ok() {
+    println!("Performing decapsulation of a {} ciphertext...", key_file.parameter_set_name());
+    if kem::kem::crypto_kem_dec(&params, &mut ss_new, ciphertext, private_key).is_ok() {
         println!("Decapsulation Completed");
-         if ss_new.len() >1
-         {
-            if ss == ss_new
-            {
-                println!("Generated Secret: {}", hex::encode(ss_new));
+        match &key_file.shared_secret
+        {
+            Some(ss) if *ss == ss_new => {
+                println!("Generated Secret: {}", hex::encode(&*ss_new));
                 println!("Both shared secrets match!");
                 println!("Press return to continue...");
                 io::stdin().read_line(&mut nothing);
                 Ok(())
             }
-            else
-            {
+            Some(_) => {
                 println!("Comparison Failed!\nSecrets DO NOT match!");
                 println!("Press return to continue...");
                 io::stdin().read_line(&mut nothing);
-                return Err("Secrets don't match".into());
+                Err("Secrets don't match".into())
+            }
+            None => {
+                println!("No shared secret to compare");
+                println!("Derived Shared Secret: {:?}", hex::encode(&*ss_new));
+                println!("Press return to continue...");
+                io::stdin().read_line(&mut nothing);
+                Ok(())
             }
-         }
-         else
-         {
-            println!("No shared secret to compare");
-            println!("Derived Shared Secret: {:?}", hex::encode(ss));
-            println!("Press return to continue...");
-            io::stdin().read_line(&mut nothing);
-            Ok(())
-         }
-       
-   
+        }
     } else {
         println!("Decapsulation Failed");
         println!("Press return to continue...");
@@ -339,6 +289,7 @@ pub fn decapsulate_long() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+#[allow(dead_code)]
 fn parse_file_content(content: &str) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
     let mut private_key = Vec::new();
     let mut shared_secret = Vec::new();
@@ -365,6 +316,7 @@ fn parse_file_content(content: &str) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
     (private_key, shared_secret, ciphertext)
 }
 
+    #[allow(dead_code)]
     fn output_to_file_hex(filename: &str, data: &[u8]) {
         let mut new_filename = String::from(filename);
         while Path::new(&new_filename).exists() {
@@ -388,6 +340,7 @@ fn parse_file_content(content: &str) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
         file.write_all(hex::encode(data).as_bytes()).expect("Failed to write to file");
     }
     
+    #[allow(dead_code)]
     fn output_to_file_vector(filename: &str, data: &[u8]) {
         let mut new_filename = String::from(filename);
         while Path::new(&new_filename).exists() {
@@ -410,40 +363,3 @@ fn parse_file_content(content: &str) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
         let mut file = File::create(&new_filename).expect("Failed to create file");
         file.write_all(data).expect("Failed to write to file");
     }
-pub fn set_env_vars(params: kyber::KyberParams)
-{
-    env::set_var("KYBER_K", params.kyber_k.to_string());
-    env::set_var("KYBER_90S", "false");
-    env::set_var("KYBER_NAMESPACE", params.kyber_namespace);
-    env::set_var("KYBER_N", params.kyber_n.to_string());
-    env::set_var("KYBER_Q", params.kyber_q.to_string());
-    env::set_var("KYBER_SYMBYTES", params.kyber_symbytes.to_string());
-    env::set_var("KYBER_SSBYTES", params.kyber_ssbytes.to_string());
-    env::set_var("KYBER_POLYBYTES", params.kyber_polybytes.to_string());
-    env::set_var("KYBER_POLYCOMPRESSEDBYTES", params.kyber_polycompressedbytes.to_string());
-    env::set_var("KYBER_POLYVECBYTES", params.kyber_polyvecbytes.to_string());
-    env::set_var("KYBER_ETA1", params.kyber_eta1.to_string());
-    env::set_var("KYBER_POLYVECCOMPRESSEDBYTES", params.kyber_polyveccompressedbytes.to_string());
-    env::set_var("KYBER_ETA2", params.kyber_eta2.to_string());
-    env::set_var("KYBER_INDCPA_MSGBYTES", params.kyber_indcpa_msgbytes.to_string());
-    env::set_var("KYBER_INDCPA_PUBLICKEYBYTES", params.kyber_indcpa_publickeybytes.to_string());
-    env::set_var("KYBER_INDCPA_SECRETKEYBYTES", params.kyber_indcpa_secretkeybytes.to_string());
-    env::set_var("KYBER_INDCPA_BYTES", params.kyber_indcpa_bytes.to_string());
-    env::set_var("KYBER_PUBLICKEYBYTES", params.kyber_publickeybytes.to_string());
-    env::set_var("KYBER_SECRETKEYBYTES", params.kyber_secretkeybytes.to_string());
-    env::set_var("KYBER_CIPHERTEXTBYTES", params.kyber_ciphertextbytes.to_string());
-}
-
-pub fn get_env_var<T>(name: &str) -> Result<T, String>
-where
-    T: std::str::FromStr,
-    T::Err: std::fmt::Debug,
-{
-    match env::var(name) {
-        Ok(value) => match value.parse() {
-            Ok(parsed) => Ok(parsed),
-            Err(err) => Err(format!("Failed to parse {}: {:?}", name, err)),
-        },
-        Err(_) => Err(format!("{} environment variable not set", name)),
-    }
-}
\ No newline at end of file
@@ -91,7 +91,7 @@ pub fn init_ntt() {
 * Arguments:   - int16_t r[256]: pointer to input/output vector of elements
 *                                of Zq
 **************************************************/
-pub fn ntt(r: &mut Vec<i16>) {
+pub fn ntt(r: &mut [i16]) {
     let mut k = 1;
     let mut t;
     let mut zeta;
@@ -0,0 +1,210 @@
+// AVX2 versions of ntt, invntt and the base multiplication, 16 coefficients per
+// register. They follow the structure of the pqcrystals avx2 code (Montgomery
+// multiplication with vpmullw/vpmulhw, shuffled butterflies for the layers
+// narrower than a register) but keep the reference coefficient order, so the
+// output is bit for bit that of the scalar code in ntt.rs.
+
+use core::arch::x86_64::*;
+
+use crate::kyber::config::{KYBER_N, KYBER_Q};
+use crate::kyber::ntt::ntt::{ZETAS, ZETAS_INV};
+
+const QINV: i16 = -3327; // q^-1 mod 2^16
+const BARRETT_V: i16 = (((1 << 26) + KYBER_Q / 2) / KYBER_Q) as i16;
+
+// The zeta of each coefficient pair in basemul, +zeta and -zeta alternating
+pub(crate) const BASEMUL_ZETAS: [i16; KYBER_N / 2] = {
+    let mut zetas = [0i16; KYBER_N / 2];
+    let mut i = 0;
+    while i < KYBER_N / 4 {
+        zetas[2 * i] = ZETAS[64 + i];
+        zetas[2 * i + 1] = -ZETAS[64 + i];
+        i += 1;
+    }
+    zetas
+};
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn load(r: &[i16], i: usize) -> __m256i {
+    _mm256_loadu_si256(r[i..i + 16].as_ptr() as *const __m256i)
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn store(r: &mut [i16], i: usize, x: __m256i) {
+    _mm256_storeu_si256(r[i..i + 16].as_mut_ptr() as *mut __m256i, x)
+}
+
+// montgomery_reduce(a * b) in every lane
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn fqmul(a: __m256i, b: __m256i) -> __m256i {
+    let lo = _mm256_mullo_epi16(a, b);
+    let hi = _mm256_mulhi_epi16(a, b);
+    let u = _mm256_mullo_epi16(lo, _mm256_set1_epi16(QINV));
+    let t = _mm256_mulhi_epi16(u, _mm256_set1_epi16(KYBER_Q as i16));
+    _mm256_sub_epi16(hi, t)
+}
+
+// barrett_reduce in every lane
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn barrett_reduce(a: __m256i) -> __m256i {
+    let t = _mm256_mulhi_epi16(a, _mm256_set1_epi16(BARRETT_V));
+    let t = _mm256_srai_epi16(t, 10);
+    let t = _mm256_mullo_epi16(t, _mm256_set1_epi16(KYBER_Q as i16));
+    _mm256_sub_epi16(a, t)
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn ct_butterfly(x: &mut __m256i, y: &mut __m256i, zeta: __m256i) {
+    let t = fqmul(zeta, *y);
+    *y = _mm256_sub_epi16(*x, t);
+    *x = _mm256_add_epi16(*x, t);
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn gs_butterfly(x: &mut __m256i, y: &mut __m256i, zeta: __m256i) {
+    let t = *x;
+    *x = barrett_reduce(_mm256_add_epi16(t, *y));
+    *y = fqmul(zeta, _mm256_sub_epi16(t, *y));
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn zetas_len8(z: &[i16; 128], k: usize) -> __m256i {
+    _mm256_setr_m128i(_mm_set1_epi16(z[k]), _mm_set1_epi16(z[k + 1]))
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn zetas_len4(z: &[i16; 128], k: usize) -> __m256i {
+    let (a, b, c, d) = (z[k], z[k + 2], z[k + 1], z[k + 3]);
+    _mm256_setr_epi16(a, a, a, a, b, b, b, b, c, c, c, c, d, d, d, d)
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn zetas_len2(z: &[i16; 128], k: usize) -> __m256i {
+    let z0 = [z[k], z[k + 1], z[k + 4], z[k + 5], z[k + 2], z[k + 3], z[k + 6], z[k + 7]];
+    _mm256_setr_epi16(
+        z0[0], z0[0], z0[1], z0[1], z0[2], z0[2], z0[3], z0[3],
+        z0[4], z0[4], z0[5], z0[5], z0[6], z0[6], z0[7], z0[7],
+    )
+}
+
+// Butterflies on blocks narrower than a register: 32 coefficients at a time are
+// shuffled so that every lane of x is paired with the same lane of y, then
+// shuffled back. `len` is 8, 4 or 2.
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn narrow_layer(r: &mut [i16], len: usize, zetas: &[i16; 128], k: &mut usize, inverse: bool) {
+    for s in (0..KYBER_N).step_by(32) {
+        let a = load(r, s);
+        let b = load(r, s + 16);
+        let (mut x, mut y, zeta) = match len {
+            8 => (_mm256_permute2x128_si256(a, b, 0x20), _mm256_permute2x128_si256(a, b, 0x31), zetas_len8(zetas, *k)),
+            4 => (_mm256_unpacklo_epi64(a, b), _mm256_unpackhi_epi64(a, b), zetas_len4(zetas, *k)),
+            _ => {
+                let a = _mm256_shuffle_epi32(a, 0xd8);
+                let b = _mm256_shuffle_epi32(b, 0xd8);
+                (_mm256_unpacklo_epi64(a, b), _mm256_unpackhi_epi64(a, b), zetas_len2(zetas, *k))
+            }
+        };
+        if inverse {
+            gs_butterfly(&mut x, &mut y, zeta);
+        } else {
+            ct_butterfly(&mut x, &mut y, zeta);
+        }
+        let (a, b) = match len {
+            8 => (_mm256_permute2x128_si256(x, y, 0x20), _mm256_permute2x128_si256(x, y, 0x31)),
+            4 => (_mm256_unpacklo_epi64(x, y), _mm256_unpackhi_epi64(x, y)),
+            _ => (
+                _mm256_shuffle_epi32(_mm256_unpacklo_epi64(x, y), 0xd8),
+                _mm256_shuffle_epi32(_mm256_unpackhi_epi64(x, y), 0xd8),
+            ),
+        };
+        store(r, s, a);
+        store(r, s + 16, b);
+        *k += 16 / len;
+    }
+}
+
+// Butterflies on blocks of at least 16 coefficients, one zeta per block
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn wide_layer(r: &mut [i16], len: usize, zetas: &[i16; 128], k: &mut usize, inverse: bool) {
+    for start in (0..KYBER_N).step_by(2 * len) {
+        let zeta = _mm256_set1_epi16(zetas[*k]);
+        *k += 1;
+        for j in (start..start + len).step_by(16) {
+            let mut x = load(r, j);
+            let mut y = load(r, j + len);
+            if inverse {
+                gs_butterfly(&mut x, &mut y, zeta);
+            } else {
+                ct_butterfly(&mut x, &mut y, zeta);
+            }
+            store(r, j, x);
+            store(r, j + len, y);
+        }
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn ntt(r: &mut [i16; KYBER_N]) {
+    let mut k = 1;
+    for len in [128, 64, 32, 16] {
+        wide_layer(r, len, &ZETAS, &mut k, false);
+    }
+    for len in [8, 4, 2] {
+        narrow_layer(r, len, &ZETAS, &mut k, false);
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn invntt(r: &mut [i16; KYBER_N]) {
+    let mut k = 0;
+    for len in [2, 4, 8] {
+        narrow_layer(r, len, &ZETAS_INV, &mut k, true);
+    }
+    for len in [16, 32, 64, 128] {
+        wide_layer(r, len, &ZETAS_INV, &mut k, true);
+    }
+    let f = _mm256_set1_epi16(ZETAS_INV[127]);
+    for j in (0..KYBER_N).step_by(16) {
+        let x = load(r, j);
+        store(r, j, fqmul(x, f));
+    }
+}
+
+// Splits 16 coefficient pairs into their even and odd coefficients. The pairs
+// come out in the lane order of vpackssdw, [0..4, 8..12, 4..8, 12..16].
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn deinterleave(x: __m256i, y: __m256i) -> (__m256i, __m256i) {
+    let even = _mm256_packs_epi32(
+        _mm256_srai_epi32(_mm256_slli_epi32(x, 16), 16),
+        _mm256_srai_epi32(_mm256_slli_epi32(y, 16), 16),
+    );
+    let odd = _mm256_packs_epi32(_mm256_srai_epi32(x, 16), _mm256_srai_epi32(y, 16));
+    (even, odd)
+}
+
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn basemul(r: &mut [i16; KYBER_N], a: &[i16; KYBER_N], b: &[i16; KYBER_N]) {
+    for s in (0..KYBER_N).step_by(32) {
+        let (a0, a1) = deinterleave(load(a, s), load(a, s + 16));
+        let (b0, b1) = deinterleave(load(b, s), load(b, s + 16));
+        let zeta = _mm256_permute4x64_epi64(load(&BASEMUL_ZETAS, s / 2), 0xd8);
+
+        let r0 = _mm256_add_epi16(fqmul(fqmul(a1, b1), zeta), fqmul(a0, b0));
+        let r1 = _mm256_add_epi16(fqmul(a0, b1), fqmul(a1, b0));
+
+        store(r, s, _mm256_unpacklo_epi16(r0, r1));
+        store(r, s + 16, _mm256_unpackhi_epi16(r0, r1));
+    }
+}
@@ -0,0 +1,165 @@
+// SSE2 versions of ntt, invntt and the base multiplication, 8 coefficients per
+// register, for x86_64 CPUs without AVX2. Same structure as ntt_avx2.rs and the
+// same output as the scalar code in ntt.rs.
+
+use core::arch::x86_64::*;
+
+use crate::kyber::config::{KYBER_N, KYBER_Q};
+use crate::kyber::ntt::ntt::{ZETAS, ZETAS_INV};
+use crate::kyber::ntt_avx2::BASEMUL_ZETAS;
+
+const QINV: i16 = -3327; // q^-1 mod 2^16
+const BARRETT_V: i16 = (((1 << 26) + KYBER_Q / 2) / KYBER_Q) as i16;
+
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn load(r: &[i16], i: usize) -> __m128i {
+    _mm_loadu_si128(r[i..i + 8].as_ptr() as *const __m128i)
+}
+
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn store(r: &mut [i16], i: usize, x: __m128i) {
+    _mm_storeu_si128(r[i..i + 8].as_mut_ptr() as *mut __m128i, x)
+}
+
+// montgomery_reduce(a * b) in every lane
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn fqmul(a: __m128i, b: __m128i) -> __m128i {
+    let lo = _mm_mullo_epi16(a, b);
+    let hi = _mm_mulhi_epi16(a, b);
+    let u = _mm_mullo_epi16(lo, _mm_set1_epi16(QINV));
+    let t = _mm_mulhi_epi16(u, _mm_set1_epi16(KYBER_Q as i16));
+    _mm_sub_epi16(hi, t)
+}
+
+// barrett_reduce in every lane
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn barrett_reduce(a: __m128i) -> __m128i {
+    let t = _mm_mulhi_epi16(a, _mm_set1_epi16(BARRETT_V));
+    let t = _mm_srai_epi16(t, 10);
+    let t = _mm_mullo_epi16(t, _mm_set1_epi16(KYBER_Q as i16));
+    _mm_sub_epi16(a, t)
+}
+
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn ct_butterfly(x: &mut __m128i, y: &mut __m128i, zeta: __m128i) {
+    let t = fqmul(zeta, *y);
+    *y = _mm_sub_epi16(*x, t);
+    *x = _mm_add_epi16(*x, t);
+}
+
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn gs_butterfly(x: &mut __m128i, y: &mut __m128i, zeta: __m128i) {
+    let t = *x;
+    *x = barrett_reduce(_mm_add_epi16(t, *y));
+    *y = fqmul(zeta, _mm_sub_epi16(t, *y));
+}
+
+// Butterflies on blocks narrower than a register, 16 coefficients at a time.
+// `len` is 4 or 2.
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn narrow_layer(r: &mut [i16], len: usize, zetas: &[i16; 128], k: &mut usize, inverse: bool) {
+    for s in (0..KYBER_N).step_by(16) {
+        let a = load(r, s);
+        let b = load(r, s + 8);
+        let z = &zetas[*k..];
+        let (mut x, mut y, zeta) = if len == 4 {
+            (_mm_unpacklo_epi64(a, b), _mm_unpackhi_epi64(a, b), _mm_setr_epi16(z[0], z[0], z[0], z[0], z[1], z[1], z[1], z[1]))
+        } else {
+            let a = _mm_shuffle_epi32(a, 0xd8);
+            let b = _mm_shuffle_epi32(b, 0xd8);
+            (_mm_unpacklo_epi64(a, b), _mm_unpackhi_epi64(a, b), _mm_setr_epi16(z[0], z[0], z[1], z[1], z[2], z[2], z[3], z[3]))
+        };
+        if inverse {
+            gs_butterfly(&mut x, &mut y, zeta);
+        } else {
+            ct_butterfly(&mut x, &mut y, zeta);
+        }
+        let (a, b) = if len == 4 {
+            (_mm_unpacklo_epi64(x, y), _mm_unpackhi_epi64(x, y))
+        } else {
+            (_mm_shuffle_epi32(_mm_unpacklo_epi64(x, y), 0xd8), _mm_shuffle_epi32(_mm_unpackhi_epi64(x, y), 0xd8))
+        };
+        store(r, s, a);
+        store(r, s + 8, b);
+        *k += 8 / len;
+    }
+}
+
+// Butterflies on blocks of at least 8 coefficients, one zeta per block
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn wide_layer(r: &mut [i16], len: usize, zetas: &[i16; 128], k: &mut usize, inverse: bool) {
+    for start in (0..KYBER_N).step_by(2 * len) {
+        let zeta = _mm_set1_epi16(zetas[*k]);
+        *k += 1;
+        for j in (start..start + len).step_by(8) {
+            let mut x = load(r, j);
+            let mut y = load(r, j + len);
+            if inverse {
+                gs_butterfly(&mut x, &mut y, zeta);
+            } else {
+                ct_butterfly(&mut x, &mut y, zeta);
+            }
+            store(r, j, x);
+            store(r, j + len, y);
+        }
+    }
+}
+
+#[target_feature(enable = "sse2")]
+pub(crate) unsafe fn ntt(r: &mut [i16; KYBER_N]) {
+    let mut k = 1;
+    for len in [128, 64, 32, 16, 8] {
+        wide_layer(r, len, &ZETAS, &mut k, false);
+    }
+    for len in [4, 2] {
+        narrow_layer(r, len, &ZETAS, &mut k, false);
+    }
+}
+
+#[target_feature(enable = "sse2")]
+pub(crate) unsafe fn invntt(r: &mut [i16; KYBER_N]) {
+    let mut k = 0;
+    for len in [2, 4] {
+        narrow_layer(r, len, &ZETAS_INV, &mut k, true);
+    }
+    for len in [8, 16, 32, 64, 128] {
+        wide_layer(r, len, &ZETAS_INV, &mut k, true);
+    }
+    let f = _mm_set1_epi16(ZETAS_INV[127]);
+    for j in (0..KYBER_N).step_by(8) {
+        let x = load(r, j);
+        store(r, j, fqmul(x, f));
+    }
+}
+
+// Splits 8 coefficient pairs into their even and odd coefficients
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn deinterleave(x: __m128i, y: __m128i) -> (__m128i, __m128i) {
+    let even = _mm_packs_epi32(_mm_srai_epi32(_mm_slli_epi32(x, 16), 16), _mm_srai_epi32(_mm_slli_epi32(y, 16), 16));
+    let odd = _mm_packs_epi32(_mm_srai_epi32(x, 16), _mm_srai_epi32(y, 16));
+    (even, odd)
+}
+
+#[target_feature(enable = "sse2")]
+pub(crate) unsafe fn basemul(r: &mut [i16; KYBER_N], a: &[i16; KYBER_N], b: &[i16; KYBER_N]) {
+    for s in (0..KYBER_N).step_by(16) {
+        let (a0, a1) = deinterleave(load(a, s), load(a, s + 8));
+        let (b0, b1) = deinterleave(load(b, s), load(b, s + 8));
+        let zeta = load(&BASEMUL_ZETAS, s / 2);
+
+        let r0 = _mm_add_epi16(fqmul(fqmul(a1, b1), zeta), fqmul(a0, b0));
+        let r1 = _mm_add_epi16(fqmul(a0, b1), fqmul(a1, b0));
+
+        store(r, s, _mm_unpacklo_epi16(r0, r1));
+        store(r, s + 8, _mm_unpackhi_epi16(r0, r1));
+    }
+}
@@ -1,5 +1,8 @@
 pub mod poly
 {
+    use crate::kyber::config::{KYBER_ETA_MAX, KYBER_N, KYBER_Q};
+    use crate::kyber::kyber::KyberParams;
+
         /*************************************************
     * Name:        poly_compress
     *
@@ -9,11 +12,11 @@ pub mod poly
     *                            (of length KYBER_POLYCOMPRESSEDBYTES)
     *              - poly *a:    pointer to input polynomial
     **************************************************/
-    pub fn poly_compress(r: &mut [u8], a: &mut crate::kyber::poly_struct::PolyStruct) {
+    pub fn poly_compress(params: &KyberParams, r: &mut [u8], a: &mut crate::kyber::poly_struct::PolyStruct) {
        
-        let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
-        let kyber_polycompressedbytes: u32 = crate::kyber ::get_env_var("KYBER_POLYCOMPRESSEDBYTES").unwrap();
-        let kyber_q: u32 = crate::kyber ::get_env_var("KYBER_Q").unwrap();
+        let kyber_n = KYBER_N as u32;
+        let kyber_polycompressedbytes = params.kyber_polycompressedbytes;
+        let kyber_q = KYBER_Q as u32;
             let mut t: [u8; 8] = [0; 8];
             let mut r_index = 0; // Mutable index variable
 
@@ -58,10 +61,10 @@ pub mod poly
 *              - const uint8_t *a: pointer to input byte array
 *                                  (of length KYBER_POLYCOMPRESSEDBYTES bytes)
 **************************************************/
-pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u8]) {
-    let kyber_polycompressedbytes: u32 = crate::kyber ::get_env_var("KYBER_POLYCOMPRESSEDBYTES").unwrap();
-    let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
-    let kyber_q: u32 = crate::kyber ::get_env_var("KYBER_Q").unwrap();
+pub fn poly_decompress(params: &KyberParams, r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u8]) {
+    let kyber_polycompressedbytes = params.kyber_polycompressedbytes;
+    let kyber_n = KYBER_N as u32;
+    let kyber_q = KYBER_Q as u32;
 
     if kyber_polycompressedbytes == 128 {
         for i in 0..kyber_n / 2 {
@@ -110,7 +113,7 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
 **************************************************/
     pub fn poly_tobytes(r: &mut [u8], a: &mut crate::kyber::poly_struct::PolyStruct){
 
-        let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
+        let kyber_n = KYBER_N as u32;
                 let mut t0: u16;
                 let mut t1: u16;
 
@@ -137,7 +140,7 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     *                                  (of kyber_polybytes bytes)
     **************************************************/
     pub fn poly_frombytes(r: &mut crate::kyber::poly_struct::PolyStruct, a: &[u8]) {
-        let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
+        let kyber_n = KYBER_N as u32;
             for i in 0..kyber_n / 2 {
                 r.coeffs[(2 * i) as usize] = (((a[(3 * i + 0) as usize] as u16) | ((a[(3 * i + 1) as usize] as u16) << 8)) & 0xFFF) as i16;
                 r.coeffs[(2 * i + 1) as usize] = (((a[(3 * i + 1) as usize] as u16) >> 4 | ((a[(3 * i + 2) as usize] as u16) << 4)) & 0xFFF) as i16;
@@ -156,8 +159,8 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
 *              - const uint8_t *msg: pointer to input message
 **************************************************/
         pub fn poly_frommsg(r: &mut crate::kyber::poly_struct::PolyStruct, msg: &[u8]) {
-            let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
-            let kyber_q: u32 = crate::kyber ::get_env_var("KYBER_Q").unwrap();
+            let kyber_n = KYBER_N as u32;
+            let kyber_q = KYBER_Q as u32;
                 let mut mask: i16;
 
                 /* TODO sort this out \/
@@ -182,8 +185,8 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     *              - poly *a:      pointer to input polynomial
     **************************************************/
         pub fn poly_tomsg(msg: &mut [u8], a: &mut crate::kyber::poly_struct::PolyStruct) {
-            let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
-            let kyber_q: u16 = crate::kyber ::get_env_var("KYBER_Q").unwrap();
+            let kyber_n = KYBER_N as u32;
+            let kyber_q = KYBER_Q as u16;
                 let mut t: u16;
 
                 poly_csubq(a);
@@ -210,23 +213,23 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     *                                     (of length kyber_symbytes bytes)
     *              - uint8_t nonce:       one-byte input nonce
     **************************************************/
-        pub fn poly_getnoise_eta1(r: &mut crate::kyber::poly_struct::PolyStruct, seed: &[u8], nonce: u8) {
-            let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
-            let kyber_eta1: u32 = crate::kyber ::get_env_var("KYBER_ETA1").unwrap();
-            let kyber_ssbytes: u32 = crate::kyber ::get_env_var("KYBER_SSBYTES").unwrap();
+        pub fn poly_getnoise_eta1(params: &KyberParams, r: &mut crate::kyber::poly_struct::PolyStruct, seed: &[u8], nonce: u8) {
+            let kyber_n = KYBER_N as u32;
+            let kyber_eta1 = params.kyber_eta1;
 
-                let mut buf = vec![0u8; kyber_eta1 as usize * kyber_n as usize / 4];
-                if kyber_ssbytes != 32
+                let mut noise = zeroize::Zeroizing::new([0u8; KYBER_ETA_MAX * KYBER_N / 4]);
+                let buf = &mut noise[..kyber_eta1 as usize * kyber_n as usize / 4];
+                if params.kyber_90s
                 {
-                    crate::kyber::symmetric_aes::symmetric_aes::kyber_aes256ctr_prf(&mut buf, seed, nonce);
+                    crate::kyber::symmetric_aes::symmetric_aes::kyber_aes256ctr_prf(buf, seed, nonce);
                 }
                 else
                 {
-                    crate::kyber::symmetric_shake::symmetric_shake::kyber_shake256_prf(&mut buf, seed, nonce);
+                    crate::kyber::symmetric_shake::symmetric_shake::kyber_shake256_prf(buf, seed, nonce);
                 }
 
 
-                crate::kyber::cbd::cbd::cbd_eta1(r, &buf);
+                crate::kyber::cbd::cbd::cbd_eta1(params, r, buf);
             
         }
     
@@ -242,21 +245,21 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     *                                     (of length kyber_symbytes bytes)
     *              - uint8_t nonce:       one-byte input nonce
     **************************************************/
-        pub fn poly_getnoise_eta2(r: &mut crate::kyber::poly_struct::PolyStruct, seed: &[u8], nonce: u8) {
-            let kyber_eta2: u32 = crate::kyber ::get_env_var("KYBER_ETA2").unwrap();
-            let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
-            let kyber_ssbytes: u32 = crate::kyber ::get_env_var("KYBER_SSBYTES").unwrap();
+        pub fn poly_getnoise_eta2(params: &KyberParams, r: &mut crate::kyber::poly_struct::PolyStruct, seed: &[u8], nonce: u8) {
+            let kyber_eta2 = params.kyber_eta2;
+            let kyber_n = KYBER_N as u32;
 
-                let mut buf = vec![0u8; kyber_eta2 as usize * kyber_n as usize / 4];
-                if kyber_ssbytes != 32
+                let mut noise = zeroize::Zeroizing::new([0u8; KYBER_ETA_MAX * KYBER_N / 4]);
+                let buf = &mut noise[..kyber_eta2 as usize * kyber_n as usize / 4];
+                if params.kyber_90s
                 {
-                    crate::kyber::symmetric_aes::symmetric_aes::kyber_aes256ctr_prf(&mut buf, seed, nonce);
+                    crate::kyber::symmetric_aes::symmetric_aes::kyber_aes256ctr_prf(buf, seed, nonce);
                 }
                 else
                 {
-                    crate::kyber::symmetric_shake::symmetric_shake::kyber_shake256_prf(&mut buf, seed, nonce);
+                    crate::kyber::symmetric_shake::symmetric_shake::kyber_shake256_prf(buf, seed, nonce);
                 }
-                crate::kyber::cbd::cbd::cbd_eta2(r, &buf);
+                crate::kyber::cbd::cbd::cbd_eta2(params, r, buf);
             
         }
     
@@ -271,7 +274,7 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     * Arguments:   - uint16_t *r: pointer to in/output polynomial
     **************************************************/
         pub fn poly_ntt(r: &mut crate::kyber::poly_struct::PolyStruct) {
-            crate::kyber::ntt::ntt::ntt(&mut r.coeffs);
+            crate::kyber::backend::Backend::detected().ntt(&mut r.coeffs);
             poly_reduce(r);
         }
         /*************************************************
@@ -284,7 +287,7 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     * Arguments:   - uint16_t *a: pointer to in/output polynomial
     **************************************************/
         pub fn poly_invntt_tomont(r: &mut crate::kyber::poly_struct::PolyStruct) {
-            crate::kyber::ntt::ntt::invntt(&mut r.coeffs);
+            crate::kyber::backend::Backend::detected().invntt(&mut r.coeffs);
         }
     
         /*************************************************
@@ -309,29 +312,7 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     }
     */
     pub fn poly_basemul_montgomery(r: &mut crate::kyber::poly_struct::PolyStruct, a: &crate::kyber::poly_struct::PolyStruct, b: &crate::kyber::poly_struct::PolyStruct) {
-        let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
-
-            for i in 0..kyber_n / 4 {
-                // Temporary arrays to hold pairs of coefficients
-                let mut temp_r1 = [r.coeffs[(4 * i) as usize], r.coeffs[(4 * i + 1) as usize]];
-                let mut temp_a1 = [a.coeffs[(4 * i) as usize], a.coeffs[(4 * i + 1) as usize]];
-                let temp_b1 = [b.coeffs[(4 * i) as usize], b.coeffs[(4 * i + 1) as usize]];
-
-                let mut temp_r2 = [r.coeffs[(4 * i + 2) as usize], r.coeffs[(4 * i + 3) as usize]];
-                let mut temp_a2 = [a.coeffs[(4 * i + 2) as usize], a.coeffs[(4 * i + 3) as usize]];
-                let temp_b2 = [b.coeffs[(4 * i + 2) as usize], b.coeffs[(4 * i + 3) as usize]];
-
-                // Perform basemul on these temporary arrays
-                crate::kyber::ntt::ntt::basemul(&mut temp_r1, &mut temp_a1, &temp_b1, crate::kyber::ntt::ntt::ZETAS[(64 + i) as usize]);
-                crate::kyber::ntt::ntt::basemul(&mut temp_r2, &mut temp_a2, &temp_b2, -crate::kyber::ntt::ntt::ZETAS[(64 + i) as usize]);
-
-                // Assign the results back to the coeffs array
-                r.coeffs[(4 * i) as usize] = temp_r1[0];
-                r.coeffs[(4 * i + 1) as usize] = temp_r1[1];
-                r.coeffs[(4 * i + 2) as usize] = temp_r2[0];
-                r.coeffs[(4 * i + 3) as usize] = temp_r2[1];
-            }
-        
+        crate::kyber::backend::Backend::detected().basemul(&mut r.coeffs, &a.coeffs, &b.coeffs);
     }
 
 
@@ -345,8 +326,8 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     * Arguments:   - poly *r: pointer to input/output polynomial
     **************************************************/
    pub fn poly_tomont(r: &mut crate::kyber::poly_struct::PolyStruct) {
-    let kyber_q: u64 = crate::kyber ::get_env_var("KYBER_Q").unwrap();
-    let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
+    let kyber_q = KYBER_Q as u64;
+    let kyber_n = KYBER_N as u32;
            let f = (1u64 << 32) % kyber_q ;  // Assuming KYBER_Q is defined
            for i in 0..kyber_n {
                r.coeffs[i as usize] = crate::kyber::reduce::reduce::montgomery_reduce(r.coeffs[i as usize] as i32 * f as i32);
@@ -363,7 +344,7 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     * Arguments:   - poly *r: pointer to input/output polynomial
     **************************************************/
     pub fn poly_reduce(r: &mut crate::kyber::poly_struct::PolyStruct) {
-        let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
+        let kyber_n = KYBER_N as u32;
             for i in 0..kyber_n {
                 r.coeffs[i as usize] = crate::kyber::reduce::reduce::barrett_reduce(r.coeffs[i as usize]);
             }
@@ -381,7 +362,7 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     **************************************************/
     
     pub fn poly_csubq(r: &mut crate::kyber::poly_struct::PolyStruct) {
-        let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
+        let kyber_n = KYBER_N as u32;
             for i in 0..kyber_n {
                 r.coeffs[i as usize] = crate::kyber::reduce::reduce::csubq(r.coeffs[i as usize]);
             }
@@ -398,13 +379,27 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     **************************************************/
     pub fn poly_add(r: &mut crate::kyber::poly_struct::PolyStruct, a: &crate::kyber::poly_struct::PolyStruct, b: &crate::kyber::poly_struct::PolyStruct) 
      {
-        let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
+        let kyber_n = KYBER_N as u32;
             for i in 0..kyber_n {
                 r.coeffs[i as usize] = a.coeffs[i as usize] + b.coeffs[i as usize];
             }
         
     }
     /*************************************************
+    * Name:        poly_add_assign
+    *
+    * Description: Add a polynomial to r in place, poly_add(r, r, a) in the
+    *              reference code
+    *
+    * Arguments: - poly *r:       pointer to input/output polynomial
+    *            - const poly *a: pointer to polynomial to add
+    **************************************************/
+    pub fn poly_add_assign(r: &mut crate::kyber::poly_struct::PolyStruct, a: &crate::kyber::poly_struct::PolyStruct) {
+        for i in 0..KYBER_N {
+            r.coeffs[i] += a.coeffs[i];
+        }
+    }
+    /*************************************************
     * Name:        poly_sub
     *
     * Description: Subtract two polynomials
@@ -414,7 +409,7 @@ pub fn poly_decompress(r: &mut crate::kyber::poly_struct::PolyStruct, mut a: &[u
     *            - const poly *b: pointer to second input polynomial
     **************************************************/
     pub fn poly_sub(r: &mut crate::kyber::poly_struct::PolyStruct, a: &crate::kyber::poly_struct::PolyStruct, b: &crate::kyber::poly_struct::PolyStruct) {
-        let kyber_n: u32 = crate::kyber ::get_env_var("KYBER_N").unwrap();
+        let kyber_n = KYBER_N as u32;
             for i in 0..kyber_n {
                 r.coeffs[i as usize] = a.coeffs[i as usize] - b.coeffs[i as usize];
             }
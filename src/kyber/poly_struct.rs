@@ -1,19 +1,24 @@
-use std::env;
+use zeroize::Zeroize;
 
+use crate::kyber::config::KYBER_N;
+
+// Polynomials hold secret and noise coefficients, so every copy is wiped on drop.
+// The coefficients live inline, creating or copying a polynomial never allocates.
 #[derive(Clone)]
 pub struct PolyStruct {
-    pub coeffs: Vec<i16>,
+    pub coeffs: [i16; KYBER_N],
+}
+
+impl Drop for PolyStruct {
+    fn drop(&mut self) {
+        self.coeffs.zeroize();
+    }
 }
 
 impl PolyStruct {
     pub fn new() -> Self {
-        let kyber_n: usize = match env::var("KYBER_N") {
-            Ok(val) => val.parse().expect("Invalid KYBER_N value"),
-            Err(_) => 0, // Default value if environment variable is not set
-        };
-
         PolyStruct {
-            coeffs: vec![0; kyber_n], // Set the size of coeffs to kyber_n
+            coeffs: [0; KYBER_N],
         }
     }
 }
@@ -1,6 +1,10 @@
 
 pub mod polyvec
 {
+    use crate::kyber::config::{KYBER_N, KYBER_POLYBYTES, KYBER_Q};
+    use crate::kyber::kyber::KyberParams;
+    use crate::kyber::poly_struct::PolyStruct;
+    use crate::kyber::polyvec_struct::PolyVec;
 
     /*************************************************
     * Name:        polyvec_compress
@@ -11,11 +15,11 @@ pub mod polyvec
     *                            (needs space for kyber_polyveccompressedbytes)
     *              - polyvec *a: pointer to input vector of polynomials
     **************************************************/
-    pub fn polyvec_compress(r: &mut [u8], a: &mut crate::kyber::polyvec_struct::PolyVec) {
-        let kyber_n: u32 = crate::kyber::get_env_var("KYBER_N").unwrap();
-        let kyber_k: u32 = crate::kyber::get_env_var("KYBER_K").unwrap();
-        let kyber_polyveccompressedbytes: u32 = crate::kyber::get_env_var("KYBER_POLYVECCOMPRESSEDBYTES").unwrap();
-        let kyber_q: u32 = crate::kyber::get_env_var("KYBER_Q").unwrap();
+    pub fn polyvec_compress<const K: usize>(params: &KyberParams, r: &mut [u8], a: &mut PolyVec<K>) {
+        let kyber_n = KYBER_N as u32;
+        let kyber_k = K as u32;
+        let kyber_polyveccompressedbytes = params.kyber_polyveccompressedbytes;
+        let kyber_q = KYBER_Q as u32;
             let mut r_idx = 0;
 
             polyvec_csubq(a);
@@ -78,11 +82,11 @@ pub mod polyvec
     *              - const uint8_t *a: pointer to input byte array
     *                                  (of length kyber_polyveccompressedbytes)
     **************************************************/
-    pub fn polyvec_decompress(r: &mut crate::kyber::polyvec_struct::PolyVec, a: &[u8]) {
-        let kyber_k: u32 = crate::kyber::get_env_var("KYBER_K").unwrap();
-        let kyber_n: u32 = crate::kyber::get_env_var("KYBER_N").unwrap();
-        let kyber_q: u32 = crate::kyber::get_env_var("KYBER_Q").unwrap();
-        let kyber_polyveccompressedbytes: u32 = crate::kyber::get_env_var("KYBER_POLYVECCOMPRESSEDBYTES").unwrap();
+    pub fn polyvec_decompress<const K: usize>(params: &KyberParams, r: &mut PolyVec<K>, a: &[u8]) {
+        let kyber_k = K as u32;
+        let kyber_n = KYBER_N as u32;
+        let kyber_q = KYBER_Q as u32;
+        let kyber_polyveccompressedbytes = params.kyber_polyveccompressedbytes;
             let mut idx = 0; // Initialize an index variable
 
             if kyber_polyveccompressedbytes == (kyber_k * 352) {
@@ -140,13 +144,9 @@ pub mod polyvec
     *                            (needs space for KYBER_POLYVECBYTES)
     *              - polyvec *a: pointer to input vector of polynomials
     **************************************************/
-    pub fn polyvec_tobytes(r: &mut [u8], a: &mut crate::kyber::polyvec_struct::PolyVec) {
-        let kyber_k: u32 = crate::kyber::get_env_var("KYBER_K").unwrap();
-        let kyber_polybytes: u32 = crate::kyber::get_env_var("KYBER_POLYBYTES").unwrap();
-        let range:u32 = kyber_polybytes;
-            let mut i = 0;
-            for i in 0..kyber_k {
-                crate::kyber::poly::poly::poly_tobytes(&mut r[(i * range) as usize..], &mut a.vec[i as usize]);
+    pub fn polyvec_tobytes<const K: usize>(r: &mut [u8], a: &mut PolyVec<K>) {
+            for i in 0..K {
+                crate::kyber::poly::poly::poly_tobytes(&mut r[i * KYBER_POLYBYTES..], &mut a.vec[i]);
             }
         
     }
@@ -160,12 +160,10 @@ pub mod polyvec
     *              - const polyvec *a: pointer to input vector of polynomials
     *                                  (of length KYBER_POLYVECBYTES)
     **************************************************/
-    pub fn polyvec_frombytes(r: &mut crate::kyber::polyvec_struct::PolyVec, a: &[u8]) {
-        let kyber_k: u32 = crate::kyber::get_env_var("KYBER_K").unwrap();
-        let kyber_polybytes: u32 = crate::kyber::get_env_var("KYBER_POLYBYTES").unwrap();
-            for j in 0..kyber_k as usize {
-                let start = j * kyber_polybytes as usize;
-                let end = start + kyber_polybytes as usize;
+    pub fn polyvec_frombytes<const K: usize>(r: &mut PolyVec<K>, a: &[u8]) {
+            for j in 0..K {
+                let start = j * KYBER_POLYBYTES;
+                let end = start + KYBER_POLYBYTES;
                 crate::kyber::poly::poly::poly_frombytes(&mut r.vec[j], &a[start..end]);
             }
         
@@ -179,13 +177,9 @@ pub mod polyvec
     *
     * Arguments:   - polyvec *r: pointer to in/output vector of polynomials
     **************************************************/
-    pub fn polyvec_ntt(r: &mut crate::kyber::polyvec_struct::PolyVec) {
-        let kyber_k: u32 = crate::kyber::get_env_var("KYBER_K").unwrap();
-            for i in 0..kyber_k {
-                let mut x: crate::kyber::poly_struct::PolyStruct =  r.vec[i as usize].clone();
-                crate::kyber::poly::poly::poly_ntt(&mut x);//&mut r.vec[i as usize]);
-                r.vec[i as usize] = x;
-
+    pub fn polyvec_ntt<const K: usize>(r: &mut PolyVec<K>) {
+            for i in 0..K {
+                crate::kyber::poly::poly::poly_ntt(&mut r.vec[i]);
             }
         
     }
@@ -198,9 +192,8 @@ pub mod polyvec
     *
     * Arguments:   - polyvec *r: pointer to in/output vector of polynomials
     **************************************************/
-    pub fn polyvec_invntt_tomont(r: &mut crate::kyber::polyvec_struct::PolyVec) {
-        let kyber_k: u32 = crate::kyber::get_env_var("KYBER_K").unwrap();
-            for i in 0..kyber_k {
+    pub fn polyvec_invntt_tomont<const K: usize>(r: &mut PolyVec<K>) {
+            for i in 0..K {
                 crate::kyber::poly::poly::poly_invntt_tomont(&mut r.vec[i as usize]);
             }
         
@@ -216,17 +209,14 @@ pub mod polyvec
     *            - const polyvec *a: pointer to first input vector of polynomials
     *            - const polyvec *b: pointer to second input vector of polynomials
     **************************************************/
-    pub fn polyvec_pointwise_acc_montgomery(r: &mut crate::kyber::poly_struct::PolyStruct, a: &crate::kyber::polyvec_struct::PolyVec, b: &crate::kyber::polyvec_struct::PolyVec) {
-        let kyber_k: u32 = crate::kyber::get_env_var("KYBER_K").unwrap();
-            let mut result = crate::kyber::poly_struct::PolyStruct::new(); // Create a mutable result variable
+    pub fn polyvec_pointwise_acc_montgomery<const K: usize>(r: &mut PolyStruct, a: &PolyVec<K>, b: &PolyVec<K>) {
+            let mut t = PolyStruct::new();
 
             crate::kyber::poly::poly::poly_basemul_montgomery(r, &a.vec[0], &b.vec[0]);
 
-            for i in 1..kyber_k {
-                let mut t = crate::kyber::poly_struct::PolyStruct::new();
-                crate::kyber::poly::poly::poly_basemul_montgomery(&mut t, &a.vec[i as usize], &b.vec[i as usize]);
-                crate::kyber::poly::poly::poly_add(&mut result, r, &t); // Accumulate the result in `result`
-                *r = result.clone(); 
+            for i in 1..K {
+                crate::kyber::poly::poly::poly_basemul_montgomery(&mut t, &a.vec[i], &b.vec[i]);
+                crate::kyber::poly::poly::poly_add_assign(r, &t);
             }
 
             
@@ -243,10 +233,9 @@ pub mod polyvec
     *
     * Arguments:   - poly *r: pointer to input/output polynomial
     **************************************************/
-    pub fn polyvec_reduce(r: &mut crate::kyber::polyvec_struct::PolyVec) 
+    pub fn polyvec_reduce<const K: usize>(r: &mut PolyVec<K>) 
     {
-        let kyber_k: u32 = crate::kyber::get_env_var("KYBER_K").unwrap();
-            for i in 0..kyber_k {
+            for i in 0..K {
                 crate::kyber::poly::poly::poly_reduce(&mut r.vec[i as usize]);
             }
         
@@ -262,9 +251,8 @@ pub mod polyvec
     *
     * Arguments:   - poly *r: pointer to input/output polynomial
     **************************************************/
-    pub fn polyvec_csubq(r: &mut crate::kyber::polyvec_struct::PolyVec) {
-        let kyber_k: u32 = crate::kyber::get_env_var("KYBER_K").unwrap();
-            for i in 0..kyber_k {
+    pub fn polyvec_csubq<const K: usize>(r: &mut PolyVec<K>) {
+            for i in 0..K {
                 crate::kyber::poly::poly::poly_csubq(&mut r.vec[i as usize]);
             
         }
@@ -278,11 +266,24 @@ pub mod polyvec
     *            - const polyvec *a: pointer to first input vector of polynomials
     *            - const polyvec *b: pointer to second input vector of polynomials
     **************************************************/
-    pub fn polyvec_add(r: &mut crate::kyber::polyvec_struct::PolyVec, a: &crate::kyber::polyvec_struct::PolyVec, b: &crate::kyber::polyvec_struct::PolyVec) {
-        let kyber_k: u32 = crate::kyber::get_env_var("KYBER_K").unwrap();
-            for i in 0..kyber_k {
+    pub fn polyvec_add<const K: usize>(r: &mut PolyVec<K>, a: &PolyVec<K>, b: &PolyVec<K>) {
+            for i in 0..K {
                 crate::kyber::poly::poly::poly_add(&mut r.vec[i as usize], &a.vec[i as usize], &b.vec[i as usize]);
             }
         }
-    }
+    
+    /*************************************************
+    * Name:        polyvec_add_assign
+    *
+    * Description: Add a vector of polynomials to r in place
+    *
+    * Arguments: - polyvec *r:       pointer to input/output vector of polynomials
+    *            - const polyvec *a: pointer to vector of polynomials to add
+    **************************************************/
+    pub fn polyvec_add_assign<const K: usize>(r: &mut PolyVec<K>, a: &PolyVec<K>) {
+            for i in 0..K {
+                crate::kyber::poly::poly::poly_add_assign(&mut r.vec[i], &a.vec[i]);
+            }
+        }
+}
 
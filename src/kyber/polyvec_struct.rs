@@ -1,20 +1,29 @@
 use crate::kyber::poly_struct::PolyStruct;
-use std::env;
 
+// A vector of K polynomials held inline, K is the module rank (2, 3 or 4)
 #[derive(Clone)]
-pub struct PolyVec {
-    pub vec: Vec<PolyStruct>,
+pub struct PolyVec<const K: usize> {
+    pub vec: [PolyStruct; K],
 }
 
-impl PolyVec {
+impl<const K: usize> PolyVec<K> {
     pub fn new() -> Self {
-        let kyber_k: usize = match env::var("KYBER_K") {
-            Ok(val) => val.parse().expect("Invalid KYBER_K value"),
-            Err(_) => 0, // Default value if environment variable is not set
-        };
-
         PolyVec {
-            vec: (0..kyber_k).map(|_| PolyStruct::new()).collect(),
+            vec: core::array::from_fn(|_| PolyStruct::new()),
         }
     }
 }
+
+// Calls `$func::<K>` with K the module rank of `$params`, so the polynomial
+// vectors of each parameter set are sized at compile time
+macro_rules! with_rank {
+    ($params:expr, $func:ident($($arg:expr),*)) => {
+        match $params.kyber_k {
+            2 => $func::<2>($($arg),*),
+            3 => $func::<3>($($arg),*),
+            4 => $func::<4>($($arg),*),
+            k => panic!("unsupported module rank {}", k),
+        }
+    };
+}
+pub(crate) use with_rank;
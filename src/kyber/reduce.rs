@@ -13,7 +13,7 @@ pub mod reduce
 * Returns:     integer in {-q+1,...,q-1} congruent to a * R^-1 modulo q.
 **************************************************/
     pub fn montgomery_reduce(a: i32) -> i16 {
-        let kyber_q: u32 = crate::kyber::get_env_var("KYBER_Q").unwrap();
+        let kyber_q = crate::kyber::config::KYBER_Q as u32;
             let mut t: i32;
             let mut u: i16;
 
@@ -37,7 +37,7 @@ pub mod reduce
 * Returns:     integer in {0,...,q} congruent to a modulo q.
 **************************************************/
 pub fn barrett_reduce(a: i16) -> i16 {
-    let kyber_q: u32 = crate::kyber::get_env_var("KYBER_Q").unwrap();
+    let kyber_q = crate::kyber::config::KYBER_Q as u32;
     let v: i32 = (((1 << 26) + (kyber_q / 2)) / kyber_q).try_into().unwrap();
 
     let mut t: i32 = (v * (a as i32)) >> 26;
@@ -58,7 +58,7 @@ pub fn barrett_reduce(a: i16) -> i16 {
 **************************************************/
     pub fn csubq(a: i16) -> i16 {
         
-        let kyber_q: u32 = crate::kyber::get_env_var("KYBER_Q").unwrap();
+        let kyber_q = crate::kyber::config::KYBER_Q as u32;
             let mut a = a - kyber_q as i16;
             a += (a >> 15) & kyber_q as i16;
             return a; 
@@ -1,7 +1,52 @@
 pub mod speed_print
 {
-    //THIS IS TRANSLATED BUT NOT USED CURRENTLY
     use std::cmp::Ordering;
+    use lazy_static::lazy_static;
+
+    /// Unit of the counts returned by `cpucycles`.
+    #[cfg(target_arch = "x86_64")]
+    pub const UNIT: &str = "cycles";
+    #[cfg(not(target_arch = "x86_64"))]
+    pub const UNIT: &str = "ns";
+
+    /*************************************************
+    * Name:        cpucycles
+    *
+    * Description: Read the cycle counter, or the nanoseconds since the first
+    *              call where there is no cycle counter
+    **************************************************/
+    #[cfg(target_arch = "x86_64")]
+    pub fn cpucycles() -> u64 {
+        unsafe { core::arch::x86_64::_rdtsc() }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn cpucycles() -> u64 {
+        use std::sync::OnceLock;
+        use std::time::Instant;
+        static START: OnceLock<Instant> = OnceLock::new();
+        START.get_or_init(Instant::now).elapsed().as_nanos() as u64
+    }
+
+    /*************************************************
+    * Name:        cpucycles_overhead
+    *
+    * Description: Smallest difference between two back to back calls to
+    *              cpucycles, subtracted from every measurement
+    **************************************************/
+    pub fn cpucycles_overhead() -> u64 {
+        let mut overhead = u64::MAX;
+        for _ in 0..1000 {
+            let t0 = cpucycles();
+            let t1 = cpucycles();
+            overhead = overhead.min(t1.wrapping_sub(t0));
+        }
+        overhead
+    }
+
+    lazy_static! {
+        static ref OVERHEAD: u64 = cpucycles_overhead();
+    }
 
     pub fn cmp_uint64(a: &u64, b: &u64) -> Ordering {
         a.cmp(b)
@@ -22,28 +67,40 @@ pub mod speed_print
         acc / t.len() as u64
     }
 
-    pub fn print_results(s: &str, t: &mut [u64]) {
+    /*************************************************
+    * Name:        summarize
+    *
+    * Description: Turn the counter readings t, taken before each of a series of
+    *              calls, into the median and average count per call
+    *
+    * Returns None if there are fewer than two readings
+    **************************************************/
+    pub fn summarize(t: &mut [u64]) -> Option<(u64, u64)> {
         let tlen = t.len();
-        let mut overhead = u64::MAX;
-
         if tlen < 2 {
-            eprintln!("ERROR: Need at least two cycle counts!");
-            return;
-        }
-
-        if overhead == u64::MAX {
-            
-            overhead = 0;
+            return None;
         }
 
+        let overhead = *OVERHEAD;
         let tlen = tlen - 1;
         for i in 0..tlen {
-            t[i] = t[i + 1] - t[i] - overhead;
+            t[i] = t[i + 1].wrapping_sub(t[i]).saturating_sub(overhead);
         }
 
+        Some((median(&mut t[..tlen], tlen), average(&t[..tlen])))
+    }
+
+    pub fn print_summary(s: &str, median: u64, average: u64) {
         println!("{}", s);
-        println!("median: {} cycles/ticks", median(t, tlen));
-        println!("average: {} cycles/ticks", average(&t[..tlen]));
+        println!("median: {} {}", median, UNIT);
+        println!("average: {} {}", average, UNIT);
         println!();
     }
-}
\ No newline at end of file
+
+    pub fn print_results(s: &str, t: &mut [u64]) {
+        match summarize(t) {
+            Some((median, average)) => print_summary(s, median, average),
+            None => eprintln!("ERROR: Need at least two cycle counts!"),
+        }
+    }
+}
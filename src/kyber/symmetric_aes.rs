@@ -1,7 +1,7 @@
 pub mod symmetric_aes
 {
     pub fn kyber_aes256xof_absorb(state: &mut crate::kyber::xof_state::Aes256CtrCtx, seed: &[u8], x: u8, y: u8) {
-        let kyber_symbytes: u32 = crate::kyber::get_env_var("KYBER_SYMBYTES").unwrap();
+        let kyber_symbytes = crate::kyber::config::KYBER_SYMBYTES;
             assert_eq!(kyber_symbytes, 32, "Kyber-90s only supports kyber_symbytes = 32!");
 
             let mut expnonce = [0u8; 12];
@@ -12,6 +12,19 @@ pub mod symmetric_aes
         
     }
 
+    /// Fills `out` with whole 64 byte blocks of the Kyber-90s XOF, AES-256-CTR
+    /// keyed with `seed` under the nonce `x || y || 0 ...`, as matrix generation
+    /// squeezes it.
+    pub fn kyber_aes256xof(out: &mut [u8], seed: &[u8], x: u8, y: u8) {
+        use crate::kyber::xof_state::XofAbsorb;
+
+        let mut state = crate::kyber::xof_state::Aes256CtrCtx::new();
+        kyber_aes256xof_absorb(&mut state, seed, x, y);
+        crate::kyber::aes256ctr::aes256ctr::aes256ctr_squeezeblocks(out, out.len() / 64, &mut state);
+    }
+
+    /// Fills `out` with the Kyber-90s PRF, AES-256-CTR keyed with `key` under the
+    /// nonce `nonce || 0 ...`, as noise sampling uses it.
     pub fn kyber_aes256ctr_prf(out: &mut [u8], key: &[u8], nonce: u8) {
 
         let mut expnonce = [0u8; 12];
@@ -20,5 +33,6 @@ pub mod symmetric_aes
         key_copy.copy_from_slice(key);
 
         crate::kyber::aes256ctr::aes256ctr::aes256ctr_prf(out, out.len(), &key_copy, &expnonce);
+        zeroize::Zeroize::zeroize(&mut key_copy);
     }
 }
@@ -13,8 +13,8 @@ pub mod symmetric_shake {
     *              - uint8_t j            additional byte of input
     **************************************************/
     pub fn kyber_shake128_absorb(state: &mut crate::kyber::xof_state::KeccakState, seed:&[u8], x: u8, y: u8) {
-        let kyber_symbytes: usize = crate::kyber::get_env_var("KYBER_SYMBYTES").unwrap();
-            let mut extseed = vec![0u8; kyber_symbytes as usize + 2];
+        let kyber_symbytes = crate::kyber::config::KYBER_SYMBYTES;
+            let mut extseed = [0u8; crate::kyber::config::KYBER_SYMBYTES + 2];
 
             for i in 0..kyber_symbytes{
                 extseed[i as usize] = seed[i as usize];
@@ -42,9 +42,9 @@ pub mod symmetric_shake {
 **************************************************/
 
     pub fn kyber_shake256_prf(out: &mut [u8], key: &[u8], nonce: u8) {
-        let kyber_symbytes: usize = crate::kyber::get_env_var("KYBER_SYMBYTES").unwrap();
+        let kyber_symbytes = crate::kyber::config::KYBER_SYMBYTES;
 
-            let mut extkey = vec![0u8; kyber_symbytes as usize+ 1];
+            let mut extkey = [0u8; crate::kyber::config::KYBER_SYMBYTES + 1];
 
             for i in  0..kyber_symbytes
             {
@@ -54,6 +54,7 @@ pub mod symmetric_shake {
                 extkey[len] = nonce;
 
                 crate::kyber::fips202::fips202::shake256(out, out.len(), &extkey);
+                zeroize::Zeroize::zeroize(&mut extkey);
     
         
     }
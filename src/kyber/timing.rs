@@ -0,0 +1,179 @@
+// dudect-style timing leakage test ("Dude, is my code constant time?",
+// Reparaz, Balasch and Verbauwhede). Decapsulation is timed on two classes of
+// input, one fixed valid ciphertext and uniformly random ciphertexts, and the
+// two timing distributions are compared with Welch's t-test. A |t| above
+// LEAK_THRESHOLD means the classes are distinguishable from timing alone.
+
+use std::fmt;
+use std::hint::black_box;
+
+use rand::rngs::OsRng;
+use rand_core::RngCore;
+
+use crate::error::KyberError;
+use crate::kyber::kyber_api::{Kem, KemBytes};
+use crate::kyber::speed_print::speed_print::cpucycles;
+
+/// |t| above which dudect reports a probable leak.
+pub const LEAK_THRESHOLD: f64 = 4.5;
+
+// Measurements above these percentiles are cropped, as dudect does, so rare
+// interrupts and cache misses do not hide a leak in the bulk of the samples
+const CROP_PERCENTILES: [f64; 5] = [0.50, 0.75, 0.90, 0.95, 0.99];
+
+/// Number of ciphertexts `decapsulation_leakage` generates and times at a time.
+pub const BATCH: usize = 10_000;
+
+/// Welch's t-test accumulated online with Welford's algorithm.
+#[derive(Clone, Debug, Default)]
+pub struct WelchT {
+    n: [f64; 2],
+    mean: [f64; 2],
+    m2: [f64; 2],
+}
+
+impl WelchT {
+    pub fn new() -> Self {
+        WelchT::default()
+    }
+
+    /// Adds one measurement `x` to class 0 or 1.
+    pub fn push(&mut self, class: usize, x: f64) {
+        self.n[class] += 1.0;
+        let delta = x - self.mean[class];
+        self.mean[class] += delta / self.n[class];
+        self.m2[class] += delta * (x - self.mean[class]);
+    }
+
+    /// Number of measurements in each class.
+    pub fn samples(&self) -> [usize; 2] {
+        [self.n[0] as usize, self.n[1] as usize]
+    }
+
+    /// The t statistic, 0 until both classes hold two measurements.
+    pub fn t(&self) -> f64 {
+        if self.n[0] < 2.0 || self.n[1] < 2.0 {
+            return 0.0;
+        }
+        let var0 = self.m2[0] / (self.n[0] - 1.0);
+        let var1 = self.m2[1] / (self.n[1] - 1.0);
+        let den = (var0 / self.n[0] + var1 / self.n[1]).sqrt();
+        if den == 0.0 {
+            return 0.0;
+        }
+        (self.mean[0] - self.mean[1]) / den
+    }
+}
+
+/// One t-test over the measurements below a crop threshold.
+#[derive(Clone, Debug)]
+pub struct TTest {
+    pub label: String,
+    pub t: f64,
+    pub samples: [usize; 2],
+}
+
+/// Result of `decapsulation_leakage`.
+#[derive(Clone, Debug)]
+pub struct TimingReport {
+    pub parameter_set: String,
+    pub tests: Vec<TTest>,
+}
+
+impl TimingReport {
+    /// Largest |t| over all crops.
+    pub fn max_t(&self) -> f64 {
+        self.tests.iter().map(|test| test.t.abs()).fold(0.0, f64::max)
+    }
+
+    pub fn leak_detected(&self) -> bool {
+        self.max_t() > LEAK_THRESHOLD
+    }
+}
+
+impl fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Decapsulation timing, {} (fixed vs random ciphertexts)", self.parameter_set)?;
+        for test in &self.tests {
+            writeln!(f, "  {:<12} t = {:>8.3}   ({} fixed, {} random)", test.label, test.t, test.samples[0], test.samples[1])?;
+        }
+        let verdict = if self.leak_detected() { "POSSIBLE LEAK" } else { "no leak detected" };
+        write!(f, "  max |t| = {:.3}, threshold {}: {}", self.max_t(), LEAK_THRESHOLD, verdict)
+    }
+}
+
+/// Times `samples` decapsulations with `K`, each on either a fixed valid
+/// ciphertext or a fresh random one, and runs Welch's t-test on the two classes.
+pub fn decapsulation_leakage<K: Kem>(samples: usize) -> Result<TimingReport, KyberError> {
+    decapsulation_leakage_in_batches::<K>(samples, BATCH)
+}
+
+/// `decapsulation_leakage` generating and timing `batch` ciphertexts at a time.
+/// Only one batch is held in memory, however many samples are taken.
+pub fn decapsulation_leakage_in_batches<K: Kem>(samples: usize, batch: usize) -> Result<TimingReport, KyberError> {
+    let batch = batch.max(1);
+    let (pk, sk) = K::keypair()?;
+    let (fixed, _) = K::encapsulate(&pk)?;
+
+    // The first tenth, at most one batch, warms caches and branch predictors.
+    // Its timings set the crop thresholds, as dudect's first batch does
+    let warmup = (samples / 10).min(batch);
+    let mut thresholds = [u64::MAX; CROP_PERCENTILES.len() + 1];
+    let mut welch = vec![WelchT::new(); thresholds.len()];
+
+    let mut classes = vec![0u8; batch];
+    let mut inputs = Vec::with_capacity(batch);
+    let mut times = Vec::with_capacity(batch);
+    let mut random = vec![0u8; K::CIPHERTEXT_BYTES];
+    let mut done = 0;
+    while done < samples {
+        let count = if done == 0 && warmup > 0 { warmup } else { (samples - done).min(batch) };
+
+        // Each batch's inputs are prepared before it is timed so only decapsulation is timed
+        let classes = &mut classes[..count];
+        OsRng.fill_bytes(classes);
+        inputs.clear();
+        for class in classes.iter_mut() {
+            *class &= 1;
+            if *class == 0 {
+                inputs.push(K::Ciphertext::from_bytes(fixed.as_bytes())?);
+            } else {
+                OsRng.fill_bytes(&mut random);
+                inputs.push(K::Ciphertext::from_bytes(&random)?);
+            }
+        }
+
+        times.clear();
+        for ct in &inputs {
+            let start = cpucycles();
+            let ss = K::decapsulate(&sk, black_box(ct));
+            let end = cpucycles();
+            black_box(ss)?;
+            times.push(end.wrapping_sub(start));
+        }
+
+        if done == 0 && warmup > 0 {
+            times.sort_unstable();
+            for (threshold, percentile) in thresholds[1..].iter_mut().zip(CROP_PERCENTILES) {
+                *threshold = times[((times.len() - 1) as f64 * percentile) as usize];
+            }
+        } else {
+            for (&class, &time) in classes.iter().zip(&times) {
+                for (welch, &threshold) in welch.iter_mut().zip(&thresholds) {
+                    if time <= threshold {
+                        welch.push(class as usize, time as f64);
+                    }
+                }
+            }
+        }
+        done += count;
+    }
+
+    let labels = std::iter::once("all".to_string()).chain(CROP_PERCENTILES.iter().map(|percentile| format!("below p{}", (percentile * 100.0) as u32)));
+    let tests = labels.zip(&welch).map(|(label, welch)| TTest { label, t: welch.t(), samples: welch.samples() }).collect();
+
+    Ok(TimingReport {
+        parameter_set: K::PARAMETER_SET.to_string(),
+        tests,
+    })
+}
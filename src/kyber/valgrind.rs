@@ -0,0 +1,118 @@
+// Constant-time checking with Valgrind memcheck, as done by ctgrind and the
+// reference Kyber code. Secret inputs are marked undefined, so memcheck reports
+// every branch and memory index that depends on them as a use of uninitialised
+// memory. Memcheck does not see variable-time instructions such as division;
+// those are what the dudect test in `timing` is for.
+
+use std::env;
+use std::io;
+use std::process::Command;
+
+use crate::error::KyberError;
+use crate::kyber::kyber_api::{Kem, KemBytes};
+
+const VG_USERREQ_RUNNING_ON_VALGRIND: usize = 0x1001;
+const VG_USERREQ_MAKE_MEM_UNDEFINED: usize = 0x4d43_0001;
+const VG_USERREQ_MAKE_MEM_DEFINED: usize = 0x4d43_0002;
+
+// The client request sequence from valgrind.h. Outside Valgrind the rotations
+// of rdi cancel out and `default` is returned unchanged.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn client_request(default: usize, request: usize, arg1: usize, arg2: usize) -> usize {
+    let args: [usize; 6] = [request, arg1, arg2, 0, 0, 0];
+    let result;
+    unsafe {
+        core::arch::asm!(
+            "rol rdi, 3",
+            "rol rdi, 13",
+            "rol rdi, 61",
+            "rol rdi, 51",
+            "xchg rbx, rbx",
+            inout("rdx") default => result,
+            in("rax") args.as_ptr(),
+            options(nostack),
+        );
+    }
+    result
+}
+
+#[cfg(not(all(target_arch = "x86_64", target_os = "linux")))]
+fn client_request(default: usize, _request: usize, _arg1: usize, _arg2: usize) -> usize {
+    default
+}
+
+pub fn running_on_valgrind() -> bool {
+    client_request(0, VG_USERREQ_RUNNING_ON_VALGRIND, 0, 0) != 0
+}
+
+/// Marks `secret` as undefined, memcheck then reports any branch on it.
+pub fn classify(secret: &[u8]) {
+    client_request(0, VG_USERREQ_MAKE_MEM_UNDEFINED, secret.as_ptr() as usize, secret.len());
+}
+
+/// Marks `public` as defined again once it is safe to branch on.
+pub fn declassify(public: &[u8]) {
+    client_request(0, VG_USERREQ_MAKE_MEM_DEFINED, public.as_ptr() as usize, public.len());
+}
+
+// Key generation, encapsulation and decapsulation with their secret inputs classified
+fn secret_paths<K: Kem>() -> Result<(), KyberError> {
+    let d = [0x11u8; 32];
+    let z = [0x22u8; 32];
+    let m = [0x33u8; 32];
+    classify(&d);
+    classify(&z);
+    classify(&m);
+
+    let (pk, sk) = K::keypair_derand(&d, &z)?;
+    // The public key is output in the clear
+    declassify(pk.as_bytes());
+
+    let (ct, ss) = K::encapsulate_derand(&pk, &m)?;
+    declassify(ct.as_bytes());
+
+    // Only the secret polynomial vector and z are secret in sk, the rest is the public key and its hash
+    let sk_bytes = sk.as_bytes();
+    classify(&sk_bytes[..K::SECURITY_LEVEL as usize * 384]);
+    classify(&sk_bytes[K::SECRET_KEY_BYTES - 32..]);
+    let ss_dec = K::decapsulate(&sk, &ct)?;
+
+    declassify(ss.as_bytes());
+    declassify(ss_dec.as_bytes());
+    if ss_dec != ss {
+        return Err(KyberError::InvalidKey("decapsulation did not recover the shared secret"));
+    }
+    Ok(())
+}
+
+/// Runs the secret-dependent paths of `K` under Valgrind memcheck. Outside
+/// Valgrind this re-runs the current executable with `args` under `valgrind`,
+/// which must be on the PATH. Returns false if memcheck reported any error.
+pub fn ct_check<K: Kem>(args: &[String]) -> Result<bool, KyberError> {
+    if running_on_valgrind() {
+        secret_paths::<K>()?;
+        return Ok(true);
+    }
+
+    let exe = env::current_exe()?;
+    let status = Command::new("valgrind")
+        .args(["--error-exitcode=99", "--track-origins=yes", "--quiet"])
+        .arg(exe)
+        .args(args)
+        .status()
+        .map_err(|err| {
+            if err.kind() == io::ErrorKind::NotFound {
+                KyberError::Io(io::Error::new(io::ErrorKind::NotFound, "valgrind not found on the PATH"))
+            } else {
+                KyberError::Io(err)
+            }
+        })?;
+
+    match status.code() {
+        Some(0) => Ok(true),
+        Some(99) => Ok(false),
+        _ => Err(KyberError::Io(io::Error::other(
+            format!("{} check under valgrind did not complete ({})", K::PARAMETER_SET, status),
+        ))),
+    }
+}
@@ -18,7 +18,7 @@ pub mod verify{
             r |= a[i] ^ b[i];
         }
 
-        (-(r as i8) as u64) >> 63
+        (r as u64).wrapping_neg() >> 63
     }
 
 
@@ -1,3 +1,5 @@
+use zeroize::Zeroize;
+
 pub enum XofState {
     Aes256CtrCtx(Aes256CtrCtx),
     KeccakState(KeccakState),
@@ -8,7 +10,6 @@ pub trait XofAbsorb {
     fn new() -> Self;
 }
 
-#[derive(Clone)]
 pub struct Aes256CtrCtx {
     pub(crate) sk_exp: [u64; 120],
     pub(crate) ivw: [u32; 16],
@@ -27,7 +28,6 @@ impl XofAbsorb for Aes256CtrCtx {
     }
 }
 
-#[derive(Clone)]
 pub struct KeccakState {
     pub(crate) s: [u64; 25],
 }
@@ -44,3 +44,17 @@ impl XofAbsorb for KeccakState {
     }
 }
 
+
+// The expanded AES key and the Keccak state may have absorbed secret seeds
+impl Drop for Aes256CtrCtx {
+    fn drop(&mut self) {
+        self.sk_exp.zeroize();
+        self.ivw.zeroize();
+    }
+}
+
+impl Drop for KeccakState {
+    fn drop(&mut self) {
+        self.s.zeroize();
+    }
+}
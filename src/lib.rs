@@ -0,0 +1,45 @@
+//! Kyber key encapsulation converted into Rust from the C reference implementation.
+//!
+//! The typed API lives in `Kyber512`, `Kyber768` and `Kyber1024` (round-3 Kyber),
+//! `Kyber512_90s`, `Kyber768_90s` and `Kyber1024_90s` (Kyber-90s) and `MlKem512`,
+//! `MlKem768` and `MlKem1024` (FIPS 203), all of which implement the [`Kem`] trait:
+//!
+//! ```no_run
+//! use kyber::{Kem, Kyber768};
+//!
+//! # fn main() -> Result<(), kyber::KyberError> {
+//! let (pk, sk) = Kyber768::keypair()?;
+//! let (ct, ss) = Kyber768::encapsulate(&pk)?;
+//! assert!(Kyber768::decapsulate(&sk, &ct)? == ss);
+//! # Ok(())
+//! # }
+//! ```
+#[allow(non_camel_case_types)]
+pub mod enums;
+pub mod error;
+pub mod kyber;
+#[allow(non_snake_case, unused_variables, clippy::needless_return, clippy::unnecessary_cast, clippy::useless_conversion)]
+pub mod key_generation;
+#[allow(redundant_semicolons, unused_assignments, unused_must_use, unused_mut)]
+#[allow(clippy::manual_flatten, clippy::module_inception, clippy::needless_borrow, clippy::needless_return)]
+pub mod helping_functions;
+pub mod keyfile;
+#[allow(unused_mut, unused_variables)]
+#[allow(clippy::legacy_numeric_constants, clippy::needless_return, clippy::result_unit_err)]
+pub mod random_number_generation;
+pub mod sealed;
+
+pub use enums::KyberVariant;
+pub use error::KyberError;
+pub use keyfile::{EncryptedPrivateKey, KeyFile, KeyFileFormat};
+pub use kyber::kem_rng::{GlobalKyberRng, KemRng, KyberRng};
+pub use kyber::{ake, backend, bench, export, hpke, hybrid, timing, valgrind};
+pub use kyber::hpke::{Hpke, HpkeKem};
+pub use kyber::hybrid::{Hybrid, X25519Kyber768, X25519MlKem768};
+pub use kyber::backend::Backend;
+pub use kyber::symmetric_aes::symmetric_aes;
+pub use kyber::kyber_api::{
+    Ciphertext, Kem, KemBytes, KeyPair, Kyber1024, Kyber1024_90s, Kyber512, Kyber512_90s,
+    Kyber768, Kyber768_90s, MlKem1024, MlKem512, MlKem768, PreparedPublicKey, PreparedSecretKey,
+    PublicKey, SecretKey, SharedSecret, randombytes, randombytes_init,
+};
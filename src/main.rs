@@ -1,25 +1,64 @@
 
-#![allow(warnings)]
-use std::io::Write;
-use std::{fs::File,io, ptr::null};
-mod enums;
-use enums::{Kyber_Category, RNG_Category};
-use random_number_generation::{RngOutput, RngType};
-mod kyber;
-mod key_generation;
-mod helping_functions;
-mod random_number_generation;
+use std::{fs::File,io};
+use kyber::enums::{Export_Category, KyberVariant, Kyber_Category};
+use kyber::{KeyFile, KeyFileFormat, KyberError};
+use zeroize::Zeroizing;
+use kyber::helping_functions;
+use kyber::{
+    Kem, KemBytes, Kyber1024, Kyber1024_90s, Kyber512, Kyber512_90s, Kyber768, Kyber768_90s,
+    MlKem1024, MlKem512, MlKem768,
+};
 
 
+// A subcommand, run with the arguments after its name
+type Command = fn(&[String]) -> Result<(), KyberError>;
+
 // Main facade method
 use std::{env, vec};
 
+// Calls the generic operation with the Kem type matching the variant and security level
+macro_rules! with_parameter_set {
+    ($variant:expr, $level:expr, $func:ident($($arg:expr),*)) => {
+        match ($variant, $level) {
+            (KyberVariant::Round3, Some(2)) => $func::<Kyber512>($($arg),*),
+            (KyberVariant::Round3, Some(3)) => $func::<Kyber768>($($arg),*),
+            (KyberVariant::Round3, Some(5)) => $func::<Kyber1024>($($arg),*),
+            (KyberVariant::Kyber90s, Some(2)) => $func::<Kyber512_90s>($($arg),*),
+            (KyberVariant::Kyber90s, Some(3)) => $func::<Kyber768_90s>($($arg),*),
+            (KyberVariant::Kyber90s, Some(5)) => $func::<Kyber1024_90s>($($arg),*),
+            (KyberVariant::MlKem, Some(2)) => $func::<MlKem512>($($arg),*),
+            (KyberVariant::MlKem, Some(3)) => $func::<MlKem768>($($arg),*),
+            (KyberVariant::MlKem, Some(5)) => $func::<MlKem1024>($($arg),*),
+            (_, level) => Err(KyberError::UnsupportedParameterSet(format!("security level {:?}", level))),
+        }
+    };
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
     // 1. Check if any arguments are provided
     if args.len() == 1 {
-        println!("No arguments provided. Use --help or -h for usage information.");
+        exit_with_error(KyberError::Usage("no arguments provided, use --help or -h for usage information".to_string()));
+    }
+
+    // Subcommands, the flags below are the older combined interface
+    let command: Option<Command> = match args[1].as_str() {
+        "bench" => Some(bench_command),
+        "keygen" => Some(|args| keygen_command(args, false)),
+        "encaps" => Some(|args| encaps_command(args, false)),
+        "decaps" => Some(|args| decaps_command(args, false)),
+        "hybrid-keygen" => Some(|args| keygen_command(args, true)),
+        "hybrid-encaps" => Some(|args| encaps_command(args, true)),
+        "hybrid-decaps" => Some(|args| decaps_command(args, true)),
+        "seal" => Some(seal_command),
+        "open" => Some(open_command),
+        _ => None,
+    };
+    if let Some(command) = command {
+        if let Err(err) = command(&args[2..]) {
+            exit_with_error(err);
+        }
         return;
     }
 
@@ -27,11 +66,15 @@ fn main() {
     let mut mode: Option<&str> = None; // "enc" for encapsulation, "dec" for decapsulation
     let mut key_file: Option<String> = None;
     let mut use_own_key: bool = false;
-    let mut quick_encaps_decaps = 0;// 0 not set 1 => E2, 2=>E3 3=> E5     4 => D2, 5=>D3, 6=> D5
+    let mut variant: Option<KyberVariant> = None;
+    let mut timing_samples: usize = 100_000;
+    let mut count: Option<usize> = None;
+    let mut export_format: Option<Export_Category> = None;
+    let mut password_file: Option<String> = None;
+    let mut encrypt_private_key = false;
 
     // Parse arguments
     let mut i = 1;
-    let mut args_iter = args.iter().skip(1); // Skip the program name
 
     while i < args.len() {
         match args[i].as_str() {
@@ -49,24 +92,20 @@ fn main() {
                         if [2, 3, 5].contains(&sec) {
                             security_level = Some(sec);
                         } else {
-                            println!("Invalid security level. Use 2, 3, or 5.");
-                            return;
+                            exit_with_error(KyberError::Usage("invalid security level, use 2, 3, or 5".to_string()));
                         }
                     } else {
-                        println!("Invalid security level value. It must be a number.");
-                        return;
+                        exit_with_error(KyberError::Usage("invalid security level value, it must be a number".to_string()));
                     }
                     i += 1; // Skip the next argument as it is the value for --sec
                 } else {
-                    println!("Missing value for --sec or -s.");
-                    return;
+                    exit_with_error(KyberError::Usage("missing value for --sec or -s".to_string()));
                 }
             }
              // Handle quick use modes
              _ if args[i].starts_with("-E") || args[i].starts_with("-D") => {
                 if mode.is_some() {
-                    println!("Both encapsulation (-E<number>) and decapsulation (-D<number>) cannot be specified together. Or with --enc, -e / --dec, -d");
-                    return;
+                    exit_with_error(KyberError::Usage("both encapsulation (-E<number>) and decapsulation (-D<number>) cannot be specified together, or with --enc, -e / --dec, -d".to_string()));
                 }
             
                 let (prefix, num_str) = args[i].split_at(2); // Split into "-E"/"-D" and the number
@@ -91,8 +130,7 @@ fn main() {
                                     if let Some(file_arg) = args_iter.next() {
                                         key_file = Some(file_arg.to_string());
                                     } else {
-                                        println!("Expected a file name after {}.", arg);
-                                        return;
+                                        exit_with_error(KyberError::Usage(format!("expected a file name after {}", arg)));
                                     }
                                     break;
                                 }
@@ -100,78 +138,138 @@ fn main() {
                             }
                         }
                     } else {
-                        println!("Invalid number for {}. Allowed values are 2, 3, or 5.", prefix);
-                        return;
+                        exit_with_error(KyberError::Usage(format!("invalid number for {}, allowed values are 2, 3, or 5", prefix)));
                     }
                 } else {
-                    println!("Invalid argument: {}. Expected format: -E<number> or -D<number>.", args[i]);
-                    return;
+                    exit_with_error(KyberError::Usage(format!("invalid argument: {}, expected format: -E<number> or -D<number>", args[i])));
                 }
             }
             "--enc" | "-e" => {
                 if mode.is_some() {
-                    println!("Both encapsulation (--enc) and decapsulation (--dec) cannot be specified together.");
-                    return;
+                    exit_with_error(KyberError::Usage("both encapsulation (--enc) and decapsulation (--dec) cannot be specified together".to_string()));
                 }
                 mode = Some("enc");
             }
             "--dec" | "-d" => {
                 if mode.is_some() {
-                    println!("Both encapsulation (--enc) and decapsulation (--dec) cannot be specified together.");
-                    return;
+                    exit_with_error(KyberError::Usage("both encapsulation (--enc) and decapsulation (--dec) cannot be specified together".to_string()));
                 }
                 mode = Some("dec");
             }
+            "--validate" => {
+                if mode.is_some() {
+                    exit_with_error(KyberError::Usage("--validate cannot be combined with encapsulation or decapsulation".to_string()));
+                }
+                mode = Some("validate");
+            }
+            "--export" => {
+                if mode.is_some() {
+                    exit_with_error(KyberError::Usage("--export cannot be combined with another mode".to_string()));
+                }
+                match args.get(i + 1).map(|arg| export_format_from_name(arg)) {
+                    Some(Some(format)) => export_format = Some(format),
+                    Some(None) => {
+                        exit_with_error(KyberError::Usage("invalid export format, use binary, hex, base64, pem, json or protobuf".to_string()));
+                    }
+                    None => {
+                        exit_with_error(KyberError::Usage("missing value for --export".to_string()));
+                    }
+                }
+                mode = Some("export");
+                i += 1;
+            }
+            "--timing" => {
+                if mode.is_some() {
+                    exit_with_error(KyberError::Usage("--timing cannot be combined with another mode".to_string()));
+                }
+                mode = Some("timing");
+                // The sample count is optional
+                if let Some(count) = args.get(i + 1).and_then(|arg| arg.parse::<usize>().ok()) {
+                    timing_samples = count;
+                    i += 1;
+                }
+            }
+            "--ct-check" => {
+                if mode.is_some() {
+                    exit_with_error(KyberError::Usage("--ct-check cannot be combined with another mode".to_string()));
+                }
+                mode = Some("ct-check");
+            }
+            "--count" => {
+                match args.get(i + 1).map(|arg| arg.parse::<usize>()) {
+                    Some(Ok(n)) if n > 0 => count = Some(n),
+                    Some(_) => {
+                        exit_with_error(KyberError::Usage("invalid value for --count, it must be a positive number".to_string()));
+                    }
+                    None => {
+                        exit_with_error(KyberError::Usage("missing value for --count".to_string()));
+                    }
+                }
+                i += 1;
+            }
             "--key-file" | "-f" => {
                 if let Some(file_arg) = args.get(i + 1) {
                     key_file = Some(file_arg.to_string());
                     i += 1; // Skip the next argument as it is the value for --key-file
                 } else {
-                    println!("Missing value for --key-file or -f.");
-                    return;
+                    exit_with_error(KyberError::Usage("missing value for --key-file or -f".to_string()));
                 }
             }
+            "--password-file" => {
+                if let Some(file_arg) = args.get(i + 1) {
+                    password_file = Some(file_arg.to_string());
+                    i += 1;
+                } else {
+                    exit_with_error(KyberError::Usage("missing value for --password-file".to_string()));
+                }
+            }
+            "--encrypt" => encrypt_private_key = true,
             "--own-key" | "-o" => {
             if key_file.is_none() {
                 // Check if --key-file was provided
-                println!("Error: --own-key or -o requires --key-file or -f to be specified first.");
-                return;
+                exit_with_error(KyberError::Usage("--own-key or -o requires --key-file or -f to be specified first".to_string()));
             }
             else {
                 use_own_key = true;
             }
 
         }
-            other => println!("Unrecognized argument: {}", other),
+            "--ml-kem" | "-m" => {
+                if variant.is_some() {
+                    exit_with_error(KyberError::Usage("only one of --ml-kem and --90s can be specified".to_string()));
+                }
+                variant = Some(KyberVariant::MlKem);
+            }
+            "--90s" => {
+                if variant.is_some() {
+                    exit_with_error(KyberError::Usage("only one of --ml-kem and --90s can be specified".to_string()));
+                }
+                variant = Some(KyberVariant::Kyber90s);
+            }
+            other => exit_with_error(KyberError::Usage(format!("unrecognized argument: {}", other))),
         }
         i += 1;
     }
 
 
     let file_path_clone = key_file.clone();
+    let reads_key_file = mode == Some("dec") || mode == Some("validate") || mode == Some("export") || use_own_key;
 
-    // 2. Check if --sec or -s is provided
-    if security_level.is_none() {
-        println!("Error: Security level (--sec or -s) is required.");
-        return;
+    // 2. Check if --sec or -s is provided, an existing key file records it
+    if security_level.is_none() && !reads_key_file {
+        exit_with_error(KyberError::Usage("security level (--sec or -s) is required".to_string()));
     }
-    let mut strength: u32 = 0;
-    let mut kyber =  match security_level
-        {
-
-            Some(2) => kyber::kyber::Kyber::create(2),
-            Some(3) => kyber::kyber::Kyber::create(3), 
-            Some(5) => kyber::kyber::Kyber::create(4),
-            Some(_) => panic!("Invalid security level! Quitting!"),
-            None => panic!("No security level! Quitting!")
-        };
-
-    kyber::set_env_vars(kyber.params.clone());
-
     // 3. Check if either --enc or --dec is specified
     if mode.is_none() {
-        println!("Error: You must specify either --enc or --dec.");
-        return;
+        exit_with_error(KyberError::Usage("you must specify either --enc, --dec, --validate, --export, --timing or --ct-check".to_string()));
+    }
+
+    if count.is_some() && (mode != Some("enc") || use_own_key) {
+        exit_with_error(KyberError::Usage("--count only applies to encapsulation with generated keys".to_string()));
+    }
+
+    if encrypt_private_key && (mode != Some("enc") || use_own_key) {
+        exit_with_error(KyberError::Usage("--encrypt only applies to encapsulation with generated keys".to_string()));
     }
 
     // 4. Handle mode-specific requirements
@@ -181,310 +279,804 @@ fn main() {
             if let Some(file) = key_file {
                 println!("Key file specified: {}", file);
             } else {
-                panic!("Keyfile required! Quitting!")
+                exit_with_error(KyberError::Usage("key file (--key-file or -f) is required for encapsulation".to_string()));
             }
         }
         "dec" => {
             println!("Decapsulation mode selected.");
             if key_file.is_none() {
-                println!("Error: Key file (--key-file or -f) is required for decapsulation.");
-                return;
+                exit_with_error(KyberError::Usage("key file (--key-file or -f) is required for decapsulation".to_string()));
             }
             println!("Key file specified: {}", key_file.unwrap());
         }
+        "validate" => {
+            println!("Key validation mode selected.");
+            if key_file.is_none() {
+                exit_with_error(KyberError::Usage("key file (--key-file or -f) is required for validation".to_string()));
+            }
+            println!("Key file specified: {}", key_file.unwrap());
+        }
+        "export" => {
+            println!("Export mode selected.");
+            if key_file.is_none() {
+                exit_with_error(KyberError::Usage("key file (--key-file or -f) is required for export".to_string()));
+            }
+            println!("Key file specified: {}", key_file.unwrap());
+        }
+        "timing" => println!("Decapsulation timing test selected."),
+        "ct-check" => println!("Valgrind constant-time check selected."),
         _ => unreachable!(),
     }
 
+    // An existing key file records which parameter set its values belong to,
+    // --sec, --ml-kem and --90s only decide the parameter set of new keys
+    let mut key_file_contents = None;
+    let mut split_files = false;
+    if reads_key_file {
+        let path = file_path_clone.as_ref().unwrap();
+        let read = if mode == Some("dec") { read_dec_key_files(path, variant) } else { read_key_file(path, variant).map(|(contents, format)| (contents, format, false)) };
+        let (mut contents, format) = match read {
+            Ok((contents, format, split)) => {
+                split_files = split;
+                (contents, format)
+            }
+            Err(err) => exit_with_error(err),
+        };
+        if format == KeyFileFormat::Legacy && mode == Some("dec") {
+            println!("         Decapsulation rewrites it in the versioned format.");
+        }
+        if variant.is_some() && variant != Some(contents.variant) {
+            println!("Warning: key file holds a {} key, ignoring the requested variant.", contents.variant.name());
+        }
+        if security_level.is_some() && security_level != Some(contents.security_level) {
+            println!("Warning: key file holds a {} key, ignoring the requested security level.", contents.parameter_set_name());
+        }
+        variant = Some(contents.variant);
+        security_level = Some(contents.security_level);
+
+        // Only the modes using the private key ask for its password
+        if contents.is_encrypted() && mode != Some("enc") {
+            let result = get_password(password_file.as_deref(), false).and_then(|password| contents.decrypt_private_key(password.as_bytes()));
+            if let Err(err) = result {
+                exit_with_error(err);
+            }
+        }
+        key_file_contents = Some(contents);
+    }
+
+    // New private keys are encrypted when a password is given
+    let mut new_key_password = None;
+    if mode == Some("enc") && !use_own_key && (encrypt_private_key || password_file.is_some()) {
+        match get_password(password_file.as_deref(), true) {
+            Ok(password) => new_key_password = Some(password),
+            Err(err) => exit_with_error(err),
+        }
+    }
+    let new_key_password = new_key_password.as_ref().map(|password| password.as_bytes());
+    let variant = variant.unwrap_or(KyberVariant::Round3);
+
     println!("Security level: {}", security_level.unwrap());
+    println!("Variant: {}", variant.name());
     println!("Performing requested operation:");
 
     
-    if mode == Some("enc") {
-        seed_rng();
-        encapsulate_short(use_own_key, file_path_clone, security_level);
+    // The audit modes exit with 1 when they find a leak
+    let audit = match mode {
+        Some("timing") => Some(with_parameter_set!(variant, security_level, timing_short(timing_samples))),
+        Some("ct-check") => Some(with_parameter_set!(variant, security_level, ct_check_short(&args[1..]))),
+        _ => None,
+    };
+    if let Some(result) = audit {
+        match result {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(err) => exit_with_error(err),
+        }
     }
-    
-    else if mode == Some("dec")
-    {
-        decapsulate_short( file_path_clone, security_level);
+
+    let result = match mode {
+        Some("enc") if count.is_some() => with_parameter_set!(variant, security_level, encapsulate_batch_short(count.unwrap(), new_key_password, file_path_clone)),
+        Some("enc") if use_own_key => with_parameter_set!(variant, security_level, encapsulate_short(key_file_contents.as_ref().unwrap(), file_path_clone)),
+        Some("enc") => with_parameter_set!(variant, security_level, keygen_encaps_short(new_key_password, file_path_clone)),
+        Some("validate") => with_parameter_set!(variant, security_level, validate_short(key_file_contents.as_ref().unwrap())),
+        Some("export") => with_parameter_set!(variant, security_level, export_short(export_format.unwrap(), key_file_contents.as_ref().unwrap(), file_path_clone)),
+        _ => with_parameter_set!(variant, security_level, decapsulate_short(key_file_contents.as_ref().unwrap(), if split_files { None } else { file_path_clone })),
+    };
+
+    if let Err(err) = result {
+        exit_with_error(err);
     }
-    else {
-        panic!("Unable to encrypt!");
+}
+
+// Each error kind has its own exit code, see KyberError::exit_code
+fn exit_with_error(err: KyberError) -> ! {
+    eprintln!("Error: {}", err);
+    std::process::exit(err.exit_code());
+}
+
+// Reads a key file of either format, warning about the legacy one. `variant` is
+// the one requested on the command line, assumed for legacy files without one
+fn read_key_file(path: &str, variant: Option<KyberVariant>) -> Result<(KeyFile, KeyFileFormat), KyberError> {
+    // Key files written before ML-KEM support have no VARIANT line and hold round-3 keys
+    let (contents, format) = KeyFile::read(path, variant.unwrap_or(KyberVariant::Round3))?;
+    if format == KeyFileFormat::Legacy {
+        println!("Warning: {} is in the legacy key file format, which is deprecated and will stop being read in a future version.", path.trim());
     }
+    Ok((contents, format))
 }
-fn seed_rng()
+
+// The key file for --dec. Given the <file> that --enc wrote <file>.pk, .sk,
+// .ct and .ss for, it reads the private key, ciphertext and shared secret from
+// those, true in that case
+fn read_dec_key_files(path: &str, variant: Option<KyberVariant>) -> Result<(KeyFile, KeyFileFormat, bool), KyberError> {
+    let base = path.trim();
+    let sk_path = format!("{}.sk", base);
+    if !std::path::Path::new(&sk_path).exists() {
+        let (contents, format) = read_key_file(path, variant)?;
+        return Ok((contents, format, false));
+    }
+    if std::path::Path::new(base).exists() {
+        let (contents, format) = read_key_file(path, variant)?;
+        if contents.ciphertext.is_some() {
+            return Ok((contents, format, false));
+        }
+    }
+
+    println!("Reading the private key from {} and the ciphertext from {}.ct.", sk_path, base);
+    let (mut contents, format) = read_key_file(&sk_path, variant)?;
+    let (ct_file, _) = read_key_file(&format!("{}.ct", base), variant)?;
+    if ct_file.parameter_set_name() != contents.parameter_set_name() {
+        return Err(KyberError::KeyFile(format!("the ciphertext is for {}, the private key for {}", ct_file.parameter_set_name(), contents.parameter_set_name())));
+    }
+    contents.ciphertext = ct_file.ciphertext;
+    let ss_path = format!("{}.ss", base);
+    if std::path::Path::new(&ss_path).exists() {
+        contents.shared_secret = read_key_file(&ss_path, variant)?.0.shared_secret;
+    }
+    Ok((contents, format, true))
+}
+
+// The password from --password-file, otherwise asked for on the terminal,
+// twice for a new password
+fn get_password(password_file: Option<&str>, new: bool) -> Result<Zeroizing<String>, KyberError> {
+    match password_file {
+        Some(path) => helping_functions::helping_functions::read_password_file(path),
+        None if new => helping_functions::helping_functions::read_password("Enter a password for the private key: ", true),
+        None => helping_functions::helping_functions::read_password("Enter the password of the private key: ", false),
+    }
+}
+
+// Rewrites the key file at `file_path_clone` with the derived shared secret,
+// given None for the separate files of --enc, which are left as they are
+fn decapsulate_short<K: Kem>(key_file: &KeyFile, file_path_clone:Option<String>) -> Result<(), KyberError>
 {
-    let mut rng = crate::kyber ::GLOBAL_RANDOM.lock().unwrap();
-    match random_number_generation::generate_random_vector(RngType::Os, (0u8, 255u8), 48) {
-        RngOutput::Vector(random_values) => {
-            rng.randombytes_init(random_values, None, 256);
+    key_file.check_parameter_set::<K>()?;
+
+    let priv_key = key_file.private_key.as_ref().ok_or_else(|| KyberError::MissingKeyword("private key".to_string()))?;
+    let cipher_text = key_file.ciphertext.as_ref().ok_or_else(|| KyberError::MissingKeyword("ciphertext".to_string()))?;
+    if key_file.public_key.is_none() {
+        println!("No public key");
+    }
+    if key_file.shared_secret.is_none() {
+        println!("No shared secret, will not compare");
+    }
+
+    // Check the values before the file is rewritten so a bad file is left intact
+    let sk = K::SecretKey::from_bytes(priv_key)?;
+    let ct = K::Ciphertext::from_bytes(cipher_text)?;
+
+    // Perform decapsulation
+    let new_ss = K::decapsulate(&sk, &ct)?;
+
+    println!("Decapsulation Completed!");
+    if file_path_clone.is_some() {
+        println!("Please see file for keys.");
+    }
+    println!("Ciphertext: {}", hex::encode(cipher_text));
+    println!("Shared Secret: {}", hex::encode(new_ss.as_bytes()));
+    if let Some(share_secret) = &key_file.shared_secret {
+        if share_secret.as_slice() == new_ss.as_bytes() {
+            println!("Both shared secrets match!");
         }
-        _ => {
-            panic!("Could not seed RNG!");
+        else {
+            eprintln!("BOTH SHARED SECRETS DO NOT MATCH!");
         }
     }
+
+    let file_path = match file_path_clone {
+        Some(file_path) => file_path,
+        None => return Ok(()),
+    };
+
+    // Rewrite the file with the derived shared secret, in the current format
+    let mut out = KeyFile::for_kem::<K>();
+    out.private_key = Some(priv_key.clone());
+    out.encrypted_private_key = key_file.encrypted_private_key.clone();
+    out.public_key = key_file.public_key.clone();
+    out.ciphertext = Some(cipher_text.clone());
+    out.shared_secret = Some(Zeroizing::new(new_ss.as_bytes().to_vec()));
+    out.write(&file_path)
 }
-fn decapsulate_short(file_path_clone:Option<String>, security_level:Option<u8>)
+
+// Encapsulates to the public key in the existing key file
+fn encapsulate_short<K: Kem>(key_file: &KeyFile, file_path_clone:Option<String>) -> Result<(), KyberError>
+{
+    let file_path = file_path_clone.as_ref().unwrap();
+    let mut out = KeyFile::for_kem::<K>();
+
+    key_file.check_parameter_set::<K>()?;
+    let pk = key_file.public_key.as_ref().ok_or_else(|| KyberError::MissingKeyword("public key".to_string()))?;
+    if pk.iter().all(|&x| x == 0) {
+        return Err(KyberError::InvalidKey("public key is empty or all zero"));
+    }
+    let public_key = K::PublicKey::from_bytes(pk)?;
+
+    // Perform encapsulation
+    let (ciphertext, ss) = K::encapsulate(&public_key)?;
+
+    println!("Encapsulation Completed!");
+    println!("Please see file for keys.");
+    println!("Ciphertext: {}", hex::encode(ciphertext.as_bytes()));
+    println!("Shared Secret: {}", hex::encode(ss.as_bytes()));
+
+    out.public_key = Some(public_key.as_bytes().to_vec());
+    out.ciphertext = Some(ciphertext.as_bytes().to_vec());
+    out.shared_secret = Some(Zeroizing::new(ss.as_bytes().to_vec()));
+    out.write(file_path)
+}
+
+// --enc without --own-key is keygen followed by encaps, the private key
+// encrypted under `password` if given
+fn keygen_encaps_short<K: Kem>(password: Option<&[u8]>, file_path_clone:Option<String>) -> Result<(), KyberError>
+{
+    let file_path = file_path_clone.as_ref().unwrap().trim();
+    let (pk, sk) = K::keypair()?;
+    println!("Key pair generated successfully.");
+    let (ciphertext, ss) = K::encapsulate(&pk)?;
+    write_generated_files::<K>(file_path, &pk, &sk, &ciphertext, &ss, password)?;
+
+    println!("Encapsulation Completed!");
+    println!("Public key written to {}.pk, ciphertext to {}.ct and shared secret to {}.ss.", file_path, file_path, file_path);
+    println!("Private key written to {}.sk, keep it secret.", file_path);
+    println!("Decapsulate with: kyber decaps --sk {}.sk --ct {}.ct", file_path, file_path);
+    Ok(())
+}
+
+// Writes a generated key pair and the encapsulation to it as <base>.pk,
+// <base>.sk, <base>.ct and <base>.ss, so the private key never shares a file
+// with the ciphertext the sender passes on
+fn write_generated_files<K: Kem>(base: &str, pk: &K::PublicKey, sk: &K::SecretKey, ciphertext: &K::Ciphertext, ss: &kyber::SharedSecret, password: Option<&[u8]>) -> Result<(), KyberError>
+{
+    let mut sk_file = KeyFile::for_kem::<K>();
+    sk_file.private_key = Some(Zeroizing::new(sk.as_bytes().to_vec()));
+    if let Some(password) = password {
+        sk_file.encrypt_private_key(password)?;
+    }
+    sk_file.write(&format!("{}.sk", base))?;
+
+    let mut pk_file = KeyFile::for_kem::<K>();
+    pk_file.public_key = Some(pk.as_bytes().to_vec());
+    pk_file.write(&format!("{}.pk", base))?;
+
+    let mut ct_file = KeyFile::for_kem::<K>();
+    ct_file.ciphertext = Some(ciphertext.as_bytes().to_vec());
+    ct_file.write(&format!("{}.ct", base))?;
+
+    let mut ss_file = KeyFile::for_kem::<K>();
+    ss_file.shared_secret = Some(Zeroizing::new(ss.as_bytes().to_vec()));
+    ss_file.write(&format!("{}.ss", base))
+}
+
+// Generates `count` key pairs and encapsulates to each in one batch, writing
+// key pair i to "<file>.i.pk", ".sk", ".ct" and ".ss" as a single encapsulation does
+fn encapsulate_batch_short<K: Kem>(count: usize, password: Option<&[u8]>, file_path_clone:Option<String>) -> Result<(), KyberError>
 {
-    let mut file: Option<File> = None;
     let file_path = file_path_clone.as_ref().unwrap();
     let file_path_trimmed = file_path.trim();
 
-    
-        // Attempt to open the file
-        file = Some(File::open(file_path_trimmed).unwrap_or_else(|err| {
-            panic!("Failed to open file at '{}': {}", file_path_trimmed, err);
-        }));
-
-        let mut doskippub = false;
-        let mut doskipss = false;
-
-        // Read the public key from the file
-        let mut public_key = None; // Initialize public_key as None
-        match helping_functions::helping_functions::read_hex_from_file_after_keyword(
-            &mut file.as_mut().unwrap(),
-            "public key:",)
-            {
-                Ok(value) => {
-                    public_key = Some(value); 
-                }
-                Err(err) => {
-                    println!("No public key");
-                    doskippub = true; 
-                }
-            }
-        
-        let mut private_key = Some(
-            helping_functions::helping_functions::read_hex_from_file_after_keyword(
-                &mut file.as_mut().unwrap(),
-                "private key:",
-            )
-            .unwrap_or_else(|err| panic!("No private key, cannot continue!: {}", err)),
-        );
-
-        let mut ss = None; // Initialize public_key as None
-        match helping_functions::helping_functions::read_hex_from_file_after_keyword(
-            &mut file.as_mut().unwrap(),
-            "shared secret:",)
-            {
-                Ok(value) => {
-                    ss = Some(value); 
-                }
-                Err(err) => {
-                    println!("No shared secret, will not compare");
-                    doskipss = true; 
-                }
+    let keys = K::keypair_batch(count)?;
+    let public_keys: Vec<K::PublicKey> = keys.iter()
+        .map(|(pk, _)| K::PublicKey::from_bytes(pk.as_bytes()))
+        .collect::<Result<_, _>>()?;
+    let encapsulated = K::encapsulate_batch(&public_keys)?;
+    println!("{} key pairs generated successfully.", count);
+
+    for (index, ((pk, sk), (ciphertext, ss))) in keys.iter().zip(&encapsulated).enumerate() {
+        write_generated_files::<K>(&format!("{}.{}", file_path_trimmed, index + 1), pk, sk, ciphertext, ss, password)?;
+    }
+
+    println!("Encapsulation Completed!");
+    println!("Keys written to {}.1.pk, .sk, .ct and .ss to {}.{}.pk, .sk, .ct and .ss", file_path_trimmed, file_path_trimmed, count);
+    Ok(())
+}
+
+// Runs the FIPS 203 input checks on whichever keys the file holds and reports each one
+fn validate_short<K: Kem>(key_file: &KeyFile) -> Result<(), KyberError>
+{
+    key_file.check_parameter_set::<K>()?;
+    let pub_key = key_file.public_key.as_ref();
+    let priv_key = key_file.private_key.as_ref();
+
+    if pub_key.is_none() && priv_key.is_none() {
+        return Err(KyberError::MissingKeyword("public key or private key".to_string()));
+    }
+
+    let mut first_failure = None;
+
+    if let Some(pk) = pub_key {
+        let result = K::PublicKey::from_bytes(pk).and_then(|pk| K::validate_public_key(&pk));
+        match result {
+            Ok(()) => println!("Public key: passed length and modulus checks."),
+            Err(err) => {
+                println!("Public key: FAILED, {}", err);
+                first_failure = first_failure.or(Some(err));
             }
-        let mut ciphertext = Some(
-            helping_functions::helping_functions::read_hex_from_file_after_keyword(
-                &mut file.as_mut().unwrap(),
-                "ciphertext:",
-            )
-            .unwrap_or_else(|err| panic!("No ciphertext, cannot continue!: {}", err)),
-        );
-
-        let mut pub_key = vec![0u8;1];
-        let mut share_secret = vec![0u8;1];
-        let mut priv_key = vec![0u8;1];
-        let mut cipher_text= vec![0u8;1];
-        if public_key.is_none() || doskippub
-        {
-            pub_key = vec![0u8;1];
-        }
-        else {
-            pub_key = public_key.unwrap();
         }
+    }
 
-        if !private_key.is_none()
-        {
-            priv_key = private_key.as_ref().unwrap().to_vec();
-            if priv_key.is_empty() || priv_key.iter().all(|&x| x == 0) {
-                panic!("Invalid private key! Quitting!.");
+    if let Some(sk) = priv_key {
+        let result = K::SecretKey::from_bytes(sk).and_then(|sk| K::validate_secret_key(&sk));
+        match result {
+            Ok(()) => println!("Private key: passed length and hash checks."),
+            Err(err) => {
+                println!("Private key: FAILED, {}", err);
+                first_failure = first_failure.or(Some(err));
             }
-
         }
-        else {
-            panic!("Private key was empty! Quitting!")
-        }
-        if ss.is_none() || doskipss
-        {
-            share_secret = vec![0u8;1];
+    }
+
+    match first_failure {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+fn export_format_from_name(name: &str) -> Option<Export_Category> {
+    match name.to_ascii_lowercase().as_str() {
+        "binary" | "bin" => Some(Export_Category::Binary),
+        "hex" => Some(Export_Category::Hexadecimal),
+        "base64" => Some(Export_Category::Base64),
+        "pem" => Some(Export_Category::PEM),
+        "json" => Some(Export_Category::JSON),
+        "protobuf" => Some(Export_Category::Protobuf),
+        _ => None,
+    }
+}
+
+// Writes each value found in the key file to <file path>.pub.<ext>, .key.<ext> and .ct.<ext>
+fn export_short<K: Kem>(format: Export_Category, key_file: &KeyFile, file_path_clone:Option<String>) -> Result<(), KyberError>
+{
+    key_file.check_parameter_set::<K>()?;
+    let file_path = file_path_clone.as_ref().unwrap().trim().to_string();
+    let extension = match format {
+        Export_Category::Binary => "bin",
+        Export_Category::Hexadecimal => "hex",
+        Export_Category::Base64 => "b64",
+        Export_Category::PEM => "pem",
+        Export_Category::JSON => "json",
+        _ => "pb",
+    };
+
+    let mut written = 0;
+    if let Some(pk) = &key_file.public_key {
+        let data = kyber::export::export_public_key::<K>(&K::PublicKey::from_bytes(pk)?, format)?;
+        std::fs::write(format!("{}.pub.{}", file_path, extension), data)?;
+        println!("Public key written to {}.pub.{}", file_path, extension);
+        written += 1;
+    }
+    if let Some(sk) = &key_file.private_key {
+        let data = kyber::export::export_secret_key::<K>(&K::SecretKey::from_bytes(sk)?, format)?;
+        std::fs::write(format!("{}.key.{}", file_path, extension), &*data)?;
+        println!("Private key written to {}.key.{}", file_path, extension);
+        written += 1;
+    }
+    if let Some(ct) = &key_file.ciphertext {
+        let data = kyber::export::export_ciphertext::<K>(&K::Ciphertext::from_bytes(ct)?, format)?;
+        std::fs::write(format!("{}.ct.{}", file_path, extension), data)?;
+        println!("Ciphertext written to {}.ct.{}", file_path, extension);
+        written += 1;
+    }
+
+    if written == 0 {
+        return Err(KyberError::MissingKeyword("public key, private key or ciphertext".to_string()));
+    }
+    Ok(())
+}
+
+// Ok(false) when the t-test finds the two ciphertext classes distinguishable
+fn timing_short<K: Kem>(samples: usize) -> Result<bool, KyberError>
+{
+    println!("Timing {} decapsulations, build with --release for meaningful numbers...", samples);
+    let report = kyber::timing::decapsulation_leakage::<K>(samples)?;
+    println!("{}", report);
+    Ok(!report.leak_detected())
+}
+
+// Options of keygen, encaps, decaps, seal and open, each a key file path or a
+// parameter set, and the input and output files of seal and open
+#[derive(Default)]
+struct FileOptions {
+    paths: Vec<String>,
+    pk: Option<String>,
+    // Every --pk, for seal to several recipients
+    pks: Vec<String>,
+    sk: Option<String>,
+    ct: Option<String>,
+    ss: Option<String>,
+    password_file: Option<String>,
+    encrypt: bool,
+    security_level: Option<u8>,
+    variant: Option<KyberVariant>,
+}
+
+// Parses the options in `allowed`, a usage error for anything else
+fn parse_file_options(args: &[String], allowed: &[&str]) -> Result<FileOptions, KyberError>
+{
+    let mut options = FileOptions::default();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i].as_str();
+        if !arg.starts_with('-') && allowed.contains(&"<file>") {
+            options.paths.push(arg.to_string());
+            i += 1;
+            continue;
         }
-        else {
-            share_secret = ss.clone().unwrap();
+        if !allowed.contains(&arg) {
+            return Err(KyberError::Usage(format!("unrecognized argument: {}", arg)));
         }
-        if !ciphertext.is_none()
-        {
-            cipher_text = ciphertext.as_ref().unwrap().to_vec();
-            if cipher_text.is_empty() || cipher_text.iter().all(|&x| x == 0) {
-                println!("Invalid public key.");
+        match arg {
+            "--encrypt" => options.encrypt = true,
+            "--ml-kem" | "-m" | "--90s" => {
+                if options.variant.is_some() {
+                    return Err(KyberError::Usage("only one of --ml-kem and --90s can be specified".to_string()));
+                }
+                options.variant = Some(if arg == "--90s" { KyberVariant::Kyber90s } else { KyberVariant::MlKem });
+            }
+            _ => {
+                let value = args.get(i + 1).ok_or_else(|| KyberError::Usage(format!("missing value for {}", arg)))?.to_string();
+                match arg {
+                    "--sec" | "-s" => match value.parse::<u8>() {
+                        Ok(sec) if [2, 3, 5].contains(&sec) => options.security_level = Some(sec),
+                        _ => return Err(KyberError::Usage("invalid security level, use 2, 3 or 5".to_string())),
+                    },
+                    "--pk" => {
+                        options.pks.push(value.clone());
+                        options.pk = Some(value);
+                    }
+                    "--sk" => options.sk = Some(value),
+                    "--ct" => options.ct = Some(value),
+                    "--ss" => options.ss = Some(value),
+                    _ => options.password_file = Some(value),
+                }
+                i += 1;
             }
-
-        }
-        else {
-            panic!("Ciphertext was empty! Quitting!");
         }
+        i += 1;
+    }
+    Ok(options)
+}
 
-    // Open file and truncate it for writing
-    file = Some(File::create(file_path_trimmed).unwrap_or_else(|err| {
-        panic!("Failed to create/truncate file at '{}': {}", file_path_trimmed, err);
-    }));
-    let mut new_ss = vec![0u8; 32];
-    let file = file.as_mut().unwrap();
-    // Write keys to file
-    writeln!(file, "PRIVATE KEY: '{}'", hex::encode(&priv_key))
-        .expect("Failed to write private key to file");
-    writeln!(file, "PUBLIC KEY: '{}'", hex::encode(&pub_key))
-        .expect("Failed to write public key to file");
+// An empty key file for the values of `K`, alone or with X25519
+fn new_key_file<K: Kem>(hybrid: bool) -> KeyFile {
+    if hybrid { KeyFile::for_hybrid::<K>() } else { KeyFile::for_kem::<K>() }
+}
 
-    // Perform decapsulation
+fn check_key_file<K: Kem>(key_file: &KeyFile, hybrid: bool) -> Result<(), KyberError> {
+    if hybrid { key_file.check_hybrid_parameter_set::<K>() } else { key_file.check_parameter_set::<K>() }
+}
 
-    if kyber::kem::kem::crypto_kem_dec(&mut new_ss,  &mut cipher_text, &mut priv_key).is_ok() {
-        println!("Decapsulation Completed!");
-        println!("Please see file for keys.");
-        println!("Ciphertext: {}", hex::encode(&cipher_text));
-        println!("Shared Secret: {}", hex::encode(&new_ss));
-        if share_secret != vec![0u8;1]
-        {
-           
-            if share_secret == new_ss
-            {
-                println!("Both shared secrets match!");
-            }
-            else {
-                eprintln!("BOTH SHARED SECRETS DO NOT MATCH!");
-            }
+// kyber keygen --pk <file> --sk <file> -s <level> [--ml-kem | --90s] [--encrypt | --password-file <file>]
+// kyber hybrid-keygen takes the same options, for X25519 with Kyber768 or ML-KEM-768
+fn keygen_command(args: &[String], hybrid: bool) -> Result<(), KyberError>
+{
+    let allowed = ["--pk", "--sk", "--sec", "-s", "--ml-kem", "-m", "--90s", "--encrypt", "--password-file"];
+    let options = parse_file_options(args, &allowed)?;
+    let (pk_path, sk_path) = match (&options.pk, &options.sk) {
+        (Some(pk), Some(sk)) if pk.trim() != sk.trim() => (pk, sk),
+        (Some(_), Some(_)) => return Err(KyberError::Usage("--pk and --sk must be different files".to_string())),
+        _ => return Err(KyberError::Usage("keygen needs --pk <file> and --sk <file>".to_string())),
+    };
+    if hybrid {
+        if options.security_level.unwrap_or(3) != 3 || options.variant == Some(KyberVariant::Kyber90s) {
+            return Err(KyberError::Usage("hybrid-keygen pairs X25519 with Kyber768 or ML-KEM-768 only".to_string()));
         }
+    } else if options.security_level.is_none() {
+        return Err(KyberError::Usage("security level (--sec or -s) is required".to_string()));
+    }
+    let security_level = options.security_level.or(Some(3));
 
-        writeln!(file, "Ciphertext: '{}'", hex::encode(&cipher_text))
-            .expect("Failed to write ciphertext to file");
-        writeln!(file, "Shared Secret: '{}'", hex::encode(&new_ss))
-            .expect("Failed to write shared secret to file");
+    let password = if options.encrypt || options.password_file.is_some() {
+        Some(get_password(options.password_file.as_deref(), true)?)
     } else {
-        panic!("Encapsulation Failed");
+        None
+    };
+    let password = password.as_ref().map(|password| password.as_bytes());
+    with_parameter_set!(options.variant.unwrap_or(KyberVariant::Round3), security_level, keygen_short(pk_path, sk_path, password, hybrid))
+}
+
+// Writes the public key and the private key, encrypted under `password` if
+// given, to separate files
+fn keygen_short<K: Kem>(pk_path: &str, sk_path: &str, password: Option<&[u8]>, hybrid: bool) -> Result<(), KyberError>
+{
+    let (pk, sk) = if hybrid {
+        let (pk, sk) = kyber::Hybrid::<K>::keypair()?;
+        (pk.to_bytes(), sk.to_bytes())
+    } else {
+        let (pk, sk) = K::keypair()?;
+        (pk.as_bytes().to_vec(), Zeroizing::new(sk.as_bytes().to_vec()))
+    };
+
+    let mut sk_file = new_key_file::<K>(hybrid);
+    sk_file.private_key = Some(sk);
+    if let Some(password) = password {
+        sk_file.encrypt_private_key(password)?;
     }
+    sk_file.write(sk_path)?;
+
+    let mut pk_file = new_key_file::<K>(hybrid);
+    pk_file.public_key = Some(pk);
+    pk_file.write(pk_path)?;
+
+    println!("{} key pair generated.", pk_file.parameter_set_name());
+    println!("Public key written to {}, send it to the sender.", pk_path.trim());
+    println!("Private key written to {}, keep it secret.", sk_path.trim());
+    Ok(())
 }
 
-fn encapsulate_short(use_own_key:bool, file_path_clone:Option<String>, security_level:Option<u8>)
+// kyber encaps --pk <file> --ct <file> --ss <file>, or hybrid-encaps for a hybrid key
+fn encaps_command(args: &[String], hybrid: bool) -> Result<(), KyberError>
 {
-    let mut public_key = None;
-    let mut file: Option<File> = None;
-    let file_path = file_path_clone.as_ref().unwrap();
-    let file_path_trimmed = file_path.trim();
+    let options = parse_file_options(args, &["--pk", "--ct", "--ss", "--ml-kem", "-m", "--90s"])?;
+    let (pk_path, ct_path, ss_path) = match (&options.pk, &options.ct, &options.ss) {
+        (Some(pk), Some(ct), Some(ss)) => (pk, ct, ss),
+        _ => return Err(KyberError::Usage("encaps needs --pk <file>, --ct <file> and --ss <file>".to_string())),
+    };
 
-    
-    let mut private_key = match security_level {
-        Some(2) => vec![0u8; 1632],
-        Some(3) => vec![0u8; 2400],
-        Some(5) => vec![0u8; 3168],
-        Some(_) => {
-            panic!("Invalid security level detected!");
-        },
-        None => {
-            panic!("No security level detected!");
-        },
+    // The public key file names its parameter set
+    let (pk_file, _) = read_key_file(pk_path, options.variant)?;
+    with_parameter_set!(pk_file.variant, Some(pk_file.security_level), encaps_short(&pk_file, ct_path, ss_path, hybrid))
+}
+
+fn encaps_short<K: Kem>(pk_file: &KeyFile, ct_path: &str, ss_path: &str, hybrid: bool) -> Result<(), KyberError>
+{
+    check_key_file::<K>(pk_file, hybrid)?;
+    let pk = pk_file.public_key.as_ref().ok_or_else(|| KyberError::MissingKeyword("public key".to_string()))?;
+    let (ct, ss) = if hybrid {
+        let (ct, ss) = kyber::Hybrid::<K>::encapsulate(&kyber::hybrid::HybridPublicKey::from_bytes(pk)?)?;
+        (ct.to_bytes(), ss)
+    } else {
+        let (ct, ss) = K::encapsulate(&K::PublicKey::from_bytes(pk)?)?;
+        (ct.as_bytes().to_vec(), ss)
+    };
+
+    let mut ct_file = new_key_file::<K>(hybrid);
+    ct_file.ciphertext = Some(ct);
+    ct_file.write(ct_path)?;
+
+    let mut ss_file = new_key_file::<K>(hybrid);
+    ss_file.shared_secret = Some(Zeroizing::new(ss.as_bytes().to_vec()));
+    ss_file.write(ss_path)?;
+
+    println!("Encapsulation Completed!");
+    println!("Ciphertext written to {}, send it to the receiver.", ct_path.trim());
+    println!("Shared secret written to {}.", ss_path.trim());
+    Ok(())
+}
+
+// kyber decaps --sk <file> --ct <file> [--ss <file>] [--password-file <file>],
+// or hybrid-decaps for a hybrid key
+fn decaps_command(args: &[String], hybrid: bool) -> Result<(), KyberError>
+{
+    let options = parse_file_options(args, &["--sk", "--ct", "--ss", "--password-file", "--ml-kem", "-m", "--90s"])?;
+    let (sk_path, ct_path) = match (&options.sk, &options.ct) {
+        (Some(sk), Some(ct)) => (sk, ct),
+        _ => return Err(KyberError::Usage("decaps needs --sk <file> and --ct <file>".to_string())),
     };
-    let mut ciphertext = match security_level {
-        Some(2) => vec![0u8; 768],
-        Some(3) => vec![0u8; 1088],
-        Some(5) => vec![0u8; 1568],
-        Some(_) => {
-            panic!("Invalid security level detected!");
-        },
-        None => {
-            panic!("No security level detected!");
-        },
+
+    let (mut sk_file, _) = read_key_file(sk_path, options.variant)?;
+    let (ct_file, _) = read_key_file(ct_path, options.variant)?;
+    if ct_file.parameter_set_name() != sk_file.parameter_set_name() {
+        return Err(KyberError::KeyFile(format!("the ciphertext is for {}, the private key for {}", ct_file.parameter_set_name(), sk_file.parameter_set_name())));
+    }
+    if sk_file.is_encrypted() {
+        let password = get_password(options.password_file.as_deref(), false)?;
+        sk_file.decrypt_private_key(password.as_bytes())?;
+    }
+    with_parameter_set!(sk_file.variant, Some(sk_file.security_level), decaps_short(&sk_file, &ct_file, options.ss.as_deref(), hybrid))
+}
+
+// Writes the shared secret to `ss_path`, or prints it without one
+fn decaps_short<K: Kem>(sk_file: &KeyFile, ct_file: &KeyFile, ss_path: Option<&str>, hybrid: bool) -> Result<(), KyberError>
+{
+    check_key_file::<K>(sk_file, hybrid)?;
+    let sk = sk_file.private_key.as_ref().ok_or_else(|| KyberError::MissingKeyword("private key".to_string()))?;
+    let ct = ct_file.ciphertext.as_ref().ok_or_else(|| KyberError::MissingKeyword("ciphertext".to_string()))?;
+    let ss = if hybrid {
+        kyber::Hybrid::<K>::decapsulate(&kyber::hybrid::HybridSecretKey::from_bytes(sk)?, &kyber::hybrid::HybridCiphertext::from_bytes(ct)?)?
+    } else {
+        K::decapsulate(&K::SecretKey::from_bytes(sk)?, &K::Ciphertext::from_bytes(ct)?)?
     };
 
-    if use_own_key {
-        // Attempt to open the file
-        file = Some(File::open(file_path_trimmed).unwrap_or_else(|err| {
-            panic!("Failed to open file at '{}': {}", file_path_trimmed, err);
-        }));
-
-        // Read the public key from the file
-        public_key = Some(
-            helping_functions::helping_functions::read_hex_from_file_after_keyword(
-                &mut file.as_mut().unwrap(),
-                "public key:",
-            )
-            .unwrap_or_else(|err| panic!("Error reading public key: {}", err)),
-        );
-
-        let pk = public_key.as_ref().unwrap();
-        if pk.is_empty() || pk.iter().all(|&x| x == 0) {
-            panic!("Invalid public key! Quitting!");
-        }
-
-    } 
-    else {
-
-
-        // Generate public key based on security level
-        public_key = Some(match security_level {
-            Some(2) => vec![0u8; 800],
-            Some(3) => vec![0u8; 1184],
-            Some(5) => vec![0u8; 1568],
-            _ => panic!("Invalid security level detected!"),
-        });                
-        // Generate the private key
-        if use_own_key
-        {
+    println!("Decapsulation Completed!");
+    match ss_path {
+        Some(ss_path) => {
+            let mut ss_file = new_key_file::<K>(hybrid);
+            ss_file.shared_secret = Some(Zeroizing::new(ss.as_bytes().to_vec()));
+            ss_file.write(ss_path)?;
+            println!("Shared secret written to {}.", ss_path.trim());
+        }
+        None => println!("Shared Secret: {}", hex::encode(ss.as_bytes())),
+    }
+    Ok(())
+}
+
+// The input and output files of seal and open
+fn input_and_output<'a>(options: &'a FileOptions, command: &str) -> Result<(&'a str, &'a str), KyberError> {
+    match options.paths.as_slice() {
+        [input, output] if input.trim() != output.trim() => Ok((input, output)),
+        [_, _] => Err(KyberError::Usage(format!("the input and output of {} must be different files", command))),
+        _ => Err(KyberError::Usage(format!("{} needs an input file and an output file", command))),
+    }
+}
 
-                helping_functions::helping_functions::do_key_generation(
-                1,
-                &mut public_key.as_mut().unwrap(),
-                &mut private_key,
-                true);
+// Runs `write` on a new file at `path`, removing it again if `write` fails so
+// no partial output is left behind
+fn write_or_remove(path: &str, write: impl FnOnce(&mut io::BufWriter<File>) -> Result<u64, KyberError>) -> Result<u64, KyberError> {
+    let mut output = io::BufWriter::new(File::create(path.trim())?);
+    let result = write(&mut output);
+    drop(output);
+    if result.is_err() {
+        let _ = std::fs::remove_file(path.trim());
+    }
+    result
+}
+
+// kyber seal --pk <file> [--pk <file> ...] <input> <output>
+fn seal_command(args: &[String]) -> Result<(), KyberError>
+{
+    let options = parse_file_options(args, &["--pk", "--ml-kem", "-m", "--90s", "<file>"])?;
+    let (input, output) = input_and_output(&options, "seal")?;
+
+    match options.pks.as_slice() {
+        [] => Err(KyberError::Usage("seal needs --pk <file>".to_string())),
+        [pk_path] => {
+            let (pk_file, _) = read_key_file(pk_path, options.variant)?;
+            with_parameter_set!(pk_file.variant, Some(pk_file.security_level), seal_short(&pk_file, input, output))
         }
-        else
-         {
-                helping_functions::helping_functions::do_key_generation(
-                1,
-                &mut public_key.as_mut().unwrap(),
-                &mut private_key,
-                false);
+        pk_paths => {
+            // An envelope, each key with the parameter set its file names
+            let mut envelope = kyber::sealed::Envelope::new()?;
+            for pk_path in pk_paths {
+                let (pk_file, _) = read_key_file(pk_path, options.variant)?;
+                with_parameter_set!(pk_file.variant, Some(pk_file.security_level), add_recipient_short(&mut envelope, &pk_file))?;
+            }
+            let plaintext = File::open(input.trim())?;
+            let written = write_or_remove(output, |out| envelope.seal(plaintext, out))?;
+            println!("Sealed {} bytes of {} to {} recipients, written to {}.", written, input.trim(), pk_paths.len(), output.trim());
+            Ok(())
         }
     }
+}
 
+fn add_recipient_short<K: Kem>(envelope: &mut kyber::sealed::Envelope, pk_file: &KeyFile) -> Result<(), KyberError>
+{
+    check_key_file::<K>(pk_file, false)?;
+    let pk = pk_file.public_key.as_ref().ok_or_else(|| KyberError::MissingKeyword("public key".to_string()))?;
+    let pk = K::PublicKey::from_bytes(pk)?;
+    envelope.add_recipient::<K>(&pk)?;
+    println!("Recipient {}: {} key {}", envelope.recipients().len(), pk_file.parameter_set_name(), hex::encode(kyber::sealed::fingerprint::<K>(&pk)));
+    Ok(())
+}
 
-    // Open file and truncate it for writing
-    file = Some(File::create(file_path_trimmed).unwrap_or_else(|err| {
-        panic!("Failed to create/truncate file at '{}': {}", file_path_trimmed, err);
-    }));
+fn seal_short<K: Kem>(pk_file: &KeyFile, input: &str, output: &str) -> Result<(), KyberError>
+{
+    check_key_file::<K>(pk_file, false)?;
+    let pk = pk_file.public_key.as_ref().ok_or_else(|| KyberError::MissingKeyword("public key".to_string()))?;
+    let pk = K::PublicKey::from_bytes(pk)?;
+    let plaintext = File::open(input.trim())?;
+    let written = write_or_remove(output, |out| kyber::sealed::seal::<K, _, _>(&pk, plaintext, out))?;
+    println!("Sealed {} bytes of {} to the {} key, written to {}.", written, input.trim(), pk_file.parameter_set_name(), output.trim());
+    Ok(())
+}
 
-    let file = file.as_mut().unwrap();
-    // Write keys to file
-    writeln!(file, "PRIVATE KEY: '{}'", hex::encode(&private_key))
-        .expect("Failed to write private key to file");
-    writeln!(file, "PUBLIC KEY: '{}'", hex::encode(public_key.as_ref().unwrap()))
-        .expect("Failed to write public key to file");
+// kyber open --sk <file> [--password-file <file>] <input> <output>
+fn open_command(args: &[String]) -> Result<(), KyberError>
+{
+    let options = parse_file_options(args, &["--sk", "--password-file", "--ml-kem", "-m", "--90s", "<file>"])?;
+    let sk_path = options.sk.as_ref().ok_or_else(|| KyberError::Usage("open needs --sk <file>".to_string()))?;
+    let (input, output) = input_and_output(&options, "open")?;
 
-    // Perform encapsulation
-    let mut ss: Vec<u8> = vec![0u8; 32];
-    let mut ciphertext = match security_level {
-        Some(2) => vec![0u8; 768],
-        Some(3) => vec![0u8; 1088],
-        Some(5) => vec![0u8; 1568],
-        _ => panic!("Invalid security level detected!"),
-    };
+    let (mut sk_file, _) = read_key_file(sk_path, options.variant)?;
+    if sk_file.is_encrypted() {
+        let password = get_password(options.password_file.as_deref(), false)?;
+        sk_file.decrypt_private_key(password.as_bytes())?;
+    }
+    with_parameter_set!(sk_file.variant, Some(sk_file.security_level), open_short(&sk_file, input, output))
+}
 
-    let pk = public_key.as_mut().unwrap();
-    if kyber::kem::kem::crypto_kem_enc(&mut ciphertext, &mut ss, pk).is_ok() {
-        println!("Encapsulation Completed!");
-        println!("Please see file for keys.");
-        println!("Ciphertext: {}", hex::encode(&ciphertext));
-        println!("Shared Secret: {}", hex::encode(&ss));
+fn open_short<K: Kem>(sk_file: &KeyFile, input: &str, output: &str) -> Result<(), KyberError>
+{
+    check_key_file::<K>(sk_file, false)?;
+    let sk = sk_file.private_key.as_ref().ok_or_else(|| KyberError::MissingKeyword("private key".to_string()))?;
+    let sk = K::SecretKey::from_bytes(sk)?;
+    let sealed = File::open(input.trim())?;
+    let written = write_or_remove(output, |out| kyber::sealed::open::<K, _, _>(&sk, sealed, out))?;
+    println!("Opened {}, {} bytes written to {}.", input.trim(), written, output.trim());
+    Ok(())
+}
 
-        writeln!(file, "Ciphertext: '{}'", hex::encode(&ciphertext))
-            .expect("Failed to write ciphertext to file");
-        writeln!(file, "Shared Secret: '{}'", hex::encode(&ss))
-            .expect("Failed to write shared secret to file");
-    } else {
-        panic!("Encapsulation Failed");
+// kyber bench [-s <level>] [--ml-kem | --90s] [--iterations <N>] [--json]
+fn bench_command(args: &[String]) -> Result<(), KyberError>
+{
+    let mut levels = vec![2u8, 3, 5];
+    let mut variants = vec![KyberVariant::Round3, KyberVariant::Kyber90s, KyberVariant::MlKem];
+    let mut iterations = kyber::bench::DEFAULT_ITERATIONS;
+    let mut json = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--sec" | "-s" => {
+                match args.get(i + 1).and_then(|arg| arg.parse::<u8>().ok()) {
+                    Some(sec) if [2, 3, 5].contains(&sec) => levels = vec![sec],
+                    _ => return Err(KyberError::UnsupportedParameterSet("bench needs --sec 2, 3 or 5".to_string())),
+                }
+                i += 1;
+            }
+            "--ml-kem" | "-m" => variants = vec![KyberVariant::MlKem],
+            "--90s" => variants = vec![KyberVariant::Kyber90s],
+            "--iterations" | "-n" => {
+                match args.get(i + 1).and_then(|arg| arg.parse::<usize>().ok()) {
+                    Some(n) if n > 0 => iterations = n,
+                    _ => return Err(KyberError::Usage("--iterations needs a positive number".to_string())),
+                }
+                i += 1;
+            }
+            "--json" => json = true,
+            other => return Err(KyberError::Usage(format!("unrecognized argument: {}", other))),
+        }
+        i += 1;
     }
+
+    let mut reports = Vec::new();
+    for &variant in &variants {
+        for &level in &levels {
+            let report = with_parameter_set!(variant, Some(level), bench_short(iterations))?;
+            if !json {
+                report.print();
+            }
+            reports.push(report);
+        }
+    }
+    if json {
+        println!("{}", kyber::bench::to_json(&reports));
+    }
+    Ok(())
 }
 
+fn bench_short<K: Kem>(iterations: usize) -> Result<kyber::bench::BenchReport, KyberError>
+{
+    kyber::bench::run::<K>(iterations)
+}
+
+// Ok(false) when memcheck saw a branch or memory access depending on secret data
+fn ct_check_short<K: Kem>(args: &[String]) -> Result<bool, KyberError>
+{
+    if kyber::valgrind::running_on_valgrind() {
+        println!("Running key generation, encapsulation and decapsulation with secrets marked undefined.");
+    }
+    let clean = kyber::valgrind::ct_check::<K>(args)?;
+    if !kyber::valgrind::running_on_valgrind() {
+        if clean {
+            println!("Memcheck found no secret-dependent branches or memory accesses.");
+        } else {
+            println!("Memcheck reported secret-dependent behaviour, see the valgrind output above.");
+        }
+    }
+    Ok(clean)
+}
+
+#[allow(dead_code)]
 fn parse_argument<T: std::str::FromStr>(
     args_iter: &mut std::slice::Iter<String>,
     option_name: &str,
@@ -505,23 +1097,67 @@ fn parse_argument<T: std::str::FromStr>(
 
 fn print_help() {
     println!("Please note that many terminals have input restrictions on length. As such for decapsulation mode please supply keys in a seperate file. the format is demonstrated below:");
-    println!("Order does not matter, for decapsulation only private key and ciphertext are required. If you would like to validate the shared secret, include it in the file and the program will alert to a match");
+    println!("For decapsulation only private key and ciphertext are required. If you would like to validate the shared secret, include it in the file and the program will alert to a match");
     println!("This is also the format that the program will output in encapsulation mode.");
-    println!("PRIVATE KEY: '<HEX VALUE>'");
-    println!("PUBLIC KEY: '<HEX VALUE>'");
-    println!("Ciphertext: '<HEX VALUE>'");
-    println!("Shared Secret: '<HEX VALUE>'");
+    println!("KYBER-KEYFILE v1");
+    println!("algorithm: <Kyber, Kyber-90s or ML-KEM>");
+    println!("parameter-set: <algorithm>-<512, 768 or 1024>, e.g. ML-KEM-768");
+    println!("private-key: <hex value>  (or encrypted-private-key: scrypt:<log2 N>:<r>:<p>:<salt>:<nonce>:<ciphertext>)");
+    println!("public-key: <hex value>");
+    println!("ciphertext: <hex value>");
+    println!("shared-secret: <hex value>");
+    println!("checksum: <SHA-256 of the lines above>");
+    println!("Values are optional but must appear in this order. The file is rejected if it is truncated, edited or the checksum does not match.");
+    println!("A private key encrypted with a password is decrypted with AES-256-GCM under a key derived by scrypt, the password is asked for or read with --password-file.");
+    println!("When a key file is read --sec, --ml-kem and --90s may be left out, the file names its parameter set.");
+    println!("Key files from earlier versions (VARIANT: '...', PRIVATE KEY: '<HEX VALUE>', ...) are still read with a deprecation warning.");
     println!("Encapsulation mode does not require any input. However, you can supply your own key if you wish.");
     println!("Your output will be saved in the keyfile provided.");
 
-    println!("Usage: my_program [options]");
+    println!("Usage: my_program <keygen | encaps | decaps | seal | open | hybrid-keygen | hybrid-encaps | hybrid-decaps | bench> [options]");
+    println!("       my_program [options]");
     println!("Options:");
     println!("  --help, -h     Show this help message");
+    println!("\n~~~Key Generation, Encapsulation and Decapsulation~~~");
+    println!("Each party only sees its own material: the receiver keeps the private key, the sender gets the public key and returns the ciphertext.");
+    println!("  keygen --pk <file> --sk <file>    Generate a key pair, the public and private key in separate files");
+    println!("         --sec, -s                  Security level selection: 2,3 or 5");
+    println!("         --ml-kem, -m / --90s       Variant, round-3 Kyber by default");
+    println!("         --encrypt / --password-file <file>  Encrypt the private key with a password");
+    println!("  encaps --pk <file> --ct <file> --ss <file>  Encapsulate to a public key, the ciphertext and shared secret in separate files");
+    println!("  decaps --sk <file> --ct <file>    Decapsulate, printing the shared secret or writing it to --ss <file>");
+    println!("         --password-file <file>     Password of an encrypted private key, asked for when left out");
+    println!("Each file is a key file naming its parameter set, so encaps and decaps need no --sec.");
+    println!("./kyber keygen --pk alice.pk --sk alice.sk -s 3 -m");
+    println!("./kyber encaps --pk alice.pk --ct bob.ct --ss bob.ss");
+    println!("./kyber decaps --sk alice.sk --ct bob.ct --ss alice.ss");
+    println!("\n~~~File Encryption~~~");
+    println!("  seal --pk <file> <input> <output>  Encrypt a file of any size to a public key, or to several with one --pk each");
+    println!("  open --sk <file> <input> <output>  Decrypt it with the private key, --password-file <file> for an encrypted one");
+    println!("The file is encrypted with AES-256-GCM in 64 KiB chunks under a key derived from a fresh encapsulation, stored in the output's header.");
+    println!("Any change, reordering or truncation is detected, and nothing is left in <output> when open fails.");
+    println!("With several keys, of any parameter sets, a random content key is wrapped for each, and open only tries the line whose fingerprint matches its key.");
+    println!("./kyber seal --pk alice.pk report.pdf report.pdf.sealed");
+    println!("./kyber open --sk alice.sk report.pdf.sealed report.pdf");
+    println!("./kyber seal --pk alice.pk --pk bob.pk --pk carol.pk release.tar release.tar.sealed");
+    println!("\n~~~Hybrid X25519 + Kyber~~~");
+    println!("  hybrid-keygen, hybrid-encaps, hybrid-decaps  The same options, with X25519 alongside Kyber768 (default) or ML-KEM-768 (-m)");
+    println!("The shared secret is SHA3-256 over both shared secrets, both ciphertexts and both public keys. Hybrid and Kyber-only keys are not interchangeable.");
+    println!("./kyber hybrid-keygen --pk alice.pk --sk alice.sk -m");
+    println!("./kyber hybrid-encaps --pk alice.pk --ct bob.ct --ss bob.ss");
+    println!("./kyber hybrid-decaps --sk alice.sk --ct bob.ct --ss alice.ss");
+    println!("The options below are the older interface, still supported. --enc without --own-key is keygen and encaps, writing <file>.pk, <file>.sk, <file>.ct and <file>.ss, which --dec with the same <file> reads.");
     println!("\n~~~For Encapsulation Mode~~~");
     println!("  --sec,                       -s   Security level selection: 2,3 or 5");
     println!("  --enc,                       -e   Encapsulation mode");
     println!("  --key-file <file path>,      -f   Use keyfile");
     println!("  --own-key                    -o   Use own public key");
+    println!("  --ml-kem                     -m   Generate FIPS 203 ML-KEM keys instead of round-3 Kyber");
+    println!("  --90s                             Generate Kyber-90s keys (AES-256-CTR and SHA-2)");
+    println!("                                    (an existing key file keeps the variant it records)");
+    println!("  --count <N>                       Generate N key pairs in one batch, written to <file>.1.pk, .sk, .ct and .ss to <file>.N.pk, .sk, .ct and .ss");
+    println!("  --encrypt                         Encrypt the generated private key with a password asked for twice");
+    println!("  --password-file <file>            Encrypt the generated private key with the password on the first line of the file");
   
     println!("example use for using own key:");
     println!("./kyber --sec 5 --enc --key-file /home/my_output/kyber_output --own-key  ");
@@ -536,11 +1172,17 @@ fn print_help() {
     println!("./kyber --sec 3 --enc --key-file /home/my_output/kyber_output ");
     println!("Alternatively:");
     println!("./kyber -s 3 -e -f /home/my_output/kyber_output ");
+    println!("Example input to generate a password-protected key:");
+    println!("./kyber -s 3 -e -f /home/my_output/kyber_output --encrypt");
+    println!("Example input to generate 1000 key pairs:");
+    println!("./kyber -s 3 -e -f /home/my_output/kyber_output --count 1000");
+    println!("Decapsulate a generated key pair with: ./kyber decaps --sk /home/my_output/kyber_output.sk --ct /home/my_output/kyber_output.ct");
 
     println!("\n~~~For Decapsulation Mode~~~");
     println!("  --sec,                       -s   Security level selection: 2,3 or 5");
     println!("  --dec,                       -d   Decapsulation mode");
     println!("  --key-file <file path>,      -f   Use keyfile");
+    println!("  --password-file <file>            Password of an encrypted private key, asked for when left out");
     println!("Example input to decapsulate:");
     println!("./kyber --sec 3 --dec --key-file /home/my_output/kyber_output ");
     println!("Alternatively:");
@@ -550,12 +1192,62 @@ fn print_help() {
     println!("./kyber -D<security_level> /home/file_for_key");
     println!("e.g ./kyber -D5 /home/my_decapsulation");
 
+    println!("\n~~~For Key Validation Mode~~~");
+    println!("  --sec,                       -s   Security level selection: 2,3 or 5");
+    println!("  --validate                        Run the FIPS 203 input checks on the keys in the keyfile");
+    println!("  --key-file <file path>,      -f   Use keyfile");
+    println!("Reports which check each key fails: length, modulus (public key) or hash (private key).");
+    println!("./kyber -s 3 --validate -f /home/my_output/kyber_output");
+
+    println!("\n~~~For Export Mode~~~");
+    println!("  --sec,                       -s   Security level selection: 2,3 or 5");
+    println!("  --export <format>                 Write the keys and ciphertext of the keyfile as binary, hex, base64, pem, json or protobuf");
+    println!("  --key-file <file path>,      -f   Use keyfile");
+    println!("Writes <file path>.pub.<ext>, <file path>.key.<ext> and <file path>.ct.<ext>. PEM keys are SubjectPublicKeyInfo and PKCS#8, readable by OpenSSL 3.5 for ML-KEM.");
+    println!("./kyber -s 3 --export pem -f /home/my_output/kyber_output");
+
+    println!("\n~~~For Constant-Time Auditing~~~");
+    println!("  --sec,                       -s   Security level selection: 2,3 or 5");
+    println!("  --timing [samples]                Welch t-test on decapsulation timing, fixed vs random ciphertexts (default 100000)");
+    println!("  --ct-check                        Run keygen, encapsulation and decapsulation under valgrind memcheck with secrets marked undefined");
+    println!("  --ml-kem, -m / --90s              Variant to audit, round-3 Kyber by default");
+    println!("Both exit with 1 if a leak is found. --ct-check needs valgrind on the PATH.");
+    println!("./kyber -s 3 -m --timing 1000000");
+    println!("./kyber -s 3 --ct-check");
+
+    println!("\n~~~For Benchmarking~~~");
+    println!("  bench                             Time gen_matrix, the NTTs, noise sampling, IND-CPA and the KEM operations");
+    println!("  --sec,                       -s   Only this security level, all three by default");
+    println!("  --ml-kem, -m / --90s              Only this variant, all three by default");
+    println!("  --iterations <N>,            -n   Calls per operation (default 1000)");
+    println!("  --json                            Print the medians and averages as JSON for regression tracking");
+    println!("Counts are CPU cycles on x86_64 and nanoseconds elsewhere. Build with --release for meaningful numbers.");
+    println!("./kyber bench -s 3 -m --iterations 10000");
+
     println!("Options for security level");
     println!("  2                             Kyber 512 ");
     println!("  3                             Kyber 768 ");
     println!("  5                             Kyber 1024 ");
+
+    println!("Exit codes");
+    println!("  2   Command line arguments are missing, unknown or invalid");
+    println!("  3   Key has the wrong length");
+    println!("  4   Ciphertext has the wrong length");
+    println!("  5   Key file value is not valid hex");
+    println!("  6   Key file is missing a required value");
+    println!("  7   Key failed validation (all zero, FIPS 203 modulus or hash check, rejected X25519 key)");
+    println!("  8   Random number generation failed");
+    println!("  9   Unsupported security level or variant");
+    println!("  10  Key file could not be read or written");
+    println!("  11  Exported data could not be decoded");
+    println!("  12  Key file is malformed, truncated or fails its checksum");
+    println!("  13  Wrong password, or the encrypted private key was altered");
+    println!("  14  HPKE context cannot be set up, or a message does not open under it");
+    println!("  15  Sealed file is malformed, altered, truncated or for another key");
+    println!("  16  Key exchange message is malformed, or its key confirmation does not match");
 }
 
+#[allow(dead_code, unused_assignments, unused_must_use, clippy::needless_return)]
 fn call_kyber()
 {
     let mut category_choice: u32  = 0;
@@ -584,11 +1276,11 @@ fn call_kyber()
         }
         if category_choice == 1
         {
-            kyber::encapsulate_long(Some(strength));
+            kyber::kyber::encapsulate_long(Some(strength));
         }
         else if category_choice == 2
         {
-            kyber::decapsulate_long();
+            kyber::kyber::decapsulate_long();
         }
         else {
             eprintln!("Something is going wrong with the mode selection!\nReturning...");
@@ -597,6 +1289,7 @@ fn call_kyber()
 
 }
 // Kyber Key Encapsulation
+#[allow(dead_code, unused_assignments, unused_must_use)]
 fn list_types_and_strengths_kyber() -> Kyber_Category {
     println!("Kyber Key Encapsulation Strengths");
     println!("Please select the strength required");
@@ -18,6 +18,15 @@ pub enum RngOutput<T> {
     Vector(Vec<T>),
 }
 
+/// A randomness source of the selected type for the KEM, seeded from the operating system.
+pub fn kem_rng(rng_type: RngType) -> Box<dyn crate::kyber::kem_rng::KemRng + Send> {
+    match rng_type {
+        RngType::Os => Box::new(OsRng),
+        RngType::Std => Box::new(StdRng::from_entropy()),
+        RngType::ChaCha20 => Box::new(ChaCha20Rng::from_entropy()),
+    }
+}
+
 pub fn generate_random<T>(
     rng_type: RngType,
     range: (T, T),
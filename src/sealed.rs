@@ -0,0 +1,452 @@
+// Files sealed to Kyber public keys with a KEM-DEM construction.
+//
+// A sealed file is a short text header followed by the encrypted payload.
+// Version 1 is sealed to a single public key:
+//
+//     KYBER-SEALED v1
+//     parameter-set: ML-KEM-768
+//     encapsulation: <hex KEM ciphertext, KYBER_CIPHERTEXTBYTES long>
+//     salt: <hex, 32 bytes>
+//     ---
+//     <payload>
+//
+// The sender encapsulates to the recipient's public key and derives the
+// AES-256-GCM payload key with HKDF-SHA256 from the shared secret, with the
+// salt as HKDF salt and the whole header, "---" line included, as info. A
+// changed header therefore gives another key and the payload does not decrypt.
+//
+// Version 2 is an envelope sealed to any number of public keys, of any
+// parameter sets, with one line per recipient:
+//
+//     KYBER-SEALED v2
+//     recipient: <fingerprint> <parameter set> <encapsulation> <wrapped key>
+//     ...
+//     salt: <hex, 32 bytes>
+//     ---
+//     <payload>
+//
+// A random content key takes the place of the shared secret above. For each
+// recipient it is wrapped with AES-256-GCM, under a zero nonce, by a key used
+// only once: HKDF-SHA256 of that recipient's own encapsulation, with the
+// fingerprint as salt and the encapsulation as info. The fingerprint, the first
+// 16 bytes of SHA-256 over the parameter set name, a newline and the public key,
+// lets a recipient find its line without trying the others. Every recipient
+// can read the whole header, and any of them could seal another file to the
+// rest, since nothing proves who wrote it.
+//
+// The payload is the input in chunks of 64 KiB, each encrypted and followed by
+// its 16 byte tag, in the STREAM construction of Hoang, Reyhanitabar, Rogaway
+// and Vizár: chunk i has the nonce i as 11 big-endian bytes followed by 1 for
+// the last chunk and 0 for the others. Chunks cannot be reordered, dropped or
+// added, and a file cut after any chunk is caught because that chunk is not
+// marked last. Every chunk but the last is full, and the last one is only
+// empty for an empty input. The payload is decrypted and written a chunk at a
+// time, so files of any size take constant memory.
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::error::KyberError;
+use crate::kyber::hpke::Kdf;
+use crate::kyber::kyber_api::{Kem, KemBytes};
+
+/// Version written by `seal`.
+pub const SEALED_VERSION: u32 = 1;
+
+/// Version written by `Envelope::seal`.
+pub const ENVELOPE_VERSION: u32 = 2;
+
+/// Plaintext bytes in each payload chunk but the last.
+pub const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Most recipients an envelope may have.
+pub const MAX_RECIPIENTS: usize = 1024;
+
+/// Length of a public key fingerprint.
+pub const FINGERPRINT_BYTES: usize = 16;
+
+const MAGIC: &str = "KYBER-SEALED";
+const END: &str = "---";
+const SALT_BYTES: usize = 32;
+const TAG_BYTES: usize = 16;
+const CONTENT_KEY_BYTES: usize = 32;
+const KEY_LABEL: &[u8] = b"KYBER-SEALED payload key";
+const WRAP_LABEL: &[u8] = b"KYBER-SEALED wrapping key";
+
+// Longer header lines are refused before they are read in full
+const MAX_LINE_BYTES: u64 = 8192;
+
+fn error(reason: impl Into<String>) -> KyberError {
+    KyberError::Sealed(reason.into())
+}
+
+/// The header of a file sealed to one key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SealedHeader {
+    /// e.g. "ML-KEM-768", as in key files.
+    pub parameter_set: String,
+    /// The KEM ciphertext.
+    pub encapsulation: Vec<u8>,
+    pub salt: [u8; SALT_BYTES],
+}
+
+/// One recipient of an envelope, the content key wrapped for its public key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recipient {
+    pub fingerprint: [u8; FINGERPRINT_BYTES],
+    pub parameter_set: String,
+    pub encapsulation: Vec<u8>,
+    /// The content key encrypted under the wrapping key, tag included.
+    pub wrapped_key: Vec<u8>,
+}
+
+/// The header of an envelope, a file sealed to several keys.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvelopeHeader {
+    pub recipients: Vec<Recipient>,
+    pub salt: [u8; SALT_BYTES],
+}
+
+/// The fingerprint identifying `pk` in an envelope.
+pub fn fingerprint<K: Kem>(pk: &K::PublicKey) -> [u8; FINGERPRINT_BYTES] {
+    let digest = Sha256::new().chain_update(K::PARAMETER_SET.as_bytes()).chain_update(b"\n").chain_update(pk.as_bytes()).finalize();
+    digest[..FINGERPRINT_BYTES].try_into().unwrap()
+}
+
+// The public key held in `sk`, before H(pk) and z
+fn public_key_of<K: Kem>(sk: &K::SecretKey) -> Result<K::PublicKey, KyberError> {
+    K::PublicKey::from_bytes(&sk.as_bytes()[K::SECRET_KEY_BYTES - 64 - K::PUBLIC_KEY_BYTES..K::SECRET_KEY_BYTES - 64])
+}
+
+// A header line without its newline, refused if over MAX_LINE_BYTES
+fn read_line<R: BufRead>(input: &mut R, name: &str) -> Result<String, KyberError> {
+    let mut line = Vec::new();
+    input.by_ref().take(MAX_LINE_BYTES).read_until(b'\n', &mut line)?;
+    let line = String::from_utf8(line).map_err(|_| error("the header is not text"))?;
+    line.strip_suffix('\n').map(str::to_string).ok_or_else(|| error(format!("the header is truncated or too long at the {} line", name)))
+}
+
+// The value of a `name: value` line
+fn field(line: &str, name: &str) -> Result<String, KyberError> {
+    line.strip_prefix(name).and_then(|rest| rest.strip_prefix(": ")).map(str::to_string).ok_or_else(|| error(format!("expected {}, found '{}'", name, line)))
+}
+
+// Only the lowercase hex the encoders write, so a header reads back to the
+// same bytes and the same payload key
+fn decode_hex(value: &str, name: &str) -> Result<Vec<u8>, KyberError> {
+    if value.bytes().any(|b| b.is_ascii_uppercase()) {
+        return Err(error(format!("the {} must be lowercase hex", name)));
+    }
+    Ok(hex::decode(value)?)
+}
+
+fn decode_salt(line: &str) -> Result<[u8; SALT_BYTES], KyberError> {
+    decode_hex(&field(line, "salt")?, "salt")?.try_into().map_err(|_| error(format!("the salt must be {} bytes", SALT_BYTES)))
+}
+
+fn read_end<R: BufRead>(input: &mut R) -> Result<(), KyberError> {
+    if read_line(input, END)? != END {
+        return Err(error("the header does not end in ---"));
+    }
+    Ok(())
+}
+
+// The version on the first line
+fn read_version<R: BufRead>(input: &mut R) -> Result<u32, KyberError> {
+    match read_line(input, "first")?.strip_prefix(MAGIC).and_then(|rest| rest.strip_prefix(" v")) {
+        Some("1") => Ok(SEALED_VERSION),
+        Some("2") => Ok(ENVELOPE_VERSION),
+        Some(version) => Err(error(format!("unsupported version {}", version))),
+        None => Err(error("not a sealed file")),
+    }
+}
+
+fn new_salt() -> Result<[u8; SALT_BYTES], KyberError> {
+    let mut salt = [0u8; SALT_BYTES];
+    openssl::rand::rand_bytes(&mut salt).map_err(|_| KyberError::Rng("OpenSSL could not generate a salt"))?;
+    Ok(salt)
+}
+
+// HKDF-SHA256(ikm = shared secret or content key, salt, info = label || header)
+fn payload_key(secret: &[u8], salt: &[u8], header: &str) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+    let prk = Kdf::HkdfSha256.extract(salt, secret)?;
+    Kdf::HkdfSha256.expand(&prk, &[KEY_LABEL, header.as_bytes()].concat(), 32)
+}
+
+impl SealedHeader {
+    /// The header as written, "---" line included.
+    pub fn encode(&self) -> String {
+        format!(
+            "{} v{}\nparameter-set: {}\nencapsulation: {}\nsalt: {}\n{}\n",
+            MAGIC, SEALED_VERSION, self.parameter_set, hex::encode(&self.encapsulation), hex::encode(self.salt), END
+        )
+    }
+
+    /// Reads a version 1 header from `input`, leaving it at the start of the payload.
+    pub fn read<R: BufRead>(input: &mut R) -> Result<SealedHeader, KyberError> {
+        match read_version(input)? {
+            SEALED_VERSION => SealedHeader::read_fields(input),
+            _ => Err(error("the file is an envelope for several recipients")),
+        }
+    }
+
+    fn read_fields<R: BufRead>(input: &mut R) -> Result<SealedHeader, KyberError> {
+        let parameter_set = field(&read_line(input, "parameter-set")?, "parameter-set")?;
+        let encapsulation = decode_hex(&field(&read_line(input, "encapsulation")?, "encapsulation")?, "encapsulation")?;
+        let salt = decode_salt(&read_line(input, "salt")?)?;
+        read_end(input)?;
+        Ok(SealedHeader { parameter_set, encapsulation, salt })
+    }
+}
+
+impl Recipient {
+    // <fingerprint> <parameter set> <encapsulation> <wrapped key>
+    fn encode(&self) -> String {
+        format!("{} {} {} {}", hex::encode(self.fingerprint), self.parameter_set, hex::encode(&self.encapsulation), hex::encode(&self.wrapped_key))
+    }
+
+    fn decode(value: &str) -> Result<Recipient, KyberError> {
+        let parts: Vec<&str> = value.split(' ').collect();
+        let [fingerprint, parameter_set, encapsulation, wrapped_key] = parts[..] else {
+            return Err(error("a recipient line needs a fingerprint, parameter set, encapsulation and wrapped key"));
+        };
+        let fingerprint = decode_hex(fingerprint, "fingerprint")?.try_into().map_err(|_| error(format!("a fingerprint must be {} bytes", FINGERPRINT_BYTES)))?;
+        let wrapped_key = decode_hex(wrapped_key, "wrapped key")?;
+        if wrapped_key.len() != CONTENT_KEY_BYTES + TAG_BYTES {
+            return Err(error(format!("a wrapped key must be {} bytes", CONTENT_KEY_BYTES + TAG_BYTES)));
+        }
+        Ok(Recipient { fingerprint, parameter_set: parameter_set.to_string(), encapsulation: decode_hex(encapsulation, "encapsulation")?, wrapped_key })
+    }
+
+    // HKDF-SHA256(ikm = shared secret, salt = fingerprint, info = label || encapsulation)
+    fn wrapping_key(&self, shared_secret: &[u8]) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        let prk = Kdf::HkdfSha256.extract(&self.fingerprint, shared_secret)?;
+        Kdf::HkdfSha256.expand(&prk, &[WRAP_LABEL, &self.encapsulation].concat(), 32)
+    }
+
+    /// Encapsulates to `pk` and wraps `content_key` for it.
+    fn wrap<K: Kem>(pk: &K::PublicKey, content_key: &[u8]) -> Result<Recipient, KyberError> {
+        let (ct, ss) = K::encapsulate(pk)?;
+        let mut recipient = Recipient {
+            fingerprint: fingerprint::<K>(pk),
+            parameter_set: K::PARAMETER_SET.to_string(),
+            encapsulation: ct.as_bytes().to_vec(),
+            wrapped_key: Vec::new(),
+        };
+        let mut tag = [0u8; TAG_BYTES];
+        let wrapping_key = recipient.wrapping_key(ss.as_bytes())?;
+        recipient.wrapped_key = encrypt_aead(Cipher::aes_256_gcm(), &wrapping_key, Some(&[0u8; 12]), b"", content_key, &mut tag)
+            .map_err(|_| error("OpenSSL encryption failed"))?;
+        recipient.wrapped_key.extend_from_slice(&tag);
+        Ok(recipient)
+    }
+
+    /// Decapsulates with `sk` and unwraps the content key.
+    fn unwrap<K: Kem>(&self, sk: &K::SecretKey) -> Result<Zeroizing<Vec<u8>>, KyberError> {
+        let ss = K::decapsulate(sk, &K::Ciphertext::from_bytes(&self.encapsulation)?)?;
+        let (wrapped_key, tag) = self.wrapped_key.split_at(CONTENT_KEY_BYTES);
+        let content_key = decrypt_aead(Cipher::aes_256_gcm(), &self.wrapping_key(ss.as_bytes())?, Some(&[0u8; 12]), b"", wrapped_key, tag)
+            .map_err(|_| error("the recipient line for this key was altered"))?;
+        Ok(Zeroizing::new(content_key))
+    }
+}
+
+impl EnvelopeHeader {
+    /// The header as written, "---" line included.
+    pub fn encode(&self) -> String {
+        let mut out = format!("{} v{}\n", MAGIC, ENVELOPE_VERSION);
+        for recipient in &self.recipients {
+            out.push_str(&format!("recipient: {}\n", recipient.encode()));
+        }
+        out.push_str(&format!("salt: {}\n{}\n", hex::encode(self.salt), END));
+        out
+    }
+
+    /// Reads a version 2 header from `input`, leaving it at the start of the payload.
+    pub fn read<R: BufRead>(input: &mut R) -> Result<EnvelopeHeader, KyberError> {
+        match read_version(input)? {
+            ENVELOPE_VERSION => EnvelopeHeader::read_fields(input),
+            _ => Err(error("the file is sealed to a single key")),
+        }
+    }
+
+    fn read_fields<R: BufRead>(input: &mut R) -> Result<EnvelopeHeader, KyberError> {
+        let mut recipients: Vec<Recipient> = Vec::new();
+        let salt = loop {
+            let line = read_line(input, "recipient")?;
+            if line.starts_with("salt: ") {
+                break decode_salt(&line)?;
+            }
+            let recipient = Recipient::decode(&field(&line, "recipient")?)?;
+            if recipients.iter().any(|other| other.fingerprint == recipient.fingerprint) {
+                return Err(error("a recipient is listed twice"));
+            }
+            if recipients.len() == MAX_RECIPIENTS {
+                return Err(error(format!("more than {} recipients", MAX_RECIPIENTS)));
+            }
+            recipients.push(recipient);
+        };
+        if recipients.is_empty() {
+            return Err(error("the envelope has no recipients"));
+        }
+        read_end(input)?;
+        Ok(EnvelopeHeader { recipients, salt })
+    }
+}
+
+/// An envelope being addressed, holding the random content key that every
+/// recipient's line wraps.
+pub struct Envelope {
+    content_key: Zeroizing<[u8; CONTENT_KEY_BYTES]>,
+    recipients: Vec<Recipient>,
+}
+
+impl Envelope {
+    /// An envelope with a fresh content key and no recipients yet.
+    pub fn new() -> Result<Envelope, KyberError> {
+        let mut content_key = Zeroizing::new([0u8; CONTENT_KEY_BYTES]);
+        openssl::rand::rand_bytes(&mut content_key[..]).map_err(|_| KyberError::Rng("OpenSSL could not generate a content key"))?;
+        Ok(Envelope { content_key, recipients: Vec::new() })
+    }
+
+    /// Adds the holder of `pk` as a recipient, with an encapsulation of its own.
+    pub fn add_recipient<K: Kem>(&mut self, pk: &K::PublicKey) -> Result<(), KyberError> {
+        if self.recipients.iter().any(|recipient| recipient.fingerprint == fingerprint::<K>(pk)) {
+            return Err(error("a recipient is added twice"));
+        }
+        if self.recipients.len() == MAX_RECIPIENTS {
+            return Err(error(format!("more than {} recipients", MAX_RECIPIENTS)));
+        }
+        self.recipients.push(Recipient::wrap::<K>(pk, &self.content_key[..])?);
+        Ok(())
+    }
+
+    pub fn recipients(&self) -> &[Recipient] {
+        &self.recipients
+    }
+
+    /// Encrypts everything read from `input` to every recipient, writing the
+    /// envelope to `output`. Returns the number of plaintext bytes.
+    pub fn seal<R: Read, W: Write>(self, input: R, mut output: W) -> Result<u64, KyberError> {
+        if self.recipients.is_empty() {
+            return Err(error("the envelope has no recipients"));
+        }
+        let header = EnvelopeHeader { recipients: self.recipients, salt: new_salt()? };
+        output.write_all(header.encode().as_bytes())?;
+        let key = payload_key(&self.content_key[..], &header.salt, &header.encode())?;
+        let written = encrypt_payload(&key, &mut BufReader::new(input), &mut output)?;
+        output.flush()?;
+        Ok(written)
+    }
+}
+
+/// Encrypts everything read from `input` to `pk`, writing the sealed file to
+/// `output`. Returns the number of plaintext bytes.
+pub fn seal<K: Kem, R: Read, W: Write>(pk: &K::PublicKey, input: R, mut output: W) -> Result<u64, KyberError> {
+    let (ct, ss) = K::encapsulate(pk)?;
+    let header = SealedHeader { parameter_set: K::PARAMETER_SET.to_string(), encapsulation: ct.as_bytes().to_vec(), salt: new_salt()? };
+
+    output.write_all(header.encode().as_bytes())?;
+    let key = payload_key(ss.as_bytes(), &header.salt, &header.encode())?;
+    let written = encrypt_payload(&key, &mut BufReader::new(input), &mut output)?;
+    output.flush()?;
+    Ok(written)
+}
+
+/// Decrypts a sealed file or an envelope read from `input` with `sk`, writing
+/// the plaintext to `output` as each chunk is verified. In an envelope only
+/// the line with the fingerprint of `sk`'s public key is tried. Returns the
+/// number of plaintext bytes.
+///
+/// On an error `output` may already hold the chunks before the bad one, which
+/// the caller should discard.
+pub fn open<K: Kem, R: Read, W: Write>(sk: &K::SecretKey, input: R, mut output: W) -> Result<u64, KyberError> {
+    let mut input = BufReader::new(input);
+    let key = if read_version(&mut input)? == SEALED_VERSION {
+        let header = SealedHeader::read_fields(&mut input)?;
+        if header.parameter_set != K::PARAMETER_SET {
+            return Err(error(format!("the file is sealed for {}, not {}", header.parameter_set, K::PARAMETER_SET)));
+        }
+        let ss = K::decapsulate(sk, &K::Ciphertext::from_bytes(&header.encapsulation)?)?;
+        payload_key(ss.as_bytes(), &header.salt, &header.encode())?
+    } else {
+        let header = EnvelopeHeader::read_fields(&mut input)?;
+        let own = fingerprint::<K>(&public_key_of::<K>(sk)?);
+        let recipient = header.recipients.iter().find(|recipient| recipient.fingerprint == own).ok_or_else(|| error("the file is not sealed to this key"))?;
+        payload_key(&recipient.unwrap::<K>(sk)?, &header.salt, &header.encode())?
+    };
+    let written = decrypt_payload(&key, &mut input, &mut output)?;
+    output.flush()?;
+    Ok(written)
+}
+
+// The counter as 11 big-endian bytes, then 1 for the last chunk
+fn nonce(counter: u64, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+// Fills `buf` unless the input ends first, returning how much was read
+fn read_full<R: Read>(input: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match input.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
+
+fn encrypt_payload<R: BufRead, W: Write>(key: &[u8], input: &mut R, output: &mut W) -> Result<u64, KyberError> {
+    let mut chunk = Zeroizing::new(vec![0u8; CHUNK_BYTES]);
+    let mut total = 0u64;
+    for counter in 0u64.. {
+        let len = read_full(input, &mut chunk)?;
+        // A full chunk is the last one when nothing follows it
+        let last = len < CHUNK_BYTES || input.fill_buf()?.is_empty();
+        let mut tag = [0u8; TAG_BYTES];
+        let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), key, Some(&nonce(counter, last)), b"", &chunk[..len], &mut tag)
+            .map_err(|_| error("OpenSSL encryption failed"))?;
+        output.write_all(&ciphertext)?;
+        output.write_all(&tag)?;
+        total += len as u64;
+        if last {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+fn decrypt_payload<R: BufRead, W: Write>(key: &[u8], input: &mut R, output: &mut W) -> Result<u64, KyberError> {
+    let mut chunk = vec![0u8; CHUNK_BYTES + TAG_BYTES];
+    let mut total = 0u64;
+    for counter in 0u64.. {
+        let len = read_full(input, &mut chunk)?;
+        if len < TAG_BYTES {
+            return Err(error("the payload is truncated"));
+        }
+        let last = len < chunk.len() || input.fill_buf()?.is_empty();
+        let (ciphertext, tag) = chunk[..len].split_at(len - TAG_BYTES);
+        let plaintext = Zeroizing::new(
+            decrypt_aead(Cipher::aes_256_gcm(), key, Some(&nonce(counter, last)), b"", ciphertext, tag)
+                .map_err(|_| error("the file was not sealed to this key, or was altered or truncated"))?,
+        );
+        if last && plaintext.is_empty() && counter > 0 {
+            return Err(error("the payload ends in an empty chunk"));
+        }
+        output.write_all(&plaintext)?;
+        total += plaintext.len() as u64;
+        if last {
+            break;
+        }
+    }
+    Ok(total)
+}
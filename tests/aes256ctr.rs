@@ -0,0 +1,48 @@
+// The bitsliced AES-256-CTR behind Kyber-90s against OpenSSL: the PRF at
+// lengths that end inside a block and a four-block batch, and the XOF over
+// enough blocks to carry the counter through several batches.
+
+use kyber::symmetric_aes::{kyber_aes256ctr_prf, kyber_aes256xof};
+use openssl::symm::{encrypt, Cipher};
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+// AES-256-CTR keystream for a 12 byte nonce and a 32-bit counter from 0
+fn openssl_keystream(key: &[u8], nonce: &[u8; 12], len: usize) -> Vec<u8> {
+    let mut iv = [0u8; 16];
+    iv[..12].copy_from_slice(nonce);
+    encrypt(Cipher::aes_256_ctr(), key, Some(&iv), &vec![0u8; len]).unwrap()
+}
+
+#[test]
+fn prf_matches_openssl() {
+    let mut rng = ChaCha20Rng::from_seed([5; 32]);
+    for len in [0, 1, 15, 16, 17, 63, 64, 65, 128, 192, 255, 256, 257, 640, 1000] {
+        let mut key = [0u8; 32];
+        rng.fill_bytes(&mut key);
+        let nonce: u8 = rng.gen();
+
+        let mut out = vec![0u8; len];
+        kyber_aes256ctr_prf(&mut out, &key, nonce);
+        let mut expnonce = [0u8; 12];
+        expnonce[0] = nonce;
+        assert_eq!(hex::encode(&out), hex::encode(openssl_keystream(&key, &expnonce, len)), "{} bytes", len);
+    }
+}
+
+#[test]
+fn xof_matches_openssl() {
+    let mut rng = ChaCha20Rng::from_seed([6; 32]);
+    for blocks in [1, 3, 4, 5, 8, 13] {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        let (x, y): (u8, u8) = (rng.gen(), rng.gen());
+
+        let mut out = vec![0u8; 64 * blocks];
+        kyber_aes256xof(&mut out, &seed, x, y);
+        let mut expnonce = [0u8; 12];
+        expnonce[0] = x;
+        expnonce[1] = y;
+        assert_eq!(hex::encode(&out), hex::encode(openssl_keystream(&seed, &expnonce, out.len())), "{} blocks", blocks);
+    }
+}
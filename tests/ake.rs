@@ -0,0 +1,232 @@
+// Kyber.UAKE and Kyber.AKE: both sides agree over a byte stream, the key and
+// tags are the documented hashes of the transcript, and wrong keys or altered
+// messages are caught by key confirmation.
+
+use std::io::{Read, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use kyber::ake::{AwaitingConfirmation, Initiator, Protocol, Responder, CONFIRMATION_BYTES};
+use kyber::{Kem, KemBytes, Kyber1024_90s, Kyber512, Kyber768, KyberError, MlKem768, SharedSecret};
+use openssl::hash::{hash, MessageDigest};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+// One end of an in-memory byte stream. Writes arrive at the other end in
+// pieces, so messages must be framed by their known lengths.
+struct Pipe {
+    tx: Sender<Vec<u8>>,
+    rx: Receiver<Vec<u8>>,
+    pending: Vec<u8>,
+}
+
+fn pipe() -> (Pipe, Pipe) {
+    let (a_tx, b_rx) = channel();
+    let (b_tx, a_rx) = channel();
+    (Pipe { tx: a_tx, rx: a_rx, pending: Vec::new() }, Pipe { tx: b_tx, rx: b_rx, pending: Vec::new() })
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(700);
+        self.tx.send(buf[..n].to_vec()).map_err(|_| std::io::ErrorKind::BrokenPipe)?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_empty() {
+            match self.rx.recv() {
+                Ok(bytes) => self.pending = bytes,
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+fn receive(stream: &mut impl Read, len: usize) -> Vec<u8> {
+    let mut message = vec![0u8; len];
+    stream.read_exact(&mut message).unwrap();
+    message
+}
+
+fn agree_over_a_stream<K: Kem>(protocol: Protocol)
+where
+    K::PublicKey: Clone + Send + 'static,
+    K::SecretKey: Send + 'static,
+{
+    let (responder_pk, responder_sk) = K::keypair().unwrap();
+    let (initiator_pk, initiator_sk) = K::keypair().unwrap();
+    let (mut client, mut server) = pipe();
+
+    let (server_pk, client_pk) = (responder_pk.clone(), initiator_pk.clone());
+    let server = thread::spawn(move || {
+        let responder = match protocol {
+            Protocol::Uake => Responder::<K>::uake(&server_pk, &responder_sk),
+            Protocol::Ake => Responder::<K>::ake(&server_pk, &responder_sk, &client_pk),
+        };
+        let initiation = receive(&mut server, protocol.initiation_bytes::<K>());
+        let (pending, response) = responder.respond(&initiation).unwrap();
+        server.write_all(&response).unwrap();
+        pending.finish(&receive(&mut server, CONFIRMATION_BYTES)).unwrap()
+    });
+
+    let (initiator, initiation) = match protocol {
+        Protocol::Uake => Initiator::<K>::uake(&responder_pk).unwrap(),
+        Protocol::Ake => Initiator::<K>::ake(&responder_pk, &initiator_pk, &initiator_sk).unwrap(),
+    };
+    assert_eq!(initiation.len(), protocol.initiation_bytes::<K>());
+    client.write_all(&initiation).unwrap();
+    let response = receive(&mut client, protocol.response_bytes::<K>());
+    let (client_key, confirmation) = initiator.finish(&response).unwrap();
+    client.write_all(&confirmation).unwrap();
+
+    let server_key = server.join().unwrap();
+    assert!(client_key == server_key, "{} {}", protocol.name(), K::CIPHERTEXT_BYTES);
+}
+
+#[test]
+fn both_sides_agree_over_a_byte_stream() {
+    for protocol in [Protocol::Uake, Protocol::Ake] {
+        agree_over_a_stream::<MlKem768>(protocol);
+        agree_over_a_stream::<Kyber512>(protocol);
+        agree_over_a_stream::<Kyber1024_90s>(protocol);
+    }
+    assert_eq!(Protocol::Uake.response_bytes::<MlKem768>(), MlKem768::CIPHERTEXT_BYTES + CONFIRMATION_BYTES);
+    assert_eq!(Protocol::Ake.response_bytes::<MlKem768>(), 2 * MlKem768::CIPHERTEXT_BYTES + CONFIRMATION_BYTES);
+}
+
+fn sha3(parts: &[&[u8]]) -> Vec<u8> {
+    hash(MessageDigest::sha3_256(), &parts.concat()).unwrap().to_vec()
+}
+
+// Runs Kyber.AKE over ML-KEM-768 with seeded randomness, returning the
+// initiator's key, both messages carrying a tag, and the responder's state
+fn seeded_ake(seed: u8) -> (SharedSecret, Vec<u8>, Vec<u8>, AwaitingConfirmation) {
+    let mut rng = ChaCha20Rng::from_seed([seed; 32]);
+    let (responder_pk, responder_sk) = MlKem768::keypair_with_rng(&mut rng).unwrap();
+    let (initiator_pk, initiator_sk) = MlKem768::keypair_with_rng(&mut rng).unwrap();
+    let (initiator, initiation) = Initiator::<MlKem768>::ake_with_rng(&responder_pk, &initiator_pk, &initiator_sk, &mut rng).unwrap();
+    let (pending, response) = Responder::<MlKem768>::ake(&responder_pk, &responder_sk, &initiator_pk).respond_with_rng(&initiation, &mut rng).unwrap();
+    let (key, confirmation) = initiator.finish(&response).unwrap();
+    (key, response, confirmation, pending)
+}
+
+#[test]
+fn keys_and_tags_hash_the_whole_transcript() {
+    let (key, response, confirmation, _) = seeded_ake(9);
+    let (again, ..) = seeded_ake(9);
+    assert!(key == again);
+
+    // The same exchange step by step, with OpenSSL's SHA3-256
+    let mut rng = ChaCha20Rng::from_seed([9; 32]);
+    let (pk_r, sk_r) = MlKem768::keypair_with_rng(&mut rng).unwrap();
+    let (pk_i, sk_i) = MlKem768::keypair_with_rng(&mut rng).unwrap();
+    let (pk_e, sk_e) = MlKem768::keypair_with_rng(&mut rng).unwrap();
+    let (c_r, k_r) = MlKem768::encapsulate_with_rng(&pk_r, &mut rng).unwrap();
+    let (c_e, k_e) = MlKem768::encapsulate_with_rng(&pk_e, &mut rng).unwrap();
+    let (c_i, k_i) = MlKem768::encapsulate_with_rng(&pk_i, &mut rng).unwrap();
+    assert!(MlKem768::decapsulate(&sk_r, &c_r).unwrap() == k_r);
+    assert!(MlKem768::decapsulate(&sk_e, &c_e).unwrap() == k_e);
+    assert!(MlKem768::decapsulate(&sk_i, &c_i).unwrap() == k_i);
+
+    let label = b"Kyber.AKE ML-KEM-768";
+    let th = sha3(&[label, pk_r.as_bytes(), pk_i.as_bytes(), pk_e.as_bytes(), c_r.as_bytes(), c_e.as_bytes(), c_i.as_bytes()]);
+    let k = [k_e.as_bytes(), k_r.as_bytes(), k_i.as_bytes()].concat();
+    let kc = sha3(&[label, b" confirmation key", &k, &th]);
+    assert_eq!(key.as_bytes(), sha3(&[label, b" session key", &k, &th]));
+    assert_eq!(response, [c_e.as_bytes(), c_i.as_bytes(), &sha3(&[&kc, b"responder"])].concat());
+    assert_eq!(confirmation, sha3(&[&kc, b"initiator"]));
+}
+
+fn is_ake_error<T>(result: Result<T, KyberError>) -> bool {
+    matches!(result, Err(KyberError::Ake(_)))
+}
+
+#[test]
+fn altered_messages_are_caught() {
+    let (_, response, confirmation, _) = seeded_ake(3);
+    let mut rng = ChaCha20Rng::from_seed([3; 32]);
+    let (responder_pk, responder_sk) = MlKem768::keypair_with_rng(&mut rng).unwrap();
+    let (initiator_pk, initiator_sk) = MlKem768::keypair_with_rng(&mut rng).unwrap();
+    let responder = Responder::<MlKem768>::ake(&responder_pk, &responder_sk, &initiator_pk);
+
+    // A flipped bit in the initiation, or in the first or second ciphertext or
+    // the tag of the response, fails at the initiator
+    let (_, initiation) = Initiator::<MlKem768>::ake_with_rng(&responder_pk, &initiator_pk, &initiator_sk, &mut rng.clone()).unwrap();
+    for i in [0, MlKem768::PUBLIC_KEY_BYTES + 5, initiation.len() - 1] {
+        let (initiator, initiation) = Initiator::<MlKem768>::ake_with_rng(&responder_pk, &initiator_pk, &initiator_sk, &mut rng.clone()).unwrap();
+        let mut edited = initiation.clone();
+        edited[i] ^= 1;
+        let (_, response) = responder.respond(&edited).unwrap();
+        assert!(is_ake_error(initiator.finish(&response)), "initiation byte {}", i);
+    }
+    for i in [0, MlKem768::CIPHERTEXT_BYTES + 1, response.len() - 1] {
+        let (initiator, _) = Initiator::<MlKem768>::ake_with_rng(&responder_pk, &initiator_pk, &initiator_sk, &mut rng.clone()).unwrap();
+        let mut edited = response.clone();
+        edited[i] ^= 1;
+        assert!(is_ake_error(initiator.finish(&edited)), "response byte {}", i);
+    }
+
+    // An altered, cut or missing confirmation fails at the responder
+    let mut edited = confirmation.clone();
+    edited[CONFIRMATION_BYTES - 1] ^= 0x80;
+    for bad in [edited, confirmation[..CONFIRMATION_BYTES - 1].to_vec(), Vec::new()] {
+        let (.., pending) = seeded_ake(3);
+        assert!(is_ake_error(pending.finish(&bad)));
+    }
+    let (.., pending) = seeded_ake(3);
+    assert!(pending.finish(&confirmation).is_ok());
+
+    // Messages of the wrong length
+    assert!(is_ake_error(responder.respond(&initiation[1..])));
+    let (initiator, _) = Initiator::<MlKem768>::ake_with_rng(&responder_pk, &initiator_pk, &initiator_sk, &mut rng).unwrap();
+    let err = initiator.finish(&response[..MlKem768::CIPHERTEXT_BYTES + CONFIRMATION_BYTES]).err().unwrap();
+    assert_eq!(err.exit_code(), 16);
+}
+
+#[test]
+fn only_the_holders_of_the_static_keys_finish() {
+    let (responder_pk, responder_sk) = MlKem768::keypair().unwrap();
+    let (initiator_pk, initiator_sk) = MlKem768::keypair().unwrap();
+    let (_, other_sk) = MlKem768::keypair().unwrap();
+
+    // A responder without the secret key initiated to
+    let (initiator, initiation) = Initiator::<MlKem768>::uake(&responder_pk).unwrap();
+    let (_, response) = Responder::<MlKem768>::uake(&responder_pk, &other_sk).respond(&initiation).unwrap();
+    let err = initiator.finish(&response).err().unwrap();
+    assert!(err.to_string().contains("responder does not hold its static key"), "{}", err);
+
+    // An initiator claiming a static key it does not hold
+    let (impostor, initiation) = Initiator::<MlKem768>::ake(&responder_pk, &initiator_pk, &other_sk).unwrap();
+    let (_, response) = Responder::<MlKem768>::ake(&responder_pk, &responder_sk, &initiator_pk).respond(&initiation).unwrap();
+    assert!(is_ake_error(impostor.finish(&response)));
+
+    // A responder expecting another initiator
+    let (initiator, initiation) = Initiator::<MlKem768>::ake(&responder_pk, &initiator_pk, &initiator_sk).unwrap();
+    let (other_pk, _) = MlKem768::keypair().unwrap();
+    let (_, response) = Responder::<MlKem768>::ake(&responder_pk, &responder_sk, &other_pk).respond(&initiation).unwrap();
+    assert!(is_ake_error(initiator.finish(&response)));
+
+    // The two protocols, and parameter sets of the same size, do not mix
+    let (initiator, initiation) = Initiator::<MlKem768>::uake(&responder_pk).unwrap();
+    let (_, response) = Responder::<MlKem768>::ake(&responder_pk, &responder_sk, &initiator_pk).respond(&initiation).unwrap();
+    assert!(is_ake_error(initiator.finish(&response)));
+    let (initiator, initiation) = Initiator::<MlKem768>::uake(&responder_pk).unwrap();
+    let (_, response) = Responder::<Kyber768>::uake(
+        &<Kyber768 as Kem>::PublicKey::from_bytes(responder_pk.as_bytes()).unwrap(),
+        &<Kyber768 as Kem>::SecretKey::from_bytes(responder_sk.as_bytes()).unwrap(),
+    )
+    .respond(&initiation)
+    .unwrap();
+    assert!(is_ake_error(initiator.finish(&response)));
+}
@@ -0,0 +1,68 @@
+// Key generation, encapsulation and decapsulation work entirely on the stack.
+// A counting allocator checks that none of them touches the heap once the
+// library has been initialised, whether the randomness comes from a caller's
+// RNG or the library-wide DRBG. The DRBG's AES runs in OpenSSL, whose cipher
+// context is allocated by OpenSSL itself and not counted here.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use kyber::{Kem, Kyber1024_90s, Kyber512, MlKem768};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations_during(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+fn round_trip<K: Kem>(rng: &mut ChaCha20Rng) {
+    let (pk, sk) = K::keypair().unwrap();
+    let (ct, ss) = K::encapsulate(&pk).unwrap();
+    assert!(K::decapsulate(&sk, &ct).unwrap() == ss);
+
+    let (pk, sk) = K::keypair_with_rng(rng).unwrap();
+    let (ct, ss) = K::encapsulate_with_rng(&pk, rng).unwrap();
+    assert!(K::decapsulate(&sk, &ct).unwrap() == ss);
+
+    let (pk, sk) = K::keypair_derand(&[1u8; 32], &[2u8; 32]).unwrap();
+    let (ct, ss) = K::encapsulate_derand(&pk, &[3u8; 32]).unwrap();
+    assert!(K::decapsulate(&sk, &ct).unwrap() == ss);
+
+    let pk = K::prepare_public_key(&pk).unwrap();
+    let sk = K::prepare_secret_key(&sk).unwrap();
+    let (ct, ss) = K::encapsulate_prepared_with_rng(&pk, rng).unwrap();
+    assert!(K::decapsulate_prepared(&sk, &ct).unwrap() == ss);
+}
+
+#[test]
+fn kem_operations_do_not_allocate() {
+    let mut rng = ChaCha20Rng::from_seed([9u8; 32]);
+    // The first operation seeds the library-wide DRBG, which allocates once
+    round_trip::<Kyber512>(&mut rng);
+
+    assert_eq!(allocations_during(|| round_trip::<Kyber512>(&mut rng)), 0);
+    assert_eq!(allocations_during(|| round_trip::<MlKem768>(&mut rng)), 0);
+    assert_eq!(allocations_during(|| round_trip::<Kyber1024_90s>(&mut rng)), 0);
+}
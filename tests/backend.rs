@@ -0,0 +1,101 @@
+// Differential tests of the SIMD backends against the scalar reference code on
+// random inputs. Backends the CPU lacks are skipped.
+
+use kyber::Backend;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+const TRIALS: usize = 1000;
+const Q: i16 = 3329;
+
+fn random_poly(rng: &mut ChaCha20Rng, bound: i16) -> [i16; 256] {
+    let mut r = [0i16; 256];
+    for c in r.iter_mut() {
+        *c = rng.gen_range(-bound..=bound);
+    }
+    r
+}
+
+fn simd_backends() -> Vec<Backend> {
+    Backend::available().into_iter().filter(|&backend| backend != Backend::Scalar).collect()
+}
+
+#[test]
+fn detected_backend_is_available() {
+    assert!(Backend::detected().is_available());
+    assert!(Backend::available().contains(&Backend::Scalar));
+    #[cfg(target_arch = "x86_64")]
+    assert!(Backend::Sse2.is_available());
+}
+
+#[test]
+fn ntt_matches_scalar() {
+    let mut rng = ChaCha20Rng::seed_from_u64(1);
+    for backend in simd_backends() {
+        for _ in 0..TRIALS {
+            // poly_ntt is only ever given coefficients below q in absolute value
+            let input = random_poly(&mut rng, Q - 1);
+            let (mut expected, mut actual) = (input, input);
+            Backend::Scalar.ntt(&mut expected);
+            backend.ntt(&mut actual);
+            assert_eq!(actual, expected, "{} ntt", backend.name());
+        }
+    }
+}
+
+#[test]
+fn invntt_matches_scalar() {
+    let mut rng = ChaCha20Rng::seed_from_u64(2);
+    for backend in simd_backends() {
+        for _ in 0..TRIALS {
+            let input = random_poly(&mut rng, Q);
+            let (mut expected, mut actual) = (input, input);
+            Backend::Scalar.invntt(&mut expected);
+            backend.invntt(&mut actual);
+            assert_eq!(actual, expected, "{} invntt", backend.name());
+        }
+    }
+}
+
+#[test]
+fn basemul_matches_scalar() {
+    let mut rng = ChaCha20Rng::seed_from_u64(3);
+    for backend in simd_backends() {
+        for _ in 0..TRIALS {
+            let a = random_poly(&mut rng, i16::MAX);
+            let b = random_poly(&mut rng, i16::MAX);
+            let (mut expected, mut actual) = ([0i16; 256], [0i16; 256]);
+            Backend::Scalar.basemul(&mut expected, &a, &b);
+            backend.basemul(&mut actual, &a, &b);
+            assert_eq!(actual, expected, "{} basemul", backend.name());
+        }
+    }
+}
+
+#[test]
+fn keccak_x4_matches_scalar() {
+    let mut rng = ChaCha20Rng::seed_from_u64(4);
+    for backend in simd_backends() {
+        for _ in 0..TRIALS / 10 {
+            let mut states = [[0u64; 25]; 4];
+            for lane in states.iter_mut().flatten() {
+                *lane = rng.next_u64();
+            }
+            let mut expected = states;
+            Backend::Scalar.keccak_f1600_x4(&mut expected);
+            backend.keccak_f1600_x4(&mut states);
+            assert_eq!(states, expected, "{} keccak_f1600_x4", backend.name());
+        }
+    }
+}
+
+#[test]
+fn scalar_keccak_x4_is_keccak_f1600() {
+    // First permutation of the all-zero state, from the Keccak team's KeccakF-1600 test vectors
+    let mut states = [[0u64; 25]; 4];
+    Backend::Scalar.keccak_f1600_x4(&mut states);
+    for state in &states {
+        assert_eq!(state[0], 0xF1258F7940E1DDE7);
+        assert_eq!(state[24], 0xEAF1FF7B5CECA249);
+    }
+}
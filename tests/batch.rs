@@ -0,0 +1,65 @@
+// Batch key generation and encapsulation give exactly what the single calls do.
+
+use kyber::{Kem, KemBytes, Kyber1024, Kyber512_90s, MlKem768};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+// 6 keys cover one full group of four and a partial one
+fn check_batch_matches_single<K: Kem>() {
+    let mut batch_rng = ChaCha20Rng::from_seed([9u8; 32]);
+    let mut single_rng = ChaCha20Rng::from_seed([9u8; 32]);
+
+    let keys = K::keypair_batch_with_rng(6, &mut batch_rng).unwrap();
+    assert_eq!(keys.len(), 6);
+    for (pk, sk) in &keys {
+        let (pk_single, sk_single) = K::keypair_with_rng(&mut single_rng).unwrap();
+        assert_eq!(pk.as_bytes(), pk_single.as_bytes());
+        assert!(sk.as_bytes() == sk_single.as_bytes());
+    }
+
+    let pks: Vec<K::PublicKey> = keys.iter().map(|(pk, _)| K::PublicKey::from_bytes(pk.as_bytes()).unwrap()).collect();
+    let encapsulated = K::encapsulate_batch_with_rng(&pks, &mut batch_rng).unwrap();
+    assert_eq!(encapsulated.len(), 6);
+    for ((pk, sk), (ct, ss)) in keys.iter().zip(&encapsulated) {
+        let (ct_single, ss_single) = K::encapsulate_with_rng(pk, &mut single_rng).unwrap();
+        assert_eq!(ct.as_bytes(), ct_single.as_bytes());
+        assert!(*ss == ss_single);
+        assert!(K::decapsulate(sk, ct).unwrap() == *ss);
+    }
+}
+
+#[test]
+fn mlkem768_batch_matches_single() {
+    check_batch_matches_single::<MlKem768>();
+}
+
+#[test]
+fn kyber1024_batch_matches_single() {
+    check_batch_matches_single::<Kyber1024>();
+}
+
+#[test]
+fn kyber512_90s_batch_matches_single() {
+    check_batch_matches_single::<Kyber512_90s>();
+}
+
+#[test]
+fn global_drbg_batches_round_trip() {
+    let keys = MlKem768::keypair_batch(5).unwrap();
+    let pks: Vec<_> = keys.iter().map(|(pk, _)| pk.clone()).collect();
+    let encapsulated = MlKem768::encapsulate_batch(&pks).unwrap();
+    for ((_, sk), (ct, ss)) in keys.iter().zip(&encapsulated) {
+        assert!(MlKem768::decapsulate(sk, ct).unwrap() == *ss);
+    }
+    assert!(MlKem768::keypair_batch(0).unwrap().is_empty());
+}
+
+#[test]
+fn batch_rejects_invalid_public_key() {
+    let (pk, _) = MlKem768::keypair().unwrap();
+    // Coefficient bytes of all ones decode to 4095, which is not reduced modulo q
+    let mut bad = pk.as_bytes().to_vec();
+    bad[..3].copy_from_slice(&[0xff, 0xff, 0xff]);
+    let bad = <MlKem768 as Kem>::PublicKey::from_bytes(&bad).unwrap();
+    assert!(MlKem768::encapsulate_batch(&[pk, bad]).is_err());
+}
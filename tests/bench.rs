@@ -0,0 +1,39 @@
+// The benchmark runner times every operation and serializes its report.
+
+use kyber::bench;
+use kyber::{Kyber512_90s, MlKem768};
+
+const OPERATIONS: [&str; 10] = [
+    "gen_matrix",
+    "poly_ntt",
+    "poly_invntt_tomont",
+    "poly_getnoise_eta1",
+    "indcpa_keypair",
+    "indcpa_enc",
+    "indcpa_dec",
+    "kyber_keypair",
+    "kyber_encaps",
+    "kyber_decaps",
+];
+
+#[test]
+fn report_covers_every_operation() {
+    let report = bench::run::<MlKem768>(5).unwrap();
+    assert_eq!(report.parameter_set, "ML-KEM-768");
+    assert_eq!(report.iterations, 5);
+    let names: Vec<&str> = report.results.iter().map(|result| result.name).collect();
+    assert_eq!(names, OPERATIONS);
+    // Key generation does strictly more work than the NTT of one polynomial
+    let median = |name| report.results.iter().find(|result| result.name == name).unwrap().median;
+    assert!(median("kyber_keypair") > median("poly_ntt"));
+}
+
+#[test]
+fn json_lists_every_report() {
+    let reports = vec![bench::run::<MlKem768>(2).unwrap(), bench::run::<Kyber512_90s>(2).unwrap()];
+    let json = bench::to_json(&reports);
+    assert!(json.starts_with("[{\"parameter_set\":\"ML-KEM-768\","));
+    assert!(json.contains("{\"parameter_set\":\"Kyber-90s-512\","));
+    assert!(json.ends_with("]}]"));
+    assert_eq!(json.matches("\"name\":").count(), 2 * OPERATIONS.len());
+}
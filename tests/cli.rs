@@ -0,0 +1,243 @@
+// keygen, encaps and decaps keep each party's material in its own files: the
+// sender only ever sees the public key, and both sides end up with the same
+// shared secret.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use kyber::{KeyFile, KyberVariant};
+
+// One directory per test, as the tests run in parallel
+fn scratch_dir(test: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("kyber-cli-{}-{}", std::process::id(), test))
+}
+
+fn scratch(test: &str, name: &str) -> String {
+    std::fs::create_dir_all(scratch_dir(test)).unwrap();
+    scratch_dir(test).join(name).to_str().unwrap().to_string()
+}
+
+fn kyber(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_kyber")).args(args).output().unwrap()
+}
+
+fn read(path: &str) -> KeyFile {
+    KeyFile::read(path, KyberVariant::Round3).unwrap().0
+}
+
+#[test]
+fn each_party_sees_only_its_own_material() {
+    let file = |name| scratch("own", name);
+    let (pk, sk, ct, sender_ss, receiver_ss) = (file("pk"), file("sk"), file("ct"), file("sender.ss"), file("receiver.ss"));
+
+    assert!(kyber(&["keygen", "--pk", &pk, "--sk", &sk, "-s", "3", "-m"]).status.success());
+    let public = read(&pk);
+    assert_eq!(public.parameter_set_name(), "ML-KEM-768");
+    assert!(public.public_key.is_some() && public.private_key.is_none());
+    assert!(read(&sk).private_key.is_some());
+
+    // The sender needs neither --sec nor the variant, the public key names them
+    assert!(kyber(&["encaps", "--pk", &pk, "--ct", &ct, "--ss", &sender_ss]).status.success());
+    let ciphertext = read(&ct);
+    assert!(ciphertext.ciphertext.is_some() && ciphertext.private_key.is_none() && ciphertext.shared_secret.is_none());
+
+    assert!(kyber(&["decaps", "--sk", &sk, "--ct", &ct, "--ss", &receiver_ss]).status.success());
+    let (sender, receiver) = (read(&sender_ss), read(&receiver_ss));
+    assert!(sender.shared_secret.is_some() && sender.shared_secret == receiver.shared_secret);
+
+    std::fs::remove_dir_all(scratch_dir("own")).ok();
+}
+
+#[test]
+fn mismatched_files_are_rejected() {
+    let file = |name| scratch("mismatch", name);
+    let (pk, sk, other_pk, other_sk, ct, ss) = (file("pk"), file("sk"), file("other.pk"), file("other.sk"), file("ct"), file("ss"));
+    assert!(kyber(&["keygen", "--pk", &pk, "--sk", &sk, "-s", "2"]).status.success());
+    assert!(kyber(&["keygen", "--pk", &other_pk, "--sk", &other_sk, "-s", "5"]).status.success());
+    assert!(kyber(&["encaps", "--pk", &pk, "--ct", &ct, "--ss", &ss]).status.success());
+
+    // A ciphertext for another parameter set, and a private key where the public key goes
+    assert_eq!(kyber(&["decaps", "--sk", &other_sk, "--ct", &ct]).status.code(), Some(12));
+    assert_eq!(kyber(&["encaps", "--pk", &sk, "--ct", &ct, "--ss", &ss]).status.code(), Some(6));
+
+    std::fs::remove_dir_all(scratch_dir("mismatch")).ok();
+}
+
+#[test]
+fn validate_reports_the_failing_check() {
+    let file = |name| scratch("validate", name);
+    let (pk, sk) = (file("pk"), file("sk"));
+    assert!(kyber(&["keygen", "--pk", &pk, "--sk", &sk, "-s", "3", "-m"]).status.success());
+    for path in [&pk, &sk] {
+        let output = kyber(&["--validate", "-f", path]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    }
+
+    // A public key coefficient of 0xfff, and a changed H(pk) in the private key
+    let mut public = read(&pk);
+    let key = public.public_key.as_mut().unwrap();
+    key[0] = 0xff;
+    key[1] |= 0x0f;
+    public.write(&pk).unwrap();
+    let mut private = read(&sk);
+    let key = private.private_key.as_mut().unwrap();
+    let len = key.len();
+    key[len - 64] ^= 0x01;
+    private.write(&sk).unwrap();
+
+    for (path, failure) in [(&pk, "Public key: FAILED, invalid key: public key modulus check failed"), (&sk, "Private key: FAILED, invalid key: private key hash check failed")] {
+        let output = kyber(&["--validate", "-f", path]);
+        assert_eq!(output.status.code(), Some(7));
+        assert!(String::from_utf8_lossy(&output.stdout).contains(failure), "{}", String::from_utf8_lossy(&output.stdout));
+    }
+
+    std::fs::remove_dir_all(scratch_dir("validate")).ok();
+}
+
+#[test]
+fn legacy_enc_keeps_the_private_key_apart() {
+    let file = |name| scratch("legacy", name);
+    let (base, batch, receiver_ss) = (file("out"), file("batch"), file("receiver.ss"));
+    let with = |base: &str, ext: &str| format!("{}.{}", base, ext);
+
+    assert!(kyber(&["-s", "2", "--enc", "-f", &base]).status.success());
+    assert!(!std::path::Path::new(&base).exists());
+    for ext in ["pk", "ct", "ss"] {
+        assert!(read(&with(&base, ext)).private_key.is_none(), "{}", ext);
+    }
+    assert!(read(&with(&base, "sk")).private_key.is_some());
+    assert!(read(&with(&base, "sk")).ciphertext.is_none() && read(&with(&base, "sk")).shared_secret.is_none());
+    assert!(kyber(&["decaps", "--sk", &with(&base, "sk"), "--ct", &with(&base, "ct"), "--ss", &receiver_ss]).status.success());
+    assert_eq!(read(&receiver_ss).shared_secret, read(&with(&base, "ss")).shared_secret);
+
+    // The old --dec still takes the same <file>, and writes nothing next to the private key
+    let output = kyber(&["-s", "2", "--dec", "-f", &base]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Both shared secrets match!"));
+    assert!(!std::path::Path::new(&base).exists() && read(&with(&base, "sk")).ciphertext.is_none());
+
+    // An encrypted private key, decapsulated with the quick form
+    let (protected, password) = (file("protected"), file("password"));
+    std::fs::write(&password, "correct horse\n").unwrap();
+    assert!(kyber(&["-s", "5", "--enc", "-f", &protected, "--password-file", &password]).status.success());
+    assert!(read(&with(&protected, "sk")).is_encrypted());
+    let output = kyber(&["-D5", "-f", &protected, "--password-file", &password]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Both shared secrets match!"));
+
+    assert!(kyber(&["-s", "3", "-m", "--enc", "-f", &batch, "--count", "2"]).status.success());
+    for index in ["1", "2"] {
+        let base = with(&batch, index);
+        assert!(read(&with(&base, "ct")).private_key.is_none() && read(&with(&base, "sk")).private_key.is_some());
+        assert_eq!(read(&with(&base, "pk")).parameter_set_name(), "ML-KEM-768");
+    }
+
+    std::fs::remove_dir_all(scratch_dir("legacy")).ok();
+}
+
+#[test]
+fn usage_errors_fail_with_exit_code_2() {
+    let file = |name| scratch("usage", name);
+    let (pk, sk, ct, ss) = (file("pk"), file("sk"), file("ct"), file("ss"));
+
+    for args in [
+        &["keygen", "--pk", &pk][..],
+        &["keygen", "--pk", &pk, "--sk", &sk],
+        &["keygen", "--pk", &pk, "--sk", &sk, "-s", "3", "--bogus"],
+        &["keygen", "--pk", &pk, "--sk", &sk, "-s", "4"],
+        &["encaps", "--pk", &pk, "--ct", &ct],
+        &["decaps", "--ct", &ct],
+        &["seal", "--pk", &pk],
+        &["open", &ct, &ss],
+        &["bench", "--iterations", "0"],
+        // The older flags
+        &[],
+        &["--enc", "-f", &pk],
+        &["-s", "3"],
+        &["-s", "3", "--enc"],
+        &["-s", "4", "--enc", "-f", &pk],
+        &["-s", "3", "--enc", "-f", &pk, "--bogus"],
+        &["-E2", "-D3", "-f", &pk],
+        &["-s", "3", "--dec", "-f", &pk, "--count", "2"],
+    ] {
+        let output = kyber(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("Error: "), "{:?}", args);
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("Error"), "{:?}", args);
+    }
+    assert!(!std::path::Path::new(&pk).exists() && !std::path::Path::new(&sk).exists());
+
+    std::fs::remove_dir_all(scratch_dir("usage")).ok();
+}
+
+#[test]
+fn hybrid_keys_only_work_with_the_hybrid_commands() {
+    let file = |name| scratch("hybrid", name);
+    let (pk, sk, ct, sender_ss, receiver_ss) = (file("pk"), file("sk"), file("ct"), file("sender.ss"), file("receiver.ss"));
+
+    assert!(kyber(&["hybrid-keygen", "--pk", &pk, "--sk", &sk, "-m"]).status.success());
+    assert_eq!(read(&pk).parameter_set_name(), "X25519+ML-KEM-768");
+    assert!(kyber(&["hybrid-encaps", "--pk", &pk, "--ct", &ct, "--ss", &sender_ss]).status.success());
+    assert!(kyber(&["hybrid-decaps", "--sk", &sk, "--ct", &ct, "--ss", &receiver_ss]).status.success());
+    assert!(read(&sender_ss).shared_secret.is_some() && read(&sender_ss).shared_secret == read(&receiver_ss).shared_secret);
+
+    // Neither kind of key is taken for the other
+    assert_eq!(kyber(&["encaps", "--pk", &pk, "--ct", &ct, "--ss", &sender_ss]).status.code(), Some(12));
+    assert!(kyber(&["keygen", "--pk", &pk, "--sk", &sk, "-s", "3", "-m"]).status.success());
+    assert_eq!(kyber(&["hybrid-encaps", "--pk", &pk, "--ct", &ct, "--ss", &sender_ss]).status.code(), Some(12));
+
+    std::fs::remove_dir_all(scratch_dir("hybrid")).ok();
+}
+
+#[test]
+fn seal_and_open_a_file() {
+    let file = |name| scratch("seal", name);
+    let (pk, sk, input, sealed, output) = (file("pk"), file("sk"), file("input"), file("input.sealed"), file("output"));
+    let plaintext: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+    std::fs::write(&input, &plaintext).unwrap();
+
+    assert!(kyber(&["keygen", "--pk", &pk, "--sk", &sk, "-s", "5", "-m"]).status.success());
+    assert!(kyber(&["seal", "--pk", &pk, &input, &sealed]).status.success());
+    assert!(std::fs::read(&sealed).unwrap().starts_with(b"KYBER-SEALED v1\nparameter-set: ML-KEM-1024\n"));
+    assert!(kyber(&["open", "--sk", &sk, &sealed, &output]).status.success());
+    assert_eq!(std::fs::read(&output).unwrap(), plaintext);
+
+    // A cut file fails without leaving partial output
+    let contents = std::fs::read(&sealed).unwrap();
+    std::fs::write(&sealed, &contents[..contents.len() - 100]).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!(kyber(&["open", "--sk", &sk, &sealed, &output]).status.code(), Some(15));
+    assert!(!std::path::Path::new(&output).exists());
+
+    std::fs::remove_dir_all(scratch_dir("seal")).ok();
+}
+
+#[test]
+fn seal_to_several_keys() {
+    let file = |name: &str| scratch("envelope", name);
+    let (input, sealed) = (file("input"), file("input.sealed"));
+    std::fs::write(&input, b"for the whole team").unwrap();
+    let keys = [("a", "2"), ("b", "5")];
+    for (name, level) in keys {
+        let (pk, sk) = (file(&format!("{}.pk", name)), file(&format!("{}.sk", name)));
+        assert!(kyber(&["keygen", "--pk", &pk, "--sk", &sk, "-s", level, "-m"]).status.success());
+    }
+
+    let (pk_a, pk_b) = (file("a.pk"), file("b.pk"));
+    let out = kyber(&["seal", "--pk", &pk_a, "--pk", &pk_b, &input, &sealed]);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("to 2 recipients"));
+    assert!(std::fs::read(&sealed).unwrap().starts_with(b"KYBER-SEALED v2\n"));
+    for (name, _) in keys {
+        let output = file(&format!("{}.out", name));
+        assert!(kyber(&["open", "--sk", &file(&format!("{}.sk", name)), &sealed, &output]).status.success());
+        assert_eq!(std::fs::read(&output).unwrap(), b"for the whole team");
+    }
+
+    // The same key twice is refused without writing anything
+    std::fs::remove_file(&sealed).unwrap();
+    assert_eq!(kyber(&["seal", "--pk", &pk_a, "--pk", &pk_a, &input, &sealed]).status.code(), Some(15));
+    assert!(!std::path::Path::new(&sealed).exists());
+
+    std::fs::remove_dir_all(scratch_dir("envelope")).ok();
+}
@@ -0,0 +1,104 @@
+// Private keys encrypted under a password are never written in the clear, come
+// back only with the right password, and any change to them is detected.
+
+use kyber::{KeyFile, Kem, KemBytes, KyberError, MlKem768};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+fn encrypted_key_file() -> KeyFile {
+    let (pk, sk) = MlKem768::keypair_derand(&[1u8; 32], &[2u8; 32]).unwrap();
+    let (ct, ss) = MlKem768::encapsulate_derand(&pk, &[3u8; 32]).unwrap();
+    let mut file = KeyFile::for_kem::<MlKem768>();
+    file.private_key = Some(Zeroizing::new(sk.as_bytes().to_vec()));
+    file.public_key = Some(pk.as_bytes().to_vec());
+    file.ciphertext = Some(ct.as_bytes().to_vec());
+    file.shared_secret = Some(Zeroizing::new(ss.as_bytes().to_vec()));
+    file.encrypt_private_key(b"correct horse").unwrap();
+    file
+}
+
+// Replaces one character of the encrypted-private-key line and the checksum,
+// so only the encrypted key is wrong
+fn edit_encrypted_key(text: &str, offset_from_end: usize) -> String {
+    let start = text.find("encrypted-private-key: ").unwrap();
+    let end = start + text[start..].find('\n').unwrap();
+    let mut body = text[..text.rfind("checksum: ").unwrap()].to_string();
+    let i = end - offset_from_end;
+    let flipped = if &body[i..i + 1] == "0" { "1" } else { "0" };
+    body.replace_range(i..i + 1, flipped);
+    format!("{}checksum: {}\n", body, hex::encode(Sha256::digest(body.as_bytes())))
+}
+
+#[test]
+fn round_trip_with_the_right_password() {
+    let file = encrypted_key_file();
+    let text = file.encode().unwrap();
+    let private_key_hex = hex::encode(file.private_key.as_ref().unwrap().as_slice());
+    assert!(!text.contains(&private_key_hex));
+    assert!(!text.contains("\nprivate-key: "));
+    assert!(text.contains("\nencrypted-private-key: scrypt:17:8:1:"));
+
+    let mut parsed = KeyFile::parse_v1(&text).unwrap();
+    assert!(parsed.is_encrypted());
+    assert!(parsed.private_key.is_none());
+    parsed.decrypt_private_key(b"correct horse").unwrap();
+    assert!(parsed.private_key == file.private_key);
+
+    let sk = <MlKem768 as Kem>::SecretKey::from_bytes(parsed.private_key.as_ref().unwrap()).unwrap();
+    let ct = <MlKem768 as Kem>::Ciphertext::from_bytes(parsed.ciphertext.as_ref().unwrap()).unwrap();
+    assert_eq!(MlKem768::decapsulate(&sk, &ct).unwrap().as_bytes(), parsed.shared_secret.as_ref().unwrap().as_slice());
+
+    // A decrypted file is written encrypted again, with the same salt and nonce
+    assert_eq!(parsed.encode().unwrap(), text);
+
+    // Each encryption uses a fresh salt and nonce
+    let mut again = KeyFile::parse_v1(&text).unwrap();
+    again.decrypt_private_key(b"correct horse").unwrap();
+    again.encrypt_private_key(b"correct horse").unwrap();
+    assert_ne!(again.encrypted_private_key, file.encrypted_private_key);
+}
+
+#[test]
+fn wrong_passwords_and_edits_are_rejected() {
+    let text = encrypted_key_file().encode().unwrap();
+
+    let mut parsed = KeyFile::parse_v1(&text).unwrap();
+    let err = parsed.decrypt_private_key(b"correct horse ").unwrap_err();
+    assert!(matches!(err, KyberError::Password(_)));
+    assert_eq!(err.exit_code(), 13);
+    assert!(parsed.private_key.is_none());
+
+    // The tag, the ciphertext, the nonce and the salt, counting hex digits from
+    // the end of the line
+    let data = 2 * (MlKem768::SECRET_KEY_BYTES + 16);
+    for offset in [1, 100, data + 10, data + 40] {
+        let mut edited = KeyFile::parse_v1(&edit_encrypted_key(&text, offset)).unwrap();
+        assert!(matches!(edited.decrypt_private_key(b"correct horse"), Err(KyberError::Password(_))), "offset {}", offset);
+    }
+
+    let mut empty = encrypted_key_file();
+    assert!(matches!(empty.encrypt_private_key(b""), Err(KyberError::Password(_))));
+}
+
+#[test]
+fn parser_checks_the_encrypted_key() {
+    let file = encrypted_key_file();
+    let text = file.encode().unwrap();
+    let line = text.lines().find(|line| line.starts_with("encrypted-private-key: ")).unwrap();
+    let rebuild = |replacement: &str| {
+        let body = text[..text.rfind("checksum: ").unwrap()].replace(line, replacement);
+        format!("{}checksum: {}\n", body, hex::encode(Sha256::digest(body.as_bytes())))
+    };
+
+    // Costly or malformed scrypt parameters
+    for bad in ["scrypt:30:8:1:", "scrypt:17:0:1:", "scrypt:017:8:1:", "argon2:17:8:1:"] {
+        assert!(KeyFile::parse_v1(&rebuild(&line.replace("scrypt:17:8:1:", bad))).is_err(), "{}", bad);
+    }
+    // Missing a part, or the tag cut off
+    assert!(KeyFile::parse_v1(&rebuild(&line[..line.rfind(':').unwrap()])).is_err());
+    assert!(KeyFile::parse_v1(&rebuild(&line[..line.len() - 32])).is_err());
+
+    // Not together with a private key in the clear
+    let private_key_line = format!("private-key: {}", hex::encode(file.private_key.as_ref().unwrap().as_slice()));
+    assert!(KeyFile::parse_v1(&rebuild(&format!("{}\n{}", private_key_line, line))).is_err());
+}
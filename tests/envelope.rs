@@ -0,0 +1,135 @@
+// Envelopes: one payload sealed to keys of several parameter sets, each
+// recipient finding its own line by fingerprint, and the header bound to the
+// payload so no line can be changed, dropped or added.
+
+use kyber::sealed::{fingerprint, open, seal, Envelope, EnvelopeHeader, SealedHeader};
+use kyber::{Kem, KemBytes, Kyber512, Kyber768_90s, KyberError, MlKem1024, MlKem768};
+
+const MESSAGE: &[u8] = b"release artefacts for every team";
+
+fn opened<K: Kem>(sk: &K::SecretKey, file: &[u8]) -> Result<Vec<u8>, KyberError> {
+    let mut out = Vec::new();
+    open::<K, _, _>(sk, file, &mut out)?;
+    Ok(out)
+}
+
+fn is_sealed_error<T>(result: Result<T, KyberError>) -> bool {
+    matches!(result, Err(KyberError::Sealed(_)))
+}
+
+#[test]
+fn every_recipient_opens_and_nobody_else() {
+    let (pk_a, sk_a) = Kyber512::keypair().unwrap();
+    let (pk_b, sk_b) = MlKem1024::keypair().unwrap();
+    let (pk_c, sk_c) = Kyber768_90s::keypair().unwrap();
+    let (_, outsider) = MlKem1024::keypair().unwrap();
+
+    let mut envelope = Envelope::new().unwrap();
+    envelope.add_recipient::<Kyber512>(&pk_a).unwrap();
+    envelope.add_recipient::<MlKem1024>(&pk_b).unwrap();
+    envelope.add_recipient::<Kyber768_90s>(&pk_c).unwrap();
+    let mut file = Vec::new();
+    assert_eq!(envelope.seal(MESSAGE, &mut file).unwrap(), MESSAGE.len() as u64);
+
+    let header = EnvelopeHeader::read(&mut &file[..]).unwrap();
+    let fingerprints: Vec<_> = header.recipients.iter().map(|recipient| recipient.fingerprint).collect();
+    assert_eq!(fingerprints, [fingerprint::<Kyber512>(&pk_a), fingerprint::<MlKem1024>(&pk_b), fingerprint::<Kyber768_90s>(&pk_c)]);
+    let names: Vec<_> = header.recipients.iter().map(|recipient| recipient.parameter_set.as_str()).collect();
+    assert_eq!(names, ["Kyber-512", "ML-KEM-1024", "Kyber-90s-768"]);
+    assert_eq!(header.recipients[1].encapsulation.len(), MlKem1024::CIPHERTEXT_BYTES);
+
+    assert_eq!(opened::<Kyber512>(&sk_a, &file).unwrap(), MESSAGE);
+    assert_eq!(opened::<MlKem1024>(&sk_b, &file).unwrap(), MESSAGE);
+    assert_eq!(opened::<Kyber768_90s>(&sk_c, &file).unwrap(), MESSAGE);
+    let err = opened::<MlKem1024>(&outsider, &file).unwrap_err();
+    assert!(err.to_string().contains("not sealed to this key"), "{}", err);
+
+    // The same key bytes under another parameter set have another fingerprint
+    let as_ml_kem = <MlKem768 as Kem>::PublicKey::from_bytes(pk_c.as_bytes()).unwrap();
+    assert_ne!(fingerprint::<MlKem768>(&as_ml_kem), fingerprint::<Kyber768_90s>(&pk_c));
+}
+
+// Rebuilds `file` with its header lines passed through `edit`
+fn edit_header(file: &[u8], edit: impl FnOnce(&mut Vec<String>)) -> Vec<u8> {
+    let mut reader = file;
+    EnvelopeHeader::read(&mut reader).unwrap();
+    let header_len = file.len() - reader.len();
+    let mut lines: Vec<String> = String::from_utf8(file[..header_len].to_vec()).unwrap().lines().map(str::to_string).collect();
+    edit(&mut lines);
+    [lines.join("\n").as_bytes(), b"\n", &file[header_len..]].concat()
+}
+
+#[test]
+fn recipient_lines_cannot_be_changed_dropped_or_added() {
+    let (pk_a, sk_a) = MlKem768::keypair().unwrap();
+    let (pk_b, sk_b) = MlKem768::keypair().unwrap();
+    let mut envelope = Envelope::new().unwrap();
+    envelope.add_recipient::<MlKem768>(&pk_a).unwrap();
+    envelope.add_recipient::<MlKem768>(&pk_b).unwrap();
+    let mut file = Vec::new();
+    envelope.seal(MESSAGE, &mut file).unwrap();
+
+    // A's own wrapped key changed, B's line changed, B dropped
+    let flip_last = |line: &mut String| {
+        let last = line.pop().unwrap();
+        line.push(if last == '0' { '1' } else { '0' });
+    };
+    let own_changed = edit_header(&file, |lines| flip_last(&mut lines[1]));
+    assert!(opened::<MlKem768>(&sk_a, &own_changed).unwrap_err().to_string().contains("recipient line for this key was altered"));
+    let other_changed = edit_header(&file, |lines| flip_last(&mut lines[2]));
+    assert!(is_sealed_error(opened::<MlKem768>(&sk_a, &other_changed)));
+    assert_eq!(opened::<MlKem768>(&sk_b, &other_changed).unwrap_err().exit_code(), 15);
+    let dropped = edit_header(&file, |lines| {
+        lines.remove(2);
+    });
+    assert!(is_sealed_error(opened::<MlKem768>(&sk_a, &dropped)));
+
+    // Another recipient's line copied in from a second envelope
+    let (pk_c, _) = MlKem768::keypair().unwrap();
+    let mut other = Envelope::new().unwrap();
+    other.add_recipient::<MlKem768>(&pk_c).unwrap();
+    let mut other_file = Vec::new();
+    other.seal(MESSAGE, &mut other_file).unwrap();
+    let copied = String::from_utf8_lossy(&other_file).lines().nth(1).unwrap().to_string();
+    let added = edit_header(&file, |lines| lines.insert(3, copied));
+    assert!(is_sealed_error(opened::<MlKem768>(&sk_a, &added)));
+
+    // The same line twice, and no line at all
+    let repeated = edit_header(&file, |lines| {
+        let line = lines[1].clone();
+        lines.insert(2, line);
+    });
+    assert!(is_sealed_error(EnvelopeHeader::read(&mut &repeated[..])));
+    let empty = edit_header(&file, |lines| {
+        lines.drain(1..3);
+    });
+    assert!(is_sealed_error(EnvelopeHeader::read(&mut &empty[..])));
+}
+
+#[test]
+fn addressing_mistakes_are_refused() {
+    let (pk, _) = MlKem768::keypair().unwrap();
+    let mut envelope = Envelope::new().unwrap();
+    envelope.add_recipient::<MlKem768>(&pk).unwrap();
+    assert!(is_sealed_error(envelope.add_recipient::<MlKem768>(&pk)));
+    assert_eq!(envelope.recipients().len(), 1);
+
+    assert!(is_sealed_error(Envelope::new().unwrap().seal(MESSAGE, Vec::new())));
+}
+
+#[test]
+fn single_key_files_and_envelopes_are_told_apart() {
+    let (pk, sk) = MlKem768::keypair().unwrap();
+    let mut single = Vec::new();
+    seal::<MlKem768, _, _>(&pk, MESSAGE, &mut single).unwrap();
+    let mut envelope = Envelope::new().unwrap();
+    envelope.add_recipient::<MlKem768>(&pk).unwrap();
+    let mut multiple = Vec::new();
+    envelope.seal(MESSAGE, &mut multiple).unwrap();
+
+    assert!(single.starts_with(b"KYBER-SEALED v1\n") && multiple.starts_with(b"KYBER-SEALED v2\n"));
+    assert_eq!(opened::<MlKem768>(&sk, &single).unwrap(), MESSAGE);
+    assert_eq!(opened::<MlKem768>(&sk, &multiple).unwrap(), MESSAGE);
+    assert!(is_sealed_error(EnvelopeHeader::read(&mut &single[..])));
+    assert!(is_sealed_error(SealedHeader::read(&mut &multiple[..])));
+}
@@ -0,0 +1,141 @@
+// Every export format reads back to the same key or ciphertext, and ML-KEM PEM
+// keys interoperate with OpenSSL 3.5 where its command line tool is installed.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use kyber::enums::Export_Category;
+use kyber::export;
+use kyber::{Kem, KemBytes, Kyber512_90s, Kyber768, MlKem1024, MlKem768};
+
+const FORMATS: [Export_Category; 6] = [
+    Export_Category::Binary,
+    Export_Category::Hexadecimal,
+    Export_Category::Base64,
+    Export_Category::PEM,
+    Export_Category::JSON,
+    Export_Category::Protobuf,
+];
+
+fn check_round_trip<K: Kem>() {
+    let (pk, sk) = K::keypair_derand(&[1u8; 32], &[2u8; 32]).unwrap();
+    let (ct, _) = K::encapsulate_derand(&pk, &[3u8; 32]).unwrap();
+    for format in FORMATS {
+        let data = export::export_public_key::<K>(&pk, format).unwrap();
+        assert_eq!(export::import_public_key::<K>(&data, format).unwrap().as_bytes(), pk.as_bytes(), "{:?}", format);
+
+        let data = export::export_secret_key::<K>(&sk, format).unwrap();
+        assert!(export::import_secret_key::<K>(&data, format).unwrap().as_bytes() == sk.as_bytes(), "{:?}", format);
+
+        let data = export::export_ciphertext::<K>(&ct, format).unwrap();
+        assert_eq!(export::import_ciphertext::<K>(&data, format).unwrap().as_bytes(), ct.as_bytes(), "{:?}", format);
+    }
+}
+
+#[test]
+fn mlkem768_round_trips_every_format() {
+    check_round_trip::<MlKem768>();
+}
+
+#[test]
+fn kyber768_round_trips_every_format() {
+    check_round_trip::<Kyber768>();
+}
+
+#[test]
+fn kyber512_90s_round_trips_every_format() {
+    check_round_trip::<Kyber512_90s>();
+}
+
+#[test]
+fn spki_has_the_ml_kem_oid() {
+    let (pk, _) = MlKem768::keypair().unwrap();
+    let der = export::public_key_to_der::<MlKem768>(&pk);
+    // SEQUENCE { SEQUENCE { OID 2.16.840.1.101.3.4.4.2 } BIT STRING (1185 bytes) }
+    let header = [
+        0x30, 0x82, 0x04, 0xb2, 0x30, 0x0b, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x04, 0x02, 0x03, 0x82, 0x04, 0xa1, 0x00,
+    ];
+    assert_eq!(der[..header.len()], header);
+    assert_eq!(&der[header.len()..], pk.as_bytes());
+}
+
+#[test]
+fn other_parameter_sets_are_rejected() {
+    let (pk, sk) = MlKem768::keypair().unwrap();
+    // Same key sizes, different OID or parameter set name
+    for format in [Export_Category::PEM, Export_Category::JSON, Export_Category::Protobuf] {
+        let data = export::export_public_key::<MlKem768>(&pk, format).unwrap();
+        assert!(export::import_public_key::<Kyber768>(&data, format).is_err(), "{:?}", format);
+        let data = export::export_secret_key::<MlKem768>(&sk, format).unwrap();
+        assert!(export::import_secret_key::<Kyber768>(&data, format).is_err(), "{:?}", format);
+    }
+    // A secret key is not a public key
+    let data = export::export_secret_key::<MlKem768>(&sk, Export_Category::JSON).unwrap();
+    assert!(export::import_public_key::<MlKem768>(&data, Export_Category::JSON).is_err());
+    assert!(export::export_public_key::<MlKem768>(&pk, Export_Category::Exit).is_err());
+}
+
+#[test]
+fn imports_run_the_key_checks() {
+    let (pk, _) = MlKem768::keypair().unwrap();
+    let mut bad = pk.as_bytes().to_vec();
+    bad[..3].copy_from_slice(&[0xff, 0xff, 0xff]);
+    let bad = <MlKem768 as Kem>::PublicKey::from_bytes(&bad).unwrap();
+    let data = export::export_public_key::<MlKem768>(&bad, Export_Category::PEM).unwrap();
+    assert!(export::import_public_key::<MlKem768>(&data, Export_Category::PEM).is_err());
+}
+
+// None when the installed openssl has no ML-KEM
+fn openssl(args: &[&str]) -> Option<()> {
+    let output = Command::new("openssl").args(args).output().ok()?;
+    output.status.success().then_some(())
+}
+
+fn scratch_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("kyber-export-{}", std::process::id()))
+}
+
+fn scratch(name: &str) -> PathBuf {
+    std::fs::create_dir_all(scratch_dir()).unwrap();
+    scratch_dir().join(name)
+}
+
+#[test]
+fn openssl_reads_and_writes_ml_kem_pem() {
+    let path = |name: &str| scratch(name).to_str().unwrap().to_string();
+    let (theirs_sk, theirs_pk, ours_sk, ours_pk) = (path("theirs_sk.pem"), path("theirs_pk.pem"), path("ours_sk.pem"), path("ours_pk.pem"));
+    let (ct_file, ss_file) = (path("ct.bin"), path("ss.bin"));
+    if openssl(&["genpkey", "-algorithm", "ML-KEM-1024", "-out", &theirs_sk]).is_none() {
+        eprintln!("openssl without ML-KEM, skipping");
+        return;
+    }
+    openssl(&["pkey", "-in", &theirs_sk, "-pubout", "-out", &theirs_pk]).unwrap();
+    let read = |file: &str| std::fs::read(file).unwrap();
+
+    // OpenSSL's keys hold both the seed and the expanded key
+    let sk = export::import_secret_key::<MlKem1024>(&read(&theirs_sk), Export_Category::PEM).unwrap();
+    let pk = export::import_public_key::<MlKem1024>(&read(&theirs_pk), Export_Category::PEM).unwrap();
+    openssl(&["pkeyutl", "-encap", "-inkey", &theirs_pk, "-pubin", "-out", &ct_file, "-secret", &ss_file]).unwrap();
+    let ct = export::import_ciphertext::<MlKem1024>(&read(&ct_file), Export_Category::Binary).unwrap();
+    assert_eq!(MlKem1024::decapsulate(&sk, &ct).unwrap().as_bytes(), read(&ss_file));
+
+    let (ct, ss) = MlKem1024::encapsulate(&pk).unwrap();
+    std::fs::write(&ct_file, ct.as_bytes()).unwrap();
+    openssl(&["pkeyutl", "-decap", "-inkey", &theirs_sk, "-in", &ct_file, "-secret", &ss_file]).unwrap();
+    assert_eq!(ss.as_bytes(), read(&ss_file));
+
+    // Our keys, used by OpenSSL
+    let (pk, sk) = MlKem1024::keypair().unwrap();
+    std::fs::write(&ours_pk, export::export_public_key::<MlKem1024>(&pk, Export_Category::PEM).unwrap()).unwrap();
+    std::fs::write(&ours_sk, export::export_secret_key::<MlKem1024>(&sk, Export_Category::PEM).unwrap()).unwrap();
+    openssl(&["pkeyutl", "-encap", "-inkey", &ours_pk, "-pubin", "-out", &ct_file, "-secret", &ss_file]).unwrap();
+    let ct = <MlKem1024 as Kem>::Ciphertext::from_bytes(&read(&ct_file)).unwrap();
+    assert_eq!(MlKem1024::decapsulate(&sk, &ct).unwrap().as_bytes(), read(&ss_file));
+
+    let (ct, ss) = MlKem1024::encapsulate(&pk).unwrap();
+    std::fs::write(&ct_file, ct.as_bytes()).unwrap();
+    openssl(&["pkeyutl", "-decap", "-inkey", &ours_sk, "-in", &ct_file, "-secret", &ss_file]).unwrap();
+    assert_eq!(ss.as_bytes(), read(&ss_file));
+
+    let _ = std::fs::remove_dir_all(scratch_dir());
+}
@@ -0,0 +1,240 @@
+// HPKE: the key schedule, AEADs and exporter against vectors from OpenSSL's
+// own RFC 9180 implementation, through a DHKEM(X25519, HKDF-SHA256) written
+// for the test, and every mode and suite of the Kyber KEMs round tripping.
+
+use kyber::hpke::{Aead, Hpke, HpkeKem, Kdf, Mode, Psk};
+use kyber::{KemBytes, KemRng, KyberError, MlKem768, SharedSecret, X25519MlKem768};
+use openssl::derive::Deriver;
+use openssl::pkey::{Id, PKey};
+
+fn bytes32(value: &str) -> [u8; 32] {
+    hex::decode(value).unwrap().try_into().unwrap()
+}
+
+#[test]
+fn hkdf_matches_rfc5869() {
+    // Test case 1
+    let ikm = [0x0bu8; 22];
+    let salt = hex::decode("000102030405060708090a0b0c").unwrap();
+    let info = hex::decode("f0f1f2f3f4f5f6f7f8f9").unwrap();
+    let prk = Kdf::HkdfSha256.extract(&salt, &ikm).unwrap();
+    assert_eq!(hex::encode(&prk[..]), "077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5");
+    let okm = Kdf::HkdfSha256.expand(&prk, &info, 42).unwrap();
+    assert_eq!(hex::encode(&okm[..]), "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865");
+
+    assert_ne!(Kdf::HkdfSha3_256.extract(&salt, &ikm).unwrap(), prk);
+    assert!(Kdf::HkdfSha256.expand(&prk, &info, 255 * 32 + 1).is_err());
+}
+
+// Keys derived by OpenSSL from ikmR = 20..3f, ikmS = 40..5f and ikmE = 00..1f
+const SK_R: &str = "38aa61bc13e56473e913a85c33cf4d603ac99a517eea95ed4573e772b6443577";
+const PK_R: &str = "693658254630f73ad8da78fb331bf976cd42f90e0e9c9e83f40c51072a6f7417";
+const SK_S: &str = "58661a2f70e4407ce264c6ec792b13faf122b0731108afe887d2a7994387d461";
+const PK_S: &str = "b259f6ee92dcba0111850b13b3f6dccc827726f9b08235ab62922b6b3f3f2a19";
+const SK_E: &str = "90f7a467df4ef97053ec2a47b6e619f632df9547bb009fd0bcc747909f1b7b54";
+const PK_E: &str = "b1f1b840de7a3241b02748cf9b05b74dc8c5e8451298738817bd76aa8ebe8c2b";
+
+// DHKEM(X25519, HKDF-SHA256) of RFC 9180 section 4.1, always with the
+// ephemeral key above
+struct DhKemX25519;
+
+fn x25519(sk: &[u8; 32], pk: &[u8; 32]) -> Vec<u8> {
+    let sk = PKey::private_key_from_raw_bytes(sk, Id::X25519).unwrap();
+    let pk = PKey::public_key_from_raw_bytes(pk, Id::X25519).unwrap();
+    let mut deriver = Deriver::new(&sk).unwrap();
+    deriver.set_peer(&pk).unwrap();
+    deriver.derive_to_vec().unwrap()
+}
+
+fn x25519_public_key(sk: &[u8; 32]) -> [u8; 32] {
+    PKey::private_key_from_raw_bytes(sk, Id::X25519).unwrap().raw_public_key().unwrap().try_into().unwrap()
+}
+
+// ExtractAndExpand with the suite ID "KEM" || 0x0020
+fn extract_and_expand(dh: &[u8], kem_context: &[u8]) -> SharedSecret {
+    let suite_id = b"KEM\x00\x20";
+    let prk = Kdf::HkdfSha256.extract(b"", &[b"HPKE-v1", &suite_id[..], b"eae_prk", dh].concat()).unwrap();
+    let info = [&[0u8, 32][..], b"HPKE-v1", suite_id, b"shared_secret", kem_context].concat();
+    SharedSecret::from_bytes(&Kdf::HkdfSha256.expand(&prk, &info, 32).unwrap()).unwrap()
+}
+
+impl HpkeKem for DhKemX25519 {
+    const KEM_ID: u16 = 0x0020;
+    const N_ENC: usize = 32;
+
+    type PublicKey = [u8; 32];
+    type SecretKey = [u8; 32];
+
+    fn encap<R: KemRng + ?Sized>(pk_r: &[u8; 32], _rng: &mut R) -> Result<(Vec<u8>, SharedSecret), KyberError> {
+        let sk_e = bytes32(SK_E);
+        let enc = x25519_public_key(&sk_e);
+        Ok((enc.to_vec(), extract_and_expand(&x25519(&sk_e, pk_r), &[enc, *pk_r].concat())))
+    }
+
+    fn decap(enc: &[u8], sk_r: &[u8; 32]) -> Result<SharedSecret, KyberError> {
+        let enc: [u8; 32] = enc.try_into().unwrap();
+        Ok(extract_and_expand(&x25519(sk_r, &enc), &[enc, x25519_public_key(sk_r)].concat()))
+    }
+
+    fn auth_encap<R: KemRng + ?Sized>(pk_r: &[u8; 32], sk_s: &[u8; 32], _rng: &mut R) -> Result<(Vec<u8>, SharedSecret), KyberError> {
+        let sk_e = bytes32(SK_E);
+        let enc = x25519_public_key(&sk_e);
+        let dh = [x25519(&sk_e, pk_r), x25519(sk_s, pk_r)].concat();
+        Ok((enc.to_vec(), extract_and_expand(&dh, &[enc, *pk_r, x25519_public_key(sk_s)].concat())))
+    }
+
+    fn auth_decap(enc: &[u8], sk_r: &[u8; 32], pk_s: &[u8; 32]) -> Result<SharedSecret, KyberError> {
+        let enc: [u8; 32] = enc.try_into().unwrap();
+        let dh = [x25519(sk_r, &enc), x25519(sk_r, pk_s)].concat();
+        Ok(extract_and_expand(&dh, &[enc, x25519_public_key(sk_r), *pk_s].concat()))
+    }
+}
+
+const INFO: &[u8] = b"HPKE test info";
+const PSK: Psk = Psk { psk: b"0123456789abcdef0123456789abcdef", id: b"psk id" };
+const PLAINTEXT: &[u8] = b"Beauty is truth, truth beauty";
+
+// The first two messages, with the associated data "aad 0" and "aad 1", and 32
+// bytes exported for "test export", in the base, PSK, auth and auth-PSK modes
+const OPENSSL_AES_256_GCM: [[&str; 3]; 4] = [
+    ["1ff9cda301afbcb102450e8d97f68398b294731a7124474b503d537e91ec8a91eba5549e76c1e1248a69e818b5", "5d5cfad4c6358dd71f7e25a0b331b0b4ad0b97641f6d7c0a8749318472580eca177dc875171932311fd5a0e044", "174fcf196c52b8a51bbca307b071da44edad17c397312042686c05f0fce53856"],
+    ["d53db6bb5a42dd0e96c426a4c2bb0ca0e120b3e23a6c4e361e93130337d1e697e891f511f0aa8bbb126ab64aeb", "660f913c8831e4ab5fcbf6e765be5b6e30698df4fa61c888e3dc0e5bee3c38c6bf01c691c401ebc0109052cea4", "f7625db0673b38da71307f7ae6ab38eb1876e68c599fcb44afbb8505ff666526"],
+    ["6480b2b8ec1c9e9feb40b4eb7d724597bc68c8a0b38e1e0cb6c7a171e34dae995744f9caaefb8d060c81fb7a58", "1ca549d09fa434edd24a927f9ac38adc4e1955ac6efbc1df20de731656a836ef081374e97ba727ec0b7077d637", "93461e52aa80978c8a14fcbd7f2bf8f4d4c7019fa8ae7ce75ec4ecf145a1aabd"],
+    ["97167b06945f85c07816f70df5cb33a4ab9879bcc7bf40af572984def3636ebf258c9a8db29dd11bcc8ff4e533", "fc3ee2824752dfd6d9965cfbe7336db7204449e5f9239e1473f425136c08d05c082f79cb248eddfd23a6e1c3c2", "925cdd4cb4613668bf01d178408f4d1a6b3e2fda573b240940fe5c049763c2e4"],
+];
+const OPENSSL_CHACHA20_POLY1305: [[&str; 3]; 4] = [
+    ["ef498e7e9f818d045f9a9dc1a3e5e31e6cefb05d77a7d8825a02b99a6f0ab1dec533813059dfaa4128005708ed", "29abdff6cb8bb8042fce64805ee636c6722efbad0c6b918b2896251f9339e11e2f90ec9fdface6f649668925dd", "1669a7d088fdd688d893690244828f5b347e5d988d533470df2d083f200c72a9"],
+    ["494455f0e4a3fec429134855c35628d919da5050e87115a3da992c71c4ecdb1b19f1ea48deca29d01b766067f8", "fb7731d62661c33542f636ac5d839847651376eadc7732747cb1f5fabb751bfe51a281f3fc83372c3845c53df6", "5be49ef586250354406f3af7ddd3fb0320b9ff2a8609b59538f38250ef3884c2"],
+    ["ac4b34e730090777115a73cfd5de9a7bc61656f9771c850709d8dcf2485bbc7522823ab3ce4e234c4699675e78", "f53c58e021a504390768993dd3abb75b1841807d5aca5314d529ea68ab0fdc4551ccc965f0f1d07b68b572b129", "5329806a378d5868bc4c74f47fd038905bfd1f1429c89371f81ba1cbdf9ef828"],
+    ["d2319b6c2df18160cee485f5a49bc1e89bf5f2bb290e445f4dfa25c1c7ac03ff9e7491da12a065e6d0f5dffe70", "49ac72f5635aaf8c71b75cb88b1042611f2d243c82c91e5c85e5c0932cb00b419463c66f7744f20ac6bb90dd17", "3f7cce72bca95f4bc74a2d0bb26e0efc4008a23827c56aebdd0bbbeade3c4c97"],
+];
+
+fn modes<'a, T>(key: &'a T) -> [Mode<'a, T>; 4] {
+    [Mode::Base, Mode::Psk(PSK), Mode::Auth(key), Mode::AuthPsk(PSK, key)]
+}
+
+#[test]
+fn key_schedule_aead_and_exporter_match_openssl() {
+    let (sk_r, pk_r, sk_s, pk_s) = (bytes32(SK_R), bytes32(PK_R), bytes32(SK_S), bytes32(PK_S));
+    assert_eq!(x25519_public_key(&sk_s), pk_s);
+
+    for (aead, vectors) in [(Aead::Aes256Gcm, OPENSSL_AES_256_GCM), (Aead::ChaCha20Poly1305, OPENSSL_CHACHA20_POLY1305)] {
+        let hpke = Hpke::<DhKemX25519>::new(Kdf::HkdfSha256, aead);
+        for ((sender, receiver), [ct0, ct1, exported]) in modes(&sk_s).into_iter().zip(modes(&pk_s)).zip(vectors) {
+            let label = format!("{:?} mode {}", aead, sender.id());
+            let (enc, mut context) = hpke.setup_sender(&pk_r, INFO, sender).unwrap();
+            assert_eq!(hex::encode(&enc), PK_E);
+            assert_eq!(hex::encode(context.seal(b"aad 0", PLAINTEXT).unwrap()), ct0, "{}", label);
+            assert_eq!(hex::encode(context.seal(b"aad 1", PLAINTEXT).unwrap()), ct1, "{}", label);
+            assert_eq!(hex::encode(&context.export(b"test export", 32).unwrap()[..]), exported, "{}", label);
+
+            let mut context = hpke.setup_receiver(&enc, &sk_r, INFO, receiver).unwrap();
+            assert_eq!(&context.open(b"aad 0", &hex::decode(ct0).unwrap()).unwrap()[..], PLAINTEXT);
+            assert_eq!(&context.open(b"aad 1", &hex::decode(ct1).unwrap()).unwrap()[..], PLAINTEXT);
+            assert_eq!(context.sequence_number(), 2);
+        }
+    }
+}
+
+fn round_trip<K: HpkeKem>(pk_r: &K::PublicKey, sk_r: &K::SecretKey, sender: Mode<'_, K::SecretKey>, receiver: Mode<'_, K::PublicKey>) {
+    for kdf in [Kdf::HkdfSha256, Kdf::HkdfSha3_256] {
+        for aead in [Aead::Aes256Gcm, Aead::ChaCha20Poly1305] {
+            let hpke = Hpke::<K>::new(kdf, aead);
+            let (enc, mut sending) = hpke.setup_sender(pk_r, INFO, clone_mode(&sender)).unwrap();
+            assert_eq!(enc.len(), K::N_ENC);
+            let mut receiving = hpke.setup_receiver(&enc, sk_r, INFO, clone_mode(&receiver)).unwrap();
+            for i in 0..3u8 {
+                let ciphertext = sending.seal(&[i], PLAINTEXT).unwrap();
+                assert_eq!(ciphertext.len(), PLAINTEXT.len() + aead.n_t());
+                assert_eq!(&receiving.open(&[i], &ciphertext).unwrap()[..], PLAINTEXT);
+            }
+            assert_eq!(sending.export(b"context", 64).unwrap(), receiving.export(b"context", 64).unwrap());
+        }
+    }
+}
+
+fn clone_mode<'a, T>(mode: &Mode<'a, T>) -> Mode<'a, T> {
+    match mode {
+        Mode::Base => Mode::Base,
+        Mode::Psk(psk) => Mode::Psk(*psk),
+        Mode::Auth(key) => Mode::Auth(*key),
+        Mode::AuthPsk(psk, key) => Mode::AuthPsk(*psk, *key),
+    }
+}
+
+#[test]
+fn every_mode_and_suite_round_trips() {
+    use kyber::Kem;
+
+    let (pk, sk) = MlKem768::keypair().unwrap();
+    round_trip::<MlKem768>(&pk, &sk, Mode::Base, Mode::Base);
+    round_trip::<MlKem768>(&pk, &sk, Mode::Psk(PSK), Mode::Psk(PSK));
+
+    // ML-KEM cannot authenticate the sender
+    let hpke = Hpke::<MlKem768>::new(Kdf::HkdfSha256, Aead::Aes256Gcm);
+    assert!(matches!(hpke.setup_sender(&pk, INFO, Mode::AuthPsk(PSK, &sk)), Err(KyberError::Hpke(_))));
+
+    let (pk_r, sk_r) = X25519MlKem768::keypair().unwrap();
+    let (pk_s, sk_s) = X25519MlKem768::keypair().unwrap();
+    for (sender, receiver) in modes(&sk_s).into_iter().zip(modes(&pk_s)) {
+        round_trip::<X25519MlKem768>(&pk_r, &sk_r, sender, receiver);
+    }
+    assert_eq!(Hpke::<X25519MlKem768>::new(Kdf::HkdfSha3_256, Aead::ChaCha20Poly1305).suite_id(), *b"HPKE\xff\x41\xff\x03\x00\x03");
+}
+
+#[test]
+fn changes_reordering_and_wrong_inputs_are_rejected() {
+    let (pk_r, sk_r) = X25519MlKem768::keypair().unwrap();
+    let (pk_s, sk_s) = X25519MlKem768::keypair().unwrap();
+    let (other_pk, _) = X25519MlKem768::keypair().unwrap();
+    let hpke = Hpke::<X25519MlKem768>::new(Kdf::HkdfSha256, Aead::Aes256Gcm);
+    let (enc, ciphertext) = hpke.seal(&pk_r, INFO, b"aad", PLAINTEXT, Mode::AuthPsk(PSK, &sk_s)).unwrap();
+    assert_eq!(&hpke.open(&enc, &sk_r, INFO, b"aad", &ciphertext, Mode::AuthPsk(PSK, &pk_s)).unwrap()[..], PLAINTEXT);
+
+    let opens = |enc: &[u8], info: &[u8], aad: &[u8], ciphertext: &[u8], mode| hpke.open(enc, &sk_r, info, aad, ciphertext, mode).is_ok();
+    let other_psk = Psk { psk: &[7u8; 32], id: PSK.id };
+    assert!(!opens(&enc, INFO, b"aad", &ciphertext, Mode::AuthPsk(other_psk, &pk_s)));
+    assert!(!opens(&enc, INFO, b"aad", &ciphertext, Mode::AuthPsk(PSK, &other_pk)));
+    assert!(!opens(&enc, INFO, b"aad", &ciphertext, Mode::Psk(PSK)));
+    assert!(!opens(&enc, b"other info", b"aad", &ciphertext, Mode::AuthPsk(PSK, &pk_s)));
+    assert!(!opens(&enc, INFO, b"other aad", &ciphertext, Mode::AuthPsk(PSK, &pk_s)));
+    for i in [0, ciphertext.len() - 1] {
+        let mut edited = ciphertext.clone();
+        edited[i] ^= 1;
+        assert!(!opens(&enc, INFO, b"aad", &edited, Mode::AuthPsk(PSK, &pk_s)));
+    }
+    let mut edited = enc.clone();
+    edited[0] ^= 1;
+    assert!(!opens(&edited, INFO, b"aad", &ciphertext, Mode::AuthPsk(PSK, &pk_s)));
+    assert!(!opens(&enc[1..], INFO, b"aad", &ciphertext, Mode::AuthPsk(PSK, &pk_s)));
+
+    // Messages open in order only, and a failure does not advance the context
+    let (enc, mut sending) = hpke.setup_sender(&pk_r, INFO, Mode::Base).unwrap();
+    let (first, second) = (sending.seal(b"", b"first").unwrap(), sending.seal(b"", b"second").unwrap());
+    let mut receiving = hpke.setup_receiver(&enc, &sk_r, INFO, Mode::Base).unwrap();
+    assert!(matches!(receiving.open(b"", &second), Err(KyberError::Hpke(_))));
+    assert_eq!(receiving.sequence_number(), 0);
+    assert_eq!(&receiving.open(b"", &first).unwrap()[..], b"first");
+    assert_eq!(&receiving.open(b"", &second).unwrap()[..], b"second");
+
+    // Short or half-given pre-shared keys
+    for psk in [Psk { psk: &[1u8; 31], id: b"id" }, Psk { psk: &[1u8; 32], id: b"" }] {
+        assert!(matches!(hpke.setup_sender(&pk_r, INFO, Mode::Psk(psk)), Err(KyberError::Hpke(_))));
+    }
+}
+
+#[test]
+fn export_only_contexts_only_export() {
+    let (pk, sk) = <MlKem768 as kyber::Kem>::keypair().unwrap();
+    let hpke = Hpke::<MlKem768>::new(Kdf::HkdfSha3_256, Aead::ExportOnly);
+    let (enc, mut sending) = hpke.setup_sender(&pk, INFO, Mode::Base).unwrap();
+    let receiving = hpke.setup_receiver(&enc, &sk, INFO, Mode::Base).unwrap();
+    assert!(matches!(sending.seal(b"", PLAINTEXT), Err(KyberError::Hpke(_))));
+
+    let exported = sending.export(b"context", 255 * 32).unwrap();
+    assert_eq!(exported, receiving.export(b"context", 255 * 32).unwrap());
+    assert_ne!(exported, receiving.export(b"other context", 255 * 32).unwrap());
+    // The length is part of the label, so a shorter export is not a prefix
+    assert_ne!(exported[..16], receiving.export(b"context", 16).unwrap()[..]);
+    assert!(receiving.export(b"context", 255 * 32 + 1).is_err());
+}
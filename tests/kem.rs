@@ -0,0 +1,153 @@
+// Behaviour of the typed KEM API beyond the known answer tests.
+
+use kyber::{Kem, KemBytes, Kyber1024, Kyber512_90s, Kyber768, KyberError, MlKem512, MlKem768};
+use openssl::hash::{hash, hash_xof, MessageDigest};
+
+// A modified ciphertext must fail the re-encryption check and give an unrelated secret
+fn check_implicit_rejection<K: Kem>() {
+    let (pk, sk) = K::keypair().unwrap();
+    let (ct, ss) = K::encapsulate(&pk).unwrap();
+
+    for (position, flip) in [(0, 0x01), (1, 0x80), (K::CIPHERTEXT_BYTES - 1, 0xff)] {
+        let mut tampered = ct.as_bytes().to_vec();
+        tampered[position] ^= flip;
+        let tampered = K::Ciphertext::from_bytes(&tampered).unwrap();
+        assert!(K::decapsulate(&sk, &tampered).unwrap() != ss);
+    }
+}
+
+#[test]
+fn kyber768_rejects_modified_ciphertext() {
+    check_implicit_rejection::<Kyber768>();
+}
+
+#[test]
+fn mlkem768_rejects_modified_ciphertext() {
+    check_implicit_rejection::<MlKem768>();
+}
+
+// The secret implicit rejection returns for a modified ciphertext, from the
+// z in the last 32 bytes of the secret key: J(z||c) = SHAKE256(z||c) for
+// ML-KEM, and KDF(z||H(c)) for round-3 Kyber and Kyber-90s
+fn rejection_secret<K: Kem>(sk: &K::SecretKey, ct: &[u8]) -> Vec<u8> {
+    let z = &sk.as_bytes()[K::SECRET_KEY_BYTES - 32..];
+    let mut out = vec![0u8; 32];
+    match K::VARIANT {
+        kyber::KyberVariant::MlKem => hash_xof(MessageDigest::shake_256(), &[z, ct].concat(), &mut out).unwrap(),
+        kyber::KyberVariant::Round3 => {
+            let h = hash(MessageDigest::sha3_256(), ct).unwrap();
+            hash_xof(MessageDigest::shake_256(), &[z, &h[..]].concat(), &mut out).unwrap();
+        }
+        kyber::KyberVariant::Kyber90s => {
+            let h = hash(MessageDigest::sha256(), ct).unwrap();
+            out = hash(MessageDigest::sha256(), &[z, &h[..]].concat()).unwrap().to_vec();
+        }
+    }
+    out
+}
+
+// Regression test for verify() reporting differences of 0x80 and above as
+// equal, which skipped implicit rejection
+fn check_rejection_secret<K: Kem>() {
+    let (pk, sk) = K::keypair().unwrap();
+    let (ct, _) = K::encapsulate(&pk).unwrap();
+
+    for (position, flip) in [(0, 0x01), (1, 0x80), (2, 0xc3), (K::CIPHERTEXT_BYTES - 1, 0xff)] {
+        let mut tampered = ct.as_bytes().to_vec();
+        tampered[position] ^= flip;
+        let ss = K::decapsulate(&sk, &K::Ciphertext::from_bytes(&tampered).unwrap()).unwrap();
+        assert_eq!(hex::encode(ss.as_bytes()), hex::encode(rejection_secret::<K>(&sk, &tampered)), "byte {} ^ {:#x}", position, flip);
+    }
+}
+
+#[test]
+fn mlkem768_rejection_secret_is_j_of_z_and_c() {
+    check_rejection_secret::<MlKem768>();
+}
+
+#[test]
+fn kyber768_rejection_secret_uses_z() {
+    check_rejection_secret::<Kyber768>();
+}
+
+#[test]
+fn kyber512_90s_rejection_secret_uses_z() {
+    check_rejection_secret::<Kyber512_90s>();
+}
+
+#[test]
+fn secrets_are_not_printed() {
+    let (pk, sk) = MlKem768::keypair().unwrap();
+    let (_, ss) = MlKem768::encapsulate(&pk).unwrap();
+    assert_eq!(format!("{:?}", sk), "SecretKey([REDACTED])");
+    assert_eq!(format!("{:?}", ss), "SharedSecret([REDACTED])");
+}
+
+fn invalid_key_reason<T>(result: Result<T, KyberError>) -> &'static str {
+    match result {
+        Err(KyberError::InvalidKey(reason)) => reason,
+        Err(err) => panic!("expected an invalid key, got {}", err),
+        Ok(_) => panic!("expected an invalid key"),
+    }
+}
+
+// The FIPS 203 key checks: a fresh key pair passes, a coefficient of q or more
+// in the public key fails the modulus check wherever it is, a changed H(pk) in
+// the secret key fails the hash check, and a key of another length is refused
+fn check_key_validation<K: Kem>() {
+    let (pk, sk) = K::keypair().unwrap();
+    K::validate_public_key(&pk).unwrap();
+    K::validate_secret_key(&sk).unwrap();
+
+    // First coefficient of the first polynomial, last of the last
+    let polyvec_bytes = K::PUBLIC_KEY_BYTES - 32;
+    for (position, bits) in [(0, 0x0fff), (polyvec_bytes - 2, 0xfff0)] {
+        let mut tampered = pk.as_bytes().to_vec();
+        tampered[position] |= (bits & 0xff) as u8;
+        tampered[position + 1] |= (bits >> 8) as u8;
+        let tampered = K::PublicKey::from_bytes(&tampered).unwrap();
+        assert!(invalid_key_reason(K::validate_public_key(&tampered)).contains("modulus check"));
+        assert!(invalid_key_reason(K::encapsulate(&tampered)).contains("modulus check"));
+    }
+    // The seed of the matrix is not checked
+    let mut seed_changed = pk.as_bytes().to_vec();
+    seed_changed[polyvec_bytes] ^= 0xff;
+    K::validate_public_key(&K::PublicKey::from_bytes(&seed_changed).unwrap()).unwrap();
+
+    let mut tampered = sk.as_bytes().to_vec();
+    tampered[K::SECRET_KEY_BYTES - 64] ^= 0x01;
+    let tampered = K::SecretKey::from_bytes(&tampered).unwrap();
+    assert!(invalid_key_reason(K::validate_secret_key(&tampered)).contains("hash check"));
+    let (ct, _) = K::encapsulate(&pk).unwrap();
+    assert!(invalid_key_reason(K::decapsulate(&tampered, &ct)).contains("hash check"));
+
+    let short_pk = K::PublicKey::from_bytes(&pk.as_bytes()[1..]);
+    assert!(matches!(short_pk, Err(KyberError::WrongKeyLength { expected, actual }) if expected == K::PUBLIC_KEY_BYTES && actual == expected - 1));
+    let long_sk = K::SecretKey::from_bytes(&[sk.as_bytes(), &[0]].concat());
+    assert!(matches!(long_sk, Err(KyberError::WrongKeyLength { expected, actual }) if expected == K::SECRET_KEY_BYTES && actual == expected + 1));
+}
+
+#[test]
+fn key_validation_kyber768() {
+    check_key_validation::<Kyber768>();
+}
+
+#[test]
+fn key_validation_kyber1024() {
+    check_key_validation::<Kyber1024>();
+}
+
+#[test]
+fn key_validation_kyber512_90s() {
+    check_key_validation::<Kyber512_90s>();
+}
+
+#[test]
+fn key_validation_mlkem512() {
+    check_key_validation::<MlKem512>();
+}
+
+#[test]
+fn key_validation_mlkem768() {
+    check_key_validation::<MlKem768>();
+}
@@ -0,0 +1,148 @@
+// The versioned key file format round trips, and its parser rejects anything
+// but exactly what the writer produces. Legacy files are still read.
+
+use kyber::{
+    KeyFile, KeyFileFormat, Kem, KemBytes, Kyber1024, Kyber1024_90s, Kyber512, Kyber512_90s, Kyber768,
+    Kyber768_90s, KyberVariant, MlKem1024, MlKem512, MlKem768,
+};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+fn full_key_file() -> KeyFile {
+    let (pk, sk) = MlKem768::keypair_derand(&[1u8; 32], &[2u8; 32]).unwrap();
+    let (ct, ss) = MlKem768::encapsulate_derand(&pk, &[3u8; 32]).unwrap();
+    let mut file = KeyFile::for_kem::<MlKem768>();
+    file.private_key = Some(Zeroizing::new(sk.as_bytes().to_vec()));
+    file.public_key = Some(pk.as_bytes().to_vec());
+    file.ciphertext = Some(ct.as_bytes().to_vec());
+    file.shared_secret = Some(Zeroizing::new(ss.as_bytes().to_vec()));
+    file
+}
+
+// Replaces the checksum so only the change under test is wrong
+fn with_checksum(body: &str) -> String {
+    format!("{}checksum: {}\n", body, hex::encode(Sha256::digest(body.as_bytes())))
+}
+
+fn body(text: &str) -> &str {
+    &text[..text.rfind("checksum: ").unwrap()]
+}
+
+#[test]
+fn round_trip() {
+    let file = full_key_file();
+    let text = file.encode().unwrap();
+    assert!(text.starts_with("KYBER-KEYFILE v1\nalgorithm: ML-KEM\nparameter-set: ML-KEM-768\nprivate-key: "));
+
+    let (parsed, format) = KeyFile::parse(&text, KyberVariant::Round3).unwrap();
+    assert_eq!(format, KeyFileFormat::V1);
+    assert_eq!(parsed.variant, KyberVariant::MlKem);
+    assert_eq!(parsed.security_level, 3);
+    assert!(parsed.private_key == file.private_key);
+    assert_eq!(parsed.public_key, file.public_key);
+    assert_eq!(parsed.ciphertext, file.ciphertext);
+    assert!(parsed.shared_secret == file.shared_secret);
+    parsed.check_parameter_set::<MlKem768>().unwrap();
+    assert!(parsed.check_parameter_set::<kyber::Kyber768>().is_err());
+
+    // Values are optional
+    let mut public_only = KeyFile::for_kem::<MlKem768>();
+    public_only.public_key = file.public_key.clone();
+    let parsed = KeyFile::parse_v1(&public_only.encode().unwrap()).unwrap();
+    assert_eq!(parsed.public_key, file.public_key);
+    assert!(parsed.private_key.is_none() && parsed.ciphertext.is_none());
+}
+
+// Key files name their parameter set the way Kem::PARAMETER_SET does, and read
+// the name back
+fn check_parameter_set_name<K: Kem>() {
+    let file = KeyFile::for_kem::<K>();
+    assert_eq!(file.parameter_set_name(), K::PARAMETER_SET);
+    assert_eq!(KeyFile::for_hybrid::<K>().parameter_set_name(), format!("X25519+{}", K::PARAMETER_SET));
+
+    let (parsed, _) = KeyFile::parse(&file.encode().unwrap(), KyberVariant::Round3).unwrap();
+    assert_eq!(parsed.parameter_set_name(), K::PARAMETER_SET);
+    parsed.check_parameter_set::<K>().unwrap();
+}
+
+#[test]
+fn parameter_set_names() {
+    check_parameter_set_name::<Kyber512>();
+    check_parameter_set_name::<Kyber768>();
+    check_parameter_set_name::<Kyber1024>();
+    check_parameter_set_name::<Kyber512_90s>();
+    check_parameter_set_name::<Kyber768_90s>();
+    check_parameter_set_name::<Kyber1024_90s>();
+    check_parameter_set_name::<MlKem512>();
+    check_parameter_set_name::<MlKem768>();
+    check_parameter_set_name::<MlKem1024>();
+}
+
+#[test]
+fn truncation_and_edits_fail_the_checksum() {
+    let text = full_key_file().encode().unwrap();
+    for len in [text.len() - 1, text.len() - 40, text.len() / 2, 20] {
+        assert!(KeyFile::parse_v1(&text[..len]).is_err(), "truncated to {}", len);
+    }
+    let i = text.find("ciphertext: ").unwrap() + "ciphertext: ".len();
+    let mut edited = text.to_string();
+    let flipped = if &edited[i..i + 1] == "0" { "1" } else { "0" };
+    edited.replace_range(i..i + 1, flipped);
+    assert!(matches!(KeyFile::parse_v1(&edited), Err(kyber::KyberError::KeyFile(_))));
+    assert!(KeyFile::parse_v1(&format!("{}extra\n", *text)).is_err());
+}
+
+#[test]
+fn strict_parser_rejects_anything_else() {
+    let text = full_key_file().encode().unwrap();
+    let body = body(&text);
+    let public_key_line = body.lines().find(|line| line.starts_with("public-key: ")).unwrap();
+
+    let bad_bodies = [
+        // Commented out value, blank line, unknown field
+        body.replacen("public-key: ", "# old public-key: ", 1),
+        body.replacen("\nciphertext: ", "\n\nciphertext: ", 1),
+        format!("{}comment: hello\n", body),
+        // Repeated and reordered fields
+        format!("{}{}\n", body, public_key_line),
+        body.replacen("algorithm: ML-KEM\nparameter-set: ML-KEM-768\n", "parameter-set: ML-KEM-768\nalgorithm: ML-KEM\n", 1),
+        // Header, algorithm and parameter set that disagree
+        body.replacen("KYBER-KEYFILE v1", "KYBER-KEYFILE v2", 1),
+        body.replacen("parameter-set: ML-KEM-768", "parameter-set: Kyber-768", 1),
+        body.replacen("algorithm: ML-KEM", "algorithm: ml-kem", 1),
+        // Wrong length for the parameter set, uppercase hex
+        body.replacen("parameter-set: ML-KEM-768", "parameter-set: ML-KEM-512", 1),
+        body.replacen(public_key_line, &public_key_line.to_ascii_uppercase().replacen("PUBLIC-KEY", "public-key", 1), 1),
+    ];
+    for bad in bad_bodies {
+        assert!(KeyFile::parse_v1(&with_checksum(&bad)).is_err(), "accepted:\n{}", bad);
+    }
+    assert!(KeyFile::parse_v1(&with_checksum(body)).is_ok());
+}
+
+#[test]
+fn legacy_files_are_still_read() {
+    let file = full_key_file();
+    let legacy = format!(
+        "VARIANT: 'ML-KEM'\nPRIVATE KEY: '{}'\nPUBLIC KEY: '{}'\nCiphertext: '{}'\nShared Secret: '{}'\n",
+        hex::encode(file.private_key.as_ref().unwrap().as_slice()),
+        hex::encode(file.public_key.as_ref().unwrap()),
+        hex::encode(file.ciphertext.as_ref().unwrap()),
+        hex::encode(file.shared_secret.as_ref().unwrap().as_slice()),
+    );
+    let (parsed, format) = KeyFile::parse(&legacy, KyberVariant::Round3).unwrap();
+    assert_eq!(format, KeyFileFormat::Legacy);
+    assert_eq!((parsed.variant, parsed.security_level), (KyberVariant::MlKem, 3));
+    assert_eq!(parsed.ciphertext, file.ciphertext);
+
+    // Without a VARIANT line the caller's default applies, the level comes from the lengths
+    let no_variant = legacy.lines().skip(1).collect::<Vec<_>>().join("\n");
+    let (parsed, _) = KeyFile::parse(&no_variant, KyberVariant::Kyber90s).unwrap();
+    assert_eq!((parsed.variant, parsed.security_level), (KyberVariant::Kyber90s, 3));
+
+    // The all-zero private key written next to a user's own public key is dropped
+    let own_key = format!("PRIVATE KEY: '{}'\nPUBLIC KEY: '{}'\n", "00".repeat(2400), hex::encode(file.public_key.as_ref().unwrap()));
+    let (parsed, _) = KeyFile::parse(&own_key, KyberVariant::Round3).unwrap();
+    assert!(parsed.private_key.is_none());
+    assert!(KeyFile::parse("PUBLIC KEY: 'abcd'\n", KyberVariant::Round3).is_err());
+}
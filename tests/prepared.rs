@@ -0,0 +1,56 @@
+// Prepared keys give exactly what the unprepared keys give.
+
+use kyber::{Kem, KemBytes, Kyber1024_90s, Kyber512, MlKem768};
+
+fn check_prepared_matches_unprepared<K: Kem>() {
+    let (pk, sk) = K::keypair_derand(&[1u8; 32], &[2u8; 32]).unwrap();
+    let prepared_pk = K::prepare_public_key(&pk).unwrap();
+    let prepared_sk = K::prepare_secret_key(&sk).unwrap();
+
+    for m in [[3u8; 32], [4u8; 32]] {
+        let (ct, ss) = K::encapsulate_derand(&pk, &m).unwrap();
+        let (ct_prepared, ss_prepared) = K::encapsulate_prepared_derand(&prepared_pk, &m).unwrap();
+        assert_eq!(ct.as_bytes(), ct_prepared.as_bytes());
+        assert!(ss == ss_prepared);
+        assert!(K::decapsulate_prepared(&prepared_sk, &ct).unwrap() == ss);
+    }
+
+    // Implicit rejection agrees as well
+    let (ct, _) = K::encapsulate_prepared(&prepared_pk).unwrap();
+    let mut tampered = ct.as_bytes().to_vec();
+    tampered[0] ^= 1;
+    let tampered = K::Ciphertext::from_bytes(&tampered).unwrap();
+    assert!(K::decapsulate_prepared(&prepared_sk, &tampered).unwrap() == K::decapsulate(&sk, &tampered).unwrap());
+}
+
+#[test]
+fn kyber512_prepared_matches_unprepared() {
+    check_prepared_matches_unprepared::<Kyber512>();
+}
+
+#[test]
+fn mlkem768_prepared_matches_unprepared() {
+    check_prepared_matches_unprepared::<MlKem768>();
+}
+
+#[test]
+fn kyber1024_90s_prepared_matches_unprepared() {
+    check_prepared_matches_unprepared::<Kyber1024_90s>();
+}
+
+#[test]
+fn preparing_runs_the_key_checks() {
+    let (pk, sk) = MlKem768::keypair().unwrap();
+    assert_eq!(MlKem768::prepare_public_key(&pk).unwrap().public_key(), &pk);
+
+    let mut bad_pk = pk.as_bytes().to_vec();
+    bad_pk[..3].copy_from_slice(&[0xff, 0xff, 0xff]);
+    let bad_pk = <MlKem768 as Kem>::PublicKey::from_bytes(&bad_pk).unwrap();
+    assert!(MlKem768::prepare_public_key(&bad_pk).is_err());
+
+    let mut bad_sk = sk.as_bytes().to_vec();
+    bad_sk[MlKem768::SECRET_KEY_BYTES - 64] ^= 1;
+    let bad_sk = <MlKem768 as Kem>::SecretKey::from_bytes(&bad_sk).unwrap();
+    assert!(MlKem768::prepare_secret_key(&bad_sk).is_err());
+    assert_eq!(format!("{:?}", MlKem768::prepare_secret_key(&sk).unwrap()), "PreparedSecretKey([REDACTED])");
+}
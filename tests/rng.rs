@@ -0,0 +1,37 @@
+// The KEM accepts any rand_core CryptoRng per operation.
+
+use kyber::random_number_generation::{kem_rng, RngType};
+use kyber::{Kem, KemBytes, MlKem512, MlKem768};
+use rand::rngs::OsRng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+#[test]
+fn seeded_chacha20_is_reproducible() {
+    let mut first = ChaCha20Rng::from_seed([7u8; 32]);
+    let mut second = ChaCha20Rng::from_seed([7u8; 32]);
+
+    let (pk, sk) = MlKem768::keypair_with_rng(&mut first).unwrap();
+    let (pk_again, _) = MlKem768::keypair_with_rng(&mut second).unwrap();
+    assert_eq!(pk.as_bytes(), pk_again.as_bytes());
+
+    let (ct, ss) = MlKem768::encapsulate_with_rng(&pk, &mut first).unwrap();
+    let (ct_again, ss_again) = MlKem768::encapsulate_with_rng(&pk_again, &mut second).unwrap();
+    assert_eq!(ct.as_bytes(), ct_again.as_bytes());
+    assert!(ss == ss_again);
+    assert!(MlKem768::decapsulate(&sk, &ct).unwrap() == ss);
+}
+
+#[test]
+fn os_and_selected_rngs_round_trip() {
+    let (pk, sk) = MlKem512::keypair_with_rng(&mut OsRng).unwrap();
+    let (ct, ss) = MlKem512::encapsulate_with_rng(&pk, &mut OsRng).unwrap();
+    assert!(MlKem512::decapsulate(&sk, &ct).unwrap() == ss);
+
+    for rng_type in [RngType::Os, RngType::Std, RngType::ChaCha20] {
+        let mut rng = kem_rng(rng_type);
+        let (pk, sk) = MlKem512::keypair_with_rng(&mut rng).unwrap();
+        let (ct, ss) = MlKem512::encapsulate_with_rng(&pk, &mut rng).unwrap();
+        assert!(MlKem512::decapsulate(&sk, &ct).unwrap() == ss);
+    }
+}
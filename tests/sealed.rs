@@ -0,0 +1,128 @@
+// Sealed files: any size round trips a chunk at a time, and changing,
+// reordering, cutting or extending the file, or opening it with another key,
+// is detected.
+
+use kyber::sealed::{open, seal, SealedHeader, CHUNK_BYTES};
+use kyber::{Kem, Kyber1024, Kyber512_90s, KyberError, MlKem768};
+
+const TAG_BYTES: usize = 16;
+
+fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+fn sealed<K: Kem>(pk: &K::PublicKey, plaintext: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    assert_eq!(seal::<K, _, _>(pk, plaintext, &mut out).unwrap(), plaintext.len() as u64);
+    out
+}
+
+fn opened<K: Kem>(sk: &K::SecretKey, file: &[u8]) -> Result<Vec<u8>, KyberError> {
+    let mut out = Vec::new();
+    open::<K, _, _>(sk, file, &mut out)?;
+    Ok(out)
+}
+
+// Where the payload starts
+fn header_len(file: &[u8]) -> usize {
+    let mut reader = file;
+    SealedHeader::read(&mut reader).unwrap();
+    file.len() - reader.len()
+}
+
+fn check_sizes<K: Kem>() {
+    let (pk, sk) = K::keypair().unwrap();
+    for len in [0, 1, CHUNK_BYTES - 1, CHUNK_BYTES, CHUNK_BYTES + 1, 3 * CHUNK_BYTES] {
+        let plaintext = payload(len);
+        let file = sealed::<K>(&pk, &plaintext);
+        let chunks = len.div_ceil(CHUNK_BYTES).max(1);
+        assert_eq!(file.len(), header_len(&file) + len + chunks * TAG_BYTES, "{} bytes", len);
+        assert_eq!(opened::<K>(&sk, &file).unwrap(), plaintext, "{} bytes", len);
+    }
+}
+
+#[test]
+fn every_size_round_trips() {
+    check_sizes::<MlKem768>();
+    check_sizes::<Kyber1024>();
+    check_sizes::<Kyber512_90s>();
+}
+
+#[test]
+fn header_names_the_parameter_set_and_carries_the_encapsulation() {
+    let (pk, _) = Kyber1024::keypair().unwrap();
+    let file = sealed::<Kyber1024>(&pk, b"data");
+    let header = SealedHeader::read(&mut &file[..]).unwrap();
+    assert_eq!(header.parameter_set, "Kyber-1024");
+    assert_eq!(header.encapsulation.len(), Kyber1024::CIPHERTEXT_BYTES);
+    assert!(file.starts_with(header.encode().as_bytes()));
+    assert!(file.starts_with(b"KYBER-SEALED v1\nparameter-set: Kyber-1024\nencapsulation: "));
+
+    // Each file has its own encapsulation and salt
+    let again = SealedHeader::read(&mut &sealed::<Kyber1024>(&pk, b"data")[..]).unwrap();
+    assert_ne!(again.encapsulation, header.encapsulation);
+    assert_ne!(again.salt, header.salt);
+}
+
+fn is_sealed_error<T>(result: Result<T, KyberError>) -> bool {
+    matches!(result, Err(KyberError::Sealed(_)))
+}
+
+#[test]
+fn changes_reordering_and_truncation_are_detected() {
+    let (pk, sk) = MlKem768::keypair().unwrap();
+    let file = sealed::<MlKem768>(&pk, &payload(2 * CHUNK_BYTES + 100));
+    let start = header_len(&file);
+    let chunk = CHUNK_BYTES + TAG_BYTES;
+
+    // A flipped bit in the first, middle and last chunk
+    for i in [start, start + chunk + 5, file.len() - 1] {
+        let mut edited = file.clone();
+        edited[i] ^= 1;
+        assert!(is_sealed_error(opened::<MlKem768>(&sk, &edited)), "byte {}", i);
+    }
+
+    // The first two chunks swapped
+    let mut swapped = file[..start].to_vec();
+    swapped.extend_from_slice(&file[start + chunk..start + 2 * chunk]);
+    swapped.extend_from_slice(&file[start..start + chunk]);
+    swapped.extend_from_slice(&file[start + 2 * chunk..]);
+    assert!(is_sealed_error(opened::<MlKem768>(&sk, &swapped)));
+
+    // Cut after a whole chunk, cut inside one, or with something appended
+    for cut in [start + chunk, start + 2 * chunk, file.len() - 1, start + 10, start] {
+        assert!(is_sealed_error(opened::<MlKem768>(&sk, &file[..cut])), "cut at {}", cut);
+    }
+    let mut extended = file.clone();
+    extended.extend_from_slice(&[0u8; TAG_BYTES]);
+    assert!(is_sealed_error(opened::<MlKem768>(&sk, &extended)));
+
+    // Header lines that are edited, missing or in capitals
+    let text = String::from_utf8(file[..start].to_vec()).unwrap();
+    let salt = text.lines().find_map(|line| line.strip_prefix("salt: ")).unwrap();
+    let first = if salt.starts_with('0') { "1" } else { "0" };
+    for edited in [
+        text.replace(salt, &format!("{}{}", first, &salt[1..])),
+        text.replace(salt, &format!("A{}", &salt[1..])),
+        text.replace("ML-KEM-768", "Kyber-768"),
+        text.replace("v1", "v9"),
+        text.replace("---\n", ""),
+        text.replace("salt: ", "salt: 00"),
+    ] {
+        assert!(is_sealed_error(opened::<MlKem768>(&sk, &[edited.as_bytes(), &file[start..]].concat())), "{}", edited);
+    }
+}
+
+#[test]
+fn other_keys_cannot_open() {
+    let (pk, _) = MlKem768::keypair().unwrap();
+    let (_, other_sk) = MlKem768::keypair().unwrap();
+    let file = sealed::<MlKem768>(&pk, b"for someone else");
+    assert!(is_sealed_error(opened::<MlKem768>(&other_sk, &file)));
+
+    // A key of another parameter set of the same size is refused by name
+    let (_, kyber_sk) = kyber::Kyber768::keypair().unwrap();
+    let err = opened::<kyber::Kyber768>(&kyber_sk, &file).unwrap_err();
+    assert_eq!(err.exit_code(), 15);
+    assert!(err.to_string().contains("sealed for ML-KEM-768, not Kyber-768"), "{}", err);
+}
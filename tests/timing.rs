@@ -0,0 +1,62 @@
+// Constant-time audit tooling: the t-test itself, the valgrind client requests
+// outside valgrind, and a full timing run (ignored, run it in release).
+
+use kyber::timing::{decapsulation_leakage, decapsulation_leakage_in_batches, WelchT, LEAK_THRESHOLD};
+use kyber::valgrind;
+use kyber::{Kyber512, MlKem768};
+
+#[test]
+fn welch_t_matches_hand_computation() {
+    let mut welch = WelchT::new();
+    for x in [1.0, 2.0, 3.0, 4.0] {
+        welch.push(0, x);
+    }
+    for x in [2.0, 4.0, 6.0, 8.0] {
+        welch.push(1, x);
+    }
+    // means 2.5 and 5, variances 5/3 and 20/3, so t = -2.5 / sqrt(25/12)
+    let expected = -2.5 / (25.0f64 / 12.0).sqrt();
+    assert!((welch.t() - expected).abs() < 1e-12);
+    assert_eq!(welch.samples(), [4, 4]);
+}
+
+#[test]
+fn identical_classes_are_not_a_leak() {
+    let mut welch = WelchT::new();
+    for i in 0..1000 {
+        let x = (i % 7) as f64;
+        welch.push(0, x);
+        welch.push(1, x);
+    }
+    assert!(welch.t().abs() < LEAK_THRESHOLD);
+}
+
+#[test]
+fn client_requests_are_no_ops_outside_valgrind() {
+    assert!(!valgrind::running_on_valgrind());
+    let secret = [0x5au8; 32];
+    valgrind::classify(&secret);
+    valgrind::declassify(&secret);
+    assert_eq!(secret, [0x5au8; 32]);
+}
+
+#[test]
+fn measurements_are_taken_in_batches() {
+    let report = decapsulation_leakage_in_batches::<Kyber512>(250, 16).unwrap();
+    let labels: Vec<_> = report.tests.iter().map(|test| test.label.as_str()).collect();
+    assert_eq!(labels, ["all", "below p50", "below p75", "below p90", "below p95", "below p99"]);
+
+    // The first 16, the warmup, only set the crop thresholds
+    let totals: Vec<usize> = report.tests.iter().map(|test| test.samples[0] + test.samples[1]).collect();
+    assert_eq!(totals[0], 250 - 16);
+    assert!(totals[1..].windows(2).all(|pair| pair[0] <= pair[1]) && totals[5] <= totals[0]);
+}
+
+#[test]
+#[ignore = "slow and noisy in debug builds, run with cargo test --release -- --ignored"]
+fn decapsulation_is_constant_time() {
+    let report = decapsulation_leakage::<MlKem768>(200_000).unwrap();
+    println!("{}", report);
+    assert!(!report.leak_detected());
+}
+